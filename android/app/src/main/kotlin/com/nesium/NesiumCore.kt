@@ -64,6 +64,29 @@ object NesiumCore {
      */
     external fun releaseButton(button: Int)
 
+    /**
+     * Press the console's reset button
+     */
+    external fun reset()
+
+    /**
+     * Turn the console off and on again (battery saves are kept)
+     */
+    external fun powerCycle()
+
+    /**
+     * Snapshot the running game
+     * @return Save state bytes, or null if no ROM is loaded
+     */
+    external fun saveState(): ByteArray?
+
+    /**
+     * Restore a snapshot made by saveState for the same ROM
+     * @param state Save state bytes
+     * @return true if the state was accepted
+     */
+    external fun loadState(state: ByteArray): Boolean
+
     /**
      * Check if a ROM is currently loaded
      */
@@ -7,143 +7,37 @@ use std::panic;
 use std::sync::Mutex;
 
 use jni::objects::{JByteArray, JClass, JIntArray, JString};
-use jni::sys::{jboolean, jfloatArray, jint, JNI_TRUE};
+use jni::sys::{jboolean, jbyteArray, jfloatArray, jint, JNI_TRUE};
 use jni::JNIEnv;
 
 use nesium::cartridge::Cartridge;
-use nesium::cpu::{Cpu, CpuBus, FLAG_I};
-use nesium::memory::MemoryBus;
-use nesium::trace::TraceState;
-use nesium::{CPU_CYCLES_PER_PPU_CYCLE, NES_HEIGHT, NES_PALETTE, NES_WIDTH, PPU_CYCLES_PER_FRAME};
-
-/// NES emulator state
-struct NesEmulator {
-    cpu: Cpu,
-    memory: MemoryBus,
-    trace: TraceState,
-    ppu_cycles_this_frame: u64,
-    cpu_cycle_accumulator: f64,
-}
-
-impl NesEmulator {
-    fn new(cartridge: Cartridge) -> Self {
-        let mut memory = MemoryBus::new(cartridge);
-        let mut cpu = Cpu::new();
-        cpu.reset(&mut memory as &mut dyn CpuBus);
-
-        log::info!("NES initialized: PC=0x{:04X}", cpu.pc);
-
-        Self {
-            cpu,
-            memory,
-            trace: TraceState::new(false),
-            ppu_cycles_this_frame: 0,
-            cpu_cycle_accumulator: 0.0,
-        }
-    }
-
-    /// Run emulation for one frame (same logic as desktop EmulationState::step_frame)
-    fn step_frame(&mut self) {
-        self.ppu_cycles_this_frame = 0;
-
-        while self.ppu_cycles_this_frame < PPU_CYCLES_PER_FRAME {
-            let nmi_triggered = self.memory.step_ppu();
-
-            if nmi_triggered {
-                self.cpu.trigger_nmi(&mut self.memory as &mut dyn CpuBus);
-            }
-
-            if self.memory.mapper_irq_pending() && (self.cpu.status & FLAG_I) == 0 {
-                self.memory.acknowledge_mapper_irq();
-                self.cpu.trigger_irq(&mut self.memory as &mut dyn CpuBus);
-            }
-
-            self.cpu_cycle_accumulator += CPU_CYCLES_PER_PPU_CYCLE;
-
-            while self.cpu_cycle_accumulator >= 1.0 {
-                let cpu_cycles = self
-                    .cpu
-                    .step(&mut self.memory as &mut dyn CpuBus, &mut self.trace);
-                self.cpu_cycle_accumulator -= cpu_cycles as f64;
-
-                let irq = self.memory.step_apu(cpu_cycles);
-                if irq && (self.cpu.status & FLAG_I) == 0 {
-                    self.cpu.trigger_irq(&mut self.memory as &mut dyn CpuBus);
-                }
-            }
-
-            self.ppu_cycles_this_frame += 1;
-        }
-    }
-
-    /// Get the PPU framebuffer (palette indices)
-    fn framebuffer(&self) -> &[u8] {
-        &self.memory.ppu.framebuffer
-    }
-
-    /// Get audio samples from the APU
-    fn take_audio_samples(&mut self) -> Vec<f32> {
-        self.memory.apu.take_samples()
-    }
-
-    /// Press a controller button
-    fn press_button(&mut self, button: NesButton) {
-        match button {
-            NesButton::A => self.memory.input.controller1.a = true,
-            NesButton::B => self.memory.input.controller1.b = true,
-            NesButton::Select => self.memory.input.controller1.select = true,
-            NesButton::Start => self.memory.input.controller1.start = true,
-            NesButton::Up => self.memory.input.controller1.up = true,
-            NesButton::Down => self.memory.input.controller1.down = true,
-            NesButton::Left => self.memory.input.controller1.left = true,
-            NesButton::Right => self.memory.input.controller1.right = true,
-        }
-    }
+use nesium::input::Button;
+use nesium::{Nes, NES_HEIGHT, NES_PALETTE, NES_WIDTH};
 
-    /// Release a controller button
-    fn release_button(&mut self, button: NesButton) {
-        match button {
-            NesButton::A => self.memory.input.controller1.a = false,
-            NesButton::B => self.memory.input.controller1.b = false,
-            NesButton::Select => self.memory.input.controller1.select = false,
-            NesButton::Start => self.memory.input.controller1.start = false,
-            NesButton::Up => self.memory.input.controller1.up = false,
-            NesButton::Down => self.memory.input.controller1.down = false,
-            NesButton::Left => self.memory.input.controller1.left = false,
-            NesButton::Right => self.memory.input.controller1.right = false,
-        }
-    }
-}
-
-/// NES controller buttons
-#[derive(Debug, Clone, Copy)]
-enum NesButton {
-    A,
-    B,
-    Select,
-    Start,
-    Right,
-    Left,
-    Up,
-    Down,
-}
-
-fn int_to_button(button: jint) -> Option<NesButton> {
+/// Map the button constants in `NesiumCore.kt` to controller buttons
+fn int_to_button(button: jint) -> Option<Button> {
     match button {
-        0 => Some(NesButton::A),
-        1 => Some(NesButton::B),
-        2 => Some(NesButton::Select),
-        3 => Some(NesButton::Start),
-        4 => Some(NesButton::Right),
-        5 => Some(NesButton::Left),
-        6 => Some(NesButton::Up),
-        7 => Some(NesButton::Down),
+        0 => Some(Button::A),
+        1 => Some(Button::B),
+        2 => Some(Button::Select),
+        3 => Some(Button::Start),
+        4 => Some(Button::Right),
+        5 => Some(Button::Left),
+        6 => Some(Button::Up),
+        7 => Some(Button::Down),
         _ => None,
     }
 }
 
+/// Create the console for a freshly loaded cartridge
+fn power_on(cartridge: Cartridge) -> Nes {
+    let nes = Nes::new(cartridge);
+    log::info!("NES initialized: PC=0x{:04X}", nes.cpu.pc);
+    nes
+}
+
 /// Global emulator instance protected by a mutex
-static EMULATOR: Mutex<Option<NesEmulator>> = Mutex::new(None);
+static EMULATOR: Mutex<Option<Nes>> = Mutex::new(None);
 
 // ============================================================================
 // JNI Functions
@@ -201,15 +95,16 @@ fn load_rom_from_bytes_inner(env: JNIEnv, rom_data: JByteArray) -> jboolean {
     match Cartridge::load_from_bytes(rom_bytes) {
         Ok(cartridge) => {
             log::info!(
-                "Cartridge loaded: mapper={}, prg={}KB, chr={}KB",
-                cartridge.mapper_id,
-                cartridge.prg_rom.len() / 1024,
-                cartridge.chr_rom.len() / 1024
+                "Cartridge loaded: mapper={}.{}, prg={}KB, chr={}KB",
+                cartridge.header.mapper,
+                cartridge.header.submapper,
+                cartridge.memory.prg_rom.len() / 1024,
+                cartridge.memory.chr_rom.len() / 1024
             );
 
             match EMULATOR.lock() {
                 Ok(mut emu) => {
-                    *emu = Some(NesEmulator::new(cartridge));
+                    *emu = Some(power_on(cartridge));
                     log::info!("ROM loaded and NES emulator initialized");
                     JNI_TRUE as jboolean
                 }
@@ -262,7 +157,7 @@ fn load_rom_from_path_inner(env: &mut JNIEnv, path: &JString) -> jboolean {
     match Cartridge::load(&path_str) {
         Ok(cartridge) => match EMULATOR.lock() {
             Ok(mut emu) => {
-                *emu = Some(NesEmulator::new(cartridge));
+                *emu = Some(power_on(cartridge));
                 log::info!("ROM loaded from path successfully");
                 JNI_TRUE as jboolean
             }
@@ -309,7 +204,7 @@ fn run_frame_inner(env: JNIEnv, framebuffer: JIntArray) -> jint {
 
     if let Some(ref mut nes) = *emu {
         // Run one frame
-        nes.step_frame();
+        nes.run_frame();
 
         // Get the PPU framebuffer (palette indices)
         let fb = nes.framebuffer();
@@ -408,7 +303,7 @@ pub extern "system" fn Java_com_nesium_NesiumCore_pressButton(
     if let Ok(mut emu) = EMULATOR.lock() {
         if let Some(ref mut nes) = *emu {
             if let Some(btn) = int_to_button(button) {
-                nes.press_button(btn);
+                nes.set_button(0, btn, true);
             }
         }
     }
@@ -424,12 +319,115 @@ pub extern "system" fn Java_com_nesium_NesiumCore_releaseButton(
     if let Ok(mut emu) = EMULATOR.lock() {
         if let Some(ref mut nes) = *emu {
             if let Some(btn) = int_to_button(button) {
-                nes.release_button(btn);
+                nes.set_button(0, btn, false);
             }
         }
     }
 }
 
+/// Press the console's reset button
+#[no_mangle]
+pub extern "system" fn Java_com_nesium_NesiumCore_reset(_env: JNIEnv, _class: JClass) {
+    if let Ok(mut emu) = EMULATOR.lock() {
+        if let Some(ref mut nes) = *emu {
+            nes.reset();
+        }
+    }
+}
+
+/// Turn the console off and on again
+#[no_mangle]
+pub extern "system" fn Java_com_nesium_NesiumCore_powerCycle(_env: JNIEnv, _class: JClass) {
+    if let Ok(mut emu) = EMULATOR.lock() {
+        if let Some(ref mut nes) = *emu {
+            nes.power_cycle();
+        }
+    }
+}
+
+/// Snapshot the running game. Returns null if no ROM is loaded.
+#[no_mangle]
+pub extern "system" fn Java_com_nesium_NesiumCore_saveState<'local>(
+    env: JNIEnv<'local>,
+    _class: JClass<'local>,
+) -> jbyteArray {
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| save_state_inner(env)));
+
+    match result {
+        Ok(array) => array,
+        Err(e) => {
+            log::error!("Panic in saveState: {:?}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+fn save_state_inner(env: JNIEnv) -> jbyteArray {
+    let state = match EMULATOR.lock() {
+        Ok(emu) => match *emu {
+            Some(ref nes) => nes.save_state(),
+            None => return std::ptr::null_mut(),
+        },
+        Err(e) => {
+            log::error!("Failed to lock emulator: {}", e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match env.byte_array_from_slice(&state) {
+        Ok(array) => array.into_raw(),
+        Err(e) => {
+            log::error!("Failed to create state array: {}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Restore a snapshot made by saveState for the same ROM
+#[no_mangle]
+pub extern "system" fn Java_com_nesium_NesiumCore_loadState<'local>(
+    env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    state: JByteArray<'local>,
+) -> jboolean {
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| load_state_inner(env, state)));
+
+    match result {
+        Ok(success) => success,
+        Err(e) => {
+            log::error!("Panic in loadState: {:?}", e);
+            0
+        }
+    }
+}
+
+fn load_state_inner(env: JNIEnv, state: JByteArray) -> jboolean {
+    let data: Vec<u8> = match env.convert_byte_array(state) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::error!("Failed to convert state bytes: {}", e);
+            return 0;
+        }
+    };
+
+    match EMULATOR.lock() {
+        Ok(mut emu) => match *emu {
+            Some(ref mut nes) => match nes.load_state(&data) {
+                Ok(()) => JNI_TRUE as jboolean,
+                Err(e) => {
+                    log::error!("Failed to load state: {}", e);
+                    0
+                }
+            },
+            None => 0,
+        },
+        Err(e) => {
+            log::error!("Failed to lock emulator: {}", e);
+            0
+        }
+    }
+}
+
 /// Check if a ROM is loaded
 #[no_mangle]
 pub extern "system" fn Java_com_nesium_NesiumCore_isRomLoaded(
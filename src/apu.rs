@@ -1,11 +1,13 @@
 // APU (Audio Processing Unit) implementation
 // Based on NES APU documentation from nesdev.org
 
+use crate::region::Region;
+use crate::savestate::{SaveState, SaveStateError, StateReader, StateWriter};
+
 const OUTPUT_SAMPLE_RATE: u32 = 44_100;
-const CPU_FREQUENCY: f64 = 1_789_773.0; // NTSC CPU clock
 
 // Duty cycle sequences for pulse channels (8 steps each)
-const DUTY_TABLE: [[u8; 8]; 4] = [
+pub(crate) const DUTY_TABLE: [[u8; 8]; 4] = [
     [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
     [0, 1, 1, 0, 0, 0, 0, 0], // 25%
     [0, 1, 1, 1, 1, 0, 0, 0], // 50%
@@ -19,21 +21,11 @@ const TRIANGLE_SEQUENCE: [u8; 32] = [
 ];
 
 // Length counter lookup table
-const LENGTH_TABLE: [u8; 32] = [
+pub(crate) const LENGTH_TABLE: [u8; 32] = [
     10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
     192, 24, 72, 26, 16, 28, 32, 30,
 ];
 
-// Noise period table (NTSC)
-const NOISE_PERIOD_TABLE: [u16; 16] = [
-    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
-];
-
-// DMC rate table (NTSC)
-const DMC_PERIOD_TABLE: [u16; 16] = [
-    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
-];
-
 // Mixer lookup tables (precomputed for efficiency)
 const PULSE_LUT_SIZE: usize = 31;
 const TND_LUT_SIZE: usize = 203;
@@ -131,6 +123,11 @@ pub struct Apu {
     // Mixer lookup tables
     pulse_lut: [f32; PULSE_LUT_SIZE],
     tnd_lut: [f32; TND_LUT_SIZE],
+
+    // Cartridge expansion audio, mixed in after the 2A03 channels
+    expansion_audio: f32,
+
+    region: Region,
 }
 
 #[derive(Debug, Clone)]
@@ -386,8 +383,8 @@ impl DmcChannel {
             shift_register: 0,
             bits_remaining: 8,
             silence: true,
-            rate: DMC_PERIOD_TABLE[0],
-            rate_counter: DMC_PERIOD_TABLE[0],
+            rate: Region::Ntsc.dmc_period_table()[0],
+            rate_counter: Region::Ntsc.dmc_period_table()[0],
         }
     }
 
@@ -463,7 +460,7 @@ impl DmcChannel {
 }
 
 impl Envelope {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             start: false,
             loop_flag: false,
@@ -474,7 +471,7 @@ impl Envelope {
         }
     }
 
-    fn clock(&mut self) {
+    pub(crate) fn clock(&mut self) {
         if self.start {
             self.start = false;
             self.decay_counter = 15;
@@ -532,6 +529,232 @@ impl Sweep {
     }
 }
 
+impl SaveState for FirstOrderFilter {
+    // Coefficients are fixed at construction; only the history is state
+    fn save_state(&self, w: &mut StateWriter) {
+        w.f32(self.prev_input);
+        w.f32(self.prev_output);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prev_input = r.f32()?;
+        self.prev_output = r.f32()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Envelope {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.start);
+        w.bool(self.loop_flag);
+        w.bool(self.constant_volume);
+        w.u8(self.volume);
+        w.u8(self.decay_counter);
+        w.u8(self.divider);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.start = r.bool()?;
+        self.loop_flag = r.bool()?;
+        self.constant_volume = r.bool()?;
+        self.volume = r.u8()?;
+        self.decay_counter = r.u8()?;
+        self.divider = r.u8()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Sweep {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.u8(self.period);
+        w.bool(self.negate);
+        w.u8(self.shift);
+        w.bool(self.reload);
+        w.u8(self.divider);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.bool()?;
+        self.period = r.u8()?;
+        self.negate = r.bool()?;
+        self.shift = r.u8()?;
+        self.reload = r.bool()?;
+        self.divider = r.u8()?;
+        Ok(())
+    }
+}
+
+impl SaveState for PulseChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.u8(self.length_counter);
+        w.bool(self.length_counter_halt);
+        self.envelope.save_state(w);
+        self.sweep.save_state(w);
+        w.u16(self.timer);
+        w.u16(self.timer_counter);
+        w.u8(self.duty_cycle);
+        w.u8(self.duty_step);
+        w.bool(self.constant_volume);
+        w.bool(self.mute);
+        w.u16(self.target_period);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.bool()?;
+        self.length_counter = r.u8()?;
+        self.length_counter_halt = r.bool()?;
+        self.envelope.load_state(r)?;
+        self.sweep.load_state(r)?;
+        self.timer = r.u16()?;
+        self.timer_counter = r.u16()?;
+        self.duty_cycle = r.u8()? & 0x03;
+        self.duty_step = r.u8()? & 0x07;
+        self.constant_volume = r.bool()?;
+        self.mute = r.bool()?;
+        self.target_period = r.u16()?;
+        Ok(())
+    }
+}
+
+impl SaveState for TriangleChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.u8(self.length_counter);
+        w.bool(self.length_counter_halt);
+        w.u8(self.linear_counter);
+        w.u8(self.linear_counter_reload);
+        w.bool(self.linear_counter_reload_flag);
+        w.u16(self.timer);
+        w.u16(self.timer_counter);
+        w.u8(self.step);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.bool()?;
+        self.length_counter = r.u8()?;
+        self.length_counter_halt = r.bool()?;
+        self.linear_counter = r.u8()?;
+        self.linear_counter_reload = r.u8()?;
+        self.linear_counter_reload_flag = r.bool()?;
+        self.timer = r.u16()?;
+        self.timer_counter = r.u16()?;
+        self.step = r.u8()? & 0x1F;
+        Ok(())
+    }
+}
+
+impl SaveState for NoiseChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.u8(self.length_counter);
+        w.bool(self.length_counter_halt);
+        self.envelope.save_state(w);
+        w.u16(self.timer);
+        w.u16(self.timer_counter);
+        w.u16(self.shift_register);
+        w.bool(self.mode);
+        w.bool(self.constant_volume);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.bool()?;
+        self.length_counter = r.u8()?;
+        self.length_counter_halt = r.bool()?;
+        self.envelope.load_state(r)?;
+        self.timer = r.u16()?;
+        self.timer_counter = r.u16()?;
+        self.shift_register = r.u16()?;
+        self.mode = r.bool()?;
+        self.constant_volume = r.bool()?;
+        Ok(())
+    }
+}
+
+impl SaveState for DmcChannel {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.bool(self.loop_flag);
+        w.bool(self.irq_enabled);
+        w.bool(self.irq_occurred);
+        w.u8(self.output_level);
+        w.u16(self.sample_address);
+        w.u16(self.sample_length);
+        w.u16(self.current_address);
+        w.u16(self.bytes_remaining);
+        w.u8(self.sample_buffer);
+        w.bool(self.sample_buffer_empty);
+        w.u8(self.shift_register);
+        w.u8(self.bits_remaining);
+        w.bool(self.silence);
+        w.u16(self.rate);
+        w.u16(self.rate_counter);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.bool()?;
+        self.loop_flag = r.bool()?;
+        self.irq_enabled = r.bool()?;
+        self.irq_occurred = r.bool()?;
+        self.output_level = r.u8()?;
+        self.sample_address = r.u16()?;
+        self.sample_length = r.u16()?;
+        self.current_address = r.u16()?;
+        self.bytes_remaining = r.u16()?;
+        self.sample_buffer = r.u8()?;
+        self.sample_buffer_empty = r.bool()?;
+        self.shift_register = r.u8()?;
+        self.bits_remaining = r.u8()?;
+        self.silence = r.bool()?;
+        self.rate = r.u16()?;
+        self.rate_counter = r.u16()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Apu {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.pulse1.save_state(w);
+        self.pulse2.save_state(w);
+        self.triangle.save_state(w);
+        self.noise.save_state(w);
+        self.dmc.save_state(w);
+
+        w.u64(self.cycle_count);
+        w.bool(self.frame_counter_mode);
+        w.bool(self.frame_counter_interrupt);
+        w.bool(self.irq_inhibit);
+        w.bool(self.reset_sequencer);
+
+        w.f64(self.sample_counter);
+        self.high_pass_90hz.save_state(w);
+        self.high_pass_440hz.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.pulse1.load_state(r)?;
+        self.pulse2.load_state(r)?;
+        self.triangle.load_state(r)?;
+        self.noise.load_state(r)?;
+        self.dmc.load_state(r)?;
+
+        self.cycle_count = r.u64()?;
+        self.frame_counter_mode = r.bool()?;
+        self.frame_counter_interrupt = r.bool()?;
+        self.irq_inhibit = r.bool()?;
+        self.reset_sequencer = r.bool()?;
+
+        self.sample_counter = r.f64()?;
+        self.high_pass_90hz.load_state(r)?;
+        self.high_pass_440hz.load_state(r)?;
+
+        // Samples generated before the load belong to the old timeline
+        self.sample_buffer.clear();
+        Ok(())
+    }
+}
+
 impl Default for Apu {
     fn default() -> Self {
         Self::new()
@@ -552,7 +775,7 @@ impl Apu {
             irq_inhibit: false,
             reset_sequencer: false,
             sample_counter: 0.0,
-            cycles_per_sample: CPU_FREQUENCY / (OUTPUT_SAMPLE_RATE as f64),
+            cycles_per_sample: Region::Ntsc.cpu_clock_rate() / (OUTPUT_SAMPLE_RATE as f64),
             sample_buffer: Vec::with_capacity(1024),
             sample_adjustment: 0.0,
             // NES has two first-order high-pass filters in the audio path
@@ -560,9 +783,28 @@ impl Apu {
             high_pass_440hz: FirstOrderFilter::high_pass(440.0, OUTPUT_SAMPLE_RATE as f32),
             pulse_lut: compute_pulse_lut(),
             tnd_lut: compute_tnd_lut(),
+            expansion_audio: 0.0,
+            region: Region::Ntsc,
         }
     }
 
+    /// Switch the frame sequencer, period tables and resampling to `region`
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.cycles_per_sample = region.cpu_clock_rate() / (OUTPUT_SAMPLE_RATE as f64);
+    }
+
+    /// Current level of the cartridge's expansion audio (see
+    /// [`crate::mapper::Mapper::audio_output`])
+    pub fn set_expansion_audio(&mut self, level: f32) {
+        self.expansion_audio = level;
+    }
+
+    /// Level of the APU's /IRQ output (frame counter or DMC)
+    pub fn irq_line(&self) -> bool {
+        self.frame_counter_interrupt || self.dmc.irq_occurred
+    }
+
     pub fn read_register(&mut self, addr: u16) -> u8 {
         match addr {
             0x4015 => {
@@ -684,7 +926,7 @@ impl Apu {
             }
             0x400E => {
                 self.noise.mode = (value & 0x80) != 0;
-                self.noise.timer = NOISE_PERIOD_TABLE[(value & 0x0F) as usize];
+                self.noise.timer = self.region.noise_period_table()[(value & 0x0F) as usize];
             }
             0x400F => {
                 if self.noise.enabled {
@@ -697,7 +939,7 @@ impl Apu {
             0x4010 => {
                 self.dmc.irq_enabled = (value & 0x80) != 0;
                 self.dmc.loop_flag = (value & 0x40) != 0;
-                self.dmc.rate = DMC_PERIOD_TABLE[(value & 0x0F) as usize];
+                self.dmc.rate = self.region.dmc_period_table()[(value & 0x0F) as usize];
                 if !self.dmc.irq_enabled {
                     self.dmc.irq_occurred = false;
                 }
@@ -784,43 +1026,30 @@ impl Apu {
                 irq = true;
             }
 
-            // Frame sequencer - use exact cycle counts for NTSC
-            if self.frame_counter_mode {
-                // 5-step mode (37281 CPU cycles per sequence)
-                match self.cycle_count {
-                    7457 => self.clock_quarter_frame(),
-                    14913 => {
-                        self.clock_quarter_frame();
-                        self.clock_half_frame();
-                    }
-                    22371 => self.clock_quarter_frame(),
-                    37281 => {
-                        self.clock_quarter_frame();
-                        self.clock_half_frame();
-                        self.cycle_count = 0;
-                    }
-                    _ => {}
+            // Frame sequencer - exact cycle counts from the region's table
+            let [step1, step2, step3, step4, step5] = *self.region.frame_sequence();
+            let cycle = self.cycle_count;
+            if cycle == step1 || cycle == step3 {
+                self.clock_quarter_frame();
+            } else if cycle == step2 {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            } else if self.frame_counter_mode {
+                // 5-step mode (37281 CPU cycles per sequence on NTSC)
+                if cycle == step5 {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                    self.cycle_count = 0;
                 }
-            } else {
-                // 4-step mode (29830 CPU cycles per sequence)
-                match self.cycle_count {
-                    7457 => self.clock_quarter_frame(),
-                    14913 => {
-                        self.clock_quarter_frame();
-                        self.clock_half_frame();
-                    }
-                    22371 => self.clock_quarter_frame(),
-                    29829 => {
-                        self.clock_quarter_frame();
-                        self.clock_half_frame();
-                        if !self.irq_inhibit {
-                            self.frame_counter_interrupt = true;
-                            irq = true;
-                        }
-                        self.cycle_count = 0;
-                    }
-                    _ => {}
+            } else if cycle == step4 {
+                // 4-step mode (29830 CPU cycles per sequence on NTSC)
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                if !self.irq_inhibit {
+                    self.frame_counter_interrupt = true;
+                    irq = true;
                 }
+                self.cycle_count = 0;
             }
 
             // Generate output samples at target rate (downsample from ~1.79MHz to 44.1kHz)
@@ -914,7 +1143,7 @@ impl Apu {
             self.tnd_lut[TND_LUT_SIZE - 1]
         };
 
-        pulse_mix + tnd_mix
+        pulse_mix + tnd_mix + self.expansion_audio
     }
 
     pub fn mix_samples(&self) -> f32 {
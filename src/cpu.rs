@@ -1,6 +1,14 @@
-use crate::trace::{disassemble_instruction, TraceState};
+use crate::savestate::{SaveState, SaveStateError, StateReader, StateWriter};
+use crate::trace::{disassemble_instruction, instruction_length, TraceState};
 use log::trace;
 
+/// 6502 core (Ricoh 2A03, no decimal mode).
+///
+/// Every bus access an instruction makes goes through [`CpuBus`] in the same
+/// order as on hardware, one access per cycle, including the dummy reads of
+/// indexed addressing and the dummy writes of read-modify-write instructions.
+/// `cycles` advances by one per access, so the cycle count of an instruction
+/// falls out of its access pattern rather than a lookup table.
 #[derive(Debug, Clone)]
 pub struct Cpu {
     pub pc: u16,
@@ -10,7 +18,18 @@ pub struct Cpu {
     pub sp: u8,
     pub status: u8,
     pub cycles: u64,
-    pub stall_cycles: u64,
+    /// /NMI level seen on the previous cycle, for edge detection
+    nmi_line: bool,
+    /// An NMI edge has been detected and not yet serviced
+    nmi_pending: bool,
+    /// `nmi_pending` as of the end of the previous cycle
+    prev_nmi_pending: bool,
+    /// /IRQ is asserted and the I flag is clear
+    irq_pending: bool,
+    /// `irq_pending` as of the end of the previous cycle
+    prev_irq_pending: bool,
+    /// Set by a JAM opcode; the CPU stops until reset
+    jammed: bool,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -20,6 +39,19 @@ pub enum Interrupt {
     Irq,
 }
 
+/// Memory operand addressing modes
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    IndirectX,
+    IndirectY,
+}
+
 // Status flags
 pub const FLAG_C: u8 = 0x01; // Carry
 pub const FLAG_Z: u8 = 0x02; // Zero
@@ -31,19 +63,24 @@ pub const FLAG_V: u8 = 0x40; // Overflow
 pub const FLAG_N: u8 = 0x80; // Negative
 
 pub trait CpuBus {
+    /// One CPU read cycle, with all of its side effects
     fn read(&mut self, addr: u16) -> u8;
+    /// One CPU write cycle
     fn write(&mut self, addr: u16, value: u8);
-    fn is_oamdma_addr(&self, _addr: u16) -> bool {
+    /// Read without side effects, for tracing and debugging
+    fn peek(&self, addr: u16) -> u8;
+    /// Level of the /NMI line (true = asserted)
+    fn nmi_line(&self) -> bool {
         false
     }
-    fn write_oam(&mut self, _oam_addr: u16, _value: u8) {
-        // Default: no-op
+    /// Level of the /IRQ line (true = asserted by any source)
+    fn irq_line(&self) -> bool {
+        false
     }
-    /// Trigger OAMDMA transfer and return the number of CPU cycles to stall
-    /// Returns 513 if current cycle is odd, 514 if even
-    fn trigger_oamdma(&mut self, _page: u8, _current_cycle_odd: bool) -> u64 {
-        // Default: no-op, return 0 stall cycles
-        0
+    /// Page written to $4014 since the last call, if any. The CPU performs
+    /// the OAM DMA itself so every transfer cycle goes through the bus.
+    fn take_oam_dma(&mut self) -> Option<u8> {
+        None
     }
 }
 
@@ -53,6 +90,41 @@ impl Default for Cpu {
     }
 }
 
+impl SaveState for Cpu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.pc);
+        w.u8(self.a);
+        w.u8(self.x);
+        w.u8(self.y);
+        w.u8(self.sp);
+        w.u8(self.status);
+        w.u64(self.cycles);
+        w.bool(self.nmi_line);
+        w.bool(self.nmi_pending);
+        w.bool(self.prev_nmi_pending);
+        w.bool(self.irq_pending);
+        w.bool(self.prev_irq_pending);
+        w.bool(self.jammed);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.pc = r.u16()?;
+        self.a = r.u8()?;
+        self.x = r.u8()?;
+        self.y = r.u8()?;
+        self.sp = r.u8()?;
+        self.status = r.u8()?;
+        self.cycles = r.u64()?;
+        self.nmi_line = r.bool()?;
+        self.nmi_pending = r.bool()?;
+        self.prev_nmi_pending = r.bool()?;
+        self.irq_pending = r.bool()?;
+        self.prev_irq_pending = r.bool()?;
+        self.jammed = r.bool()?;
+        Ok(())
+    }
+}
+
 impl Cpu {
     pub fn new() -> Self {
         Self {
@@ -63,14 +135,31 @@ impl Cpu {
             sp: 0xFD,
             status: FLAG_U | FLAG_I,
             cycles: 0,
-            stall_cycles: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            prev_nmi_pending: false,
+            irq_pending: false,
+            prev_irq_pending: false,
+            jammed: false,
         }
     }
 
+    /// Run the 7-cycle reset sequence: the interrupt sequence with its
+    /// stack writes turned into reads, then a jump through the reset vector
     pub fn reset(&mut self, bus: &mut dyn CpuBus) {
-        self.sp = self.sp.wrapping_sub(3);
+        self.nmi_pending = false;
+        self.irq_pending = false;
+        self.jammed = false;
+        self.dummy_read(bus);
+        self.dummy_read(bus);
+        for _ in 0..3 {
+            self.dummy_stack_read(bus);
+            self.sp = self.sp.wrapping_sub(1);
+        }
         self.status |= FLAG_I;
-        self.pc = self.read_word(0xFFFC, bus);
+        let low = self.read(0xFFFC, bus) as u16;
+        let high = self.read(0xFFFD, bus) as u16;
+        self.pc = (high << 8) | low;
         log::info!(
             "CPU Reset: PC=0x{:04X}, SP=0x{:02X}, Status=0x{:02X}",
             self.pc,
@@ -79,12 +168,19 @@ impl Cpu {
         );
     }
 
+    /// Execute one instruction, or service a pending interrupt, plus any OAM
+    /// DMA it started. Returns the number of CPU cycles taken.
     pub fn step(&mut self, bus: &mut dyn CpuBus, trace_state: &mut TraceState) -> u64 {
-        let interrupt = self.check_interrupts(bus);
-        let mut cycles = 0;
+        let start_cycles = self.cycles;
 
+        if self.jammed {
+            // The address bus is stuck high while the clock keeps running
+            self.read(0xFFFF, bus);
+            return self.cycles - start_cycles;
+        }
+
+        let interrupt = self.check_interrupts();
         if interrupt != Interrupt::None {
-            cycles += self.handle_interrupt(interrupt, bus);
             if trace_state.enabled {
                 // Trace interrupt handling
                 println!(
@@ -104,1842 +200,895 @@ impl Cpu {
                     trace_state.get_cycle_count()
                 );
             }
-            self.cycles += cycles;
-            return cycles;
-        }
+            self.handle_interrupt(bus);
+        } else {
+            let pc_before = self.pc;
+            let opcode = self.fetch(bus);
 
-        if self.stall_cycles > 0 {
-            self.stall_cycles -= 1;
-            cycles += 1;
-            self.cycles += cycles;
-            return cycles;
+            if trace_state.enabled {
+                // Operands are peeked so tracing never touches registers
+                let length = instruction_length(opcode);
+                let operand1 = (length > 1).then(|| bus.peek(pc_before.wrapping_add(1)));
+                let operand2 = (length > 2).then(|| bus.peek(pc_before.wrapping_add(2)));
+
+                // nestest format: PC opcode_bytes disassembly A:XX X:XX Y:XX P:XX SP:XX CYC:XXX
+                let opcode_bytes = match (operand1, operand2) {
+                    (Some(b1), Some(b2)) => format!("{:02X} {:02X} {:02X}", opcode, b1, b2),
+                    (Some(b1), None) => format!("{:02X} {:02X}   ", opcode, b1),
+                    _ => format!("{:02X}        ", opcode),
+                };
+                let disasm = disassemble_instruction(opcode, operand1, operand2);
+                println!(
+                    "{:04X} {} {:20} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+                    pc_before,
+                    opcode_bytes,
+                    disasm,
+                    self.a,
+                    self.x,
+                    self.y,
+                    self.status,
+                    self.sp,
+                    trace_state.get_cycle_count()
+                );
+            } else {
+                trace!(
+                    "{:04X} {:02X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+                    pc_before,
+                    opcode,
+                    self.a,
+                    self.x,
+                    self.y,
+                    self.status,
+                    self.sp
+                );
+            }
+
+            self.execute(opcode, bus);
         }
 
-        let pc_before = self.pc;
-        let opcode = bus.read(self.pc);
-        self.pc = self.pc.wrapping_add(1);
+        if let Some(page) = bus.take_oam_dma() {
+            self.oam_dma(page, bus);
+        }
 
-        // Read operands for disassembly (peek ahead, don't advance PC)
-        let operand1 = if self.needs_operand1(opcode) {
-            Some(bus.read(self.pc))
-        } else {
-            None
-        };
-        let operand2 = if self.needs_operand2(opcode) {
-            Some(bus.read(self.pc.wrapping_add(1)))
-        } else {
-            None
-        };
+        self.cycles - start_cycles
+    }
 
-        // Restore PC for actual execution
-        self.pc = pc_before.wrapping_add(1);
-
-        if trace_state.enabled {
-            // nestest format: PC opcode_bytes disassembly A:XX X:XX Y:XX P:XX SP:XX CYC:XXX
-            let opcode_bytes = match (operand1, operand2) {
-                (Some(b1), Some(b2)) => format!("{:02X} {:02X} {:02X}", opcode, b1, b2),
-                (Some(b1), None) => format!("{:02X} {:02X}   ", opcode, b1),
-                _ => format!("{:02X}        ", opcode),
-            };
-            let disasm = disassemble_instruction(opcode, operand1, operand2);
-            println!(
-                "{:04X} {} {:20} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
-                pc_before,
-                opcode_bytes,
-                disasm,
-                self.a,
-                self.x,
-                self.y,
-                self.status,
-                self.sp,
-                trace_state.get_cycle_count()
-            );
+    /// Interrupts are polled on the second-to-last cycle of an instruction,
+    /// so an edge on its final cycle waits for one more instruction
+    fn check_interrupts(&self) -> Interrupt {
+        if self.prev_nmi_pending {
+            Interrupt::Nmi
+        } else if self.prev_irq_pending {
+            Interrupt::Irq
         } else {
-            trace!(
-                "{:04X} {:02X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
-                pc_before,
-                opcode,
-                self.a,
-                self.x,
-                self.y,
-                self.status,
-                self.sp
-            );
+            Interrupt::None
         }
+    }
 
-        cycles += self.execute(opcode, bus);
-        self.cycles += cycles;
-        cycles
-    }
-
-    fn needs_operand1(&self, opcode: u8) -> bool {
-        // Instructions that DON'T need operand bytes (implied/accumulator addressing)
-        !matches!(
-            opcode,
-            0x00 | 0x08
-                | 0x0A
-                | 0x18
-                | 0x28
-                | 0x2A
-                | 0x38
-                | 0x40
-                | 0x48
-                | 0x4A
-                | 0x58
-                | 0x60
-                | 0x68
-                | 0x6A
-                | 0x78
-                | 0x88
-                | 0x8A
-                | 0x98
-                | 0x9A
-                | 0xA8
-                | 0xAA
-                | 0xB8
-                | 0xBA
-                | 0xC8
-                | 0xCA
-                | 0xD8
-                | 0xE8
-                | 0xEA
-                | 0xF8
-        ) // All others need at least 1 operand
-    }
-
-    fn needs_operand2(&self, opcode: u8) -> bool {
-        // Instructions that need 2 operand bytes (absolute addressing, JSR, JMP indirect)
-        matches!(
-            opcode,
-            0x20 | 0x4C | 0x6C // JSR, JMP absolute, JMP indirect
-        ) || matches!(
-            opcode,
-            0x0D | 0x0E
-                | 0x19
-                | 0x1D
-                | 0x1E
-                | 0x2C
-                | 0x2D
-                | 0x2E
-                | 0x39
-                | 0x3D
-                | 0x3E
-                | 0x4D
-                | 0x4E
-                | 0x59
-                | 0x5D
-                | 0x5E
-                | 0x6D
-                | 0x6E
-                | 0x79
-                | 0x7D
-                | 0x7E
-                | 0x8C
-                | 0x8D
-                | 0x8E
-                | 0x99
-                | 0x9D
-                | 0xAC
-                | 0xAD
-                | 0xAE
-                | 0xB9
-                | 0xBC
-                | 0xBD
-                | 0xBE
-                | 0xCC
-                | 0xCD
-                | 0xCE
-                | 0xD9
-                | 0xDD
-                | 0xDE
-                | 0xEC
-                | 0xED
-                | 0xEE
-                | 0xF9
-                | 0xFD
-                | 0xFE
-        ) // Absolute addressing modes
-    }
-
-    fn check_interrupts(&self, _bus: &mut dyn CpuBus) -> Interrupt {
-        // This will be handled by the emulator core checking PPU NMI
-        Interrupt::None
-    }
-
-    /// Helper function to handle writes that might be OAMDMA
-    /// Returns true if OAMDMA was triggered (and stall_cycles was set)
-    fn handle_write_with_oamdma(&mut self, bus: &mut dyn CpuBus, addr: u16, value: u8) -> bool {
-        if addr == 0x4014 {
-            // OAMDMA: stall CPU for 513-514 cycles depending on alignment
-            // If current cycle count is odd: 513 cycles, if even: 514 cycles
-            let current_cycle_odd = (self.cycles & 1) != 0;
-            let stall_cycles = bus.trigger_oamdma(value, current_cycle_odd);
-            self.stall_cycles = stall_cycles;
-            bus.write(addr, value);
-            true
-        } else {
-            bus.write(addr, value);
-            false
+    /// Sample the interrupt lines at the end of a cycle
+    fn poll_interrupts(&mut self, bus: &mut dyn CpuBus) {
+        let nmi_line = bus.nmi_line();
+        if nmi_line && !self.nmi_line {
+            self.nmi_pending = true;
         }
+        self.nmi_line = nmi_line;
+
+        self.prev_irq_pending = self.irq_pending;
+        self.irq_pending = bus.irq_line() && !self.get_flag(FLAG_I);
+    }
+
+    /// Hardware interrupt: two dummy reads of the next opcode, then the same
+    /// push and vector sequence as BRK but with B clear
+    fn handle_interrupt(&mut self, bus: &mut dyn CpuBus) {
+        self.dummy_read(bus);
+        self.dummy_read(bus);
+        self.interrupt_sequence((self.status & !FLAG_B) | FLAG_U, bus);
     }
 
-    pub fn trigger_nmi(&mut self, bus: &mut dyn CpuBus) {
+    /// Push PC and `status`, then jump through the NMI or IRQ/BRK vector.
+    /// An NMI that arrives before the vector is fetched hijacks the sequence.
+    fn interrupt_sequence(&mut self, status: u8, bus: &mut dyn CpuBus) {
         self.push_word(self.pc, bus);
-        // NMI should push status with B flag clear (unlike BRK which sets B)
-        self.push((self.status & !FLAG_B) | FLAG_U, bus);
+        self.push(status, bus);
         self.set_flag(FLAG_I, true);
-        self.pc = self.read_word(0xFFFA, bus);
-        self.cycles += 7;
-    }
 
-    pub fn trigger_irq(&mut self, bus: &mut dyn CpuBus) {
-        if !self.get_flag(FLAG_I) {
-            self.push_word(self.pc, bus);
-            self.push(self.status | FLAG_B | FLAG_U, bus);
-            self.set_flag(FLAG_I, true);
-            self.pc = self.read_word(0xFFFE, bus);
-            self.cycles += 7;
-        }
+        let vector = if self.nmi_pending {
+            self.nmi_pending = false;
+            0xFFFA
+        } else {
+            0xFFFE
+        };
+        let low = self.read(vector, bus) as u16;
+        let high = self.read(vector + 1, bus) as u16;
+        self.pc = (high << 8) | low;
     }
 
-    fn handle_interrupt(&mut self, interrupt: Interrupt, bus: &mut dyn CpuBus) -> u64 {
-        match interrupt {
-            Interrupt::Nmi => {
-                self.trigger_nmi(bus);
-                7
-            }
-            Interrupt::Irq => {
-                self.trigger_irq(bus);
-                7
-            }
-            _ => 0,
+    /// OAM DMA: one halt cycle, one more if needed to line up with a read
+    /// cycle, then 256 alternating reads from the page and writes to $2004
+    fn oam_dma(&mut self, page: u8, bus: &mut dyn CpuBus) {
+        self.dummy_read(bus);
+        if self.cycles & 1 != 0 {
+            self.dummy_read(bus);
+        }
+        let base = (page as u16) << 8;
+        for offset in 0..256 {
+            let value = self.read(base | offset, bus);
+            self.write(0x2004, value, bus);
         }
     }
 
-    fn execute(&mut self, opcode: u8, bus: &mut dyn CpuBus) -> u64 {
+    fn execute(&mut self, opcode: u8, bus: &mut dyn CpuBus) {
         match opcode {
             // BRK
             0x00 => {
-                self.pc = self.pc.wrapping_add(1);
-                self.push_word(self.pc, bus);
-                self.push(self.status | FLAG_B | FLAG_U, bus);
-                self.set_flag(FLAG_I, true);
-                self.pc = self.read_word(0xFFFE, bus);
-                7
+                // The byte after BRK is fetched and skipped
+                self.fetch(bus);
+                self.interrupt_sequence(self.status | FLAG_B | FLAG_U, bus);
             }
             // ORA (indirect, X)
-            0x01 => {
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                self.ora(value);
-                6 + extra_cycle
-            }
+            0x01 => self.read_op(Mode::IndirectX, Cpu::ora, bus),
+            // *SLO (indirect, X)
+            0x03 => self.rmw_op(Mode::IndirectX, Cpu::slo, bus),
+            // *NOP (zero page)
+            0x04 => self.read_op(Mode::ZeroPage, Cpu::nop, bus),
             // ORA (zero page)
-            0x05 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.ora(value);
-                3
-            }
+            0x05 => self.read_op(Mode::ZeroPage, Cpu::ora, bus),
             // ASL (zero page)
-            0x06 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let result = self.asl(value);
-                bus.write(addr, result);
-                5
-            }
+            0x06 => self.rmw_op(Mode::ZeroPage, Cpu::asl, bus),
+            // *SLO (zero page)
+            0x07 => self.rmw_op(Mode::ZeroPage, Cpu::slo, bus),
             // PHP
             0x08 => {
+                self.dummy_read(bus);
                 self.push(self.status | FLAG_B | FLAG_U, bus);
-                3
             }
             // ORA (immediate)
-            0x09 => {
-                let value = self.addr_immediate(bus);
-                self.ora(value);
-                2
-            }
+            0x09 => self.immediate_op(Cpu::ora, bus),
             // ASL A
-            0x0A => {
-                self.a = self.asl(self.a);
-                2
-            }
+            0x0A => self.accumulator_op(Cpu::asl, bus),
+            // *ANC (immediate)
+            0x0B => self.immediate_op(Cpu::anc, bus),
+            // *NOP (absolute)
+            0x0C => self.read_op(Mode::Absolute, Cpu::nop, bus),
             // ORA (absolute)
-            0x0D => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.ora(value);
-                4
-            }
+            0x0D => self.read_op(Mode::Absolute, Cpu::ora, bus),
             // ASL (absolute)
-            0x0E => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let result = self.asl(value);
-                bus.write(addr, result);
-                6
-            }
+            0x0E => self.rmw_op(Mode::Absolute, Cpu::asl, bus),
+            // *SLO (absolute)
+            0x0F => self.rmw_op(Mode::Absolute, Cpu::slo, bus),
             // BPL
             0x10 => self.branch(!self.get_flag(FLAG_N), bus),
             // ORA (indirect), Y
-            0x11 => {
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                self.ora(value);
-                5 + extra_cycle
-            }
+            0x11 => self.read_op(Mode::IndirectY, Cpu::ora, bus),
+            // *SLO (indirect), Y
+            0x13 => self.rmw_op(Mode::IndirectY, Cpu::slo, bus),
+            // *NOP (zero page, X)
+            0x14 => self.read_op(Mode::ZeroPageX, Cpu::nop, bus),
             // ORA (zero page, X)
-            0x15 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                self.ora(value);
-                4
-            }
+            0x15 => self.read_op(Mode::ZeroPageX, Cpu::ora, bus),
             // ASL (zero page, X)
-            0x16 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let result = self.asl(value);
-                bus.write(addr, result);
-                6
-            }
+            0x16 => self.rmw_op(Mode::ZeroPageX, Cpu::asl, bus),
+            // *SLO (zero page, X)
+            0x17 => self.rmw_op(Mode::ZeroPageX, Cpu::slo, bus),
             // CLC
             0x18 => {
+                self.dummy_read(bus);
                 self.set_flag(FLAG_C, false);
-                2
             }
             // ORA (absolute, Y)
-            0x19 => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                self.ora(value);
-                4 + extra_cycle
-            }
+            0x19 => self.read_op(Mode::AbsoluteY, Cpu::ora, bus),
+            // *NOP
+            0x1A => self.dummy_read(bus),
+            // *SLO (absolute, Y)
+            0x1B => self.rmw_op(Mode::AbsoluteY, Cpu::slo, bus),
+            // *NOP (absolute, X)
+            0x1C => self.read_op(Mode::AbsoluteX, Cpu::nop, bus),
             // ORA (absolute, X)
-            0x1D => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                self.ora(value);
-                4 + extra_cycle
-            }
+            0x1D => self.read_op(Mode::AbsoluteX, Cpu::ora, bus),
             // ASL (absolute, X)
-            0x1E => {
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let result = self.asl(value);
-                bus.write(addr, result);
-                7
-            }
+            0x1E => self.rmw_op(Mode::AbsoluteX, Cpu::asl, bus),
+            // *SLO (absolute, X)
+            0x1F => self.rmw_op(Mode::AbsoluteX, Cpu::slo, bus),
             // JSR
             0x20 => {
-                let addr = self.addr_absolute(bus);
-                self.push_word(self.pc.wrapping_sub(1), bus);
-                self.pc = addr;
-                6
+                let low = self.fetch(bus) as u16;
+                self.dummy_stack_read(bus);
+                self.push_word(self.pc, bus);
+                let high = self.read(self.pc, bus) as u16;
+                self.pc = (high << 8) | low;
             }
             // AND (indirect, X)
-            0x21 => {
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                self.and(value);
-                6 + extra_cycle
-            }
+            0x21 => self.read_op(Mode::IndirectX, Cpu::and, bus),
+            // *RLA (indirect, X)
+            0x23 => self.rmw_op(Mode::IndirectX, Cpu::rla, bus),
             // BIT (zero page)
-            0x24 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.bit(value);
-                3
-            }
+            0x24 => self.read_op(Mode::ZeroPage, Cpu::bit, bus),
             // AND (zero page)
-            0x25 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.and(value);
-                3
-            }
+            0x25 => self.read_op(Mode::ZeroPage, Cpu::and, bus),
             // ROL (zero page)
-            0x26 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let result = self.rol(value);
-                bus.write(addr, result);
-                5
-            }
+            0x26 => self.rmw_op(Mode::ZeroPage, Cpu::rol, bus),
+            // *RLA (zero page)
+            0x27 => self.rmw_op(Mode::ZeroPage, Cpu::rla, bus),
             // PLP
             0x28 => {
+                self.dummy_read(bus);
+                self.dummy_stack_read(bus);
                 self.status = (self.pop(bus) & !FLAG_B) | FLAG_U;
-                4
             }
             // AND (immediate)
-            0x29 => {
-                let value = self.addr_immediate(bus);
-                self.and(value);
-                2
-            }
+            0x29 => self.immediate_op(Cpu::and, bus),
             // ROL A
-            0x2A => {
-                self.a = self.rol(self.a);
-                2
-            }
+            0x2A => self.accumulator_op(Cpu::rol, bus),
+            // *ANC (immediate)
+            0x2B => self.immediate_op(Cpu::anc, bus),
             // BIT (absolute)
-            0x2C => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.bit(value);
-                4
-            }
+            0x2C => self.read_op(Mode::Absolute, Cpu::bit, bus),
             // AND (absolute)
-            0x2D => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.and(value);
-                4
-            }
+            0x2D => self.read_op(Mode::Absolute, Cpu::and, bus),
             // ROL (absolute)
-            0x2E => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let result = self.rol(value);
-                bus.write(addr, result);
-                6
-            }
+            0x2E => self.rmw_op(Mode::Absolute, Cpu::rol, bus),
+            // *RLA (absolute)
+            0x2F => self.rmw_op(Mode::Absolute, Cpu::rla, bus),
             // BMI
             0x30 => self.branch(self.get_flag(FLAG_N), bus),
             // AND (indirect), Y
-            0x31 => {
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                self.and(value);
-                5 + extra_cycle
-            }
+            0x31 => self.read_op(Mode::IndirectY, Cpu::and, bus),
+            // *RLA (indirect), Y
+            0x33 => self.rmw_op(Mode::IndirectY, Cpu::rla, bus),
+            // *NOP (zero page, X)
+            0x34 => self.read_op(Mode::ZeroPageX, Cpu::nop, bus),
             // AND (zero page, X)
-            0x35 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                self.and(value);
-                4
-            }
+            0x35 => self.read_op(Mode::ZeroPageX, Cpu::and, bus),
             // ROL (zero page, X)
-            0x36 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let result = self.rol(value);
-                bus.write(addr, result);
-                6
-            }
+            0x36 => self.rmw_op(Mode::ZeroPageX, Cpu::rol, bus),
+            // *RLA (zero page, X)
+            0x37 => self.rmw_op(Mode::ZeroPageX, Cpu::rla, bus),
             // SEC
             0x38 => {
+                self.dummy_read(bus);
                 self.set_flag(FLAG_C, true);
-                2
             }
             // AND (absolute, Y)
-            0x39 => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                self.and(value);
-                4 + extra_cycle
-            }
+            0x39 => self.read_op(Mode::AbsoluteY, Cpu::and, bus),
+            // *NOP
+            0x3A => self.dummy_read(bus),
+            // *RLA (absolute, Y)
+            0x3B => self.rmw_op(Mode::AbsoluteY, Cpu::rla, bus),
+            // *NOP (absolute, X)
+            0x3C => self.read_op(Mode::AbsoluteX, Cpu::nop, bus),
             // AND (absolute, X)
-            0x3D => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                self.and(value);
-                4 + extra_cycle
-            }
+            0x3D => self.read_op(Mode::AbsoluteX, Cpu::and, bus),
             // ROL (absolute, X)
-            0x3E => {
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let result = self.rol(value);
-                bus.write(addr, result);
-                7
-            }
+            0x3E => self.rmw_op(Mode::AbsoluteX, Cpu::rol, bus),
+            // *RLA (absolute, X)
+            0x3F => self.rmw_op(Mode::AbsoluteX, Cpu::rla, bus),
             // RTI
             0x40 => {
+                self.dummy_read(bus);
+                self.dummy_stack_read(bus);
                 self.status = (self.pop(bus) & !FLAG_B) | FLAG_U;
                 self.pc = self.pop_word(bus);
-                6
             }
             // EOR (indirect, X)
-            0x41 => {
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                self.eor(value);
-                6 + extra_cycle
-            }
+            0x41 => self.read_op(Mode::IndirectX, Cpu::eor, bus),
+            // *SRE (indirect, X)
+            0x43 => self.rmw_op(Mode::IndirectX, Cpu::sre, bus),
+            // *NOP (zero page)
+            0x44 => self.read_op(Mode::ZeroPage, Cpu::nop, bus),
             // EOR (zero page)
-            0x45 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.eor(value);
-                3
-            }
+            0x45 => self.read_op(Mode::ZeroPage, Cpu::eor, bus),
             // LSR (zero page)
-            0x46 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let result = self.lsr(value);
-                bus.write(addr, result);
-                5
-            }
+            0x46 => self.rmw_op(Mode::ZeroPage, Cpu::lsr, bus),
+            // *SRE (zero page)
+            0x47 => self.rmw_op(Mode::ZeroPage, Cpu::sre, bus),
             // PHA
             0x48 => {
+                self.dummy_read(bus);
                 self.push(self.a, bus);
-                3
             }
             // EOR (immediate)
-            0x49 => {
-                let value = self.addr_immediate(bus);
-                self.eor(value);
-                2
-            }
+            0x49 => self.immediate_op(Cpu::eor, bus),
             // LSR A
-            0x4A => {
-                self.a = self.lsr(self.a);
-                2
-            }
+            0x4A => self.accumulator_op(Cpu::lsr, bus),
+            // *ALR (immediate)
+            0x4B => self.immediate_op(Cpu::alr, bus),
             // JMP (absolute)
-            0x4C => {
-                self.pc = self.addr_absolute(bus);
-                3
-            }
+            0x4C => self.pc = self.fetch_word(bus),
             // EOR (absolute)
-            0x4D => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.eor(value);
-                4
-            }
+            0x4D => self.read_op(Mode::Absolute, Cpu::eor, bus),
             // LSR (absolute)
-            0x4E => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let result = self.lsr(value);
-                bus.write(addr, result);
-                6
-            }
+            0x4E => self.rmw_op(Mode::Absolute, Cpu::lsr, bus),
+            // *SRE (absolute)
+            0x4F => self.rmw_op(Mode::Absolute, Cpu::sre, bus),
             // BVC
             0x50 => self.branch(!self.get_flag(FLAG_V), bus),
             // EOR (indirect), Y
-            0x51 => {
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                self.eor(value);
-                5 + extra_cycle
-            }
+            0x51 => self.read_op(Mode::IndirectY, Cpu::eor, bus),
+            // *SRE (indirect), Y
+            0x53 => self.rmw_op(Mode::IndirectY, Cpu::sre, bus),
+            // *NOP (zero page, X)
+            0x54 => self.read_op(Mode::ZeroPageX, Cpu::nop, bus),
             // EOR (zero page, X)
-            0x55 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                self.eor(value);
-                4
-            }
+            0x55 => self.read_op(Mode::ZeroPageX, Cpu::eor, bus),
             // LSR (zero page, X)
-            0x56 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let result = self.lsr(value);
-                bus.write(addr, result);
-                6
-            }
+            0x56 => self.rmw_op(Mode::ZeroPageX, Cpu::lsr, bus),
+            // *SRE (zero page, X)
+            0x57 => self.rmw_op(Mode::ZeroPageX, Cpu::sre, bus),
             // CLI
             0x58 => {
+                self.dummy_read(bus);
                 self.set_flag(FLAG_I, false);
-                2
             }
             // EOR (absolute, Y)
-            0x59 => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                self.eor(value);
-                4 + extra_cycle
-            }
+            0x59 => self.read_op(Mode::AbsoluteY, Cpu::eor, bus),
+            // *NOP
+            0x5A => self.dummy_read(bus),
+            // *SRE (absolute, Y)
+            0x5B => self.rmw_op(Mode::AbsoluteY, Cpu::sre, bus),
+            // *NOP (absolute, X)
+            0x5C => self.read_op(Mode::AbsoluteX, Cpu::nop, bus),
             // EOR (absolute, X)
-            0x5D => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                self.eor(value);
-                4 + extra_cycle
-            }
+            0x5D => self.read_op(Mode::AbsoluteX, Cpu::eor, bus),
             // LSR (absolute, X)
-            0x5E => {
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let result = self.lsr(value);
-                bus.write(addr, result);
-                7
-            }
+            0x5E => self.rmw_op(Mode::AbsoluteX, Cpu::lsr, bus),
+            // *SRE (absolute, X)
+            0x5F => self.rmw_op(Mode::AbsoluteX, Cpu::sre, bus),
             // RTS
             0x60 => {
-                self.pc = self.pop_word(bus).wrapping_add(1);
-                6
+                self.dummy_read(bus);
+                self.dummy_stack_read(bus);
+                self.pc = self.pop_word(bus);
+                self.fetch(bus);
             }
             // ADC (indirect, X)
-            0x61 => {
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                self.adc(value);
-                6 + extra_cycle
-            }
+            0x61 => self.read_op(Mode::IndirectX, Cpu::adc, bus),
+            // *RRA (indirect, X)
+            0x63 => self.rmw_op(Mode::IndirectX, Cpu::rra, bus),
+            // *NOP (zero page)
+            0x64 => self.read_op(Mode::ZeroPage, Cpu::nop, bus),
             // ADC (zero page)
-            0x65 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.adc(value);
-                3
-            }
+            0x65 => self.read_op(Mode::ZeroPage, Cpu::adc, bus),
             // ROR (zero page)
-            0x66 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let result = self.ror(value);
-                bus.write(addr, result);
-                5
-            }
+            0x66 => self.rmw_op(Mode::ZeroPage, Cpu::ror, bus),
+            // *RRA (zero page)
+            0x67 => self.rmw_op(Mode::ZeroPage, Cpu::rra, bus),
             // PLA
             0x68 => {
-                self.a = self.pop(bus);
-                self.update_zero_negative(self.a);
-                4
+                self.dummy_read(bus);
+                self.dummy_stack_read(bus);
+                let value = self.pop(bus);
+                self.lda(value);
             }
             // ADC (immediate)
-            0x69 => {
-                let value = self.addr_immediate(bus);
-                self.adc(value);
-                2
-            }
+            0x69 => self.immediate_op(Cpu::adc, bus),
             // ROR A
-            0x6A => {
-                self.a = self.ror(self.a);
-                2
-            }
+            0x6A => self.accumulator_op(Cpu::ror, bus),
+            // *ARR (immediate)
+            0x6B => self.immediate_op(Cpu::arr, bus),
             // JMP (indirect)
             0x6C => {
-                let indirect_addr = self.addr_absolute(bus);
-                // 6502 bug: doesn't increment page on indirect jump
-                let addr = if (indirect_addr & 0xFF) == 0xFF {
-                    (bus.read(indirect_addr) as u16)
-                        | ((bus.read(indirect_addr & 0xFF00) as u16) << 8)
-                } else {
-                    self.read_word(indirect_addr, bus)
-                };
-                self.pc = addr;
-                5
+                let ptr = self.fetch_word(bus);
+                let low = self.read(ptr, bus) as u16;
+                // The pointer's high byte is read without carrying into the page
+                let high = self.read((ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF), bus) as u16;
+                self.pc = (high << 8) | low;
             }
             // ADC (absolute)
-            0x6D => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.adc(value);
-                4
-            }
+            0x6D => self.read_op(Mode::Absolute, Cpu::adc, bus),
             // ROR (absolute)
-            0x6E => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let result = self.ror(value);
-                bus.write(addr, result);
-                6
-            }
+            0x6E => self.rmw_op(Mode::Absolute, Cpu::ror, bus),
+            // *RRA (absolute)
+            0x6F => self.rmw_op(Mode::Absolute, Cpu::rra, bus),
             // BVS
             0x70 => self.branch(self.get_flag(FLAG_V), bus),
             // ADC (indirect), Y
-            0x71 => {
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                self.adc(value);
-                5 + extra_cycle
-            }
+            0x71 => self.read_op(Mode::IndirectY, Cpu::adc, bus),
+            // *RRA (indirect), Y
+            0x73 => self.rmw_op(Mode::IndirectY, Cpu::rra, bus),
+            // *NOP (zero page, X)
+            0x74 => self.read_op(Mode::ZeroPageX, Cpu::nop, bus),
             // ADC (zero page, X)
-            0x75 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                self.adc(value);
-                4
-            }
+            0x75 => self.read_op(Mode::ZeroPageX, Cpu::adc, bus),
             // ROR (zero page, X)
-            0x76 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let result = self.ror(value);
-                bus.write(addr, result);
-                6
-            }
+            0x76 => self.rmw_op(Mode::ZeroPageX, Cpu::ror, bus),
+            // *RRA (zero page, X)
+            0x77 => self.rmw_op(Mode::ZeroPageX, Cpu::rra, bus),
             // SEI
             0x78 => {
+                self.dummy_read(bus);
                 self.set_flag(FLAG_I, true);
-                2
             }
             // ADC (absolute, Y)
-            0x79 => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                self.adc(value);
-                4 + extra_cycle
-            }
+            0x79 => self.read_op(Mode::AbsoluteY, Cpu::adc, bus),
+            // *NOP
+            0x7A => self.dummy_read(bus),
+            // *RRA (absolute, Y)
+            0x7B => self.rmw_op(Mode::AbsoluteY, Cpu::rra, bus),
+            // *NOP (absolute, X)
+            0x7C => self.read_op(Mode::AbsoluteX, Cpu::nop, bus),
             // ADC (absolute, X)
-            0x7D => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                self.adc(value);
-                4 + extra_cycle
-            }
+            0x7D => self.read_op(Mode::AbsoluteX, Cpu::adc, bus),
             // ROR (absolute, X)
-            0x7E => {
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let result = self.ror(value);
-                bus.write(addr, result);
-                7
-            }
+            0x7E => self.rmw_op(Mode::AbsoluteX, Cpu::ror, bus),
+            // *RRA (absolute, X)
+            0x7F => self.rmw_op(Mode::AbsoluteX, Cpu::rra, bus),
+            // *NOP (immediate)
+            0x80 => self.immediate_op(Cpu::nop, bus),
             // STA (indirect, X)
-            0x81 => {
-                let (addr, _) = self.addr_indirect_x(bus);
-                self.handle_write_with_oamdma(bus, addr, self.a);
-                6
-            }
+            0x81 => self.write_op(Mode::IndirectX, self.a, bus),
+            // *NOP (immediate)
+            0x82 => self.immediate_op(Cpu::nop, bus),
+            // *SAX (indirect, X)
+            0x83 => self.write_op(Mode::IndirectX, self.a & self.x, bus),
             // STY (zero page)
-            0x84 => {
-                let addr = self.addr_zero_page(bus);
-                bus.write(addr, self.y);
-                3
-            }
+            0x84 => self.write_op(Mode::ZeroPage, self.y, bus),
             // STA (zero page)
-            0x85 => {
-                let addr = self.addr_zero_page(bus);
-                self.handle_write_with_oamdma(bus, addr, self.a);
-                3
-            }
+            0x85 => self.write_op(Mode::ZeroPage, self.a, bus),
             // STX (zero page)
-            0x86 => {
-                let addr = self.addr_zero_page(bus);
-                bus.write(addr, self.x);
-                3
-            }
+            0x86 => self.write_op(Mode::ZeroPage, self.x, bus),
+            // *SAX (zero page)
+            0x87 => self.write_op(Mode::ZeroPage, self.a & self.x, bus),
             // DEY
             0x88 => {
+                self.dummy_read(bus);
                 self.y = self.y.wrapping_sub(1);
                 self.update_zero_negative(self.y);
-                2
             }
+            // *NOP (immediate)
+            0x89 => self.immediate_op(Cpu::nop, bus),
             // TXA
             0x8A => {
+                self.dummy_read(bus);
                 self.a = self.x;
                 self.update_zero_negative(self.a);
-                2
             }
+            // *XAA (immediate)
+            0x8B => self.immediate_op(Cpu::xaa, bus),
             // STY (absolute)
-            0x8C => {
-                let addr = self.addr_absolute(bus);
-                bus.write(addr, self.y);
-                4
-            }
+            0x8C => self.write_op(Mode::Absolute, self.y, bus),
             // STA (absolute)
-            0x8D => {
-                let addr = self.addr_absolute(bus);
-                self.handle_write_with_oamdma(bus, addr, self.a);
-                4
-            }
+            0x8D => self.write_op(Mode::Absolute, self.a, bus),
             // STX (absolute)
-            0x8E => {
-                let addr = self.addr_absolute(bus);
-                bus.write(addr, self.x);
-                4
-            }
+            0x8E => self.write_op(Mode::Absolute, self.x, bus),
+            // *SAX (absolute)
+            0x8F => self.write_op(Mode::Absolute, self.a & self.x, bus),
             // BCC
             0x90 => self.branch(!self.get_flag(FLAG_C), bus),
             // STA (indirect), Y
-            0x91 => {
-                let (addr, _) = self.addr_indirect_y(bus);
-                self.handle_write_with_oamdma(bus, addr, self.a);
-                6
+            0x91 => self.write_op(Mode::IndirectY, self.a, bus),
+            // *SHA (indirect), Y
+            0x93 => {
+                let base = self.indirect_y_base(bus);
+                self.unstable_store(base, self.y, self.a & self.x, bus);
             }
             // STY (zero page, X)
-            0x94 => {
-                let addr = self.addr_zero_page_x(bus);
-                bus.write(addr, self.y);
-                4
-            }
+            0x94 => self.write_op(Mode::ZeroPageX, self.y, bus),
             // STA (zero page, X)
-            0x95 => {
-                let addr = self.addr_zero_page_x(bus);
-                self.handle_write_with_oamdma(bus, addr, self.a);
-                4
-            }
+            0x95 => self.write_op(Mode::ZeroPageX, self.a, bus),
             // STX (zero page, Y)
-            0x96 => {
-                let addr = self.addr_zero_page_y(bus);
-                bus.write(addr, self.x);
-                4
-            }
+            0x96 => self.write_op(Mode::ZeroPageY, self.x, bus),
+            // *SAX (zero page, Y)
+            0x97 => self.write_op(Mode::ZeroPageY, self.a & self.x, bus),
             // TYA
             0x98 => {
+                self.dummy_read(bus);
                 self.a = self.y;
                 self.update_zero_negative(self.a);
-                2
             }
             // STA (absolute, Y)
-            0x99 => {
-                let (addr, _) = self.addr_absolute_y(bus);
-                self.handle_write_with_oamdma(bus, addr, self.a);
-                5
-            }
+            0x99 => self.write_op(Mode::AbsoluteY, self.a, bus),
             // TXS
             0x9A => {
+                self.dummy_read(bus);
                 self.sp = self.x;
-                2
+            }
+            // *TAS (absolute, Y)
+            0x9B => {
+                let base = self.fetch_word(bus);
+                self.sp = self.a & self.x;
+                self.unstable_store(base, self.y, self.sp, bus);
+            }
+            // *SHY (absolute, X)
+            0x9C => {
+                let base = self.fetch_word(bus);
+                self.unstable_store(base, self.x, self.y, bus);
             }
             // STA (absolute, X)
-            0x9D => {
-                let (addr, _) = self.addr_absolute_x(bus);
-                self.handle_write_with_oamdma(bus, addr, self.a);
-                5
+            0x9D => self.write_op(Mode::AbsoluteX, self.a, bus),
+            // *SHX (absolute, Y)
+            0x9E => {
+                let base = self.fetch_word(bus);
+                self.unstable_store(base, self.y, self.x, bus);
             }
-            // LDY (immediate)
-            0xA0 => {
-                self.y = self.addr_immediate(bus);
-                self.update_zero_negative(self.y);
-                2
+            // *SHA (absolute, Y)
+            0x9F => {
+                let base = self.fetch_word(bus);
+                self.unstable_store(base, self.y, self.a & self.x, bus);
             }
+            // LDY (immediate)
+            0xA0 => self.immediate_op(Cpu::ldy, bus),
             // LDA (indirect, X)
-            0xA1 => {
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                self.a = bus.read(addr);
-                self.update_zero_negative(self.a);
-                6 + extra_cycle
-            }
+            0xA1 => self.read_op(Mode::IndirectX, Cpu::lda, bus),
             // LDX (immediate)
-            0xA2 => {
-                self.x = self.addr_immediate(bus);
-                self.update_zero_negative(self.x);
-                2
-            }
+            0xA2 => self.immediate_op(Cpu::ldx, bus),
+            // *LAX (indirect, X)
+            0xA3 => self.read_op(Mode::IndirectX, Cpu::lax, bus),
             // LDY (zero page)
-            0xA4 => {
-                let addr = self.addr_zero_page(bus);
-                self.y = bus.read(addr);
-                self.update_zero_negative(self.y);
-                3
-            }
+            0xA4 => self.read_op(Mode::ZeroPage, Cpu::ldy, bus),
             // LDA (zero page)
-            0xA5 => {
-                let addr = self.addr_zero_page(bus);
-                self.a = bus.read(addr);
-                self.update_zero_negative(self.a);
-                3
-            }
+            0xA5 => self.read_op(Mode::ZeroPage, Cpu::lda, bus),
             // LDX (zero page)
-            0xA6 => {
-                let addr = self.addr_zero_page(bus);
-                self.x = bus.read(addr);
-                self.update_zero_negative(self.x);
-                3
-            }
+            0xA6 => self.read_op(Mode::ZeroPage, Cpu::ldx, bus),
+            // *LAX (zero page)
+            0xA7 => self.read_op(Mode::ZeroPage, Cpu::lax, bus),
             // TAY
             0xA8 => {
+                self.dummy_read(bus);
                 self.y = self.a;
                 self.update_zero_negative(self.y);
-                2
             }
             // LDA (immediate)
-            0xA9 => {
-                self.a = self.addr_immediate(bus);
-                self.update_zero_negative(self.a);
-                2
-            }
+            0xA9 => self.immediate_op(Cpu::lda, bus),
             // TAX
             0xAA => {
+                self.dummy_read(bus);
                 self.x = self.a;
                 self.update_zero_negative(self.x);
-                2
             }
+            // *LAX (immediate)
+            0xAB => self.immediate_op(Cpu::lxa, bus),
             // LDY (absolute)
-            0xAC => {
-                let addr = self.addr_absolute(bus);
-                self.y = bus.read(addr);
-                self.update_zero_negative(self.y);
-                4
-            }
+            0xAC => self.read_op(Mode::Absolute, Cpu::ldy, bus),
             // LDA (absolute)
-            0xAD => {
-                let addr = self.addr_absolute(bus);
-                self.a = bus.read(addr);
-                self.update_zero_negative(self.a);
-                4
-            }
+            0xAD => self.read_op(Mode::Absolute, Cpu::lda, bus),
             // LDX (absolute)
-            0xAE => {
-                let addr = self.addr_absolute(bus);
-                self.x = bus.read(addr);
-                self.update_zero_negative(self.x);
-                4
-            }
+            0xAE => self.read_op(Mode::Absolute, Cpu::ldx, bus),
+            // *LAX (absolute)
+            0xAF => self.read_op(Mode::Absolute, Cpu::lax, bus),
             // BCS
             0xB0 => self.branch(self.get_flag(FLAG_C), bus),
             // LDA (indirect), Y
-            0xB1 => {
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                self.a = bus.read(addr);
-                self.update_zero_negative(self.a);
-                5 + extra_cycle
-            }
+            0xB1 => self.read_op(Mode::IndirectY, Cpu::lda, bus),
+            // *LAX (indirect), Y
+            0xB3 => self.read_op(Mode::IndirectY, Cpu::lax, bus),
             // LDY (zero page, X)
-            0xB4 => {
-                let addr = self.addr_zero_page_x(bus);
-                self.y = bus.read(addr);
-                self.update_zero_negative(self.y);
-                4
-            }
+            0xB4 => self.read_op(Mode::ZeroPageX, Cpu::ldy, bus),
             // LDA (zero page, X)
-            0xB5 => {
-                let addr = self.addr_zero_page_x(bus);
-                self.a = bus.read(addr);
-                self.update_zero_negative(self.a);
-                4
-            }
+            0xB5 => self.read_op(Mode::ZeroPageX, Cpu::lda, bus),
             // LDX (zero page, Y)
-            0xB6 => {
-                let addr = self.addr_zero_page_y(bus);
-                self.x = bus.read(addr);
-                self.update_zero_negative(self.x);
-                4
-            }
+            0xB6 => self.read_op(Mode::ZeroPageY, Cpu::ldx, bus),
+            // *LAX (zero page, Y)
+            0xB7 => self.read_op(Mode::ZeroPageY, Cpu::lax, bus),
             // CLV
             0xB8 => {
+                self.dummy_read(bus);
                 self.set_flag(FLAG_V, false);
-                2
             }
             // LDA (absolute, Y)
-            0xB9 => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                self.a = bus.read(addr);
-                self.update_zero_negative(self.a);
-                4 + extra_cycle
-            }
+            0xB9 => self.read_op(Mode::AbsoluteY, Cpu::lda, bus),
             // TSX
             0xBA => {
+                self.dummy_read(bus);
                 self.x = self.sp;
                 self.update_zero_negative(self.x);
-                2
             }
+            // *LAS (absolute, Y)
+            0xBB => self.read_op(Mode::AbsoluteY, Cpu::las, bus),
             // LDY (absolute, X)
-            0xBC => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                self.y = bus.read(addr);
-                self.update_zero_negative(self.y);
-                4 + extra_cycle
-            }
+            0xBC => self.read_op(Mode::AbsoluteX, Cpu::ldy, bus),
             // LDA (absolute, X)
-            0xBD => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                self.a = bus.read(addr);
-                self.update_zero_negative(self.a);
-                4 + extra_cycle
-            }
+            0xBD => self.read_op(Mode::AbsoluteX, Cpu::lda, bus),
             // LDX (absolute, Y)
-            0xBE => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                self.x = bus.read(addr);
-                self.update_zero_negative(self.x);
-                4 + extra_cycle
-            }
+            0xBE => self.read_op(Mode::AbsoluteY, Cpu::ldx, bus),
+            // *LAX (absolute, Y)
+            0xBF => self.read_op(Mode::AbsoluteY, Cpu::lax, bus),
             // CPY (immediate)
-            0xC0 => {
-                let value = self.addr_immediate(bus);
-                self.cpy(value);
-                2
-            }
+            0xC0 => self.immediate_op(Cpu::cpy, bus),
             // CMP (indirect, X)
-            0xC1 => {
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                self.cmp(value);
-                6 + extra_cycle
-            }
+            0xC1 => self.read_op(Mode::IndirectX, Cpu::cmp, bus),
+            // *NOP (immediate)
+            0xC2 => self.immediate_op(Cpu::nop, bus),
+            // *DCP (indirect, X)
+            0xC3 => self.rmw_op(Mode::IndirectX, Cpu::dcp, bus),
             // CPY (zero page)
-            0xC4 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.cpy(value);
-                3
-            }
+            0xC4 => self.read_op(Mode::ZeroPage, Cpu::cpy, bus),
             // CMP (zero page)
-            0xC5 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.cmp(value);
-                3
-            }
+            0xC5 => self.read_op(Mode::ZeroPage, Cpu::cmp, bus),
             // DEC (zero page)
-            0xC6 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                5
-            }
+            0xC6 => self.rmw_op(Mode::ZeroPage, Cpu::dec, bus),
+            // *DCP (zero page)
+            0xC7 => self.rmw_op(Mode::ZeroPage, Cpu::dcp, bus),
             // INY
             0xC8 => {
+                self.dummy_read(bus);
                 self.y = self.y.wrapping_add(1);
                 self.update_zero_negative(self.y);
-                2
             }
             // CMP (immediate)
-            0xC9 => {
-                let value = self.addr_immediate(bus);
-                self.cmp(value);
-                2
-            }
+            0xC9 => self.immediate_op(Cpu::cmp, bus),
             // DEX
             0xCA => {
+                self.dummy_read(bus);
                 self.x = self.x.wrapping_sub(1);
                 self.update_zero_negative(self.x);
-                2
             }
+            // *AXS (immediate)
+            0xCB => self.immediate_op(Cpu::axs, bus),
             // CPY (absolute)
-            0xCC => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.cpy(value);
-                4
-            }
+            0xCC => self.read_op(Mode::Absolute, Cpu::cpy, bus),
             // CMP (absolute)
-            0xCD => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.cmp(value);
-                4
-            }
+            0xCD => self.read_op(Mode::Absolute, Cpu::cmp, bus),
             // DEC (absolute)
-            0xCE => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                6
-            }
+            0xCE => self.rmw_op(Mode::Absolute, Cpu::dec, bus),
+            // *DCP (absolute)
+            0xCF => self.rmw_op(Mode::Absolute, Cpu::dcp, bus),
             // BNE
             0xD0 => self.branch(!self.get_flag(FLAG_Z), bus),
             // CMP (indirect), Y
-            0xD1 => {
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                self.cmp(value);
-                5 + extra_cycle
-            }
+            0xD1 => self.read_op(Mode::IndirectY, Cpu::cmp, bus),
+            // *DCP (indirect), Y
+            0xD3 => self.rmw_op(Mode::IndirectY, Cpu::dcp, bus),
+            // *NOP (zero page, X)
+            0xD4 => self.read_op(Mode::ZeroPageX, Cpu::nop, bus),
             // CMP (zero page, X)
-            0xD5 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                self.cmp(value);
-                4
-            }
+            0xD5 => self.read_op(Mode::ZeroPageX, Cpu::cmp, bus),
             // DEC (zero page, X)
-            0xD6 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                6
-            }
+            0xD6 => self.rmw_op(Mode::ZeroPageX, Cpu::dec, bus),
+            // *DCP (zero page, X)
+            0xD7 => self.rmw_op(Mode::ZeroPageX, Cpu::dcp, bus),
             // CLD
             0xD8 => {
+                self.dummy_read(bus);
                 self.set_flag(FLAG_D, false);
-                2
             }
             // CMP (absolute, Y)
-            0xD9 => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                self.cmp(value);
-                4 + extra_cycle
-            }
+            0xD9 => self.read_op(Mode::AbsoluteY, Cpu::cmp, bus),
+            // *NOP
+            0xDA => self.dummy_read(bus),
+            // *DCP (absolute, Y)
+            0xDB => self.rmw_op(Mode::AbsoluteY, Cpu::dcp, bus),
+            // *NOP (absolute, X)
+            0xDC => self.read_op(Mode::AbsoluteX, Cpu::nop, bus),
             // CMP (absolute, X)
-            0xDD => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                self.cmp(value);
-                4 + extra_cycle
-            }
+            0xDD => self.read_op(Mode::AbsoluteX, Cpu::cmp, bus),
             // DEC (absolute, X)
-            0xDE => {
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                7
-            }
+            0xDE => self.rmw_op(Mode::AbsoluteX, Cpu::dec, bus),
+            // *DCP (absolute, X)
+            0xDF => self.rmw_op(Mode::AbsoluteX, Cpu::dcp, bus),
             // CPX (immediate)
-            0xE0 => {
-                let value = self.addr_immediate(bus);
-                self.cpx(value);
-                2
-            }
+            0xE0 => self.immediate_op(Cpu::cpx, bus),
             // SBC (indirect, X)
-            0xE1 => {
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                self.sbc(value);
-                6 + extra_cycle
-            }
+            0xE1 => self.read_op(Mode::IndirectX, Cpu::sbc, bus),
+            // *NOP (immediate)
+            0xE2 => self.immediate_op(Cpu::nop, bus),
+            // *ISC (indirect, X)
+            0xE3 => self.rmw_op(Mode::IndirectX, Cpu::isc, bus),
             // CPX (zero page)
-            0xE4 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.cpx(value);
-                3
-            }
+            0xE4 => self.read_op(Mode::ZeroPage, Cpu::cpx, bus),
             // SBC (zero page)
-            0xE5 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.sbc(value);
-                3
-            }
+            0xE5 => self.read_op(Mode::ZeroPage, Cpu::sbc, bus),
             // INC (zero page)
-            0xE6 => {
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                5
-            }
+            0xE6 => self.rmw_op(Mode::ZeroPage, Cpu::inc, bus),
+            // *ISC (zero page)
+            0xE7 => self.rmw_op(Mode::ZeroPage, Cpu::isc, bus),
             // INX
             0xE8 => {
+                self.dummy_read(bus);
                 self.x = self.x.wrapping_add(1);
                 self.update_zero_negative(self.x);
-                2
             }
             // SBC (immediate)
-            0xE9 => {
-                let value = self.addr_immediate(bus);
-                self.sbc(value);
-                2
-            }
+            0xE9 => self.immediate_op(Cpu::sbc, bus),
             // NOP
-            0xEA => 2,
+            0xEA => self.dummy_read(bus),
+            // *SBC (immediate)
+            0xEB => self.immediate_op(Cpu::sbc, bus),
             // CPX (absolute)
-            0xEC => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.cpx(value);
-                4
-            }
+            0xEC => self.read_op(Mode::Absolute, Cpu::cpx, bus),
             // SBC (absolute)
-            0xED => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.sbc(value);
-                4
-            }
+            0xED => self.read_op(Mode::Absolute, Cpu::sbc, bus),
             // INC (absolute)
-            0xEE => {
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                6
-            }
+            0xEE => self.rmw_op(Mode::Absolute, Cpu::inc, bus),
+            // *ISC (absolute)
+            0xEF => self.rmw_op(Mode::Absolute, Cpu::isc, bus),
             // BEQ
             0xF0 => self.branch(self.get_flag(FLAG_Z), bus),
             // SBC (indirect), Y
-            0xF1 => {
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                self.sbc(value);
-                5 + extra_cycle
-            }
+            0xF1 => self.read_op(Mode::IndirectY, Cpu::sbc, bus),
+            // *ISC (indirect), Y
+            0xF3 => self.rmw_op(Mode::IndirectY, Cpu::isc, bus),
+            // *NOP (zero page, X)
+            0xF4 => self.read_op(Mode::ZeroPageX, Cpu::nop, bus),
             // SBC (zero page, X)
-            0xF5 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                self.sbc(value);
-                4
-            }
+            0xF5 => self.read_op(Mode::ZeroPageX, Cpu::sbc, bus),
             // INC (zero page, X)
-            0xF6 => {
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                6
-            }
+            0xF6 => self.rmw_op(Mode::ZeroPageX, Cpu::inc, bus),
+            // *ISC (zero page, X)
+            0xF7 => self.rmw_op(Mode::ZeroPageX, Cpu::isc, bus),
             // SED
             0xF8 => {
+                self.dummy_read(bus);
                 self.set_flag(FLAG_D, true);
-                2
             }
             // SBC (absolute, Y)
-            0xF9 => {
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                self.sbc(value);
-                4 + extra_cycle
-            }
+            0xF9 => self.read_op(Mode::AbsoluteY, Cpu::sbc, bus),
+            // *NOP
+            0xFA => self.dummy_read(bus),
+            // *ISC (absolute, Y)
+            0xFB => self.rmw_op(Mode::AbsoluteY, Cpu::isc, bus),
+            // *NOP (absolute, X)
+            0xFC => self.read_op(Mode::AbsoluteX, Cpu::nop, bus),
             // SBC (absolute, X)
-            0xFD => {
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                self.sbc(value);
-                4 + extra_cycle
-            }
+            0xFD => self.read_op(Mode::AbsoluteX, Cpu::sbc, bus),
             // INC (absolute, X)
-            0xFE => {
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.update_zero_negative(value);
-                7
-            }
-
-            // ============================================
-            // UNOFFICIAL/ILLEGAL OPCODES
-            // ============================================
-
-            // *SLO - ASL + ORA (Shift Left then OR with Accumulator)
-            0x03 => {
-                // (indirect, X)
-                let (addr, _) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                let shifted = self.asl(value);
-                bus.write(addr, shifted);
-                self.ora(shifted);
-                8
-            }
-            0x07 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let shifted = self.asl(value);
-                bus.write(addr, shifted);
-                self.ora(shifted);
-                5
-            }
-            0x0F => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let shifted = self.asl(value);
-                bus.write(addr, shifted);
-                self.ora(shifted);
-                6
-            }
-            0x13 => {
-                // (indirect), Y
-                let (addr, _) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                let shifted = self.asl(value);
-                bus.write(addr, shifted);
-                self.ora(shifted);
-                8
-            }
-            0x17 => {
-                // zero page, X
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let shifted = self.asl(value);
-                bus.write(addr, shifted);
-                self.ora(shifted);
-                6
-            }
-            0x1B => {
-                // absolute, Y
-                let (addr, _) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                let shifted = self.asl(value);
-                bus.write(addr, shifted);
-                self.ora(shifted);
-                7
-            }
-            0x1F => {
-                // absolute, X
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let shifted = self.asl(value);
-                bus.write(addr, shifted);
-                self.ora(shifted);
-                7
-            }
-
-            // *RLA - ROL + AND (Rotate Left then AND with Accumulator)
-            0x23 => {
-                // (indirect, X)
-                let (addr, _) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                let rotated = self.rol(value);
-                bus.write(addr, rotated);
-                self.and(rotated);
-                8
-            }
-            0x27 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let rotated = self.rol(value);
-                bus.write(addr, rotated);
-                self.and(rotated);
-                5
-            }
-            0x2F => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let rotated = self.rol(value);
-                bus.write(addr, rotated);
-                self.and(rotated);
-                6
-            }
-            0x33 => {
-                // (indirect), Y
-                let (addr, _) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                let rotated = self.rol(value);
-                bus.write(addr, rotated);
-                self.and(rotated);
-                8
-            }
-            0x37 => {
-                // zero page, X
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let rotated = self.rol(value);
-                bus.write(addr, rotated);
-                self.and(rotated);
-                6
-            }
-            0x3B => {
-                // absolute, Y
-                let (addr, _) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                let rotated = self.rol(value);
-                bus.write(addr, rotated);
-                self.and(rotated);
-                7
-            }
-            0x3F => {
-                // absolute, X
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let rotated = self.rol(value);
-                bus.write(addr, rotated);
-                self.and(rotated);
-                7
-            }
-
-            // *SRE - LSR + EOR (Shift Right then XOR with Accumulator)
-            0x43 => {
-                // (indirect, X)
-                let (addr, _) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                let shifted = self.lsr(value);
-                bus.write(addr, shifted);
-                self.eor(shifted);
-                8
-            }
-            0x47 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let shifted = self.lsr(value);
-                bus.write(addr, shifted);
-                self.eor(shifted);
-                5
-            }
-            0x4F => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let shifted = self.lsr(value);
-                bus.write(addr, shifted);
-                self.eor(shifted);
-                6
-            }
-            0x53 => {
-                // (indirect), Y
-                let (addr, _) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                let shifted = self.lsr(value);
-                bus.write(addr, shifted);
-                self.eor(shifted);
-                8
-            }
-            0x57 => {
-                // zero page, X
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let shifted = self.lsr(value);
-                bus.write(addr, shifted);
-                self.eor(shifted);
-                6
-            }
-            0x5B => {
-                // absolute, Y
-                let (addr, _) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                let shifted = self.lsr(value);
-                bus.write(addr, shifted);
-                self.eor(shifted);
-                7
-            }
-            0x5F => {
-                // absolute, X
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let shifted = self.lsr(value);
-                bus.write(addr, shifted);
-                self.eor(shifted);
-                7
-            }
-
-            // *RRA - ROR + ADC (Rotate Right then Add with Carry)
-            0x63 => {
-                // (indirect, X)
-                let (addr, _) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                let rotated = self.ror(value);
-                bus.write(addr, rotated);
-                self.adc(rotated);
-                8
-            }
-            0x67 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                let rotated = self.ror(value);
-                bus.write(addr, rotated);
-                self.adc(rotated);
-                5
-            }
-            0x6F => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                let rotated = self.ror(value);
-                bus.write(addr, rotated);
-                self.adc(rotated);
-                6
-            }
-            0x73 => {
-                // (indirect), Y
-                let (addr, _) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                let rotated = self.ror(value);
-                bus.write(addr, rotated);
-                self.adc(rotated);
-                8
-            }
-            0x77 => {
-                // zero page, X
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr);
-                let rotated = self.ror(value);
-                bus.write(addr, rotated);
-                self.adc(rotated);
-                6
-            }
-            0x7B => {
-                // absolute, Y
-                let (addr, _) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                let rotated = self.ror(value);
-                bus.write(addr, rotated);
-                self.adc(rotated);
-                7
-            }
-            0x7F => {
-                // absolute, X
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr);
-                let rotated = self.ror(value);
-                bus.write(addr, rotated);
-                self.adc(rotated);
-                7
-            }
-
-            // *SAX - Store A & X (AND A with X, store result)
-            0x83 => {
-                // (indirect, X)
-                let (addr, _) = self.addr_indirect_x(bus);
-                bus.write(addr, self.a & self.x);
-                6
-            }
-            0x87 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                bus.write(addr, self.a & self.x);
-                3
-            }
-            0x8F => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                bus.write(addr, self.a & self.x);
-                4
-            }
-            0x97 => {
-                // zero page, Y
-                let addr = self.addr_zero_page_y(bus);
-                bus.write(addr, self.a & self.x);
-                4
-            }
-
-            // *LAX - LDA + LDX (Load A and X with same value)
-            0xA3 => {
-                // (indirect, X)
-                let (addr, extra_cycle) = self.addr_indirect_x(bus);
-                let value = bus.read(addr);
-                self.a = value;
-                self.x = value;
-                self.update_zero_negative(value);
-                6 + extra_cycle
-            }
-            0xA7 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr);
-                self.a = value;
-                self.x = value;
-                self.update_zero_negative(value);
-                3
-            }
-            0xAB => {
-                // immediate (unstable)
-                let value = self.addr_immediate(bus);
-                self.a = value;
-                self.x = value;
-                self.update_zero_negative(value);
-                2
-            }
-            0xAF => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr);
-                self.a = value;
-                self.x = value;
-                self.update_zero_negative(value);
-                4
-            }
-            0xB3 => {
-                // (indirect), Y
-                let (addr, extra_cycle) = self.addr_indirect_y(bus);
-                let value = bus.read(addr);
-                self.a = value;
-                self.x = value;
-                self.update_zero_negative(value);
-                5 + extra_cycle
-            }
-            0xB7 => {
-                // zero page, Y
-                let addr = self.addr_zero_page_y(bus);
-                let value = bus.read(addr);
-                self.a = value;
-                self.x = value;
-                self.update_zero_negative(value);
-                4
-            }
-            0xBF => {
-                // absolute, Y
-                let (addr, extra_cycle) = self.addr_absolute_y(bus);
-                let value = bus.read(addr);
-                self.a = value;
-                self.x = value;
-                self.update_zero_negative(value);
-                4 + extra_cycle
+            0xFE => self.rmw_op(Mode::AbsoluteX, Cpu::inc, bus),
+            // *ISC (absolute, X)
+            0xFF => self.rmw_op(Mode::AbsoluteX, Cpu::isc, bus),
+            // *JAM
+            0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 => {
+                log::warn!(
+                    "CPU jammed by opcode 0x{:02X} at PC 0x{:04X}",
+                    opcode,
+                    self.pc.wrapping_sub(1)
+                );
+                self.dummy_read(bus);
+                self.jammed = true;
             }
+        }
+    }
 
-            // *DCP - DEC + CMP (Decrement memory then Compare)
-            0xC3 => {
-                // (indirect, X)
-                let (addr, _) = self.addr_indirect_x(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.cmp(value);
-                8
-            }
-            0xC7 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.cmp(value);
-                5
-            }
-            0xCF => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.cmp(value);
-                6
-            }
-            0xD3 => {
-                // (indirect), Y
-                let (addr, _) = self.addr_indirect_y(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.cmp(value);
-                8
-            }
-            0xD7 => {
-                // zero page, X
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.cmp(value);
-                6
-            }
-            0xDB => {
-                // absolute, Y
-                let (addr, _) = self.addr_absolute_y(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.cmp(value);
-                7
-            }
-            0xDF => {
-                // absolute, X
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr).wrapping_sub(1);
-                bus.write(addr, value);
-                self.cmp(value);
-                7
-            }
+    // Bus cycles
+    fn read(&mut self, addr: u16, bus: &mut dyn CpuBus) -> u8 {
+        self.cycles += 1;
+        self.prev_nmi_pending = self.nmi_pending;
+        let value = bus.read(addr);
+        self.poll_interrupts(bus);
+        value
+    }
 
-            // *ISB/ISC/INS - INC + SBC (Increment memory then Subtract)
-            0xE3 => {
-                // (indirect, X)
-                let (addr, _) = self.addr_indirect_x(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.sbc(value);
-                8
-            }
-            0xE7 => {
-                // zero page
-                let addr = self.addr_zero_page(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.sbc(value);
-                5
-            }
-            0xEB => {
-                // immediate (same as SBC #imm, unofficial)
-                let value = self.addr_immediate(bus);
-                self.sbc(value);
-                2
-            }
-            0xEF => {
-                // absolute
-                let addr = self.addr_absolute(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.sbc(value);
-                6
-            }
-            0xF3 => {
-                // (indirect), Y
-                let (addr, _) = self.addr_indirect_y(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.sbc(value);
-                8
-            }
-            0xF7 => {
-                // zero page, X
-                let addr = self.addr_zero_page_x(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.sbc(value);
-                6
-            }
-            0xFB => {
-                // absolute, Y
-                let (addr, _) = self.addr_absolute_y(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.sbc(value);
-                7
-            }
-            0xFF => {
-                // absolute, X (THIS IS THE ZELDA OPCODE!)
-                let (addr, _) = self.addr_absolute_x(bus);
-                let value = bus.read(addr).wrapping_add(1);
-                bus.write(addr, value);
-                self.sbc(value);
-                7
-            }
+    fn write(&mut self, addr: u16, value: u8, bus: &mut dyn CpuBus) {
+        self.cycles += 1;
+        self.prev_nmi_pending = self.nmi_pending;
+        bus.write(addr, value);
+        self.poll_interrupts(bus);
+    }
 
-            // *ANC - AND + set Carry to bit 7
-            0x0B | 0x2B => {
-                let value = self.addr_immediate(bus);
-                self.and(value);
-                self.set_flag(FLAG_C, (self.a & 0x80) != 0);
-                2
-            }
+    fn fetch(&mut self, bus: &mut dyn CpuBus) -> u8 {
+        let value = self.read(self.pc, bus);
+        self.pc = self.pc.wrapping_add(1);
+        value
+    }
 
-            // *ALR/ASR - AND + LSR
-            0x4B => {
-                let value = self.addr_immediate(bus);
-                self.a &= value;
-                self.a = self.lsr(self.a);
-                2
-            }
+    fn fetch_word(&mut self, bus: &mut dyn CpuBus) -> u16 {
+        let low = self.fetch(bus) as u16;
+        let high = self.fetch(bus) as u16;
+        (high << 8) | low
+    }
 
-            // *ARR - AND + ROR (with weird flag behavior)
-            0x6B => {
-                let value = self.addr_immediate(bus);
-                self.a &= value;
-                self.a = self.ror(self.a);
-                // Special flag handling for ARR
-                self.set_flag(FLAG_C, (self.a & 0x40) != 0);
-                self.set_flag(FLAG_V, ((self.a & 0x40) ^ ((self.a & 0x20) << 1)) != 0);
-                2
-            }
+    /// Read of the byte after the opcode that single-byte instructions
+    /// perform and discard
+    fn dummy_read(&mut self, bus: &mut dyn CpuBus) {
+        self.read(self.pc, bus);
+    }
 
-            // *AXS/SBX - (A & X) - immediate -> X
-            0xCB => {
-                let value = self.addr_immediate(bus);
-                let temp = (self.a & self.x).wrapping_sub(value);
-                self.set_flag(FLAG_C, (self.a & self.x) >= value);
-                self.x = temp;
-                self.update_zero_negative(self.x);
-                2
-            }
+    /// Read of the current stack slot while the stack pointer is incremented
+    fn dummy_stack_read(&mut self, bus: &mut dyn CpuBus) {
+        self.read(0x100 + self.sp as u16, bus);
+    }
 
-            // *NOP variants (read and discard)
-            0x04 | 0x44 | 0x64 => {
-                // zero page NOP
-                let _ = self.addr_zero_page(bus);
-                3
-            }
-            0x0C => {
-                // absolute NOP
-                let addr = self.addr_absolute(bus);
-                let _ = bus.read(addr);
-                4
-            }
-            0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 => {
-                // zero page, X NOP
-                let _ = self.addr_zero_page_x(bus);
-                4
-            }
-            0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA => {
-                // implied NOP
-                2
-            }
-            0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC => {
-                // absolute, X NOP
-                let (addr, extra_cycle) = self.addr_absolute_x(bus);
-                let _ = bus.read(addr);
-                4 + extra_cycle
-            }
-            0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 => {
-                // immediate NOP
-                let _ = self.addr_immediate(bus);
-                2
-            }
+    // Addressing modes
 
-            _ => {
-                // Remaining illegal opcodes - treat as NOP but log
-                log::warn!(
-                    "Unknown opcode: 0x{:02X} at PC 0x{:04X}",
-                    opcode,
-                    self.pc.wrapping_sub(1)
-                );
-                2
+    /// Compute the effective address of a memory operand. Indexed modes read
+    /// from the address before the carry into the high byte is fixed up:
+    /// only on a page cross for reads, always for writes and RMW.
+    fn operand_addr(&mut self, mode: Mode, write: bool, bus: &mut dyn CpuBus) -> u16 {
+        match mode {
+            Mode::ZeroPage => self.fetch(bus) as u16,
+            Mode::ZeroPageX => {
+                let base = self.fetch(bus);
+                self.read(base as u16, bus);
+                base.wrapping_add(self.x) as u16
+            }
+            Mode::ZeroPageY => {
+                let base = self.fetch(bus);
+                self.read(base as u16, bus);
+                base.wrapping_add(self.y) as u16
+            }
+            Mode::Absolute => self.fetch_word(bus),
+            Mode::AbsoluteX => {
+                let base = self.fetch_word(bus);
+                self.indexed(base, self.x, write, bus)
+            }
+            Mode::AbsoluteY => {
+                let base = self.fetch_word(bus);
+                self.indexed(base, self.y, write, bus)
+            }
+            Mode::IndirectX => {
+                let ptr = self.fetch(bus);
+                self.read(ptr as u16, bus);
+                let ptr = ptr.wrapping_add(self.x);
+                let low = self.read(ptr as u16, bus) as u16;
+                let high = self.read(ptr.wrapping_add(1) as u16, bus) as u16;
+                (high << 8) | low
+            }
+            Mode::IndirectY => {
+                let base = self.indirect_y_base(bus);
+                self.indexed(base, self.y, write, bus)
             }
         }
     }
 
-    // Addressing modes
-    fn addr_immediate(&mut self, bus: &mut dyn CpuBus) -> u8 {
-        let value = bus.read(self.pc);
-        self.pc = self.pc.wrapping_add(1);
-        value
+    /// Fetch the zero page pointer of (indirect), Y and read the base address
+    fn indirect_y_base(&mut self, bus: &mut dyn CpuBus) -> u16 {
+        let ptr = self.fetch(bus);
+        let low = self.read(ptr as u16, bus) as u16;
+        let high = self.read(ptr.wrapping_add(1) as u16, bus) as u16;
+        (high << 8) | low
     }
 
-    fn addr_zero_page(&mut self, bus: &mut dyn CpuBus) -> u16 {
-        let addr = bus.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
+    fn indexed(&mut self, base: u16, index: u8, write: bool, bus: &mut dyn CpuBus) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        if write || (base & 0xFF00) != (addr & 0xFF00) {
+            self.read((base & 0xFF00) | (addr & 0x00FF), bus);
+        }
         addr
     }
 
-    fn addr_zero_page_x(&mut self, bus: &mut dyn CpuBus) -> u16 {
-        let base = bus.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        (base.wrapping_add(self.x as u16)) & 0xFF
+    // Instruction shapes
+    fn immediate_op(&mut self, op: fn(&mut Cpu, u8), bus: &mut dyn CpuBus) {
+        let value = self.fetch(bus);
+        op(self, value);
     }
 
-    fn addr_zero_page_y(&mut self, bus: &mut dyn CpuBus) -> u16 {
-        let base = bus.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        (base.wrapping_add(self.y as u16)) & 0xFF
+    fn read_op(&mut self, mode: Mode, op: fn(&mut Cpu, u8), bus: &mut dyn CpuBus) {
+        let addr = self.operand_addr(mode, false, bus);
+        let value = self.read(addr, bus);
+        op(self, value);
     }
 
-    fn addr_absolute(&mut self, bus: &mut dyn CpuBus) -> u16 {
-        let low = bus.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let high = bus.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        (high << 8) | low
+    fn write_op(&mut self, mode: Mode, value: u8, bus: &mut dyn CpuBus) {
+        let addr = self.operand_addr(mode, true, bus);
+        self.write(addr, value, bus);
     }
 
-    fn addr_absolute_x(&mut self, bus: &mut dyn CpuBus) -> (u16, u64) {
-        let base = self.addr_absolute(bus);
-        let addr = base.wrapping_add(self.x as u16);
-        let extra_cycle = if (base & 0xFF00) != (addr & 0xFF00) {
-            1
-        } else {
-            0
-        };
-        (addr, extra_cycle)
+    /// Read-modify-write: the unmodified value is written back on the cycle
+    /// before the result
+    fn rmw_op(&mut self, mode: Mode, op: fn(&mut Cpu, u8) -> u8, bus: &mut dyn CpuBus) {
+        let addr = self.operand_addr(mode, true, bus);
+        let value = self.read(addr, bus);
+        self.write(addr, value, bus);
+        let result = op(self, value);
+        self.write(addr, result, bus);
     }
 
-    fn addr_absolute_y(&mut self, bus: &mut dyn CpuBus) -> (u16, u64) {
-        let base = self.addr_absolute(bus);
-        let addr = base.wrapping_add(self.y as u16);
-        let extra_cycle = if (base & 0xFF00) != (addr & 0xFF00) {
-            1
-        } else {
-            0
-        };
-        (addr, extra_cycle)
+    fn accumulator_op(&mut self, op: fn(&mut Cpu, u8) -> u8, bus: &mut dyn CpuBus) {
+        self.dummy_read(bus);
+        self.a = op(self, self.a);
     }
 
-    fn addr_indirect_x(&mut self, bus: &mut dyn CpuBus) -> (u16, u64) {
-        let base = bus.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let ptr = (base.wrapping_add(self.x as u16)) & 0xFF;
-        let low = bus.read(ptr) as u16;
-        let high = bus.read(ptr.wrapping_add(1) & 0xFF) as u16;
-        ((high << 8) | low, 0)
-    }
-
-    fn addr_indirect_y(&mut self, bus: &mut dyn CpuBus) -> (u16, u64) {
-        let base = bus.read(self.pc) as u16;
-        self.pc = self.pc.wrapping_add(1);
-        let low = bus.read(base) as u16;
-        let high = bus.read((base + 1) & 0xFF) as u16;
-        let indirect = (high << 8) | low;
-        let addr = indirect.wrapping_add(self.y as u16);
-        let extra_cycle = if (indirect & 0xFF00) != (addr & 0xFF00) {
-            1
+    /// SHA/SHX/SHY/TAS: store `value` ANDed with the base high byte plus one.
+    /// On a page cross that value also replaces the high byte of the address.
+    fn unstable_store(&mut self, base: u16, index: u8, value: u8, bus: &mut dyn CpuBus) {
+        let addr = base.wrapping_add(index as u16);
+        self.read((base & 0xFF00) | (addr & 0x00FF), bus);
+        let value = value & ((base >> 8) as u8).wrapping_add(1);
+        let addr = if (base & 0xFF00) != (addr & 0xFF00) {
+            ((value as u16) << 8) | (addr & 0x00FF)
         } else {
-            0
+            addr
         };
-        (addr, extra_cycle)
+        self.write(addr, value, bus);
     }
 
     // Stack operations
     fn push(&mut self, value: u8, bus: &mut dyn CpuBus) {
-        bus.write(0x100 + self.sp as u16, value);
+        self.write(0x100 + self.sp as u16, value, bus);
         self.sp = self.sp.wrapping_sub(1);
     }
 
     fn pop(&mut self, bus: &mut dyn CpuBus) -> u8 {
         self.sp = self.sp.wrapping_add(1);
-        bus.read(0x100 + self.sp as u16)
+        self.read(0x100 + self.sp as u16, bus)
     }
 
     fn push_word(&mut self, value: u16, bus: &mut dyn CpuBus) {
@@ -1953,28 +1102,16 @@ impl Cpu {
         (high << 8) | low
     }
 
-    fn read_word(&self, addr: u16, bus: &mut dyn CpuBus) -> u16 {
-        let low = bus.read(addr) as u16;
-        let high = bus.read(addr.wrapping_add(1)) as u16;
-        (high << 8) | low
-    }
-
     // Branch instructions
-    fn branch(&mut self, condition: bool, bus: &mut dyn CpuBus) -> u64 {
+    fn branch(&mut self, condition: bool, bus: &mut dyn CpuBus) {
+        let offset = self.fetch(bus) as i8;
         if condition {
-            let offset = bus.read(self.pc) as i8;
-            self.pc = self.pc.wrapping_add(1);
-            let old_pc = self.pc;
-            self.pc = self.pc.wrapping_add(offset as u16);
-            let extra_cycle = if (old_pc & 0xFF00) != (self.pc & 0xFF00) {
-                1
-            } else {
-                0
-            };
-            3 + extra_cycle
-        } else {
-            self.pc = self.pc.wrapping_add(1);
-            2
+            self.dummy_read(bus);
+            let target = self.pc.wrapping_add(offset as u16);
+            if (target & 0xFF00) != (self.pc & 0xFF00) {
+                self.read((self.pc & 0xFF00) | (target & 0x00FF), bus);
+            }
+            self.pc = target;
         }
     }
 
@@ -2071,6 +1208,123 @@ impl Cpu {
         result
     }
 
+    fn nop(&mut self, _value: u8) {}
+
+    fn lda(&mut self, value: u8) {
+        self.a = value;
+        self.update_zero_negative(value);
+    }
+
+    fn ldx(&mut self, value: u8) {
+        self.x = value;
+        self.update_zero_negative(value);
+    }
+
+    fn ldy(&mut self, value: u8) {
+        self.y = value;
+        self.update_zero_negative(value);
+    }
+
+    fn inc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.update_zero_negative(result);
+        result
+    }
+
+    fn dec(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.update_zero_negative(result);
+        result
+    }
+
+    // Unofficial combined operations
+    fn slo(&mut self, value: u8) -> u8 {
+        let result = self.asl(value);
+        self.ora(result);
+        result
+    }
+
+    fn rla(&mut self, value: u8) -> u8 {
+        let result = self.rol(value);
+        self.and(result);
+        result
+    }
+
+    fn sre(&mut self, value: u8) -> u8 {
+        let result = self.lsr(value);
+        self.eor(result);
+        result
+    }
+
+    fn rra(&mut self, value: u8) -> u8 {
+        let result = self.ror(value);
+        self.adc(result);
+        result
+    }
+
+    fn dcp(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.cmp(result);
+        result
+    }
+
+    fn isc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.sbc(result);
+        result
+    }
+
+    fn lax(&mut self, value: u8) {
+        self.a = value;
+        self.x = value;
+        self.update_zero_negative(value);
+    }
+
+    /// LAX immediate (unstable on hardware; treated as a plain load)
+    fn lxa(&mut self, value: u8) {
+        self.lax(value);
+    }
+
+    fn las(&mut self, value: u8) {
+        let result = value & self.sp;
+        self.sp = result;
+        self.lax(result);
+    }
+
+    /// AND + set Carry to bit 7
+    fn anc(&mut self, value: u8) {
+        self.and(value);
+        self.set_flag(FLAG_C, (self.a & 0x80) != 0);
+    }
+
+    /// AND + LSR
+    fn alr(&mut self, value: u8) {
+        self.a &= value;
+        self.a = self.lsr(self.a);
+    }
+
+    /// AND + ROR, with C and V taken from bits 6 and 5 of the result
+    fn arr(&mut self, value: u8) {
+        self.a &= value;
+        self.a = self.ror(self.a);
+        self.set_flag(FLAG_C, (self.a & 0x40) != 0);
+        self.set_flag(FLAG_V, ((self.a & 0x40) ^ ((self.a & 0x20) << 1)) != 0);
+    }
+
+    /// (A & X) - immediate -> X
+    fn axs(&mut self, value: u8) {
+        let and = self.a & self.x;
+        self.set_flag(FLAG_C, and >= value);
+        self.x = and.wrapping_sub(value);
+        self.update_zero_negative(self.x);
+    }
+
+    /// Unstable; uses the common 0xEE "magic" constant
+    fn xaa(&mut self, value: u8) {
+        self.a = (self.a | 0xEE) & self.x & value;
+        self.update_zero_negative(self.a);
+    }
+
     // Flag operations
     fn get_flag(&self, flag: u8) -> bool {
         (self.status & flag) != 0
@@ -2089,3 +1343,209 @@ impl Cpu {
         self.set_flag(FLAG_N, (value & FLAG_N) != 0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Access {
+        Read(u16),
+        Write(u16, u8),
+    }
+    use Access::{Read, Write};
+
+    /// 64KB of flat RAM that records every access the CPU makes
+    struct RecordingBus {
+        memory: Vec<u8>,
+        log: Vec<Access>,
+    }
+
+    impl RecordingBus {
+        fn new(addr: u16, program: &[u8]) -> Self {
+            let mut memory = vec![0; 0x10000];
+            memory[addr as usize..addr as usize + program.len()].copy_from_slice(program);
+            Self {
+                memory,
+                log: Vec::new(),
+            }
+        }
+    }
+
+    impl CpuBus for RecordingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.log.push(Read(addr));
+            self.memory[addr as usize]
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.log.push(Write(addr, value));
+            self.memory[addr as usize] = value;
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.memory[addr as usize]
+        }
+    }
+
+    /// Run one instruction at `pc` and return the accesses it made
+    fn step(cpu: &mut Cpu, bus: &mut RecordingBus, pc: u16) -> Vec<Access> {
+        cpu.pc = pc;
+        bus.log.clear();
+        let cycles = cpu.step(bus, &mut TraceState::new(false));
+        assert_eq!(cycles as usize, bus.log.len());
+        std::mem::take(&mut bus.log)
+    }
+
+    #[test]
+    fn lda_absolute_x_reads_unfixed_address_only_on_page_cross() {
+        // LDA $12F0,X
+        let mut bus = RecordingBus::new(0x0200, &[0xBD, 0xF0, 0x12]);
+        bus.memory[0x12F5] = 0x11;
+        bus.memory[0x1310] = 0x22;
+        let mut cpu = Cpu::new();
+
+        cpu.x = 0x05;
+        let log = step(&mut cpu, &mut bus, 0x0200);
+        assert_eq!(
+            log,
+            [Read(0x0200), Read(0x0201), Read(0x0202), Read(0x12F5)]
+        );
+        assert_eq!(cpu.a, 0x11);
+
+        cpu.x = 0x20;
+        let log = step(&mut cpu, &mut bus, 0x0200);
+        assert_eq!(
+            log,
+            [
+                Read(0x0200),
+                Read(0x0201),
+                Read(0x0202),
+                Read(0x1210),
+                Read(0x1310)
+            ]
+        );
+        assert_eq!(cpu.a, 0x22);
+    }
+
+    #[test]
+    fn sta_absolute_x_always_dummy_reads() {
+        // STA $12F0,X
+        let mut bus = RecordingBus::new(0x0200, &[0x9D, 0xF0, 0x12]);
+        let mut cpu = Cpu::new();
+        cpu.a = 0x5A;
+        cpu.x = 0x05;
+        let log = step(&mut cpu, &mut bus, 0x0200);
+        assert_eq!(
+            log,
+            [
+                Read(0x0200),
+                Read(0x0201),
+                Read(0x0202),
+                Read(0x12F5),
+                Write(0x12F5, 0x5A)
+            ]
+        );
+    }
+
+    #[test]
+    fn inc_absolute_writes_old_value_then_new() {
+        // INC $1234
+        let mut bus = RecordingBus::new(0x0200, &[0xEE, 0x34, 0x12]);
+        bus.memory[0x1234] = 0x41;
+        let mut cpu = Cpu::new();
+        let log = step(&mut cpu, &mut bus, 0x0200);
+        assert_eq!(
+            log,
+            [
+                Read(0x0200),
+                Read(0x0201),
+                Read(0x0202),
+                Read(0x1234),
+                Write(0x1234, 0x41),
+                Write(0x1234, 0x42)
+            ]
+        );
+    }
+
+    #[test]
+    fn jsr_rts_rti_stack_accesses() {
+        let mut bus = RecordingBus::new(0x0200, &[0x20, 0x56, 0x34]); // JSR $3456
+        bus.memory[0x3456] = 0x60; // RTS
+        let mut cpu = Cpu::new();
+        cpu.sp = 0xFD;
+
+        let log = step(&mut cpu, &mut bus, 0x0200);
+        assert_eq!(
+            log,
+            [
+                Read(0x0200),
+                Read(0x0201),
+                Read(0x01FD),
+                Write(0x01FD, 0x02),
+                Write(0x01FC, 0x02),
+                Read(0x0202)
+            ]
+        );
+        assert_eq!((cpu.pc, cpu.sp), (0x3456, 0xFB));
+
+        let log = step(&mut cpu, &mut bus, 0x3456);
+        assert_eq!(
+            log,
+            [
+                Read(0x3456),
+                Read(0x3457),
+                Read(0x01FB),
+                Read(0x01FC),
+                Read(0x01FD),
+                Read(0x0202)
+            ]
+        );
+        assert_eq!((cpu.pc, cpu.sp), (0x0203, 0xFD));
+
+        // RTI with P=$C3 and PC=$4567 on the stack
+        bus.memory[0x0300] = 0x40;
+        bus.memory[0x01FB..=0x01FD].copy_from_slice(&[0xC3, 0x67, 0x45]);
+        cpu.sp = 0xFA;
+        let log = step(&mut cpu, &mut bus, 0x0300);
+        assert_eq!(
+            log,
+            [
+                Read(0x0300),
+                Read(0x0301),
+                Read(0x01FA),
+                Read(0x01FB),
+                Read(0x01FC),
+                Read(0x01FD)
+            ]
+        );
+        assert_eq!((cpu.pc, cpu.sp), (0x4567, 0xFD));
+        assert_eq!(cpu.status, 0xC3 | FLAG_U);
+    }
+
+    #[test]
+    fn branch_across_page_reads_unfixed_target() {
+        // BNE +$20 from $02F0 lands on $0312
+        let mut bus = RecordingBus::new(0x02F0, &[0xD0, 0x20]);
+        let mut cpu = Cpu::new();
+        cpu.status &= !FLAG_Z;
+        let log = step(&mut cpu, &mut bus, 0x02F0);
+        assert_eq!(
+            log,
+            [Read(0x02F0), Read(0x02F1), Read(0x02F2), Read(0x0212)]
+        );
+        assert_eq!(cpu.pc, 0x0312);
+    }
+
+    #[test]
+    fn jammed_cpu_keeps_reading_ffff() {
+        let mut bus = RecordingBus::new(0x0200, &[0x02]); // JAM
+        let mut cpu = Cpu::new();
+        step(&mut cpu, &mut bus, 0x0200);
+        let pc = cpu.pc;
+        for _ in 0..3 {
+            let log = step(&mut cpu, &mut bus, pc);
+            assert_eq!(log, [Read(0xFFFF)]);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! iNES / NES 2.0 ROM header
+//!
+//! The 16-byte header in front of every `.nes` file describes the board the
+//! ROM was dumped from. iNES 1.0 only records the low byte of the mapper
+//! number, the ROM sizes, mirroring and a battery bit. NES 2.0 reuses the
+//! spare bytes for 12-bit mapper numbers, submappers, exact RAM sizes, the
+//! TV system, the console type and the controllers the game expects.
+//!
+//! Reference: https://www.nesdev.org/wiki/NES_2.0
+
+use crate::cartridge::{CartridgeError, Mirroring};
+use crate::region::Region;
+
+/// Size of the header at the start of a `.nes` file
+pub const HEADER_SIZE: usize = 16;
+/// Size of the optional trainer between the header and PRG-ROM
+pub const TRAINER_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    /// Original iNES, possibly with junk in bytes 7-15 from old rippers
+    INes,
+    Nes20,
+}
+
+/// TV system the game was made for (NES 2.0 byte 12)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    /// Runs on either; the game detects the system itself
+    MultiRegion,
+    Dendy,
+}
+
+/// Hardware the ROM runs on (byte 7 bits 0-1, NES 2.0 byte 13)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    /// Regular NES / Famicom / Dendy
+    Nes,
+    /// Nintendo Vs. System arcade board
+    VsSystem {
+        ppu: u8,
+        hardware: u8,
+    },
+    Playchoice10,
+    /// NES 2.0 extended console type (famiclones, VT0x, ...)
+    Extended(u8),
+}
+
+/// Controllers or other peripherals the game expects (NES 2.0 byte 15)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionDevice {
+    Unspecified,
+    StandardControllers,
+    /// NES Four Score / Satellite with two more standard controllers
+    FourScore,
+    /// Famicom four-player adapter with two more standard controllers
+    FamicomFourPlayers,
+    VsSystem,
+    VsZapper,
+    /// Zapper in port 2
+    Zapper,
+    TwoZappers,
+    PowerPadSideA,
+    PowerPadSideB,
+    ArkanoidNes,
+    ArkanoidFamicom,
+    Other(u8),
+}
+
+impl ExpansionDevice {
+    fn from_id(id: u8) -> Self {
+        match id {
+            0x00 => ExpansionDevice::Unspecified,
+            0x01 => ExpansionDevice::StandardControllers,
+            0x02 => ExpansionDevice::FourScore,
+            0x03 => ExpansionDevice::FamicomFourPlayers,
+            0x04 | 0x05 => ExpansionDevice::VsSystem,
+            0x07 => ExpansionDevice::VsZapper,
+            0x08 => ExpansionDevice::Zapper,
+            0x09 => ExpansionDevice::TwoZappers,
+            0x0B => ExpansionDevice::PowerPadSideA,
+            0x0C => ExpansionDevice::PowerPadSideB,
+            0x0F => ExpansionDevice::ArkanoidNes,
+            0x10 => ExpansionDevice::ArkanoidFamicom,
+            id => ExpansionDevice::Other(id),
+        }
+    }
+}
+
+/// Parsed `.nes` header. Sizes are in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomHeader {
+    pub format: HeaderFormat,
+    pub mapper: u16,
+    /// Board variant within a mapper number (NES 2.0 only, else 0)
+    pub submapper: u8,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    /// Volatile PRG-RAM at $6000-$7FFF
+    pub prg_ram_size: usize,
+    /// Battery-backed PRG-RAM (or EEPROM)
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    /// Hard-wired nametable mirroring; mappers with mirroring control override it
+    pub mirroring: Mirroring,
+    /// Byte 6 bit 0 as written, even when the four-screen bit overrides it;
+    /// a few boards give that combination a meaning of their own
+    pub mirroring_bit: bool,
+    pub has_battery: bool,
+    pub has_trainer: bool,
+    pub timing: Option<Timing>,
+    pub console_type: ConsoleType,
+    /// Number of extra ROM areas after CHR-ROM (NES 2.0 only)
+    pub misc_roms: u8,
+    pub expansion_device: ExpansionDevice,
+}
+
+impl RomHeader {
+    pub fn parse(data: &[u8]) -> Result<Self, CartridgeError> {
+        if data.len() < HEADER_SIZE || &data[0..4] != b"NES\x1A" {
+            return Err(CartridgeError::InvalidHeader);
+        }
+
+        let flags6 = data[6];
+        let format = if (data[7] & 0x0C) == 0x08 {
+            HeaderFormat::Nes20
+        } else {
+            HeaderFormat::INes
+        };
+
+        // iNES byte 6 bit 0: 0 = Horizontal, 1 = Vertical
+        // iNES byte 6 bit 3: 1 = Four-screen (ignores bit 0)
+        let mirroring = if (flags6 & 0x08) != 0 {
+            Mirroring::FourScreen
+        } else if (flags6 & 0x01) == 0 {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        };
+        let has_battery = (flags6 & 0x02) != 0;
+        let has_trainer = (flags6 & 0x04) != 0;
+
+        let header = match format {
+            HeaderFormat::Nes20 => {
+                let mapper = (data[6] >> 4) as u16
+                    | (data[7] & 0xF0) as u16
+                    | ((data[8] & 0x0F) as u16) << 8;
+                let prg_rom_size = rom_size(data[4], data[9] & 0x0F, 0x4000)?;
+                let chr_rom_size = rom_size(data[5], data[9] >> 4, 0x2000)?;
+
+                let timing = match data[12] & 0x03 {
+                    0 => Timing::Ntsc,
+                    1 => Timing::Pal,
+                    2 => Timing::MultiRegion,
+                    _ => Timing::Dendy,
+                };
+                let console_type = match data[7] & 0x03 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem {
+                        ppu: data[13] & 0x0F,
+                        hardware: data[13] >> 4,
+                    },
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Extended(data[13] & 0x0F),
+                };
+
+                RomHeader {
+                    format,
+                    mapper,
+                    submapper: data[8] >> 4,
+                    prg_rom_size,
+                    chr_rom_size,
+                    prg_ram_size: ram_size(data[10] & 0x0F),
+                    prg_nvram_size: ram_size(data[10] >> 4),
+                    chr_ram_size: ram_size(data[11] & 0x0F),
+                    chr_nvram_size: ram_size(data[11] >> 4),
+                    mirroring,
+                    mirroring_bit: (flags6 & 0x01) != 0,
+                    has_battery,
+                    has_trainer,
+                    timing: Some(timing),
+                    console_type,
+                    misc_roms: data[14] & 0x03,
+                    expansion_device: ExpansionDevice::from_id(data[15] & 0x3F),
+                }
+            }
+            HeaderFormat::INes => {
+                // Rippers used to sign bytes 7-15 ("DiskDude!"); if the tail
+                // of the header isn't clean, byte 7 can't be trusted either
+                let clean = data[12..16].iter().all(|&b| b == 0);
+                let flags7 = if clean { data[7] } else { 0 };
+                let mapper = (flags6 >> 4) as u16 | (flags7 & 0xF0) as u16;
+
+                let prg_rom_size = data[4] as usize * 0x4000;
+                let chr_rom_size = data[5] as usize * 0x2000;
+
+                // Byte 8 counts 8 KB PRG-RAM units, with 0 meaning 8 KB. Boards
+                // without RAM ignore it, so it is safe to always provide.
+                let ram = if clean && data[8] != 0 {
+                    data[8] as usize * 0x2000
+                } else {
+                    0x2000
+                };
+                let (prg_ram_size, prg_nvram_size) = if has_battery { (0, ram) } else { (ram, 0) };
+
+                // Byte 9 bit 0 asks for PAL; a clear bit is also the default
+                let timing =
+                    (clean && (data[9] & 0x01) != 0 && data[11] == 0).then_some(Timing::Pal);
+                let console_type = match flags7 & 0x03 {
+                    1 => ConsoleType::VsSystem {
+                        ppu: 0,
+                        hardware: 0,
+                    },
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Nes,
+                };
+
+                RomHeader {
+                    format,
+                    mapper,
+                    submapper: 0,
+                    prg_rom_size,
+                    chr_rom_size,
+                    prg_ram_size,
+                    prg_nvram_size,
+                    // iNES 1.0 has no field for it; see `default_chr_ram_size`
+                    chr_ram_size: 0,
+                    chr_nvram_size: 0,
+                    mirroring,
+                    mirroring_bit: (flags6 & 0x01) != 0,
+                    has_battery,
+                    has_trainer,
+                    timing,
+                    console_type,
+                    misc_roms: 0,
+                    expansion_device: ExpansionDevice::Unspecified,
+                }
+            }
+        };
+
+        Ok(header)
+    }
+
+    /// Header standing in for a Famicom Disk System image, which has none of
+    /// its own: the RAM adapter (mapper 20) with the BIOS as PRG-ROM, 32KB
+    /// of RAM and 8KB of CHR-RAM
+    pub fn fds() -> Self {
+        RomHeader {
+            // Every size is exact, as in a NES 2.0 header
+            format: HeaderFormat::Nes20,
+            mapper: 20,
+            submapper: 0,
+            prg_rom_size: 0x2000,
+            chr_rom_size: 0,
+            prg_ram_size: 0x8000,
+            prg_nvram_size: 0,
+            chr_ram_size: 0x2000,
+            chr_nvram_size: 0,
+            mirroring: Mirroring::Horizontal,
+            mirroring_bit: false,
+            has_battery: false,
+            has_trainer: false,
+            timing: Some(Timing::Ntsc),
+            console_type: ConsoleType::Nes,
+            misc_roms: 0,
+            expansion_device: ExpansionDevice::Unspecified,
+        }
+    }
+
+    /// Offset of PRG-ROM in the file
+    pub fn prg_rom_offset(&self) -> usize {
+        HEADER_SIZE + if self.has_trainer { TRAINER_SIZE } else { 0 }
+    }
+
+    /// Region the header asks for; `None` for multi-region or unknown
+    pub fn region(&self) -> Option<Region> {
+        match self.timing? {
+            Timing::Ntsc => Some(Region::Ntsc),
+            Timing::Pal => Some(Region::Pal),
+            Timing::Dendy => Some(Region::Dendy),
+            Timing::MultiRegion => None,
+        }
+    }
+
+    /// Total PRG-RAM on the board, volatile plus battery-backed
+    pub fn total_prg_ram_size(&self) -> usize {
+        self.prg_ram_size + self.prg_nvram_size
+    }
+
+    /// Total CHR-RAM on the board, volatile plus battery-backed
+    pub fn total_chr_ram_size(&self) -> usize {
+        self.chr_ram_size + self.chr_nvram_size
+    }
+}
+
+/// NES 2.0 ROM size. A most significant nibble of $F switches the LSB byte
+/// to exponent-multiplier form: 2^E * (MM * 2 + 1) bytes.
+fn rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, CartridgeError> {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+            .ok_or(CartridgeError::InvalidHeader)
+    } else {
+        Ok((((msb as usize) << 8) | lsb as usize) * unit)
+    }
+}
+
+/// NES 2.0 RAM size from a shift count: 0 means none, otherwise 64 << shift
+fn ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
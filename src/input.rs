@@ -1,3 +1,18 @@
+use crate::savestate::{SaveState, SaveStateError, StateReader, StateWriter};
+
+/// Standard controller buttons
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
 #[derive(Debug, Clone)]
 pub struct Input {
     pub controller1: ControllerState,
@@ -41,6 +56,20 @@ impl ControllerState {
         }
     }
 
+    /// Set the pressed state of a single button
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        match button {
+            Button::A => self.a = pressed,
+            Button::B => self.b = pressed,
+            Button::Select => self.select = pressed,
+            Button::Start => self.start = pressed,
+            Button::Up => self.up = pressed,
+            Button::Down => self.down = pressed,
+            Button::Left => self.left = pressed,
+            Button::Right => self.right = pressed,
+        }
+    }
+
     /// Latch current button states into shift register
     /// Button order: A, B, Select, Start, Up, Down, Left, Right (bits 0-7)
     pub fn latch(&mut self) {
@@ -81,6 +110,35 @@ impl ControllerState {
     }
 }
 
+impl SaveState for ControllerState {
+    fn save_state(&self, w: &mut StateWriter) {
+        // Held buttons are live frontend input and are not restored
+        w.u8(self.shift_register);
+        w.u8(self.read_count);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.shift_register = r.u8()?;
+        self.read_count = r.u8()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Input {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.controller1.save_state(w);
+        self.controller2.save_state(w);
+        w.bool(self.strobe);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.controller1.load_state(r)?;
+        self.controller2.load_state(r)?;
+        self.strobe = r.bool()?;
+        Ok(())
+    }
+}
+
 impl Default for Input {
     fn default() -> Self {
         Self::new()
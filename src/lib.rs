@@ -8,26 +8,37 @@
 //! - [`ppu`] - Picture Processing Unit (2C02)
 //! - [`apu`] - Audio Processing Unit
 //! - [`memory`] - Memory bus (CPU/PPU/APU/Input/Cartridge)
-//! - [`cartridge`] - iNES ROM loading and mapper support
+//! - [`cartridge`] - iNES ROM and FDS disk image loading, and mapper support
+//! - [`header`] - iNES / NES 2.0 header parsing
+//! - [`mapper`] - Cartridge boards and the interface they see
+//! - [`region`] - NTSC/PAL/Dendy timing tables
 //! - [`input`] - Controller input handling
 //! - [`trace`] - CPU instruction tracing
+//! - [`savestate`] - Versioned save state serialization
+//! - [`rewind`] - Delta-compressed snapshot history for rewinding
+//! - [`nes`] - The complete console and its frame loop
 
 pub mod apu;
 pub mod cartridge;
 pub mod cpu;
+pub mod header;
 pub mod input;
+pub mod mapper;
 pub mod memory;
+pub mod nes;
 pub mod ppu;
+pub mod region;
+pub mod rewind;
+pub mod savestate;
 pub mod trace;
 
+pub use nes::Nes;
+pub use region::Region;
+
 /// NES display width in pixels
 pub const NES_WIDTH: usize = 256;
 /// NES display height in pixels
 pub const NES_HEIGHT: usize = 240;
-/// PPU cycles per frame (NTSC)
-pub const PPU_CYCLES_PER_FRAME: u64 = 89_342;
-/// CPU runs at 1/3 the PPU clock rate
-pub const CPU_CYCLES_PER_PPU_CYCLE: f64 = 1.0 / 3.0;
 
 /// NES 2C02 PPU palette (RGB values) - 64 colors
 /// Reference: https://www.nesdev.org/wiki/PPU_palettes
@@ -8,9 +8,15 @@
 pub use nesium::apu;
 pub use nesium::cartridge;
 pub use nesium::cpu;
+pub use nesium::header;
 pub use nesium::input;
+pub use nesium::mapper;
 pub use nesium::memory;
+pub use nesium::nes;
 pub use nesium::ppu;
+pub use nesium::region;
+pub use nesium::rewind;
+pub use nesium::savestate;
 pub use nesium::trace;
 
 // Desktop-only modules
@@ -0,0 +1,137 @@
+//! Action 53 (mapper 28): the homebrew multicart board, able to act as
+//! NROM, BNROM, UNROM or AOROM for each game on it
+//!
+//! $5000-$5FFF selects one of four registers (value bits 7 and 0), and
+//! writes to $8000-$FFFF go to the selected one:
+//!
+//! - $00: 8KB CHR-RAM page
+//! - $01: inner PRG bank, 16KB or 32KB depending on the mode
+//! - $80: mode: mirroring, PRG banking style, and the game's size
+//! - $81: outer PRG bank, in 32KB units
+//!
+//! Writes to $00 or $01 also set the one-screen page from bit 4, so games
+//! written for AOROM keep working.
+//!
+//! Reference: https://www.nesdev.org/wiki/Action_53_mapper
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct Action53Mapper {
+    has_chr_ram: bool,
+    selected: u8,
+    chr_bank: u8,
+    inner_bank: u8,
+    // Mirroring in bits 0-1, PRG banking style in 2-3, game size in 4-5
+    mode: u8,
+    outer_bank: u8,
+}
+
+impl Action53Mapper {
+    pub fn new(has_chr_ram: bool) -> Self {
+        Self {
+            has_chr_ram,
+            selected: 0,
+            chr_bank: 0,
+            inner_bank: 0,
+            mode: 0,
+            // The menu starts from the last 32KB
+            outer_bank: 0xFF,
+        }
+    }
+
+    /// 16KB PRG bank for the CPU address
+    fn prg_bank(&self, addr: u16) -> usize {
+        let a14 = ((addr >> 14) & 0x01) as usize;
+        let inner = self.inner_bank as usize;
+        let bank = match (self.mode >> 2) & 0x03 {
+            // 32KB
+            0 | 1 => (inner << 1) | a14,
+            // UNROM with the game's first bank fixed at $8000
+            2 if a14 == 0 => 0,
+            // UNROM with the game's last bank fixed at $C000
+            3 if a14 == 1 => 0xFF,
+            _ => inner,
+        };
+        // The game size decides how many of the low bank bits come from the
+        // inner bank rather than the outer one
+        let mask = (2 << ((self.mode >> 4) & 0x03)) - 1;
+        ((self.outer_bank as usize) << 1) & !mask | (bank & mask)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        self.chr_bank as usize * 0x2000 + (addr as usize & 0x1FFF)
+    }
+
+    /// In the one-screen modes, bit 4 of a CHR or PRG bank write picks the page
+    fn set_one_screen_page(&mut self, value: u8) {
+        if (self.mode & 0x02) == 0 {
+            self.mode = (self.mode & !0x01) | ((value >> 4) & 0x01);
+        }
+    }
+}
+
+impl Mapper for Action53Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        prg_rom[(self.prg_bank(addr) * 0x4000 + (addr as usize & 0x3FFF)) % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        match self.selected {
+            0x00 => {
+                self.chr_bank = value & 0x03;
+                self.set_one_screen_page(value);
+            }
+            0x01 => {
+                self.inner_bank = value & 0x0F;
+                self.set_one_screen_page(value);
+            }
+            0x80 => self.mode = value & 0x3F,
+            _ => self.outer_bank = value,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            0x5000..=0x5FFF => self.selected = value & 0x81,
+            0x6000..=0x7FFF => self.prg_ram_write(addr, value, &mut mem.prg_ram),
+            0x8000..=0xFFFF => self.register_write(addr, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mode & 0x03 {
+            0 => Mirroring::OneScreenLower,
+            1 => Mirroring::OneScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.selected);
+        w.u8(self.chr_bank);
+        w.u8(self.inner_bank);
+        w.u8(self.mode);
+        w.u8(self.outer_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.selected = r.u8()? & 0x81;
+        self.chr_bank = r.u8()? & 0x03;
+        self.inner_bank = r.u8()? & 0x0F;
+        self.mode = r.u8()? & 0x3F;
+        self.outer_bank = r.u8()?;
+        Ok(())
+    }
+}
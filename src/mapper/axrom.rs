@@ -0,0 +1,64 @@
+//! AxROM (mapper 7): switchable 32KB PRG bank and one-screen mirroring
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+// AxROM (Mapper 7) - used by Battletoads, Marble Madness, etc.
+pub struct AxromMapper {
+    has_chr_ram: bool,
+    // AMROM and AOROM boards leave PRG-ROM enabled on writes; ANROM doesn't
+    bus_conflicts: bool,
+    // $8000-$FFFF: bits 0-3 select the 32KB PRG bank, bit 4 the nametable
+    register: u8,
+}
+
+impl AxromMapper {
+    pub fn new(has_chr_ram: bool, bus_conflicts: bool) -> Self {
+        Self {
+            has_chr_ram,
+            bus_conflicts,
+            register: 0,
+        }
+    }
+}
+
+impl Mapper for AxromMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let bank = (self.register & 0x0F) as usize;
+        prg_rom[(bank * 0x8000 + (addr as usize & 0x7FFF)) % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        self.register = value;
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if (self.register & 0x10) != 0 {
+            Mirroring::OneScreenUpper
+        } else {
+            Mirroring::OneScreenLower
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.register);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.register = r.u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,473 @@
+//! Bandai FCG boards (mappers 16, 153, 157 and 159): 16KB PRG and 1KB CHR
+//! banking with a 16-bit CPU-cycle IRQ counter
+//!
+//! - FCG-1/2 (16.4): registers at $6000-$7FFF, no save chip
+//! - LZ93D50 (16.5, 159): registers at $8000-$FFFF and a serial EEPROM
+//!   (24C02, or X24C01 on mapper 159) read through $6000-$7FFF
+//! - LZ93D50 with SRAM (153): 8KB of battery RAM and a 512KB PRG outer bank
+//! - Datach Joint ROM System (157): a 24C02 in the base unit, plus an
+//!   X24C01 on some game cartridges, clocked by its own SCL bit
+//!
+//! Mapper 157 support is partial: the Datach's barcode reader isn't
+//! emulated, so its data line always reads as if no card had been scanned.
+//!
+//! Old headers can't tell the first two apart, so mapper 16 without a
+//! submapper answers at both register ranges, each with its own chip's
+//! IRQ behaviour.
+//!
+//! Reference: https://www.nesdev.org/wiki/Bandai_FCG_board
+
+use std::ops::Range;
+
+use super::eeprom::{Eeprom, EepromKind};
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Board {
+    Fcg,
+    Lz93d50,
+    /// Unknown mapper 16 board: either of the above
+    Either,
+    /// Mapper 153
+    Sram,
+    /// Mapper 157
+    Datach,
+}
+
+pub struct BandaiMapper {
+    board: Board,
+    has_chr_ram: bool,
+    chr_banks: [u8; 8],
+    prg_bank: u8,
+    // Mapper 153: 256KB half of PRG-ROM, from bit 0 of the CHR registers
+    prg_outer_bank: u8,
+    mirroring: u8,
+    irq_enabled: bool,
+    irq_counter: u16,
+    // LZ93D50: the counter reloads from here when the IRQ is enabled
+    irq_latch: u16,
+    irq_pending: bool,
+    // $800D: bit 5 SCL, bit 6 SDA, bit 7 releases SDA for the EEPROM to
+    // drive (mapper 153: bit 5 enables SRAM)
+    eeprom_control: u8,
+    eeprom: Option<Eeprom>,
+    // Datach: the game cartridge's X24C01, on $800D bit 3 for SCL and
+    // sharing SDA with the base unit's 24C02
+    cart_eeprom: Option<Eeprom>,
+    // Where the EEPROMs' contents sit in PRG-RAM, one after the other: the
+    // battery-backed part if there is one
+    eeprom_offset: usize,
+}
+
+impl BandaiMapper {
+    /// `eeprom_offset` is the start of battery-backed PRG-RAM, where the
+    /// EEPROM's contents are kept if the header gives it room
+    pub fn new(
+        mapper: u16,
+        submapper: u8,
+        has_chr_ram: bool,
+        prg_nvram_size: usize,
+        eeprom_offset: usize,
+    ) -> Self {
+        let board = match (mapper, submapper) {
+            (16, 4) => Board::Fcg,
+            (16, 5) | (159, _) => Board::Lz93d50,
+            (153, _) => Board::Sram,
+            (157, _) => Board::Datach,
+            _ => Board::Either,
+        };
+        let eeprom = match (board, mapper) {
+            (Board::Fcg | Board::Sram, _) => None,
+            (Board::Datach, _) => Some(EepromKind::C24C02),
+            (_, 159) => Some(EepromKind::X24C01),
+            // NES 2.0 headers give the EEPROM's size as PRG-NVRAM
+            _ if prg_nvram_size == 128 => Some(EepromKind::X24C01),
+            _ => Some(EepromKind::C24C02),
+        };
+        Self {
+            board,
+            has_chr_ram,
+            chr_banks: [0; 8],
+            prg_bank: 0,
+            prg_outer_bank: 0,
+            mirroring: 0,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_pending: false,
+            eeprom_control: 0,
+            eeprom: eeprom.map(Eeprom::new),
+            // A NES 2.0 header with room for just the 24C02 rules it out
+            cart_eeprom: (board == Board::Datach && prg_nvram_size != 256)
+                .then(|| Eeprom::new(EepromKind::X24C01)),
+            eeprom_offset: if prg_nvram_size > 0 { eeprom_offset } else { 0 },
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        match self.board {
+            // CHR-RAM, not banked
+            Board::Sram | Board::Datach => addr as usize,
+            _ => {
+                let bank = self.chr_banks[(addr as usize >> 10) & 0x07] as usize;
+                bank * 0x400 + (addr as usize & 0x03FF)
+            }
+        }
+    }
+
+    /// `lz93d50`: the write went to the LZ93D50's register range
+    fn write_register(&mut self, addr: u16, value: u8, lz93d50: bool, prg_ram: &mut [u8]) {
+        match addr & 0x0F {
+            reg @ 0..=7 => {
+                self.chr_banks[reg as usize] = value;
+                self.prg_outer_bank = value & 0x01;
+            }
+            8 => self.prg_bank = value & 0x0F,
+            9 => self.mirroring = value & 0x03,
+            0x0A => {
+                self.irq_enabled = (value & 0x01) != 0;
+                self.irq_pending = false;
+                if lz93d50 {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            // The FCG-1/2 writes the counter directly, the LZ93D50 a latch
+            0x0B if lz93d50 => self.irq_latch = (self.irq_latch & 0xFF00) | value as u16,
+            0x0C if lz93d50 => self.irq_latch = (self.irq_latch & 0x00FF) | ((value as u16) << 8),
+            0x0B => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            0x0C => self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8),
+            0x0D => {
+                self.eeprom_control = value;
+                let scl = (value & 0x20) != 0;
+                // With bit 7 set the mapper lets go of SDA
+                let sda = (value & 0xC0) != 0;
+                let (data, cart_data) = self.eeprom_ranges(prg_ram.len());
+                if let Some(eeprom) = &mut self.eeprom {
+                    eeprom.write_lines(scl, sda, &mut prg_ram[data]);
+                }
+                if let Some(eeprom) = &mut self.cart_eeprom {
+                    eeprom.write_lines((value & 0x08) != 0, sda, &mut prg_ram[cart_data]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Parts of PRG-RAM holding the contents of the EEPROM, and of the
+    /// Datach cartridge's EEPROM after it
+    fn eeprom_ranges(&self, prg_ram_len: usize) -> (Range<usize>, Range<usize>) {
+        let size = |eeprom: &Option<Eeprom>| eeprom.as_ref().map_or(0, |eeprom| eeprom.size());
+        let start = self.eeprom_offset.min(prg_ram_len);
+        let middle = (start + size(&self.eeprom)).min(prg_ram_len);
+        let end = (middle + size(&self.cart_eeprom)).min(prg_ram_len);
+        (start..middle, middle..end)
+    }
+
+    fn sram_enabled(&self) -> bool {
+        self.board == Board::Sram && (self.eeprom_control & 0x20) != 0
+    }
+}
+
+impl Mapper for BandaiMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let (outer, last) = match self.board {
+            Board::Sram => (
+                self.prg_outer_bank as usize * 16,
+                self.prg_outer_bank as usize * 16 + 15,
+            ),
+            _ => (0, (prg_rom.len() / 0x4000).saturating_sub(1)),
+        };
+        let bank = match addr {
+            0x8000..=0xBFFF => outer + self.prg_bank as usize,
+            _ => last,
+        };
+        prg_rom[(bank * 0x4000 + (addr as usize & 0x3FFF)) % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, _addr: u16, _value: u8) {
+        // The EEPROM needs PRG-RAM; see cpu_write
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if self.sram_enabled() && !prg_ram.is_empty() {
+            return Some(prg_ram[(addr as usize - 0x6000) % prg_ram.len()]);
+        }
+        let eeprom = self.eeprom.as_ref()?;
+        // SDA reads back on bit 4 (low if anything pulls it down). On the
+        // Datach, bit 3 is the barcode reader's data line, which stays low
+        // with no card scanned. The rest is open bus: the address's high
+        // byte.
+        let cart_sda = self
+            .cart_eeprom
+            .as_ref()
+            .is_none_or(|eeprom| eeprom.output());
+        let sda = eeprom.output() && cart_sda && (self.eeprom_control & 0xC0) != 0;
+        Some(((addr >> 8) as u8 & !0x18) | ((sda as u8) << 4))
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if self.sram_enabled() && !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match (addr, self.board) {
+            (0x6000..=0x7FFF, Board::Fcg | Board::Either) => {
+                self.write_register(addr, value, false, &mut mem.prg_ram)
+            }
+            (0x6000..=0x7FFF, _) => self.prg_ram_write(addr, value, &mut mem.prg_ram),
+            (0x8000..=0xFFFF, Board::Fcg) => {}
+            (0x8000..=0xFFFF, _) => self.write_register(addr, value, true, &mut mem.prg_ram),
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_enabled {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0 {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::OneScreenLower,
+            _ => Mirroring::OneScreenUpper,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.chr_banks);
+        w.u8(self.prg_bank);
+        w.u8(self.prg_outer_bank);
+        w.u8(self.mirroring);
+        w.bool(self.irq_enabled);
+        w.u16(self.irq_counter);
+        w.u16(self.irq_latch);
+        w.bool(self.irq_pending);
+        w.u8(self.eeprom_control);
+        if let Some(eeprom) = &self.eeprom {
+            eeprom.save_state(w);
+        }
+        if let Some(eeprom) = &self.cart_eeprom {
+            eeprom.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.bytes_into(&mut self.chr_banks)?;
+        self.prg_bank = r.u8()? & 0x0F;
+        self.prg_outer_bank = r.u8()? & 0x01;
+        self.mirroring = r.u8()? & 0x03;
+        self.irq_enabled = r.bool()?;
+        self.irq_counter = r.u16()?;
+        self.irq_latch = r.u16()?;
+        self.irq_pending = r.bool()?;
+        self.eeprom_control = r.u8()?;
+        if let Some(eeprom) = &mut self.eeprom {
+            eeprom.load_state(r)?;
+        }
+        if let Some(eeprom) = &mut self.cart_eeprom {
+            eeprom.load_state(r)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Drives an EEPROM's SCL (the given bit of $800D) and SDA (bit 6) the
+    /// way a game does, reading SDA back from $6000 bit 4
+    struct EepromBus {
+        mapper: BandaiMapper,
+        mem: CartridgeMemory,
+        scl_bit: u8,
+        lsb_first: bool,
+        scl: bool,
+        sda: bool,
+    }
+
+    impl EepromBus {
+        fn new(mapper: BandaiMapper, prg_ram_size: usize, scl_bit: u8, lsb_first: bool) -> Self {
+            Self {
+                mapper,
+                mem: CartridgeMemory {
+                    prg_rom: vec![0; 0x8000],
+                    chr_rom: Vec::new(),
+                    prg_ram: vec![0; prg_ram_size],
+                    chr_ram: vec![0; 0x2000],
+                    nametable_ram: Vec::new(),
+                },
+                scl_bit,
+                lsb_first,
+                scl: false,
+                sda: true,
+            }
+        }
+
+        /// Datach with 8KB of battery RAM, driving the cartridge EEPROM's
+        /// SCL ($800D bit 3) while the base unit's SCL stays low
+        fn datach() -> Self {
+            Self::new(BandaiMapper::new(157, 0, true, 0x2000, 0), 0x2000, 3, true)
+        }
+
+        /// LZ93D50 with a 24C02 ($800D bit 5)
+        fn lz93d50() -> Self {
+            Self::new(BandaiMapper::new(16, 5, true, 0x100, 0), 0x100, 5, false)
+        }
+
+        fn lines(&mut self, scl: bool, sda: bool) {
+            (self.scl, self.sda) = (scl, sda);
+            let value = (scl as u8) << self.scl_bit | (sda as u8) << 6;
+            self.mapper.cpu_write(0x800D, value, &mut self.mem);
+        }
+
+        fn start(&mut self) {
+            self.lines(false, true);
+            self.lines(true, true);
+            self.lines(true, false);
+            self.lines(false, false);
+        }
+
+        fn stop(&mut self) {
+            self.lines(false, false);
+            self.lines(true, false);
+            self.lines(true, true);
+        }
+
+        /// Bit of a byte that goes over the line `i`th
+        fn bit(&self, i: u8) -> u8 {
+            if self.lsb_first {
+                i
+            } else {
+                7 - i
+            }
+        }
+
+        /// Send a byte, then clock the chip's acknowledge
+        fn send(&mut self, byte: u8) -> bool {
+            for i in 0..8 {
+                let bit = self.bit(i);
+                self.lines(false, (byte >> bit) & 0x01 != 0);
+                self.lines(true, self.sda);
+                self.lines(false, self.sda);
+            }
+            self.lines(false, true);
+            self.lines(true, true);
+            let ack = !self.sda_in();
+            self.lines(false, true);
+            ack
+        }
+
+        /// Receive a byte; `ack` asks for another one after it
+        fn receive(&mut self, ack: bool) -> u8 {
+            let mut byte = 0;
+            for i in 0..8 {
+                let bit = self.bit(i);
+                self.lines(true, true);
+                byte |= (self.sda_in() as u8) << bit;
+                self.lines(false, true);
+            }
+            self.lines(false, !ack);
+            self.lines(true, !ack);
+            self.lines(false, true);
+            byte
+        }
+
+        fn sda_in(&self) -> bool {
+            let value = self.mapper.prg_ram_read(0x6000, &self.mem.prg_ram).unwrap();
+            (value & 0x10) != 0
+        }
+    }
+
+    #[test]
+    fn datach_cartridge_eeprom_has_its_own_clock() {
+        let mut datach = EepromBus::datach();
+        datach.start();
+        assert!(datach.send(0x05));
+        assert!(datach.send(0xA7));
+        datach.stop();
+        // After the base unit's 256 bytes
+        assert_eq!(datach.mem.prg_ram[0x100 + 0x05], 0xA7);
+        assert!(datach.mem.prg_ram[..0x100].iter().all(|&b| b == 0));
+
+        datach.start();
+        assert!(datach.send(0x80 | 0x05));
+        assert_eq!(datach.receive(false), 0xA7);
+        datach.stop();
+    }
+
+    #[test]
+    fn datach_without_room_for_a_cartridge_eeprom() {
+        let mapper = BandaiMapper::new(157, 0, true, 256, 0);
+        assert!(mapper.cart_eeprom.is_none());
+    }
+
+    #[test]
+    fn lz93d50_24c02_write_then_random_read() {
+        let mut bus = EepromBus::lz93d50();
+
+        // Device address, word address, data, each acknowledged by the chip
+        // pulling SDA low for the ninth clock and letting go after it
+        bus.start();
+        assert!(bus.send(0xA0));
+        for i in 0..8 {
+            let bit = (0x12 >> (7 - i)) & 0x01 != 0;
+            bus.lines(false, bit);
+            bus.lines(true, bit);
+            bus.lines(false, bit);
+        }
+        bus.lines(false, true);
+        assert!(!bus.sda_in());
+        bus.lines(true, true);
+        assert!(!bus.sda_in());
+        bus.lines(false, true);
+        assert!(bus.sda_in());
+        assert!(bus.send(0x5A));
+        bus.stop();
+        assert_eq!(bus.mem.prg_ram[0x12], 0x5A);
+
+        // A write to another device is ignored
+        bus.start();
+        assert!(!bus.send(0xA2));
+        bus.stop();
+
+        // Dummy write of the word address, repeated start, then read
+        bus.mem.prg_ram[0x13] = 0xC3;
+        bus.start();
+        assert!(bus.send(0xA0));
+        assert!(bus.send(0x12));
+        bus.start();
+        assert!(bus.send(0xA1));
+        assert_eq!(bus.receive(true), 0x5A);
+        assert_eq!(bus.receive(false), 0xC3);
+        bus.stop();
+
+        // The master's NACK ended the read: the chip leaves SDA alone
+        bus.lines(false, true);
+        bus.lines(true, true);
+        assert!(bus.sda_in());
+    }
+}
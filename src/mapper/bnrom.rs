@@ -0,0 +1,59 @@
+//! BNROM (mapper 34): switchable 32KB PRG bank, 8KB CHR-RAM
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+// BNROM (Mapper 34) - used by Deadly Towers and a few homebrew games
+pub struct BnromMapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    prg_bank: u8, // 32KB bank at $8000-$FFFF, written anywhere in $8000-$FFFF
+}
+
+impl BnromMapper {
+    pub fn new(mirroring: Mirroring, has_chr_ram: bool) -> Self {
+        Self {
+            mirroring,
+            has_chr_ram,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for BnromMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = self.prg_bank as usize * 0x8000 + (addr as usize & 0x7FFF);
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        // Oversized homebrew boards use all 8 bits
+        self.prg_bank = value;
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_bank = r.u8()?;
+        Ok(())
+    }
+}
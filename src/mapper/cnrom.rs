@@ -0,0 +1,103 @@
+//! CNROM (mapper 3): switchable 8KB CHR bank, fixed PRG
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct CnromMapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    // Licensed boards leave PRG-ROM enabled on writes
+    bus_conflicts: bool,
+    chr_bank: u8,  // Current CHR bank selected (8KB banks)
+    chr_banks: u8, // Total number of CHR banks (8KB each)
+}
+
+impl CnromMapper {
+    pub fn new(
+        mirroring: Mirroring,
+        has_chr_ram: bool,
+        bus_conflicts: bool,
+        chr_rom_size: usize,
+    ) -> Self {
+        let chr_banks = if has_chr_ram {
+            0
+        } else {
+            (chr_rom_size / 0x2000) as u8
+        }; // Number of 8KB banks
+        Self {
+            mirroring,
+            has_chr_ram,
+            bus_conflicts,
+            chr_bank: 0, // Start with first bank
+            chr_banks,
+        }
+    }
+}
+
+impl Mapper for CnromMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        // CNROM: PRG ROM is not banked, always 32KB (or 16KB mirrored)
+        let addr = addr - 0x8000;
+        if prg_rom.len() == 0x4000 {
+            // 16KB PRG ROM, mirrored
+            prg_rom[addr as usize % 0x4000]
+        } else {
+            // 32KB PRG ROM
+            prg_rom[addr as usize]
+        }
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        // CNROM: Writing to ANY address in $8000-$FFFF selects CHR bank (8KB banks)
+        // C reference: mask = mapper->CHR_banks > 4? 0xf : 0x3;
+        // C reference: CHR_ptrs[0] = CHR_ROM + 0x2000 * (value & mask);
+        // Critical: Must trigger on ANY write >= $8000, not just specific addresses
+        // Critical: Use mask 0x03 for 4 banks (Paperboy), 0x0F for >4 banks
+        if !self.has_chr_ram {
+            // Determine mask: 0x03 for <=4 banks, 0x0F for >4 banks
+            let mask = if self.chr_banks > 4 { 0x0F } else { 0x03 };
+            let new_bank = value & mask;
+
+            // Always update bank (even if same) to match C reference behavior
+            if new_bank != self.chr_bank {
+                log::info!(
+                    "CNROM CHR bank switch: {} -> {} (value=0x{:02X}, mask=0x{:02X}, chr_banks={})",
+                    self.chr_bank,
+                    new_bank,
+                    value,
+                    mask,
+                    self.chr_banks
+                );
+            }
+            self.chr_bank = new_bank;
+        }
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        // 8KB CHR bank, always 0 with CHR-RAM
+        let offset = self.chr_bank as usize * 0x2000 + (addr as usize & 0x1FFF);
+        chr_read(mem, self.has_chr_ram, offset)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.chr_bank = r.u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+//! Serial EEPROMs on Bandai boards: the 128-byte Xicor X24C01 and the
+//! 256-byte 24C02, both talking I2C over two lines driven by a mapper
+//! register
+//!
+//! The 24C02 is a standard I2C device: a start condition, a device address
+//! byte, the word address, then data, all MSB first. The X24C01 predates
+//! the standard and skips the device byte: the first byte after a start
+//! holds the 7-bit word address and the read/write bit, and everything is
+//! sent LSB first. The chip acknowledges each byte it receives by pulling
+//! SDA low for the ninth clock; when reading, the master acknowledges
+//! instead to ask for the next byte.
+//!
+//! The contents are kept in the cartridge's PRG-RAM so they're saved along
+//! with battery RAM.
+
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EepromKind {
+    X24C01,
+    C24C02,
+}
+
+impl EepromKind {
+    fn size(self) -> usize {
+        match self {
+            EepromKind::X24C01 => 128,
+            EepromKind::C24C02 => 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Idle,
+    /// Receiving the 24C02's device address byte
+    Device,
+    /// Receiving the word address (and, on the X24C01, the read/write bit)
+    Address,
+    Write,
+    Read,
+    /// Chip pulling SDA low for the ninth clock, then moving on
+    Acknowledge(AfterAck),
+    /// Master's turn to acknowledge a byte the chip sent
+    ReadAcknowledge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AfterAck {
+    Address,
+    Write,
+    Read,
+}
+
+impl Mode {
+    fn to_u8(self) -> u8 {
+        match self {
+            Mode::Idle => 0,
+            Mode::Device => 1,
+            Mode::Address => 2,
+            Mode::Write => 3,
+            Mode::Read => 4,
+            Mode::Acknowledge(AfterAck::Address) => 5,
+            Mode::Acknowledge(AfterAck::Write) => 6,
+            Mode::Acknowledge(AfterAck::Read) => 7,
+            Mode::ReadAcknowledge => 8,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Mode::Idle,
+            1 => Mode::Device,
+            2 => Mode::Address,
+            3 => Mode::Write,
+            4 => Mode::Read,
+            5 => Mode::Acknowledge(AfterAck::Address),
+            6 => Mode::Acknowledge(AfterAck::Write),
+            7 => Mode::Acknowledge(AfterAck::Read),
+            8 => Mode::ReadAcknowledge,
+            _ => return None,
+        })
+    }
+}
+
+pub(super) struct Eeprom {
+    kind: EepromKind,
+    mode: Mode,
+    address: u8,
+    // Byte being shifted in or out, and how many bits have gone
+    shift: u8,
+    bits: u8,
+    master_acked: bool,
+    /// Level the chip drives on SDA (true = released)
+    output: bool,
+    scl: bool,
+    sda: bool,
+}
+
+impl Eeprom {
+    pub(super) fn new(kind: EepromKind) -> Self {
+        Self {
+            kind,
+            mode: Mode::Idle,
+            address: 0,
+            shift: 0,
+            bits: 0,
+            master_acked: false,
+            output: true,
+            scl: false,
+            sda: false,
+        }
+    }
+
+    pub(super) fn size(&self) -> usize {
+        self.kind.size()
+    }
+
+    /// Level of SDA as the chip drives it; the line reads low if either
+    /// side pulls it down
+    pub(super) fn output(&self) -> bool {
+        self.output
+    }
+
+    /// The master set SCL and SDA. `data` is the chip's storage.
+    pub(super) fn write_lines(&mut self, scl: bool, sda: bool, data: &mut [u8]) {
+        if self.scl && scl && sda != self.sda {
+            if sda {
+                // Stop
+                self.mode = Mode::Idle;
+                self.output = true;
+            } else {
+                // Start (or repeated start)
+                self.mode = match self.kind {
+                    EepromKind::X24C01 => Mode::Address,
+                    EepromKind::C24C02 => Mode::Device,
+                };
+                self.shift = 0;
+                self.bits = 0;
+                self.output = true;
+            }
+        } else if !self.scl && scl {
+            self.clock_rise(sda);
+        } else if self.scl && !scl {
+            self.clock_fall(data);
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    fn lsb_first(&self) -> bool {
+        self.kind == EepromKind::X24C01
+    }
+
+    /// Data is sampled while SCL goes high
+    fn clock_rise(&mut self, sda: bool) {
+        match self.mode {
+            Mode::Device | Mode::Address | Mode::Write if self.bits < 8 => {
+                if self.lsb_first() {
+                    self.shift |= (sda as u8) << self.bits;
+                } else {
+                    self.shift = (self.shift << 1) | sda as u8;
+                }
+                self.bits += 1;
+            }
+            Mode::ReadAcknowledge => self.master_acked = !sda,
+            _ => {}
+        }
+    }
+
+    /// Whoever sends changes SDA while SCL is low
+    fn clock_fall(&mut self, data: &mut [u8]) {
+        match self.mode {
+            Mode::Device | Mode::Address | Mode::Write if self.bits == 8 => {
+                let byte = self.shift;
+                self.shift = 0;
+                self.bits = 0;
+                if let Some(next) = self.receive(byte, data) {
+                    self.mode = Mode::Acknowledge(next);
+                    self.output = false;
+                } else {
+                    self.mode = Mode::Idle;
+                }
+            }
+            Mode::Acknowledge(next) => {
+                self.output = true;
+                self.mode = match next {
+                    AfterAck::Address => Mode::Address,
+                    AfterAck::Write => Mode::Write,
+                    AfterAck::Read => {
+                        self.start_read(data);
+                        Mode::Read
+                    }
+                };
+            }
+            Mode::Read if self.bits < 8 => self.send_bit(),
+            Mode::Read => {
+                self.mode = Mode::ReadAcknowledge;
+                self.output = true;
+            }
+            Mode::ReadAcknowledge => {
+                if self.master_acked {
+                    self.address = self.address.wrapping_add(1) & (self.kind.size() - 1) as u8;
+                    self.start_read(data);
+                    self.mode = Mode::Read;
+                } else {
+                    self.mode = Mode::Idle;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle a received byte; `None` means the chip doesn't acknowledge
+    fn receive(&mut self, byte: u8, data: &mut [u8]) -> Option<AfterAck> {
+        match (self.mode, self.kind) {
+            (Mode::Device, _) => {
+                // 1010 A2 A1 A0 R/W; the board ties the chip select pins low
+                if (byte & 0xFE) != 0xA0 {
+                    return None;
+                }
+                Some(if (byte & 0x01) != 0 {
+                    AfterAck::Read
+                } else {
+                    AfterAck::Address
+                })
+            }
+            (Mode::Address, EepromKind::X24C01) => {
+                self.address = byte & 0x7F;
+                Some(if (byte & 0x80) != 0 {
+                    AfterAck::Read
+                } else {
+                    AfterAck::Write
+                })
+            }
+            (Mode::Address, EepromKind::C24C02) => {
+                self.address = byte;
+                Some(AfterAck::Write)
+            }
+            _ => {
+                if let Some(cell) = data.get_mut(self.address as usize) {
+                    *cell = byte;
+                }
+                // Page writes wrap within a 4-byte (X24C01) or 8-byte page
+                let page_mask = match self.kind {
+                    EepromKind::X24C01 => 0x03,
+                    EepromKind::C24C02 => 0x07,
+                };
+                self.address =
+                    (self.address & !page_mask) | (self.address.wrapping_add(1) & page_mask);
+                Some(AfterAck::Write)
+            }
+        }
+    }
+
+    fn start_read(&mut self, data: &[u8]) {
+        self.shift = data.get(self.address as usize).copied().unwrap_or(0xFF);
+        self.bits = 0;
+        self.send_bit();
+    }
+
+    fn send_bit(&mut self) {
+        self.output = if self.lsb_first() {
+            (self.shift >> self.bits) & 0x01 != 0
+        } else {
+            (self.shift >> (7 - self.bits)) & 0x01 != 0
+        };
+        self.bits += 1;
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.mode.to_u8());
+        w.u8(self.address);
+        w.u8(self.shift);
+        w.u8(self.bits);
+        w.bool(self.master_acked);
+        w.bool(self.output);
+        w.bool(self.scl);
+        w.bool(self.sda);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.mode =
+            Mode::from_u8(r.u8()?).ok_or(SaveStateError::Corrupt("invalid EEPROM state"))?;
+        self.address = r.u8()? & (self.kind.size() - 1) as u8;
+        self.shift = r.u8()?;
+        self.bits = r.u8()?.min(8);
+        self.master_acked = r.bool()?;
+        self.output = r.bool()?;
+        self.scl = r.bool()?;
+        self.sda = r.bool()?;
+        Ok(())
+    }
+}
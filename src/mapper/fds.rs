@@ -0,0 +1,487 @@
+//! Famicom Disk System: the RAM adapter in the cartridge slot, with the
+//! disk drive behind it
+//!
+//! The adapter maps 32KB of RAM at $6000-$DFFF, the 8KB BIOS at $E000, and
+//! 8KB of CHR-RAM. Games are loaded from disk into RAM by the BIOS. The
+//! registers:
+//!
+//! - $4020-$4022: 16-bit CPU-cycle timer IRQ, reload value and control
+//! - $4023: enables the disk and sound registers
+//! - $4024: byte to write to disk; $4031: byte read from disk
+//! - $4025: drive control (motor, read/write, CRC), mirroring, disk IRQ
+//! - $4030: IRQ and transfer status (reading acknowledges both IRQs)
+//! - $4032: drive status; $4033: battery
+//! - $4040-$408A: sound, see [`FdsAudio`]
+//!
+//! The drive streams the side under the head a byte every 150 CPU cycles
+//! while the motor runs, raising an IRQ per byte. The disk itself is an
+//! [`FdsDisk`]; changing sides goes through [`Mapper::insert_disk`].
+//!
+//! Reference: https://www.nesdev.org/wiki/Family_Computer_Disk_System
+
+use super::fds_audio::FdsAudio;
+use super::fds_disk::{crc_update, FdsDisk};
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// CPU cycles per byte passing under the head
+const BYTE_CYCLES: u32 = 150;
+/// CPU cycles from the head returning to the start of the side until the
+/// first byte reaches it
+const REWIND_CYCLES: u32 = 50000;
+/// CPU cycles a disk stays out of the drive when sides are switched, long
+/// enough for the BIOS to notice (about a second)
+const SWAP_CYCLES: u32 = 1_800_000;
+
+pub struct FdsMapper {
+    disk: FdsDisk,
+    // Side in the drive, and the one going in once a swap's delay is over
+    side: Option<usize>,
+    next_side: Option<usize>,
+    swap_delay: u32,
+    timer_reload: u16,
+    timer_counter: u16,
+    timer_repeat: bool,
+    timer_enabled: bool,
+    timer_irq: bool,
+    // $4023
+    disk_registers_enabled: bool,
+    sound_registers_enabled: bool,
+    // $4025
+    motor_on: bool,
+    transfer_reset: bool,
+    read_mode: bool,
+    horizontal_mirroring: bool,
+    crc_control: bool,
+    disk_ready: bool,
+    disk_irq_enabled: bool,
+    disk_irq: bool,
+    write_data: u8,
+    read_data: u8,
+    transfer_complete: bool,
+    // Drive mechanics: the head's position on the side, cycles until the
+    // next byte, and whether the head is moving over the side
+    position: usize,
+    delay: u32,
+    scanning: bool,
+    end_of_side: bool,
+    gap_ended: bool,
+    crc: u16,
+    previous_crc_control: bool,
+    audio: FdsAudio,
+}
+
+impl FdsMapper {
+    /// Power on with `side` of `disk` in the drive
+    pub fn new(disk: FdsDisk, side: Option<usize>) -> Self {
+        Self {
+            side: side.filter(|&side| side < disk.side_count()),
+            disk,
+            next_side: None,
+            swap_delay: 0,
+            timer_reload: 0,
+            timer_counter: 0,
+            timer_repeat: false,
+            timer_enabled: false,
+            timer_irq: false,
+            disk_registers_enabled: true,
+            sound_registers_enabled: true,
+            motor_on: false,
+            transfer_reset: false,
+            read_mode: true,
+            horizontal_mirroring: true,
+            crc_control: false,
+            disk_ready: false,
+            disk_irq_enabled: false,
+            disk_irq: false,
+            write_data: 0,
+            read_data: 0,
+            transfer_complete: false,
+            position: 0,
+            delay: 0,
+            scanning: false,
+            end_of_side: true,
+            gap_ended: false,
+            crc: 0,
+            previous_crc_control: false,
+            audio: FdsAudio::new(),
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if !self.timer_enabled {
+            return;
+        }
+        if self.timer_counter == 0 {
+            self.timer_irq = true;
+            self.timer_counter = self.timer_reload;
+            if !self.timer_repeat {
+                self.timer_enabled = false;
+            }
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    /// One CPU cycle of the drive: move the head along, and hand over a
+    /// byte to or from the disk whenever one passes under it
+    fn clock_drive(&mut self) {
+        let Some(side) = self.side.filter(|_| self.motor_on) else {
+            self.end_of_side = true;
+            self.scanning = false;
+            return;
+        };
+        if self.transfer_reset && !self.scanning {
+            return;
+        }
+        if self.end_of_side {
+            // The head goes back to the start of the side
+            self.end_of_side = false;
+            self.delay = REWIND_CYCLES;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        if self.read_mode {
+            self.read_byte(side);
+        } else {
+            self.write_byte(side);
+        }
+        self.previous_crc_control = self.crc_control;
+
+        self.position += 1;
+        if self.position >= self.disk.side(side).len() {
+            self.motor_on = false;
+            self.end_of_side = true;
+        } else {
+            self.delay = BYTE_CYCLES;
+        }
+    }
+
+    fn read_byte(&mut self, side: usize) {
+        let value = self.disk.side(side)[self.position];
+        if !self.previous_crc_control {
+            self.crc = crc_update(self.crc, value);
+        }
+        // Data starts with the first set bit after a gap: the start mark
+        // itself is swallowed without an IRQ
+        let mut raise_irq = self.disk_irq_enabled;
+        if !self.disk_ready {
+            self.gap_ended = false;
+            self.crc = 0;
+        } else if value != 0 && !self.gap_ended {
+            self.gap_ended = true;
+            raise_irq = false;
+        }
+        if self.gap_ended {
+            self.transfer_complete = true;
+            self.read_data = value;
+            self.disk_irq |= raise_irq;
+        }
+    }
+
+    fn write_byte(&mut self, side: usize) {
+        let mut value = 0;
+        if !self.crc_control {
+            self.transfer_complete = true;
+            value = self.write_data;
+            self.disk_irq |= self.disk_irq_enabled;
+        }
+        if !self.disk_ready {
+            value = 0;
+        }
+        if !self.crc_control {
+            self.crc = crc_update(self.crc, value);
+        } else {
+            // The CRC goes out low byte first, once two zero bytes have
+            // been shifted through it
+            if !self.previous_crc_control {
+                self.crc = crc_update(crc_update(self.crc, 0), 0);
+            }
+            value = self.crc as u8;
+            self.crc >>= 8;
+        }
+        self.disk.write(side, self.position, value);
+        self.gap_ended = false;
+    }
+
+    /// $4030-$4033 as they read right now
+    fn peek_disk_register(&self, addr: u16) -> u8 {
+        match addr {
+            0x4030 => self.timer_irq as u8 | (self.transfer_complete as u8) << 1,
+            0x4031 => self.read_data,
+            0x4032 => {
+                let inserted = self.side.is_some();
+                // Missing disk, not ready, write protected
+                !inserted as u8
+                    | ((!inserted || !self.scanning) as u8) << 1
+                    | (!inserted as u8) << 2
+            }
+            // The battery is always good
+            _ => 0x80,
+        }
+    }
+
+    fn write_disk_register(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4020 => self.timer_reload = (self.timer_reload & 0xFF00) | value as u16,
+            0x4021 => self.timer_reload = (self.timer_reload & 0x00FF) | (value as u16) << 8,
+            0x4022 => {
+                self.timer_repeat = (value & 0x01) != 0;
+                self.timer_enabled = (value & 0x02) != 0 && self.disk_registers_enabled;
+                if self.timer_enabled {
+                    self.timer_counter = self.timer_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4023 => {
+                self.disk_registers_enabled = (value & 0x01) != 0;
+                self.sound_registers_enabled = (value & 0x02) != 0;
+                if !self.disk_registers_enabled {
+                    self.timer_enabled = false;
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                }
+            }
+            _ if !self.disk_registers_enabled => {}
+            0x4024 => {
+                self.write_data = value;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4025 => {
+                self.motor_on = (value & 0x01) != 0;
+                self.transfer_reset = (value & 0x02) != 0;
+                self.read_mode = (value & 0x04) != 0;
+                self.horizontal_mirroring = (value & 0x08) != 0;
+                self.crc_control = (value & 0x10) != 0;
+                self.disk_ready = (value & 0x40) != 0;
+                self.disk_irq_enabled = (value & 0x80) != 0;
+                self.disk_irq = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for FdsMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        if prg_rom.is_empty() {
+            return 0;
+        }
+        prg_rom[(addr as usize & 0x1FFF) % prg_rom.len()]
+    }
+
+    // The adapter's registers are all at $4020-$408A
+    fn register_write(&mut self, _addr: u16, _value: u8) {}
+
+    fn cpu_peek(&self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        match addr {
+            0x4030..=0x4033 if self.disk_registers_enabled => Some(self.peek_disk_register(addr)),
+            0x4040..=0x4097 if self.sound_registers_enabled => self.audio.read(addr),
+            0x6000..=0xDFFF => self.prg_ram_read(addr, &mem.prg_ram),
+            0xE000..=0xFFFF => Some(self.prg_rom_read(addr, &mem.prg_rom)),
+            _ => None,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16, mem: &mut CartridgeMemory) -> Option<u8> {
+        let value = self.cpu_peek(addr, mem);
+        if value.is_some() {
+            match addr {
+                0x4030 => {
+                    self.transfer_complete = false;
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                }
+                0x4031 => {
+                    self.transfer_complete = false;
+                    self.disk_irq = false;
+                }
+                _ => {}
+            }
+        }
+        value
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            0x4020..=0x4026 => self.write_disk_register(addr, value),
+            0x4040..=0x408A if self.sound_registers_enabled => self.audio.write(addr, value),
+            0x6000..=0xDFFF => self.prg_ram_write(addr, value, &mut mem.prg_ram),
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.clock_timer();
+        self.audio.clock();
+        if self.swap_delay > 0 {
+            self.swap_delay -= 1;
+            if self.swap_delay == 0 {
+                self.side = self.next_side.take();
+            }
+        }
+        self.clock_drive();
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, true, addr as usize)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, true, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.horizontal_mirroring {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn disk(&self) -> Option<&FdsDisk> {
+        Some(&self.disk)
+    }
+
+    fn disk_mut(&mut self) -> Option<&mut FdsDisk> {
+        Some(&mut self.disk)
+    }
+
+    fn disk_side(&self) -> Option<usize> {
+        self.side
+    }
+
+    fn insert_disk(&mut self, side: Option<usize>) {
+        self.side = None;
+        self.next_side = side.filter(|&side| side < self.disk.side_count());
+        self.swap_delay = if self.next_side.is_some() {
+            SWAP_CYCLES
+        } else {
+            0
+        };
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        self.disk.save_state(w);
+        w.u8(self.side.map_or(0xFF, |side| side as u8));
+        w.u8(self.next_side.map_or(0xFF, |side| side as u8));
+        w.u32(self.swap_delay);
+        w.u16(self.timer_reload);
+        w.u16(self.timer_counter);
+        w.bool(self.timer_repeat);
+        w.bool(self.timer_enabled);
+        w.bool(self.timer_irq);
+        w.bool(self.disk_registers_enabled);
+        w.bool(self.sound_registers_enabled);
+        w.bool(self.motor_on);
+        w.bool(self.transfer_reset);
+        w.bool(self.read_mode);
+        w.bool(self.horizontal_mirroring);
+        w.bool(self.crc_control);
+        w.bool(self.disk_ready);
+        w.bool(self.disk_irq_enabled);
+        w.bool(self.disk_irq);
+        w.u8(self.write_data);
+        w.u8(self.read_data);
+        w.bool(self.transfer_complete);
+        w.usize(self.position);
+        w.u32(self.delay);
+        w.bool(self.scanning);
+        w.bool(self.end_of_side);
+        w.bool(self.gap_ended);
+        w.u16(self.crc);
+        w.bool(self.previous_crc_control);
+        self.audio.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.disk.load_state(r)?;
+        let sides = self.disk.side_count();
+        let mut side = || -> Result<Option<usize>, SaveStateError> {
+            match r.u8()? {
+                0xFF => Ok(None),
+                side if (side as usize) < sides => Ok(Some(side as usize)),
+                _ => Err(SaveStateError::Corrupt("invalid disk side")),
+            }
+        };
+        self.side = side()?;
+        self.next_side = side()?;
+        self.swap_delay = r.u32()?;
+        self.timer_reload = r.u16()?;
+        self.timer_counter = r.u16()?;
+        self.timer_repeat = r.bool()?;
+        self.timer_enabled = r.bool()?;
+        self.timer_irq = r.bool()?;
+        self.disk_registers_enabled = r.bool()?;
+        self.sound_registers_enabled = r.bool()?;
+        self.motor_on = r.bool()?;
+        self.transfer_reset = r.bool()?;
+        self.read_mode = r.bool()?;
+        self.horizontal_mirroring = r.bool()?;
+        self.crc_control = r.bool()?;
+        self.disk_ready = r.bool()?;
+        self.disk_irq_enabled = r.bool()?;
+        self.disk_irq = r.bool()?;
+        self.write_data = r.u8()?;
+        self.read_data = r.u8()?;
+        self.transfer_complete = r.bool()?;
+        self.position = r.usize()?;
+        self.delay = r.u32()?;
+        self.scanning = r.bool()?;
+        self.end_of_side = r.bool()?;
+        // The head is only ever past the last byte once the side has ended,
+        // and is rewound before the next read
+        let side_len = self.disk.side(0).len();
+        if self.position >= side_len && !(self.end_of_side && self.position == side_len) {
+            return Err(SaveStateError::Corrupt("disk head out of range"));
+        }
+        self.gap_ended = r.bool()?;
+        self.crc = r.u16()?;
+        self.previous_crc_control = r.bool()?;
+        self.audio.load_state(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with_head_at(position: usize, end_of_side: bool) -> Result<(), SaveStateError> {
+        let disk = FdsDisk::parse(&vec![0; 65500]).unwrap();
+        let mut mapper = FdsMapper::new(disk.clone(), Some(0));
+        mapper.position = position;
+        mapper.end_of_side = end_of_side;
+        let mut w = StateWriter::new();
+        mapper.save_state(&mut w);
+        let state = w.into_inner();
+        FdsMapper::new(disk, Some(0)).load_state(&mut StateReader::new(&state))
+    }
+
+    #[test]
+    fn load_state_checks_the_head_position() {
+        let len = FdsDisk::parse(&vec![0; 65500]).unwrap().side(0).len();
+        assert!(state_with_head_at(len - 1, false).is_ok());
+        // Where the last byte leaves it, until the side is rewound
+        assert!(state_with_head_at(len, true).is_ok());
+        assert!(state_with_head_at(len, false).is_err());
+        assert!(state_with_head_at(len + 1, true).is_err());
+    }
+}
@@ -0,0 +1,359 @@
+//! Famicom Disk System sound: one channel playing a 64-step, 6-bit
+//! wavetable, with a volume envelope and a modulation unit that bends its
+//! pitch from a second table of 64 steps
+//!
+//! - $4040-$407F: the wavetable, writable while $4089 bit 7 is set
+//! - $4080: volume envelope; $4082-$4083: wave frequency, halt bits
+//! - $4084: modulation envelope; $4085: modulation counter
+//! - $4086-$4087: modulation frequency; $4088: modulation table
+//! - $4089: master volume; $408A: envelope speed
+//!
+//! Reference: https://www.nesdev.org/wiki/FDS_audio
+
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// Output level of one step of the channel. At full volume the channel is
+/// about 2.4 times as loud as a full-volume APU pulse on its own.
+const OUTPUT_STEP: f32 = 95.88 / (8128.0 / 15.0 + 100.0) * 2.4 / 63.0;
+
+/// Gain for each master volume setting: 2/2, 2/3, 2/4 and 2/5
+const MASTER_VOLUME: [u32; 4] = [36, 24, 17, 14];
+
+/// Envelope shared by the volume and the modulation depth ("gain")
+struct Envelope {
+    speed: u8,
+    gain: u8,
+    increase: bool,
+    // Hold the gain at `speed` instead of moving it
+    disabled: bool,
+    timer: u32,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Self {
+            speed: 0,
+            gain: 0,
+            increase: false,
+            disabled: true,
+            timer: 0,
+        }
+    }
+
+    fn write(&mut self, value: u8, master_speed: u8) {
+        self.speed = value & 0x3F;
+        self.increase = (value & 0x40) != 0;
+        self.disabled = (value & 0x80) != 0;
+        if self.disabled {
+            self.gain = self.speed;
+        }
+        self.reset_timer(master_speed);
+    }
+
+    fn reset_timer(&mut self, master_speed: u8) {
+        self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+    }
+
+    /// One CPU cycle
+    fn clock(&mut self, master_speed: u8) {
+        if self.disabled || master_speed == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.reset_timer(master_speed);
+        if self.increase && self.gain < 32 {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.speed);
+        w.u8(self.gain);
+        w.bool(self.increase);
+        w.bool(self.disabled);
+        w.u32(self.timer);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.speed = r.u8()? & 0x3F;
+        self.gain = r.u8()? & 0x3F;
+        self.increase = r.bool()?;
+        self.disabled = r.bool()?;
+        self.timer = r.u32()?;
+        Ok(())
+    }
+}
+
+/// Modulation unit: steps through its table at its own frequency, moving a
+/// 7-bit signed counter that offsets the wave's pitch
+struct Modulator {
+    envelope: Envelope,
+    frequency: u16,
+    halted: bool,
+    counter: i8,
+    table: [u8; 64],
+    position: u8,
+    accumulator: u16,
+}
+
+impl Modulator {
+    fn new() -> Self {
+        Self {
+            envelope: Envelope::new(),
+            frequency: 0,
+            halted: true,
+            counter: 0,
+            table: [0; 64],
+            position: 0,
+            accumulator: 0,
+        }
+    }
+
+    fn set_counter(&mut self, value: i32) {
+        // Wraps within -64..=63
+        self.counter = (((value + 64) & 0x7F) - 64) as i8;
+    }
+
+    /// One CPU cycle: apply the next table entry each time the accumulator
+    /// overflows
+    fn clock(&mut self) {
+        if self.halted || self.frequency == 0 {
+            return;
+        }
+        let (accumulator, overflow) = self.accumulator.overflowing_add(self.frequency);
+        self.accumulator = accumulator;
+        if !overflow {
+            return;
+        }
+        let counter = self.counter as i32;
+        match self.table[self.position as usize] {
+            0 => {}
+            1 => self.set_counter(counter + 1),
+            2 => self.set_counter(counter + 2),
+            3 => self.set_counter(counter + 4),
+            4 => self.counter = 0,
+            5 => self.set_counter(counter - 4),
+            6 => self.set_counter(counter - 2),
+            _ => self.set_counter(counter - 1),
+        }
+        self.position = (self.position + 1) & 0x3F;
+    }
+
+    /// Pitch offset for a wave frequency, from the counter scaled by the
+    /// gain, with the chip's rounding
+    fn pitch_offset(&self, frequency: u16) -> i32 {
+        let counter = self.counter as i32;
+        let mut temp = counter * self.envelope.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && (temp & 0x80) == 0 {
+            temp += if counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+        temp *= frequency as i32;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+        temp
+    }
+
+    /// Writes to $4088 fill two entries at a time, while halted
+    fn write_table(&mut self, value: u8) {
+        if self.halted {
+            self.table[self.position as usize] = value & 0x07;
+            self.table[(self.position as usize + 1) & 0x3F] = value & 0x07;
+            self.position = (self.position + 2) & 0x3F;
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        self.envelope.save_state(w);
+        w.u16(self.frequency);
+        w.bool(self.halted);
+        w.u8(self.counter as u8);
+        w.bytes(&self.table);
+        w.u8(self.position);
+        w.u16(self.accumulator);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.envelope.load_state(r)?;
+        self.frequency = r.u16()? & 0x0FFF;
+        self.halted = r.bool()?;
+        self.set_counter(r.u8()? as i8 as i32);
+        r.bytes_into(&mut self.table)?;
+        self.position = r.u8()? & 0x3F;
+        self.accumulator = r.u16()?;
+        Ok(())
+    }
+}
+
+pub(super) struct FdsAudio {
+    wave_table: [u8; 64],
+    // $4089 bit 7: the table is writable and the wave holds still
+    wave_write: bool,
+    position: u8,
+    accumulator: u16,
+    frequency: u16,
+    // $4083 bit 7 stops the wave (back at its first step), bit 6 the envelopes
+    wave_halted: bool,
+    envelopes_halted: bool,
+    volume: Envelope,
+    modulator: Modulator,
+    master_volume: u8,
+    // $408A: multiplier for both envelopes' periods
+    envelope_speed: u8,
+    // Volume gain, latched each time the wave starts over
+    gain: u8,
+}
+
+impl FdsAudio {
+    pub(super) fn new() -> Self {
+        Self {
+            wave_table: [0; 64],
+            wave_write: false,
+            position: 0,
+            accumulator: 0,
+            frequency: 0,
+            wave_halted: true,
+            envelopes_halted: false,
+            volume: Envelope::new(),
+            modulator: Modulator::new(),
+            master_volume: 0,
+            envelope_speed: 0xE8,
+            gain: 0,
+        }
+    }
+
+    /// Read $4040-$4097; `None` for addresses that aren't readable
+    pub(super) fn read(&self, addr: u16) -> Option<u8> {
+        match addr {
+            // Outside write mode the table reads back the step being played
+            0x4040..=0x407F if self.wave_write => Some(self.wave_table[addr as usize & 0x3F]),
+            0x4040..=0x407F => Some(self.wave_table[self.position as usize]),
+            0x4090 => Some(0x40 | self.volume.gain),
+            0x4092 => Some(0x40 | self.modulator.envelope.gain),
+            _ => None,
+        }
+    }
+
+    pub(super) fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4040..=0x407F if self.wave_write => {
+                self.wave_table[addr as usize & 0x3F] = value & 0x3F;
+            }
+            0x4080 => self.volume.write(value, self.envelope_speed),
+            0x4082 => self.frequency = (self.frequency & 0x0F00) | value as u16,
+            0x4083 => {
+                self.frequency = (self.frequency & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.wave_halted = (value & 0x80) != 0;
+                self.envelopes_halted = (value & 0x40) != 0;
+                if self.wave_halted {
+                    self.position = 0;
+                    self.accumulator = 0;
+                }
+                if self.envelopes_halted {
+                    self.volume.reset_timer(self.envelope_speed);
+                    self.modulator.envelope.reset_timer(self.envelope_speed);
+                }
+            }
+            0x4084 => self.modulator.envelope.write(value, self.envelope_speed),
+            0x4085 => self.modulator.set_counter((value & 0x7F) as i32),
+            0x4086 => {
+                self.modulator.frequency = (self.modulator.frequency & 0x0F00) | value as u16;
+            }
+            0x4087 => {
+                self.modulator.frequency =
+                    (self.modulator.frequency & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.modulator.halted = (value & 0x80) != 0;
+                if self.modulator.halted {
+                    self.modulator.accumulator = 0;
+                }
+            }
+            0x4088 => self.modulator.write_table(value),
+            0x4089 => {
+                self.wave_write = (value & 0x80) != 0;
+                self.master_volume = value & 0x03;
+            }
+            0x408A => {
+                self.envelope_speed = value;
+                self.volume.reset_timer(value);
+                self.modulator.envelope.reset_timer(value);
+            }
+            _ => {}
+        }
+    }
+
+    /// One CPU cycle
+    pub(super) fn clock(&mut self) {
+        if !self.wave_halted && !self.envelopes_halted {
+            self.volume.clock(self.envelope_speed);
+            self.modulator.envelope.clock(self.envelope_speed);
+        }
+        self.modulator.clock();
+
+        if self.wave_halted || self.wave_write {
+            return;
+        }
+        let pitch = self.frequency as i32 + self.modulator.pitch_offset(self.frequency);
+        if pitch <= 0 {
+            return;
+        }
+        let (accumulator, overflow) = self.accumulator.overflowing_add(pitch as u16);
+        self.accumulator = accumulator;
+        if overflow {
+            self.position = (self.position + 1) & 0x3F;
+            if self.position == 0 {
+                self.gain = self.volume.gain;
+            }
+        }
+    }
+
+    pub(super) fn output(&self) -> f32 {
+        let level = (self.gain.min(32) as u32) * MASTER_VOLUME[self.master_volume as usize];
+        let sample = self.wave_table[self.position as usize] as u32 * level / 1152;
+        sample as f32 * OUTPUT_STEP
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.wave_table);
+        w.bool(self.wave_write);
+        w.u8(self.position);
+        w.u16(self.accumulator);
+        w.u16(self.frequency);
+        w.bool(self.wave_halted);
+        w.bool(self.envelopes_halted);
+        self.volume.save_state(w);
+        self.modulator.save_state(w);
+        w.u8(self.master_volume);
+        w.u8(self.envelope_speed);
+        w.u8(self.gain);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.bytes_into(&mut self.wave_table)?;
+        self.wave_write = r.bool()?;
+        self.position = r.u8()? & 0x3F;
+        self.accumulator = r.u16()?;
+        self.frequency = r.u16()? & 0x0FFF;
+        self.wave_halted = r.bool()?;
+        self.envelopes_halted = r.bool()?;
+        self.volume.load_state(r)?;
+        self.modulator.load_state(r)?;
+        self.master_volume = r.u8()? & 0x03;
+        self.envelope_speed = r.u8()?;
+        self.gain = r.u8()? & 0x3F;
+        Ok(())
+    }
+}
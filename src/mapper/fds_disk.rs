@@ -0,0 +1,347 @@
+//! Famicom Disk System disk images
+//!
+//! A `.fds` file (fwNES format) is an optional 16-byte header ("FDS\x1A" and
+//! the side count) followed by 65,500 bytes per disk side. Each side holds
+//! the blocks the BIOS reads back to back: the disk info block, the file
+//! count, then a header and a data block per file.
+//!
+//! On a real disk the blocks are separated by gaps, each ended by a $80
+//! start mark, and followed by a CRC. The drive emulation needs to see
+//! those, so every side is kept as the bytes passing under the head, and
+//! converted back to the file format when the disk is saved.
+//!
+//! Reference: https://www.nesdev.org/wiki/FDS_disk_format
+
+use crate::cartridge::CartridgeError;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// Size of the optional header in front of the sides
+const HEADER_SIZE: usize = 16;
+/// Size of one side in the file, without gaps or CRCs
+const SIDE_SIZE: usize = 65500;
+/// Size of one side as the head sees it, with room for the gaps
+const SIDE_CAPACITY: usize = 0x14000;
+/// Zero bytes before the first block (28,300 bits)
+const LEAD_IN: usize = 28300 / 8;
+/// Zero bytes between a block's CRC and the next start mark (976 bits)
+const BLOCK_GAP: usize = 976 / 8;
+/// Byte ending each gap
+const START_MARK: u8 = 0x80;
+
+/// The disk in the drive: every side of a `.fds` image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FdsDisk {
+    sides: Vec<Vec<u8>>,
+    modified: bool,
+}
+
+impl FdsDisk {
+    /// Parse a `.fds` image, with or without its header
+    pub fn parse(data: &[u8]) -> Result<Self, CartridgeError> {
+        let data = match data.strip_prefix(b"FDS\x1A") {
+            Some(_) if data.len() >= HEADER_SIZE => &data[HEADER_SIZE..],
+            Some(_) => return Err(CartridgeError::InvalidDiskImage),
+            None => data,
+        };
+        // Some dumps carry a trailing partial side; only whole ones count
+        let sides: Vec<Vec<u8>> = data.chunks_exact(SIDE_SIZE).map(add_gaps).collect();
+        if sides.is_empty() {
+            return Err(CartridgeError::InvalidDiskImage);
+        }
+        Ok(Self {
+            sides,
+            modified: false,
+        })
+    }
+
+    /// Number of disk sides (two per physical disk)
+    pub fn side_count(&self) -> usize {
+        self.sides.len()
+    }
+
+    /// Whether the game has written to the disk since it was loaded
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// The disk as a `.fds` image, with the fwNES header
+    pub fn to_image(&self) -> Vec<u8> {
+        let mut image = Vec::with_capacity(HEADER_SIZE + self.sides.len() * SIDE_SIZE);
+        image.extend_from_slice(b"FDS\x1A");
+        image.push(self.sides.len() as u8);
+        image.resize(HEADER_SIZE, 0);
+        for side in &self.sides {
+            image.extend_from_slice(&remove_gaps(side));
+        }
+        image
+    }
+
+    /// Replace the contents with those of another image of the same disk,
+    /// such as one written out by [`FdsDisk::to_image`]
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), CartridgeError> {
+        let disk = Self::parse(data)?;
+        if disk.side_count() != self.side_count() {
+            return Err(CartridgeError::InvalidDiskImage);
+        }
+        self.sides = disk.sides;
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Bytes under the head on `side`, gaps included
+    pub(super) fn side(&self, side: usize) -> &[u8] {
+        &self.sides[side]
+    }
+
+    pub(super) fn write(&mut self, side: usize, position: usize, value: u8) {
+        if let Some(byte) = self.sides[side].get_mut(position) {
+            if *byte != value {
+                *byte = value;
+                self.modified = true;
+            }
+        }
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.modified);
+        for side in &self.sides {
+            w.bytes(side);
+        }
+    }
+
+    /// Contents that differ from the disk's count as a modification even if
+    /// the state's weren't, so the save file catches up with them
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        let mut modified = r.bool()?;
+        for side in &mut self.sides {
+            let data = r.bytes()?;
+            if data.len() != side.len() {
+                return Err(SaveStateError::Corrupt("memory block size mismatch"));
+            }
+            modified |= data != *side;
+            *side = data;
+        }
+        self.modified |= modified;
+        Ok(())
+    }
+}
+
+/// Length of the block starting at `data[i]`, or `None` at the end of the
+/// used part of the side. A file's data block takes its size from the
+/// header block in front of it.
+fn block_length(data: &[u8], i: usize, file_size: usize) -> Option<usize> {
+    match data.get(i)? {
+        1 => Some(56),
+        2 => Some(2),
+        3 => Some(16),
+        4 => Some(1 + file_size),
+        _ => None,
+    }
+}
+
+/// File size recorded in a file header block starting at `data[i]`
+fn file_size(data: &[u8], i: usize) -> usize {
+    match (data.get(i + 13), data.get(i + 14)) {
+        (Some(&low), Some(&high)) => low as usize | (high as usize) << 8,
+        _ => 0,
+    }
+}
+
+/// Lay a side out as it is on the disk: lead-in, then each block behind a
+/// start mark and followed by its CRC and a gap
+fn add_gaps(data: &[u8]) -> Vec<u8> {
+    let mut side = vec![0; LEAD_IN];
+    let mut i = 0;
+    let mut size = 0;
+    while let Some(length) = block_length(data, i, size) {
+        if data[i] == 3 {
+            size = file_size(data, i);
+        }
+        let Some(block) = data.get(i..i + length) else {
+            break;
+        };
+        side.push(START_MARK);
+        side.extend_from_slice(block);
+        let crc = block_crc(block);
+        side.extend_from_slice(&crc.to_le_bytes());
+        side.resize(side.len() + BLOCK_GAP, 0);
+        i += length;
+    }
+    if side.len() > SIDE_CAPACITY {
+        log::warn!("FDS disk side too full; its last blocks are cut off");
+    }
+    side.resize(SIDE_CAPACITY, 0);
+    side
+}
+
+/// Collect the blocks of a side back into the file format
+fn remove_gaps(side: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(SIDE_SIZE);
+    let mut i = 0;
+    let mut size = 0;
+    // Skip each gap up to its start mark
+    while let Some(offset) = side[i.min(side.len())..]
+        .iter()
+        .position(|&b| b == START_MARK)
+    {
+        i += offset + 1;
+        let Some(length) = block_length(side, i, size) else {
+            break;
+        };
+        if side[i] == 3 {
+            size = file_size(side, i);
+        }
+        let Some(block) = side.get(i..i + length) else {
+            break;
+        };
+        data.extend_from_slice(block);
+        // Skip the CRC
+        i += length + 2;
+    }
+    data.resize(SIDE_SIZE, 0);
+    data
+}
+
+/// Feed one byte into the drive's CRC (CRC-16/KERMIT, bits in LSB first)
+pub(super) fn crc_update(crc: u16, value: u8) -> u16 {
+    let mut crc = crc;
+    for bit in 0..8 {
+        let carry = (crc & 0x01) != 0;
+        crc >>= 1;
+        if carry {
+            crc ^= 0x8408;
+        }
+        if (value >> bit) & 0x01 != 0 {
+            crc ^= 0x8000;
+        }
+    }
+    crc
+}
+
+/// CRC the drive writes after a block. It covers the start mark too, and
+/// is finished by shifting in two zero bytes.
+fn block_crc(block: &[u8]) -> u16 {
+    std::iter::once(START_MARK)
+        .chain(block.iter().copied())
+        .chain([0, 0])
+        .fold(0, crc_update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One side in file format: disk info, file count, and one 16-byte file
+    fn side(fill: u8) -> Vec<u8> {
+        let mut data = vec![0x01];
+        data.extend_from_slice(b"*NINTENDO-HVC*");
+        data.resize(56, fill);
+        data.extend_from_slice(&[0x02, 0x01]);
+        let mut header = vec![0x03, 0x00, 0x00];
+        header.extend_from_slice(b"KYODAKU-");
+        header.extend_from_slice(&[0x00, 0x28, 0x10, 0x00, 0x02]);
+        data.extend_from_slice(&header);
+        data.push(0x04);
+        data.extend((0..16).map(|i| fill ^ i));
+        data.resize(SIDE_SIZE, 0);
+        data
+    }
+
+    fn image(sides: &[Vec<u8>]) -> Vec<u8> {
+        let mut image = b"FDS\x1A".to_vec();
+        image.push(sides.len() as u8);
+        image.resize(HEADER_SIZE, 0);
+        for side in sides {
+            image.extend_from_slice(side);
+        }
+        image
+    }
+
+    #[test]
+    fn crc_matches_crc16_kermit() {
+        // The drive's register needs two zero bytes shifted through it
+        // before it holds the CRC of what came before
+        let crc = b"123456789"
+            .iter()
+            .copied()
+            .chain([0, 0])
+            .fold(0, crc_update);
+        assert_eq!(crc, 0x2189);
+    }
+
+    #[test]
+    fn image_round_trips() {
+        let image = image(&[side(0x11), side(0x22)]);
+        let disk = FdsDisk::parse(&image).unwrap();
+        assert_eq!(disk.side_count(), 2);
+        assert_eq!(disk.to_image(), image);
+
+        // A headerless image comes back with a header
+        let disk = FdsDisk::parse(&image[HEADER_SIZE..]).unwrap();
+        assert_eq!(disk.to_image(), image);
+    }
+
+    #[test]
+    fn blocks_are_laid_out_between_gaps() {
+        let data = side(0x33);
+        let side = add_gaps(&data);
+        assert_eq!(side.len(), SIDE_CAPACITY);
+        assert!(side[..LEAD_IN].iter().all(|&b| b == 0));
+
+        let mut i = LEAD_IN;
+        let mut j = 0;
+        for length in [56, 2, 16, 17] {
+            assert_eq!(side[i], START_MARK);
+            let block = &side[i + 1..i + 1 + length];
+            assert_eq!(block, &data[j..j + length]);
+            let crc = u16::from_le_bytes([side[i + 1 + length], side[i + 2 + length]]);
+            assert_eq!(crc, block_crc(block));
+            i += 1 + length + 2;
+            assert!(side[i..i + BLOCK_GAP].iter().all(|&b| b == 0));
+            i += BLOCK_GAP;
+            j += length;
+        }
+        assert!(side[i..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn written_blocks_reach_the_image() {
+        let mut disk = FdsDisk::parse(&image(&[side(0x44)])).unwrap();
+        assert!(!disk.is_modified());
+
+        // Last byte of the file's data block
+        let position = LEAD_IN + (1 + 56 + 2 + BLOCK_GAP) + (1 + 2 + 2 + BLOCK_GAP);
+        let position = position + (1 + 16 + 2 + BLOCK_GAP) + 1 + 16;
+        disk.write(0, position, 0xA5);
+        assert!(disk.is_modified());
+
+        let mut expected = side(0x44);
+        expected[56 + 2 + 16 + 16] = 0xA5;
+        assert_eq!(disk.to_image(), image(&[expected]));
+    }
+
+    #[test]
+    fn load_state_keeps_modifications() {
+        let original = FdsDisk::parse(&image(&[side(0x55)])).unwrap();
+        let mut written = original.clone();
+        written.write(0, LEAD_IN + 10, 0xFF);
+
+        let mut w = StateWriter::new();
+        written.save_state(&mut w);
+        let state = w.into_inner();
+
+        let mut disk = original.clone();
+        disk.load_state(&mut StateReader::new(&state)).unwrap();
+        assert!(disk.is_modified());
+        assert_eq!(disk.side(0), written.side(0));
+
+        // Contents that differ count as modified, even from an older state
+        let mut w = StateWriter::new();
+        original.save_state(&mut w);
+        let state = w.into_inner();
+        let mut disk = written.clone();
+        disk.modified = false;
+        disk.load_state(&mut StateReader::new(&state)).unwrap();
+        assert!(disk.is_modified());
+    }
+}
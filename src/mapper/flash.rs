@@ -0,0 +1,140 @@
+//! SST39SF040 flash, the 512KB PRG chip on self-flashable homebrew boards
+//!
+//! Reads come straight from the array. Writes are commands, each opened by
+//! the unlock pair $AA to $5555 and $55 to $2AAA (chip addresses, of which
+//! only A0-A14 are decoded), then:
+//!
+//! - $A0: the next write programs one byte. Programming can only clear
+//!   bits, so the new value is ANDed in.
+//! - $80, unlock again, $30: erase the 4KB sector holding the address
+//! - $80, unlock again, $10 to $5555: erase the whole chip
+//! - $90: software ID mode, where reads return the maker and device IDs
+//!   until $F0 is written
+//!
+//! The programmed bytes live in the cartridge's PRG-ROM, which goes into
+//! save states and, on boards with the battery bit set, the save file.
+//!
+//! Reference: https://www.nesdev.org/wiki/SST39SF040
+
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const MANUFACTURER_ID: u8 = 0xBF;
+const DEVICE_ID: u8 = 0xB7;
+const SECTOR_SIZE: usize = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Idle,
+    /// $AA written to $5555
+    Unlock1,
+    /// $55 written to $2AAA
+    Unlock2,
+    /// Next write is the byte to program
+    Program,
+    /// $80 received; the second unlock sequence and the erase follow
+    EraseUnlock0,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+impl Command {
+    fn to_u8(self) -> u8 {
+        match self {
+            Command::Idle => 0,
+            Command::Unlock1 => 1,
+            Command::Unlock2 => 2,
+            Command::Program => 3,
+            Command::EraseUnlock0 => 4,
+            Command::EraseUnlock1 => 5,
+            Command::EraseUnlock2 => 6,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Command::Idle,
+            1 => Command::Unlock1,
+            2 => Command::Unlock2,
+            3 => Command::Program,
+            4 => Command::EraseUnlock0,
+            5 => Command::EraseUnlock1,
+            6 => Command::EraseUnlock2,
+            _ => return None,
+        })
+    }
+}
+
+pub(super) struct Flash {
+    command: Command,
+    software_id: bool,
+}
+
+impl Flash {
+    pub(super) fn new() -> Self {
+        Self {
+            command: Command::Idle,
+            software_id: false,
+        }
+    }
+
+    /// Read at `offset` into the chip; `None` reads the array normally
+    pub(super) fn read(&self, offset: usize) -> Option<u8> {
+        self.software_id.then_some(if (offset & 0x01) == 0 {
+            MANUFACTURER_ID
+        } else {
+            DEVICE_ID
+        })
+    }
+
+    /// CPU write at `offset` into the chip. `rom` is the flash array.
+    pub(super) fn write(&mut self, offset: usize, value: u8, rom: &mut [u8]) {
+        let command_addr = offset & 0x7FFF;
+        self.command = match (self.command, command_addr, value) {
+            (Command::Program, _, _) => {
+                if let Some(byte) = rom.get_mut(offset) {
+                    *byte &= value;
+                }
+                Command::Idle
+            }
+            (Command::Idle, 0x5555, 0xAA) => Command::Unlock1,
+            (Command::Unlock1, 0x2AAA, 0x55) => Command::Unlock2,
+            (Command::Unlock2, 0x5555, 0xA0) => Command::Program,
+            (Command::Unlock2, 0x5555, 0x80) => Command::EraseUnlock0,
+            (Command::Unlock2, 0x5555, 0x90) => {
+                self.software_id = true;
+                Command::Idle
+            }
+            (Command::EraseUnlock0, 0x5555, 0xAA) => Command::EraseUnlock1,
+            (Command::EraseUnlock1, 0x2AAA, 0x55) => Command::EraseUnlock2,
+            (Command::EraseUnlock2, _, 0x30) => {
+                let start = (offset & !(SECTOR_SIZE - 1)).min(rom.len());
+                let end = (start + SECTOR_SIZE).min(rom.len());
+                rom[start..end].fill(0xFF);
+                Command::Idle
+            }
+            (Command::EraseUnlock2, 0x5555, 0x10) => {
+                rom.fill(0xFF);
+                Command::Idle
+            }
+            // $F0 leaves ID mode from anywhere; anything unexpected aborts
+            // the sequence
+            (_, _, 0xF0) => {
+                self.software_id = false;
+                Command::Idle
+            }
+            _ => Command::Idle,
+        };
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.command.to_u8());
+        w.bool(self.software_id);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.command =
+            Command::from_u8(r.u8()?).ok_or(SaveStateError::Corrupt("invalid flash state"))?;
+        self.software_id = r.bool()?;
+        Ok(())
+    }
+}
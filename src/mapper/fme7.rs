@@ -0,0 +1,429 @@
+//! Sunsoft FME-7 and 5B (mapper 69): 8KB PRG banks (including one at $6000
+//! that can be ROM or RAM), 1KB CHR banks, a 16-bit CPU-cycle IRQ counter,
+//! and on the 5B a Yamaha YM2149 (AY-3-8910) sound core
+//!
+//! Reference: https://www.nesdev.org/wiki/Sunsoft_FME-7
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// CPU cycles per tick of the sound core's tone and envelope counters
+const AUDIO_PRESCALER: u8 = 16;
+
+/// Peak-to-peak level of a full-volume APU pulse on its own. A full-volume
+/// 5B channel is mixed at the same level.
+const CHANNEL_LEVEL: f32 = 95.88 / (8128.0 / 15.0 + 100.0);
+
+pub struct Fme7Mapper {
+    has_chr_ram: bool,
+    // Register selected for the next $A000 write
+    command: u8,
+    chr_banks: [u8; 8],
+    // Command 8: bits 0-5 bank, bit 6 RAM instead of ROM, bit 7 RAM enable
+    prg_bank_6000: u8,
+    prg_banks: [u8; 3], // $8000, $A000, $C000
+    mirroring: u8,
+    irq_enabled: bool,
+    irq_counter_enabled: bool,
+    irq_counter: u16,
+    irq_pending: bool,
+    audio: Sunsoft5bAudio,
+}
+
+impl Fme7Mapper {
+    pub fn new(has_chr_ram: bool) -> Self {
+        Self {
+            has_chr_ram,
+            command: 0,
+            chr_banks: [0; 8],
+            prg_bank_6000: 0,
+            prg_banks: [0; 3],
+            mirroring: 0,
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+            audio: Sunsoft5bAudio::new(),
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 10) & 0x07] as usize;
+        bank * 0x400 + (addr as usize & 0x03FF)
+    }
+
+    fn ram_selected(&self) -> bool {
+        (self.prg_bank_6000 & 0x40) != 0
+    }
+
+    fn ram_enabled(&self) -> bool {
+        (self.prg_bank_6000 & 0xC0) == 0xC0
+    }
+
+    fn prg_ram_offset(&self, addr: u16) -> usize {
+        (self.prg_bank_6000 & 0x3F) as usize * 0x2000 + (addr as usize & 0x1FFF)
+    }
+
+    fn write_parameter(&mut self, value: u8) {
+        match self.command {
+            0..=7 => self.chr_banks[self.command as usize] = value,
+            8 => self.prg_bank_6000 = value,
+            9..=11 => self.prg_banks[self.command as usize - 9] = value & 0x3F,
+            12 => self.mirroring = value & 0x03,
+            13 => {
+                self.irq_enabled = (value & 0x01) != 0;
+                self.irq_counter_enabled = (value & 0x80) != 0;
+                self.irq_pending = false;
+            }
+            14 => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8),
+        }
+    }
+}
+
+impl Mapper for Fme7Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = match addr {
+            0x6000..=0x7FFF => self.prg_ram_offset(addr),
+            0x8000..=0xDFFF => {
+                let bank = self.prg_banks[(addr as usize - 0x8000) >> 13] as usize;
+                bank * 0x2000 + (addr as usize & 0x1FFF)
+            }
+            _ => prg_rom.len().saturating_sub(0x2000) + (addr as usize & 0x1FFF),
+        };
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.command = value & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(value),
+            0xC000..=0xDFFF => self.audio.select(value),
+            _ => self.audio.write(value),
+        }
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if !self.ram_enabled() || prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[self.prg_ram_offset(addr) % prg_ram.len()])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if self.ram_enabled() && !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[self.prg_ram_offset(addr) % len] = value;
+        }
+    }
+
+    fn cpu_peek(&self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.ram_selected() => self.prg_ram_read(addr, &mem.prg_ram),
+            0x6000..=0xFFFF => Some(self.prg_rom_read(addr, &mem.prg_rom)),
+            _ => None,
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_counter_enabled {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF && self.irq_enabled {
+                self.irq_pending = true;
+            }
+        }
+        self.audio.clock();
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::OneScreenLower,
+            _ => Mirroring::OneScreenUpper,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.command);
+        w.bytes(&self.chr_banks);
+        w.u8(self.prg_bank_6000);
+        w.bytes(&self.prg_banks);
+        w.u8(self.mirroring);
+        w.bool(self.irq_enabled);
+        w.bool(self.irq_counter_enabled);
+        w.u16(self.irq_counter);
+        w.bool(self.irq_pending);
+        self.audio.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.command = r.u8()? & 0x0F;
+        r.bytes_into(&mut self.chr_banks)?;
+        self.prg_bank_6000 = r.u8()?;
+        r.bytes_into(&mut self.prg_banks)?;
+        self.mirroring = r.u8()? & 0x03;
+        self.irq_enabled = r.bool()?;
+        self.irq_counter_enabled = r.bool()?;
+        self.irq_counter = r.u16()?;
+        self.irq_pending = r.bool()?;
+        self.audio.load_state(r)
+    }
+}
+
+/// Sunsoft 5B sound: three square channels that can each mix in a shared
+/// noise generator, and a shared 32-step envelope. Written through $C000
+/// (register select) and $E000 (data).
+struct Sunsoft5bAudio {
+    // Register index, or none if the upper bits of the select were set
+    address: Option<u8>,
+    registers: [u8; 16],
+    prescaler: u8,
+    tone_counters: [u16; 3],
+    tone_outputs: [bool; 3],
+    // Noise runs at half the tone rate
+    noise_divider: bool,
+    noise_counter: u8,
+    // 17-bit LFSR
+    noise_shift: u32,
+    envelope_counter: u16,
+    envelope_step: u8,
+    envelope_rising: bool,
+    envelope_holding: bool,
+    /// Linear gain for each 5-bit level (1.5dB per step, 0 is silence)
+    levels: [f32; 32],
+}
+
+impl Sunsoft5bAudio {
+    fn new() -> Self {
+        let mut levels = [0.0; 32];
+        for (i, level) in levels.iter_mut().enumerate().skip(1) {
+            *level = 10f32.powf((i as f32 - 31.0) * 1.5 / 20.0) * CHANNEL_LEVEL;
+        }
+        Self {
+            address: None,
+            registers: [0; 16],
+            prescaler: 0,
+            tone_counters: [0; 3],
+            tone_outputs: [false; 3],
+            noise_divider: false,
+            noise_counter: 0,
+            noise_shift: 1,
+            envelope_counter: 0,
+            envelope_step: 0,
+            envelope_rising: false,
+            envelope_holding: false,
+            levels,
+        }
+    }
+
+    fn select(&mut self, value: u8) {
+        // The chip only answers while the upper four bits are clear
+        self.address = ((value & 0xF0) == 0).then_some(value);
+    }
+
+    fn write(&mut self, value: u8) {
+        let Some(reg) = self.address else {
+            return;
+        };
+        self.registers[reg as usize] = value;
+        if reg == 13 {
+            // Writing the shape restarts the envelope
+            self.envelope_counter = 0;
+            self.envelope_step = 0;
+            self.envelope_rising = (value & 0x04) != 0;
+            self.envelope_holding = false;
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let fine = self.registers[channel * 2] as u16;
+        let coarse = (self.registers[channel * 2 + 1] & 0x0F) as u16;
+        (coarse << 8) | fine
+    }
+
+    fn envelope_period(&self) -> u16 {
+        ((self.registers[12] as u16) << 8) | self.registers[11] as u16
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope_rising {
+            self.envelope_step
+        } else {
+            31 - self.envelope_step
+        }
+    }
+
+    fn clock(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < AUDIO_PRESCALER {
+            return;
+        }
+        self.prescaler = 0;
+
+        for channel in 0..3 {
+            self.tone_counters[channel] += 1;
+            if self.tone_counters[channel] >= self.tone_period(channel).max(1) {
+                self.tone_counters[channel] = 0;
+                self.tone_outputs[channel] = !self.tone_outputs[channel];
+            }
+        }
+
+        self.noise_divider = !self.noise_divider;
+        if self.noise_divider {
+            self.noise_counter += 1;
+            if self.noise_counter >= (self.registers[6] & 0x1F).max(1) {
+                self.noise_counter = 0;
+                let feedback = (self.noise_shift ^ (self.noise_shift >> 3)) & 0x01;
+                self.noise_shift = (self.noise_shift >> 1) | (feedback << 16);
+            }
+        }
+
+        self.envelope_counter += 1;
+        if self.envelope_counter >= self.envelope_period().max(1) {
+            self.envelope_counter = 0;
+            self.clock_envelope();
+        }
+    }
+
+    fn clock_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+        if self.envelope_step < 31 {
+            self.envelope_step += 1;
+            return;
+        }
+        // End of a ramp: the shape's continue, alternate and hold bits
+        // decide what comes next
+        let shape = self.registers[13];
+        if (shape & 0x08) == 0 {
+            self.envelope_holding = true;
+            self.envelope_rising = false;
+        } else if (shape & 0x01) != 0 {
+            self.envelope_holding = true;
+            if (shape & 0x02) != 0 {
+                self.envelope_rising = !self.envelope_rising;
+            }
+        } else {
+            if (shape & 0x02) != 0 {
+                self.envelope_rising = !self.envelope_rising;
+            }
+            self.envelope_step = 0;
+        }
+    }
+
+    fn output(&self) -> f32 {
+        let mixer = self.registers[7];
+        let noise = (self.noise_shift & 0x01) != 0;
+        let mut sum = 0.0;
+        for channel in 0..3 {
+            let tone_off = (mixer & (0x01 << channel)) != 0;
+            let noise_off = (mixer & (0x08 << channel)) != 0;
+            if (self.tone_outputs[channel] || tone_off) && (noise || noise_off) {
+                let volume = self.registers[8 + channel];
+                let level = if (volume & 0x10) != 0 {
+                    self.envelope_level()
+                } else if (volume & 0x0F) == 0 {
+                    0
+                } else {
+                    // 3dB volume steps land on every other envelope level
+                    (volume & 0x0F) * 2 + 1
+                };
+                sum += self.levels[level as usize];
+            }
+        }
+        sum
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.address.map_or(0xFF, |reg| reg));
+        w.bytes(&self.registers);
+        w.u8(self.prescaler);
+        for channel in 0..3 {
+            w.u16(self.tone_counters[channel]);
+            w.bool(self.tone_outputs[channel]);
+        }
+        w.bool(self.noise_divider);
+        w.u8(self.noise_counter);
+        w.u32(self.noise_shift);
+        w.u16(self.envelope_counter);
+        w.u8(self.envelope_step);
+        w.bool(self.envelope_rising);
+        w.bool(self.envelope_holding);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        let address = r.u8()?;
+        self.address = (address < 16).then_some(address);
+        r.bytes_into(&mut self.registers)?;
+        self.prescaler = r.u8()? % AUDIO_PRESCALER;
+        // A counter at or past its period is reset on the next clock, so
+        // clamping to one below the period keeps the timing and can't overflow
+        for channel in 0..3 {
+            let period = self.tone_period(channel).max(1);
+            self.tone_counters[channel] = r.u16()?.min(period - 1);
+            self.tone_outputs[channel] = r.bool()?;
+        }
+        self.noise_divider = r.bool()?;
+        self.noise_counter = r.u8()?.min((self.registers[6] & 0x1F).max(1) - 1);
+        // An all-zero LFSR would never produce noise again
+        self.noise_shift = (r.u32()? & 0x1FFFF).max(1);
+        self.envelope_counter = r.u16()?.min(self.envelope_period().max(1) - 1);
+        self.envelope_step = r.u8()? & 0x1F;
+        self.envelope_rising = r.bool()?;
+        self.envelope_holding = r.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_state_clamps_counters_to_their_periods() {
+        let mut audio = Sunsoft5bAudio::new();
+        audio.registers[0] = 5;
+        audio.registers[11] = 0xFF;
+        audio.registers[12] = 0xFF;
+        audio.tone_counters = [u16::MAX; 3];
+        audio.noise_counter = u8::MAX;
+        audio.envelope_counter = u16::MAX;
+        let mut w = StateWriter::new();
+        audio.save_state(&mut w);
+        let state = w.into_inner();
+
+        let mut loaded = Sunsoft5bAudio::new();
+        loaded.load_state(&mut StateReader::new(&state)).unwrap();
+        assert_eq!(loaded.tone_counters, [4, 0, 0]);
+        assert_eq!(loaded.noise_counter, 0);
+        assert_eq!(loaded.envelope_counter, 0xFFFE);
+
+        // Every counter was due, and wraps on the next tick
+        for _ in 0..AUDIO_PRESCALER {
+            loaded.clock();
+        }
+        assert_eq!(loaded.tone_counters, [0; 3]);
+        assert!(loaded.tone_outputs[0]);
+        assert_eq!(loaded.envelope_counter, 0);
+        assert_eq!(loaded.envelope_step, 1);
+    }
+}
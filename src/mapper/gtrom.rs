@@ -0,0 +1,111 @@
+//! GTROM / Cheapocabra (mapper 111): 32KB PRG banking over a self-flashable
+//! 512KB chip, and 32KB of RAM split between pattern tables and nametables
+//!
+//! One register at $5000-$5FFF (and $7000-$7FFF), `.GRNCPPPP`: the 32KB
+//! PRG bank, the 8KB CHR-RAM page, the 8KB nametable page, and two LEDs.
+//! The nametables are the upper 16KB of the board's RAM, mapped over the
+//! whole of $2000-$3EFF, so the board is always four-screen. Writes to
+//! $8000-$FFFF go to the flash.
+//!
+//! Reference: https://www.nesdev.org/wiki/GTROM
+
+use super::flash::Flash;
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct GtromMapper {
+    has_chr_ram: bool,
+    flash: Flash,
+    register: u8,
+}
+
+impl GtromMapper {
+    pub fn new(has_chr_ram: bool) -> Self {
+        Self {
+            has_chr_ram,
+            flash: Flash::new(),
+            register: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16, prg_rom_len: usize) -> usize {
+        ((self.register & 0x0F) as usize * 0x8000 + (addr as usize & 0x7FFF)) % prg_rom_len
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        ((self.register >> 4) & 0x01) as usize * 0x2000 + (addr as usize & 0x1FFF)
+    }
+
+    fn nametable_offset(&self, addr: u16) -> usize {
+        0x4000 + ((self.register >> 5) & 0x01) as usize * 0x2000 + (addr as usize & 0x1FFF)
+    }
+}
+
+impl Mapper for GtromMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = self.prg_offset(addr, prg_rom.len());
+        self.flash.read(offset).unwrap_or(prg_rom[offset])
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        self.register = value;
+    }
+
+    fn prg_ram_read(&self, _addr: u16, _prg_ram: &[u8]) -> Option<u8> {
+        None
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            0x5000..=0x5FFF | 0x7000..=0x7FFF => self.register_write(addr, value),
+            0x8000..=0xFFFF => {
+                let offset = self.prg_offset(addr, mem.prg_rom.len());
+                self.flash.write(offset, value, &mut mem.prg_rom);
+            }
+            _ => {}
+        }
+    }
+
+    fn has_flash(&self) -> bool {
+        true
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn nametable_read(&mut self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        if mem.chr_ram.is_empty() {
+            return None;
+        }
+        Some(mem.chr_ram[self.nametable_offset(addr) % mem.chr_ram.len()])
+    }
+
+    fn nametable_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) -> bool {
+        if mem.chr_ram.is_empty() {
+            return false;
+        }
+        let len = mem.chr_ram.len();
+        mem.chr_ram[self.nametable_offset(addr) % len] = value;
+        true
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::FourScreen
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.register);
+        self.flash.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.register = r.u8()?;
+        self.flash.load_state(r)
+    }
+}
@@ -0,0 +1,66 @@
+//! GxROM (mapper 66): switchable 32KB PRG and 8KB CHR banks
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+// GNROM / MHROM (Mapper 66) - used by Super Mario Bros. + Duck Hunt, Dragon Power, etc.
+pub struct GxromMapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    // $8000-$FFFF: bits 4-5 select the PRG bank, bits 0-1 the CHR bank
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl GxromMapper {
+    pub fn new(mirroring: Mirroring, has_chr_ram: bool) -> Self {
+        Self {
+            mirroring,
+            has_chr_ram,
+            prg_bank: 0,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for GxromMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = self.prg_bank as usize * 0x8000 + (addr as usize & 0x7FFF);
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        self.prg_bank = (value >> 4) & 0x03;
+        self.chr_bank = value & 0x03;
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        let offset = self.chr_bank as usize * 0x2000 + (addr as usize & 0x1FFF);
+        chr_read(mem, self.has_chr_ram, offset)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        let offset = self.chr_bank as usize * 0x2000 + (addr as usize & 0x1FFF);
+        chr_write(mem, self.has_chr_ram, offset, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.prg_bank);
+        w.u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_bank = r.u8()?;
+        self.chr_bank = r.u8()?;
+        Ok(())
+    }
+}
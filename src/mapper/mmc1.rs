@@ -0,0 +1,523 @@
+//! MMC1 (mapper 1): serial-loaded bank registers, SxROM boards
+
+use super::{next_power_of_2, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct Mmc1Mapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    prg_banks: usize,    // Number of 16KB PRG banks
+    chr_banks: usize,    // Number of 8KB CHR banks (or 0 if CHR RAM)
+    prg_rom_size: usize, // Total PRG ROM size in bytes
+
+    // Shift register state
+    shift_reg: u8, // 5-bit shift register (stored in lower 5 bits)
+    reg_init: u8,  // Initial value (0b100000 = 32)
+
+    // Control register (from 0x8000 writes)
+    chr_mode: u8, // 0 = 8KB mode, 1 = 4KB mode
+    prg_mode: u8, // 0/1 = 32KB, 2 = fix first, 3 = fix last
+
+    // Bank registers
+    prg_reg: u8,  // PRG bank register
+    chr1_reg: u8, // CHR bank 0 register
+    chr2_reg: u8, // CHR bank 1 register
+
+    // Clamp values (power of 2 - 1)
+    prg_clamp: u8,
+    chr_clamp: u8,
+
+    // Current bank pointers (as offsets)
+    prg_bank1_offset: usize,
+    prg_bank2_offset: usize,
+    chr_bank1_offset: usize,
+    chr_bank2_offset: usize,
+
+    // PRG register bit 4 (MMC1B and later): PRG-RAM disabled
+    prg_ram_disabled: bool,
+
+    // CPU cycles seen by the board, and the cycle of the last serial write.
+    // The MMC1 ignores a write on the cycle right after another one, which
+    // drops the second write of a read-modify-write instruction.
+    cpu_cycle: u64,
+    last_write_cycle: u64,
+}
+
+impl Mmc1Mapper {
+    pub fn new(
+        mirroring: Mirroring,
+        has_chr_ram: bool,
+        prg_rom_size: usize,
+        chr_rom_size: usize,
+    ) -> Self {
+        let prg_banks = prg_rom_size / 0x4000; // 16KB banks
+        let chr_banks = if has_chr_ram {
+            0
+        } else {
+            chr_rom_size / 0x2000
+        }; // 8KB banks
+
+        // Calculate clamps (next power of 2 - 1) - matches C reference
+        let prg_clamp = if prg_banks > 0 {
+            let next_pow2 = next_power_of_2(prg_banks);
+            (if next_pow2 > 0 { next_pow2 - 1 } else { 0 }) as u8
+        } else {
+            0
+        };
+
+        // CHR clamp: banks * 2 because CHR is in 4KB chunks for banking
+        let chr_clamp = if chr_banks > 0 {
+            let next_pow2 = next_power_of_2(chr_banks * 2);
+            (if next_pow2 > 0 { next_pow2 - 1 } else { 0 }) as u8
+        } else {
+            0
+        };
+
+        log::info!("MMC1 mapper initialized: prg_banks={}, chr_banks={}, prg_clamp={}, chr_clamp={}, has_chr_ram={}",
+            prg_banks, chr_banks, prg_clamp, chr_clamp, has_chr_ram);
+
+        // Initial state: PRG mode 3 (fix last bank), PRG bank 0
+        let mut mapper = Self {
+            mirroring,
+            has_chr_ram,
+            prg_banks,
+            chr_banks,
+            prg_rom_size,
+            shift_reg: 0b100000, // REG_INIT
+            reg_init: 0b100000,
+            chr_mode: 0,
+            prg_mode: 3,
+            prg_reg: 0,
+            chr1_reg: 0,
+            chr2_reg: 0,
+            prg_clamp,
+            chr_clamp,
+            prg_bank1_offset: 0,
+            prg_bank2_offset: prg_banks.saturating_sub(1) * 0x4000, // Last bank
+            chr_bank1_offset: 0,
+            chr_bank2_offset: 0x1000, // Second 4KB if in 8KB mode
+            prg_ram_disabled: false,
+            cpu_cycle: 0,
+            last_write_cycle: u64::MAX, // Never directly before any cycle
+        };
+
+        // Initialize bank offsets
+        mapper.update_prg_banks(prg_rom_size);
+        mapper.update_chr_banks(chr_rom_size);
+
+        log::info!(
+            "MMC1 initial banks: prg_bank1_offset=0x{:X}, prg_bank2_offset=0x{:X}",
+            mapper.prg_bank1_offset,
+            mapper.prg_bank2_offset
+        );
+
+        mapper
+    }
+
+    fn update_prg_banks(&mut self, prg_rom_size: usize) {
+        // Match C reference implementation exactly
+        match self.prg_mode {
+            0 | 1 => {
+                // 32KB mode: both banks switch together (PRG_reg & ~1)
+                let bank_num = (self.prg_reg & !0x01) as usize;
+                self.prg_bank1_offset = 0x4000 * bank_num;
+                self.prg_bank2_offset = self.prg_bank1_offset + 0x4000;
+            }
+            2 => {
+                // Fix first bank, switch second bank
+                // First bank is at offset based on bit 4 (for 256KB banking)
+                self.prg_bank1_offset = 0x4000 * ((self.prg_reg & 0x10) as usize);
+                self.prg_bank2_offset = 0x4000 * (self.prg_reg as usize);
+            }
+            _ => {
+                // 3: switch first bank, fix second bank (most common mode)
+                self.prg_bank1_offset = 0x4000 * (self.prg_reg as usize);
+
+                if self.prg_banks > 16 {
+                    // Large ROM (>256KB): use bit 4 to select 256KB region
+                    let bank256 = if (self.prg_reg & 0x10) != 0 {
+                        1usize
+                    } else {
+                        0
+                    };
+                    self.prg_bank2_offset = (bank256 + 1) * 0x40000 - 0x4000;
+                } else {
+                    // Normal: last bank is fixed
+                    self.prg_bank2_offset = (self.prg_banks.saturating_sub(1)) * 0x4000;
+                }
+            }
+        }
+
+        // Ensure offsets are within ROM bounds
+        if prg_rom_size > 0 {
+            self.prg_bank1_offset %= prg_rom_size;
+            self.prg_bank2_offset %= prg_rom_size;
+        }
+    }
+
+    fn update_chr_banks(&mut self, chr_rom_size: usize) {
+        // Skip CHR banking if using CHR RAM
+        if self.has_chr_ram || self.chr_banks == 0 {
+            self.chr_bank1_offset = 0;
+            self.chr_bank2_offset = 0x1000;
+            return;
+        }
+
+        if self.chr_mode == 1 {
+            // 4KB mode: two independent 4KB banks
+            self.chr_bank1_offset = 0x1000 * (self.chr1_reg as usize);
+            self.chr_bank2_offset = 0x1000 * (self.chr2_reg as usize);
+        } else {
+            // 8KB mode: one 8KB bank (CHR1_reg & ~1)
+            let bank_num = (self.chr1_reg & !0x01) as usize;
+            self.chr_bank1_offset = 0x1000 * bank_num;
+            self.chr_bank2_offset = self.chr_bank1_offset + 0x1000;
+        }
+
+        // Ensure offsets are within ROM bounds
+        if chr_rom_size > 0 {
+            self.chr_bank1_offset %= chr_rom_size;
+            self.chr_bank2_offset %= chr_rom_size;
+        }
+    }
+
+    /// Offset into PRG-RAM for a $6000-$7FFF access. SOROM (16KB) banks
+    /// with CHR bank 0 bit 3, SXROM (32KB) with bits 2-3.
+    fn prg_ram_offset(&self, addr: u16, prg_ram_size: usize) -> usize {
+        let bank = match prg_ram_size {
+            0x8000 => (self.chr1_reg >> 2) & 0x03,
+            0x4000 => (self.chr1_reg >> 3) & 0x01,
+            _ => 0,
+        };
+        (bank as usize * 0x2000 + (addr as usize & 0x1FFF)) % prg_ram_size
+    }
+}
+
+impl Mapper for Mmc1Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        if prg_rom.is_empty() {
+            return 0xFF;
+        }
+
+        if addr < 0xC000 {
+            // First 16KB bank (0x8000-0xBFFF)
+            let offset = self.prg_bank1_offset + (addr as usize & 0x3FFF);
+            prg_rom[offset % prg_rom.len()]
+        } else {
+            // Second 16KB bank (0xC000-0xFFFF)
+            let offset = self.prg_bank2_offset + (addr as usize & 0x3FFF);
+            prg_rom[offset % prg_rom.len()]
+        }
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        let prg_rom_size = self.prg_rom_size;
+        let chr_rom_size = self.chr_banks * 0x2000;
+
+        // Consecutive writes: only the first one reaches the shift register,
+        // except that a reset always gets through
+        let consecutive = self.last_write_cycle.wrapping_add(1) == self.cpu_cycle;
+        self.last_write_cycle = self.cpu_cycle;
+        if consecutive && (value & 0x80) == 0 {
+            return;
+        }
+
+        // Check for reset (bit 7 set)
+        if (value & 0x80) != 0 {
+            self.shift_reg = self.reg_init;
+            self.prg_mode = 3;
+            self.update_prg_banks(prg_rom_size);
+            return;
+        }
+
+        // Shift register: accumulate bits (5 bits total)
+        // Each write shifts right and adds the LSB of value to bit 5
+        self.shift_reg = (self.shift_reg >> 1) | ((value & 0x01) << 5);
+
+        // Check if register is full (bit 0 is set after 5 shifts)
+        if (self.shift_reg & 0x01) == 0 {
+            return; // Not full yet
+        }
+
+        // Register is full - remove the completion bit
+        let reg_value = self.shift_reg >> 1;
+
+        // Route to appropriate register based on address (matching C reference)
+        match addr & 0xE000 {
+            0x8000 => {
+                // Control register: mirroring, CHR mode, PRG mode
+                let mirroring_bits = reg_value & 0x03;
+                self.mirroring = match mirroring_bits {
+                    0 => Mirroring::OneScreenLower,
+                    1 => Mirroring::OneScreenUpper,
+                    2 => Mirroring::Vertical,
+                    3 => Mirroring::Horizontal,
+                    _ => unreachable!(),
+                };
+
+                self.chr_mode = (reg_value >> 4) & 0x01;
+                self.prg_mode = (reg_value >> 2) & 0x03;
+
+                self.update_prg_banks(prg_rom_size);
+                self.update_chr_banks(chr_rom_size);
+            }
+            0xA000 => {
+                // CHR bank 0 (or 256KB PRG bank select if CHR RAM present)
+                if self.has_chr_ram {
+                    // SOROM/SXROM: bits 2-3 select the 8KB PRG-RAM bank
+                    self.chr1_reg = reg_value;
+                    // If CHR RAM, bit 4 controls 256KB PRG bank selection
+                    self.prg_reg &= !0x10;
+                    self.prg_reg |= reg_value & 0x10;
+                    self.prg_reg &= self.prg_clamp;
+                    self.update_prg_banks(prg_rom_size);
+                } else {
+                    // CHR bank 0 register
+                    self.chr1_reg = reg_value & 0x1F;
+                    self.chr1_reg &= self.chr_clamp;
+                    self.update_chr_banks(chr_rom_size);
+                }
+            }
+            0xC000 => {
+                // CHR bank 1 (only in 4KB CHR mode)
+                if self.chr_mode == 0 {
+                    // Reset shift register and return - ignored in 8KB mode
+                    self.shift_reg = self.reg_init;
+                    return;
+                }
+                if self.has_chr_ram {
+                    self.chr2_reg = reg_value;
+                    // If CHR RAM, bit 4 controls 256KB PRG bank selection
+                    self.prg_reg &= !0x10;
+                    self.prg_reg |= reg_value & 0x10;
+                    self.prg_reg &= self.prg_clamp;
+                    self.update_prg_banks(prg_rom_size);
+                } else {
+                    // CHR bank 1 register
+                    self.chr2_reg = reg_value & 0x1F;
+                    self.chr2_reg &= self.chr_clamp;
+                    self.update_chr_banks(chr_rom_size);
+                }
+            }
+            0xE000 => {
+                // PRG bank register (lower 4 bits), bit 4 disables PRG-RAM
+                self.prg_ram_disabled = (reg_value & 0x10) != 0;
+                self.prg_reg &= !0x0F;
+                self.prg_reg |= reg_value & 0x0F;
+                self.prg_reg &= self.prg_clamp;
+                self.update_prg_banks(prg_rom_size);
+            }
+            _ => {}
+        }
+
+        // Reset shift register
+        self.shift_reg = self.reg_init;
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        if self.has_chr_ram {
+            return mem.chr_ram[addr as usize % mem.chr_ram.len().max(1)];
+        }
+
+        let chr_rom = &mem.chr_rom;
+        if chr_rom.is_empty() {
+            return 0;
+        }
+
+        let pattern_addr = addr & 0x1FFF;
+        if pattern_addr < 0x1000 {
+            let offset = self.chr_bank1_offset + pattern_addr as usize;
+            chr_rom[offset % chr_rom.len()]
+        } else {
+            let offset = self.chr_bank2_offset + (pattern_addr as usize & 0x0FFF);
+            chr_rom[offset % chr_rom.len()]
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        let chr_ram = &mut mem.chr_ram;
+        if self.has_chr_ram && !chr_ram.is_empty() {
+            let len = chr_ram.len();
+            chr_ram[addr as usize % len] = value;
+        }
+        // CHR ROM is read-only
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if self.prg_ram_disabled || prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[self.prg_ram_offset(addr, prg_ram.len())])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if !self.prg_ram_disabled && !prg_ram.is_empty() {
+            prg_ram[self.prg_ram_offset(addr, prg_ram.len())] = value;
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.cpu_cycle = self.cpu_cycle.wrapping_add(1);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        self.mirroring.save_state(w);
+        w.u8(self.shift_reg);
+        w.u8(self.chr_mode);
+        w.u8(self.prg_mode);
+        w.u8(self.prg_reg);
+        w.u8(self.chr1_reg);
+        w.u8(self.chr2_reg);
+        w.bool(self.prg_ram_disabled);
+        w.u64(self.cpu_cycle);
+        w.u64(self.last_write_cycle);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.mirroring = Mirroring::load_state(r)?;
+        self.shift_reg = r.u8()?;
+        self.chr_mode = r.u8()? & 0x01;
+        self.prg_mode = r.u8()? & 0x03;
+        self.prg_reg = r.u8()?;
+        self.chr1_reg = r.u8()?;
+        self.chr2_reg = r.u8()?;
+        self.prg_ram_disabled = r.bool()?;
+        self.cpu_cycle = r.u64()?;
+        self.last_write_cycle = r.u64()?;
+        // The bank offsets follow from the registers, and are always in range
+        self.update_prg_banks(self.prg_rom_size);
+        self.update_chr_banks(self.chr_banks * 0x2000);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Cpu, CpuBus};
+    use crate::trace::TraceState;
+
+    /// CPU RAM at $0000-$07FF and an MMC1 board at $8000-$FFFF, clocked on
+    /// every CPU cycle like the real bus
+    struct Board {
+        ram: Vec<u8>,
+        mapper: Mmc1Mapper,
+        mem: CartridgeMemory,
+    }
+
+    impl Board {
+        fn new(prg_rom: Vec<u8>) -> Self {
+            let mapper = Mmc1Mapper::new(Mirroring::Vertical, false, prg_rom.len(), 0x2000);
+            Self {
+                ram: vec![0; 0x800],
+                mapper,
+                mem: CartridgeMemory {
+                    prg_rom,
+                    chr_rom: vec![0; 0x2000],
+                    prg_ram: vec![0; 0x2000],
+                    chr_ram: Vec::new(),
+                    nametable_ram: Vec::new(),
+                },
+            }
+        }
+
+        /// Run one instruction placed in RAM at $0200
+        fn run(&mut self, program: &[u8]) {
+            self.ram[0x200..0x200 + program.len()].copy_from_slice(program);
+            let mut cpu = Cpu::new();
+            cpu.pc = 0x0200;
+            cpu.step(self, &mut TraceState::new(false));
+        }
+
+        /// A serial write from a plain store, well apart from any other
+        fn store(&mut self, addr: u16, value: u8) {
+            self.mapper.cpu_clock();
+            self.mapper.register_write(addr, value);
+            self.mapper.cpu_clock();
+        }
+    }
+
+    impl CpuBus for Board {
+        fn read(&mut self, addr: u16) -> u8 {
+            let value = self.peek(addr);
+            self.mapper.cpu_clock();
+            value
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            match addr {
+                0x0000..=0x1FFF => self.ram[addr as usize & 0x7FF] = value,
+                _ => self.mapper.cpu_write(addr, value, &mut self.mem),
+            }
+            self.mapper.cpu_clock();
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            match addr {
+                0x0000..=0x1FFF => self.ram[addr as usize & 0x7FF],
+                _ => self.mapper.cpu_peek(addr, &self.mem).unwrap_or(0),
+            }
+        }
+    }
+
+    #[test]
+    fn read_modify_write_only_latches_the_first_write() {
+        let mut prg_rom = vec![0; 0x20000];
+        prg_rom[0] = 0xFF;
+        let mut board = Board::new(prg_rom);
+
+        // Half-loaded shift register, then INC $8000: $FF resets it and the
+        // $00 written on the next cycle is dropped
+        board.store(0x8000, 1);
+        board.store(0x8000, 1);
+        board.run(&[0xEE, 0x00, 0x80]);
+        assert_eq!(board.mapper.shift_reg, board.mapper.reg_init);
+        assert_eq!(board.mapper.prg_mode, 3);
+
+        // Four bits of %01011, then INC $8001 supplies the last one ($00)
+        // and its $01 must not start the next load
+        for bit in [1, 1, 0, 1] {
+            board.store(0x8000, bit);
+        }
+        board.run(&[0xEE, 0x01, 0x80]);
+        assert_eq!(board.mapper.shift_reg, board.mapper.reg_init);
+        assert_eq!(board.mapper.mirroring, Mirroring::Horizontal);
+        assert_eq!(board.mapper.prg_mode, 2);
+        assert_eq!(board.mapper.chr_mode, 0);
+    }
+
+    #[test]
+    fn writes_apart_are_all_shifted_in() {
+        let mut board = Board::new(vec![0; 0x20000]);
+        for bit in [0, 1, 1, 0, 1] {
+            board.store(0xE000, bit);
+        }
+        assert_eq!(board.mapper.prg_reg, 0b0110);
+        assert!(board.mapper.prg_ram_disabled);
+    }
+
+    #[test]
+    fn load_state_masks_modes_and_rebuilds_offsets() {
+        let mut board = Board::new(vec![0; 0x20000]);
+        board.mapper.prg_mode = 0xFE;
+        board.mapper.chr_mode = 0xFF;
+        board.mapper.prg_reg = 0x03;
+        board.mapper.chr1_reg = 0x01;
+        let mut w = StateWriter::new();
+        board.mapper.save_state(&mut w);
+        let state = w.into_inner();
+
+        let mut loaded = Board::new(vec![0; 0x20000]).mapper;
+        loaded.load_state(&mut StateReader::new(&state)).unwrap();
+        assert_eq!((loaded.prg_mode, loaded.chr_mode), (2, 1));
+        // Fixed first bank, $C000 from the register; 4KB CHR banks
+        assert_eq!(loaded.prg_bank1_offset, 0);
+        assert_eq!(loaded.prg_bank2_offset, 3 * 0x4000);
+        assert_eq!(loaded.chr_bank1_offset, 0x1000);
+    }
+}
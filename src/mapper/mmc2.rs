@@ -0,0 +1,140 @@
+//! MMC2 (mapper 9) and MMC4 (mapper 10): CHR banks switched by tile fetches
+//!
+//! Each pattern table has two CHR bank registers and a latch picking between
+//! them. Fetching tile $FD or $FE sets the latch for the following fetches,
+//! so a game can switch CHR mid-screen just by placing those tiles.
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+// MMC2 - Punch-Out!!; MMC4 - Fire Emblem, Famicom Wars
+pub struct Mmc2Mapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    // MMC4 switches 16KB of PRG and has PRG-RAM; MMC2 switches 8KB
+    is_mmc4: bool,
+    prg_bank: u8,
+    // 4KB CHR banks: [$0000 when latch 0 is $FD, $0000 on $FE,
+    //                 $1000 when latch 1 is $FD, $1000 on $FE]
+    chr_banks: [u8; 4],
+    // Last $FD/$FE tile fetched from each pattern table
+    latches: [u8; 2],
+}
+
+impl Mmc2Mapper {
+    pub fn new(mirroring: Mirroring, has_chr_ram: bool, is_mmc4: bool) -> Self {
+        Self {
+            mirroring,
+            has_chr_ram,
+            is_mmc4,
+            prg_bank: 0,
+            chr_banks: [0; 4],
+            latches: [0xFE; 2],
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let table = (addr as usize >> 12) & 0x01;
+        let register = table * 2 + (self.latches[table] == 0xFE) as usize;
+        self.chr_banks[register] as usize * 0x1000 + (addr as usize & 0x0FFF)
+    }
+
+    /// Watch for fetches of tiles $FD/$FE. MMC2 only sets latch 0 on the
+    /// first byte of the tile; MMC4 and latch 1 react to the whole tile.
+    fn update_latch(&mut self, addr: u16) {
+        let table = (addr as usize >> 12) & 0x01;
+        let tile_addr = if table == 0 && !self.is_mmc4 {
+            addr
+        } else {
+            addr & 0xFFF8
+        };
+        match tile_addr & 0x0FFF {
+            0x0FD8 => self.latches[table] = 0xFD,
+            0x0FE8 => self.latches[table] = 0xFE,
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mmc2Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let len = prg_rom.len();
+        let offset = if self.is_mmc4 {
+            // $8000: switchable 16KB, $C000: last 16KB
+            match addr {
+                0x8000..=0xBFFF => self.prg_bank as usize * 0x4000 + (addr as usize & 0x3FFF),
+                _ => len.saturating_sub(0x4000) + (addr as usize & 0x3FFF),
+            }
+        } else {
+            // $8000: switchable 8KB, $A000-$FFFF: last three 8KB banks
+            match addr {
+                0x8000..=0x9FFF => self.prg_bank as usize * 0x2000 + (addr as usize & 0x1FFF),
+                _ => len.saturating_sub(0x8000) + (addr as usize & 0x7FFF),
+            }
+        };
+        prg_rom[offset % len]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        match addr & 0xF000 {
+            0xA000 => self.prg_bank = value & 0x0F,
+            0xB000 => self.chr_banks[0] = value & 0x1F,
+            0xC000 => self.chr_banks[1] = value & 0x1F,
+            0xD000 => self.chr_banks[2] = value & 0x1F,
+            0xE000 => self.chr_banks[3] = value & 0x1F,
+            0xF000 => {
+                self.mirroring = if (value & 0x01) != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if !self.is_mmc4 || prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[(addr as usize - 0x6000) % prg_ram.len()])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if self.is_mmc4 && !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        // The fetch that trips the latch still comes from the old bank
+        let value = chr_read(mem, self.has_chr_ram, self.chr_offset(addr));
+        self.update_latch(addr);
+        value
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        self.mirroring.save_state(w);
+        w.u8(self.prg_bank);
+        w.bytes(&self.chr_banks);
+        w.bytes(&self.latches);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.mirroring = Mirroring::load_state(r)?;
+        self.prg_bank = r.u8()?;
+        r.bytes_into(&mut self.chr_banks)?;
+        r.bytes_into(&mut self.latches)?;
+        Ok(())
+    }
+}
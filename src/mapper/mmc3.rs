@@ -0,0 +1,718 @@
+//! MMC3 (mapper 4): 8KB PRG / 1KB CHR banking with a scanline IRQ counter
+//!
+//! The IRQ counter isn't told about scanlines; it is clocked by rising edges
+//! on PPU address line A12. With backgrounds at $0000 and sprites at $1000
+//! that happens once per line, when the sprite pattern fetches start. The
+//! chip ignores a rise unless A12 was low for a few M2 cycles first, which
+//! filters out the toggling between pattern and nametable fetches.
+//!
+//! Several boards build on the same chip and are handled here too:
+//!
+//! - MMC6 (4.1): 1KB of PRG-RAM at $7000-$7FFF, each 512-byte half with its
+//!   own read and write enables
+//! - Tengen RAMBO-1 (64): three more bank registers, 1KB CHR banks at
+//!   $0000-$0FFF, and an IRQ counter that can count CPU cycles instead
+//! - TxSROM (118): bit 7 of the CHR banks for $0000-$0FFF picks the
+//!   nametable RAM page for the matching nametable
+//! - TQROM (119): bit 6 of a CHR bank switches that 1KB to 8KB of CHR-RAM
+//! - Namco 108 / DxROM (206): the MMC3's predecessor, just the bank
+//!   registers: no mirroring control, IRQ, PRG-RAM or bank mode bits
+
+use super::{next_power_of_2, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// M2 falling edges A12 must stay low for before a rise clocks the counter
+const A12_FILTER_CYCLES: u8 = 3;
+
+/// How the IRQ counter treats reaching zero, which changed between chip
+/// revisions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mmc3Revision {
+    /// MMC3B/MMC3C (Sharp): IRQ on every clock that leaves the counter at
+    /// zero, so a latch of 0 fires on every scanline
+    Sharp,
+    /// MMC3A (NEC): IRQ only when the counter decrements to zero or is
+    /// reloaded after a $C001 write, so a latch of 0 fires once
+    Nec,
+}
+
+/// Board built around the MMC3 or a chip like it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mmc3Board {
+    /// TxROM and everything else that's a plain MMC3
+    Mmc3,
+    Mmc6,
+    Rambo1,
+    Txsrom,
+    Tqrom,
+    Namco108,
+}
+
+// MMC3 Mapper (Mapper 4) - used by SMB3, Kirby's Adventure, etc.
+pub struct Mmc3Mapper {
+    board: Mmc3Board,
+    revision: Mmc3Revision,
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    prg_rom_size: usize,
+    chr_size: usize,
+
+    // PRG banking: 4x 8KB banks
+    // Bank 0: $8000-$9FFF (switchable or fixed to 2nd-last)
+    // Bank 1: $A000-$BFFF (switchable R7)
+    // Bank 2: $C000-$DFFF (fixed to 2nd-last or switchable)
+    // Bank 3: $E000-$FFFF (fixed to last)
+    prg_bank_offsets: [usize; 4],
+
+    // CHR banking: 8x 1KB banks
+    chr_bank_offsets: [usize; 8],
+
+    // Bank select register
+    bank_select: u8,     // Which bank register to update next
+    prg_mode: bool,      // false: $8000 switchable, true: $C000 switchable
+    chr_inversion: bool, // Swap CHR bank regions
+
+    // Bank data registers R0-R7
+    bank_data: [u8; 8],
+    // RAMBO-1: R8 and R9 (1KB CHR banks), RF (8KB PRG bank), and $8000 bit 5
+    // splitting R0/R1 into 1KB banks alongside R8/R9
+    rambo_banks: [u8; 3],
+    chr_1k_mode: bool,
+
+    // IRQ counter
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    // A12 edge detection: last level seen, and M2 cycles it has been low
+    a12_high: bool,
+    a12_low_cycles: u8,
+
+    // RAMBO-1 $C001 bit 0: count CPU cycles, one clock every four
+    irq_cycle_mode: bool,
+    irq_prescaler: u8,
+
+    // $A001: bit 7 enables PRG-RAM, bit 6 denies writes. MMC6: bits 7-4
+    // enable reads and writes of the high, then low, 512 bytes.
+    prg_ram_protect: u8,
+    // MMC6 $8000 bit 5: PRG-RAM enable, without which $A001 is ignored
+    mmc6_ram_enabled: bool,
+
+    // Clamp values
+    prg_clamp: u8,
+    chr_clamp: u8,
+}
+
+impl Mmc3Mapper {
+    /// `chr_size` is the CHR-ROM size, or the CHR-RAM size on boards
+    /// without CHR-ROM (CHR-RAM is banked the same way)
+    pub fn new(
+        board: Mmc3Board,
+        revision: Mmc3Revision,
+        mirroring: Mirroring,
+        has_chr_ram: bool,
+        prg_rom_size: usize,
+        chr_size: usize,
+    ) -> Self {
+        let prg_banks_8k = prg_rom_size / 0x2000; // 8KB banks
+        let chr_banks_1k = chr_size / 0x400; // 1KB banks
+
+        // Calculate clamps (next power of 2 - 1)
+        let prg_clamp = if prg_banks_8k > 0 {
+            (next_power_of_2(prg_banks_8k) - 1) as u8
+        } else {
+            0
+        };
+        let chr_clamp = if chr_banks_1k > 0 {
+            (next_power_of_2(chr_banks_1k) - 1) as u8
+        } else {
+            0
+        };
+
+        log::info!("MMC3 mapper initialized: prg_8k_banks={}, chr_1k_banks={}, prg_clamp={}, chr_clamp={}, has_chr_ram={}",
+            prg_banks_8k, chr_banks_1k, prg_clamp, chr_clamp, has_chr_ram);
+
+        let mut mapper = Self {
+            board,
+            revision,
+            mirroring,
+            has_chr_ram,
+            prg_rom_size,
+            chr_size,
+            prg_bank_offsets: [0; 4],
+            chr_bank_offsets: [0; 8],
+            bank_select: 0,
+            prg_mode: false,
+            chr_inversion: false,
+            bank_data: [0; 8],
+            rambo_banks: [0; 3],
+            chr_1k_mode: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12_high: false,
+            a12_low_cycles: 0,
+            irq_cycle_mode: false,
+            irq_prescaler: 0,
+            // Games that never touch $A001 still expect working RAM
+            prg_ram_protect: if board == Mmc3Board::Mmc6 { 0 } else { 0x80 },
+            mmc6_ram_enabled: false,
+            prg_clamp,
+            chr_clamp,
+        };
+
+        // Initialize PRG banks: last two 8KB banks fixed
+        let last_bank = prg_rom_size.saturating_sub(0x2000);
+        let second_last = prg_rom_size.saturating_sub(0x4000);
+        mapper.prg_bank_offsets[2] = second_last;
+        mapper.prg_bank_offsets[3] = last_bank;
+
+        // Initialize CHR banks
+        for i in 0..8 {
+            mapper.chr_bank_offsets[i] = i * 0x400;
+        }
+
+        mapper
+    }
+
+    fn update_prg_banks(&mut self) {
+        let second_last = self.prg_rom_size.saturating_sub(0x4000);
+        let last = self.prg_rom_size.saturating_sub(0x2000);
+
+        let r6 = (self.bank_data[6] & self.prg_clamp) as usize * 0x2000;
+        let r7 = (self.bank_data[7] & self.prg_clamp) as usize * 0x2000;
+
+        let banks = if self.board == Mmc3Board::Rambo1 {
+            // RF takes the place of the fixed second-last bank, and the
+            // mode bit rotates it through the three switchable slots
+            let rf = (self.rambo_banks[2] & self.prg_clamp) as usize * 0x2000;
+            if self.prg_mode {
+                [rf, r6, r7]
+            } else {
+                [r6, r7, rf]
+            }
+        } else if self.prg_mode {
+            // PRG mode 1: $8000 = 2nd-last, $C000 = R6
+            [second_last, r7, r6]
+        } else {
+            // PRG mode 0: $8000 = R6, $C000 = 2nd-last
+            [r6, r7, second_last]
+        };
+        self.prg_bank_offsets[..3].copy_from_slice(&banks);
+        self.prg_bank_offsets[3] = last;
+
+        // Ensure within bounds
+        for offset in &mut self.prg_bank_offsets {
+            if self.prg_rom_size > 0 {
+                *offset %= self.prg_rom_size;
+            }
+        }
+    }
+
+    /// Bank register driving a 1KB CHR slot, with the low bit filled in
+    /// for the halves of a 2KB bank
+    fn chr_register(&self, slot: usize) -> u8 {
+        // CHR A12 inversion swaps the 2KB and 1KB halves
+        let slot = if self.chr_inversion { slot ^ 4 } else { slot };
+        match slot {
+            // RAMBO-1's 1KB mode: R0, R8, R1, R9
+            0..=3 if self.chr_1k_mode => match slot {
+                0 => self.bank_data[0],
+                1 => self.rambo_banks[0],
+                2 => self.bank_data[1],
+                _ => self.rambo_banks[1],
+            },
+            // R0 and R1 are 2KB banks (bit 0 ignored)
+            0..=3 => (self.bank_data[slot / 2] & 0xFE) | (slot as u8 & 0x01),
+            // R2-R5 are 1KB banks
+            _ => self.bank_data[slot - 2],
+        }
+    }
+
+    fn update_chr_banks(&mut self) {
+        let chr_size = self.chr_size.max(1);
+        for slot in 0..8 {
+            let bank = (self.chr_register(slot) & self.chr_clamp) as usize;
+            self.chr_bank_offsets[slot] = bank * 0x400 % chr_size;
+        }
+    }
+
+    /// TQROM: the 1KB slot is mapped to CHR-RAM instead of CHR-ROM
+    fn slot_uses_chr_ram(&self, slot: usize) -> bool {
+        self.has_chr_ram
+            || (self.board == Mmc3Board::Tqrom && (self.chr_register(slot) & 0x40) != 0)
+    }
+
+    /// Clock the IRQ counter on a filtered A12 rise (or, on the RAMBO-1 in
+    /// cycle mode, every fourth CPU cycle)
+    fn clock_irq(&mut self) {
+        if self.board == Mmc3Board::Rambo1 {
+            self.clock_rambo_irq();
+            return;
+        }
+        // The NEC chip only fires when the counter arrives at zero
+        let from_nonzero = self.irq_counter != 0 || self.irq_reload;
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+        } else {
+            self.irq_counter -= 1;
+        }
+        self.irq_reload = false;
+
+        let fire = match self.revision {
+            Mmc3Revision::Sharp => self.irq_counter == 0,
+            Mmc3Revision::Nec => self.irq_counter == 0 && from_nonzero,
+        };
+        if fire && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    /// The RAMBO-1 reloads one or two higher than the MMC3 after a $C001
+    /// write, and fires on every clock that decrements to zero
+    fn clock_rambo_irq(&mut self) {
+        if self.irq_reload {
+            let extra = if self.irq_latch <= 1 { 1 } else { 2 };
+            self.irq_counter = self.irq_latch.wrapping_add(extra);
+            self.irq_reload = false;
+        } else if self.irq_counter == 0 {
+            self.irq_counter = self.irq_latch.wrapping_add(1);
+        }
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    /// MMC6: whether the 512-byte half of PRG-RAM at `addr` can be read,
+    /// or written
+    fn mmc6_half_enabled(&self, addr: u16, write: bool) -> bool {
+        let (read, write_enable) = if (addr & 0x0200) != 0 {
+            (0x80, 0x40)
+        } else {
+            (0x20, 0x10)
+        };
+        // A half can only be written while it can be read too
+        (self.prg_ram_protect & read) != 0 && (!write || (self.prg_ram_protect & write_enable) != 0)
+    }
+}
+
+impl Mapper for Mmc3Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        if prg_rom.is_empty() {
+            return 0xFF;
+        }
+
+        let bank = ((addr - 0x8000) / 0x2000) as usize;
+        let offset = self.prg_bank_offsets[bank] + (addr as usize & 0x1FFF);
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        // The Namco 108 only has the bank registers
+        if self.board == Mmc3Board::Namco108 && addr >= 0xA000 {
+            return;
+        }
+        match addr & 0xE001 {
+            0x8000 => {
+                // Bank select
+                self.bank_select = match self.board {
+                    Mmc3Board::Rambo1 => value & 0x0F,
+                    _ => value & 0x07,
+                };
+                if self.board != Mmc3Board::Namco108 {
+                    self.prg_mode = (value & 0x40) != 0;
+                    self.chr_inversion = (value & 0x80) != 0;
+                }
+                match self.board {
+                    Mmc3Board::Rambo1 => self.chr_1k_mode = (value & 0x20) != 0,
+                    Mmc3Board::Mmc6 => self.mmc6_ram_enabled = (value & 0x20) != 0,
+                    _ => {}
+                }
+                self.update_prg_banks();
+                self.update_chr_banks();
+            }
+            0x8001 => {
+                // Bank data
+                match self.bank_select {
+                    select @ 0..=7 => self.bank_data[select as usize] = value,
+                    8 => self.rambo_banks[0] = value,
+                    9 => self.rambo_banks[1] = value,
+                    0x0F => self.rambo_banks[2] = value,
+                    _ => {}
+                }
+                self.update_prg_banks();
+                self.update_chr_banks();
+            }
+            0xA000 if self.mirroring != Mirroring::FourScreen => {
+                // Mirroring (ignored for 4-screen)
+                self.mirroring = if (value & 0x01) != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            0xA001 if self.board == Mmc3Board::Mmc6 && self.mmc6_ram_enabled => {
+                self.prg_ram_protect = value & 0xF0;
+            }
+            // Locked while the MMC6's RAM is disabled
+            0xA001 if self.board == Mmc3Board::Mmc6 => {}
+            0xA001 => {
+                // PRG RAM protect
+                self.prg_ram_protect = value & 0xC0;
+            }
+            0xC000 => {
+                // IRQ latch
+                self.irq_latch = value;
+            }
+            0xC001 => {
+                // IRQ reload
+                self.irq_counter = 0;
+                self.irq_reload = true;
+                if self.board == Mmc3Board::Rambo1 {
+                    self.irq_cycle_mode = (value & 0x01) != 0;
+                    self.irq_prescaler = 0;
+                }
+            }
+            0xE000 => {
+                // IRQ disable and acknowledge
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            0xE001 => {
+                // IRQ enable
+                self.irq_enabled = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        // Each 1KB bank
+        let bank = (addr / 0x400) as usize;
+        let chr = if self.slot_uses_chr_ram(bank) {
+            &mem.chr_ram
+        } else {
+            &mem.chr_rom
+        };
+        if chr.is_empty() {
+            return 0;
+        }
+
+        let offset = self.chr_bank_offsets[bank] + (addr as usize & 0x3FF);
+        chr[offset % chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        let bank = (addr / 0x400) as usize;
+        let chr_ram = &mut mem.chr_ram;
+        if self.slot_uses_chr_ram(bank) && !chr_ram.is_empty() {
+            let offset = self.chr_bank_offsets[bank] + (addr as usize & 0x3FF);
+            let len = chr_ram.len();
+            chr_ram[offset % len] = value;
+        }
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if prg_ram.is_empty() {
+            return None;
+        }
+        match self.board {
+            Mmc3Board::Namco108 => return None,
+            Mmc3Board::Mmc6 => {
+                // 1KB at $7000-$7FFF. With neither half readable that's open
+                // bus; with one, the other reads as zero.
+                if !self.mmc6_ram_enabled || addr < 0x7000 || (self.prg_ram_protect & 0xA0) == 0 {
+                    return None;
+                }
+                if !self.mmc6_half_enabled(addr, false) {
+                    return Some(0);
+                }
+                return Some(prg_ram[(addr as usize & 0x03FF) % prg_ram.len()]);
+            }
+            _ => {}
+        }
+        if (self.prg_ram_protect & 0x80) == 0 {
+            return None;
+        }
+        Some(prg_ram[(addr as usize & 0x1FFF) % prg_ram.len()])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if self.board == Mmc3Board::Mmc6 {
+            if self.mmc6_ram_enabled
+                && addr >= 0x7000
+                && self.mmc6_half_enabled(addr, true)
+                && !prg_ram.is_empty()
+            {
+                let len = prg_ram.len();
+                prg_ram[(addr as usize & 0x03FF) % len] = value;
+            }
+            return;
+        }
+        if self.board != Mmc3Board::Namco108 && self.prg_ram_protect == 0x80 && !prg_ram.is_empty()
+        {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize & 0x1FFF) % len] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn ciram_offset(&self, addr: u16) -> usize {
+        if self.board != Mmc3Board::Txsrom {
+            return self.mirroring.ciram_offset(addr);
+        }
+        // Each nametable follows bit 7 of the CHR bank for the same 1KB of
+        // the pattern tables' lower half
+        let page = (self.chr_register((addr as usize >> 10) & 0x03) >> 7) as usize;
+        page * 0x400 + (addr as usize & 0x03FF)
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.a12_high {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        }
+        if self.irq_cycle_mode {
+            self.irq_prescaler = (self.irq_prescaler + 1) & 0x03;
+            if self.irq_prescaler == 0 {
+                self.clock_irq();
+            }
+        }
+    }
+
+    fn ppu_address(&mut self, addr: u16) {
+        let a12_high = (addr & 0x1000) != 0;
+        if a12_high {
+            if !self.a12_high && self.a12_low_cycles >= A12_FILTER_CYCLES && !self.irq_cycle_mode {
+                self.clock_irq();
+            }
+        } else if self.a12_high {
+            self.a12_low_cycles = 0;
+        }
+        self.a12_high = a12_high;
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        self.mirroring.save_state(w);
+        w.u8(self.bank_select);
+        w.bool(self.prg_mode);
+        w.bool(self.chr_inversion);
+        w.bytes(&self.bank_data);
+        w.u8(self.irq_latch);
+        w.u8(self.irq_counter);
+        w.bool(self.irq_reload);
+        w.bool(self.irq_enabled);
+        w.bool(self.irq_pending);
+        w.bool(self.a12_high);
+        w.u8(self.a12_low_cycles);
+        w.u8(self.prg_ram_protect);
+        match self.board {
+            Mmc3Board::Rambo1 => {
+                w.bytes(&self.rambo_banks);
+                w.bool(self.chr_1k_mode);
+                w.bool(self.irq_cycle_mode);
+                w.u8(self.irq_prescaler);
+            }
+            Mmc3Board::Mmc6 => w.bool(self.mmc6_ram_enabled),
+            _ => {}
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.mirroring = Mirroring::load_state(r)?;
+        self.bank_select = r.u8()?;
+        self.prg_mode = r.bool()?;
+        self.chr_inversion = r.bool()?;
+        r.bytes_into(&mut self.bank_data)?;
+        self.irq_latch = r.u8()?;
+        self.irq_counter = r.u8()?;
+        self.irq_reload = r.bool()?;
+        self.irq_enabled = r.bool()?;
+        self.irq_pending = r.bool()?;
+        self.a12_high = r.bool()?;
+        self.a12_low_cycles = r.u8()?;
+        self.prg_ram_protect = r.u8()?;
+        match self.board {
+            Mmc3Board::Rambo1 => {
+                r.bytes_into(&mut self.rambo_banks)?;
+                self.chr_1k_mode = r.bool()?;
+                self.irq_cycle_mode = r.bool()?;
+                self.irq_prescaler = r.u8()? & 0x03;
+            }
+            Mmc3Board::Mmc6 => self.mmc6_ram_enabled = r.bool()?,
+            _ => {}
+        }
+        // The bank offsets follow from the registers, and are always in range
+        self.update_prg_banks();
+        self.update_chr_banks();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapper(revision: Mmc3Revision, latch: u8) -> Mmc3Mapper {
+        let mut mapper = Mmc3Mapper::new(
+            Mmc3Board::Mmc3,
+            revision,
+            Mirroring::Vertical,
+            false,
+            0x8000,
+            0x2000,
+        );
+        mapper.register_write(0xC000, latch);
+        mapper.register_write(0xC001, 0);
+        mapper.register_write(0xE001, 0);
+        mapper
+    }
+
+    /// Hold A12 low for `low_cycles` M2 cycles, then raise it
+    fn a12_rise(mapper: &mut Mmc3Mapper, low_cycles: u8) {
+        mapper.ppu_address(0x0000);
+        for _ in 0..low_cycles {
+            mapper.cpu_clock();
+        }
+        mapper.ppu_address(0x1000);
+    }
+
+    #[test]
+    fn short_a12_low_is_filtered() {
+        let mut mapper = mapper(Mmc3Revision::Sharp, 1);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        assert_eq!(mapper.irq_counter, 1);
+
+        // Pattern/nametable toggling: too short to count
+        a12_rise(&mut mapper, A12_FILTER_CYCLES - 1);
+        a12_rise(&mut mapper, 1);
+        a12_rise(&mut mapper, 0);
+        assert_eq!(mapper.irq_counter, 1);
+        assert!(!mapper.irq_pending());
+
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        assert_eq!(mapper.irq_counter, 0);
+        assert!(mapper.irq_pending());
+    }
+
+    #[test]
+    fn a12_staying_high_clocks_once() {
+        let mut mapper = mapper(Mmc3Revision::Sharp, 5);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        for _ in 0..10 {
+            mapper.ppu_address(0x1FF0);
+            mapper.cpu_clock();
+        }
+        assert_eq!(mapper.irq_counter, 5);
+    }
+
+    #[test]
+    fn sharp_latch_zero_fires_every_clock() {
+        let mut mapper = mapper(Mmc3Revision::Sharp, 0);
+        for _ in 0..3 {
+            a12_rise(&mut mapper, A12_FILTER_CYCLES);
+            assert!(mapper.irq_pending());
+            mapper.register_write(0xE000, 0);
+            mapper.register_write(0xE001, 0);
+        }
+    }
+
+    #[test]
+    fn nec_latch_zero_fires_once_after_reload() {
+        let mut mapper = mapper(Mmc3Revision::Nec, 0);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        assert!(mapper.irq_pending());
+        mapper.register_write(0xE000, 0);
+        mapper.register_write(0xE001, 0);
+
+        // Counter is 0 and reloads to 0: no longer arriving at zero
+        for _ in 0..3 {
+            a12_rise(&mut mapper, A12_FILTER_CYCLES);
+            assert!(!mapper.irq_pending());
+        }
+
+        // Another $C001 write arms it again
+        mapper.register_write(0xC001, 0);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        assert!(mapper.irq_pending());
+    }
+
+    #[test]
+    fn nec_and_sharp_agree_with_nonzero_latch() {
+        for revision in [Mmc3Revision::Sharp, Mmc3Revision::Nec] {
+            let mut mapper = mapper(revision, 2);
+            let mut fired = Vec::new();
+            for line in 0..9 {
+                a12_rise(&mut mapper, A12_FILTER_CYCLES);
+                if mapper.irq_pending() {
+                    fired.push(line);
+                    mapper.register_write(0xE000, 0);
+                    mapper.register_write(0xE001, 0);
+                }
+            }
+            assert_eq!(fired, [2, 5, 8], "{:?}", revision);
+        }
+    }
+
+    #[test]
+    fn load_state_rebuilds_bank_offsets() {
+        let mut mapper = mapper(Mmc3Revision::Sharp, 0);
+        mapper.register_write(0x8000, 0x46);
+        mapper.register_write(0x8001, 0x03);
+        let mut w = StateWriter::new();
+        mapper.save_state(&mut w);
+        let mut state = w.into_inner();
+
+        let mut loaded = self::mapper(Mmc3Revision::Sharp, 0);
+        loaded.load_state(&mut StateReader::new(&state)).unwrap();
+        assert_eq!(loaded.prg_bank_offsets, mapper.prg_bank_offsets);
+        assert_eq!(loaded.chr_bank_offsets, mapper.chr_bank_offsets);
+
+        // Bank numbers past the end of the ROM wrap instead of panicking:
+        // the bank registers follow mirroring, bank select and the modes
+        let bank_data = 1 + 1 + 1 + 1 + 4;
+        state[bank_data..bank_data + 8].fill(0xFF);
+        loaded.load_state(&mut StateReader::new(&state)).unwrap();
+        assert_eq!(loaded.bank_data, [0xFF; 8]);
+        let prg_rom = vec![0; 0x8000];
+        for addr in (0x8000..=0xFFFF).step_by(0x1000) {
+            loaded.prg_rom_read(addr, &prg_rom);
+        }
+    }
+
+    #[test]
+    fn e000_acknowledges_and_disables_e001_enables() {
+        let mut mapper = mapper(Mmc3Revision::Sharp, 1);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        assert!(mapper.irq_pending());
+
+        mapper.register_write(0xE000, 0);
+        assert!(!mapper.irq_pending());
+
+        // Disabled: the counter keeps running but never raises the IRQ
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        assert_eq!(mapper.irq_counter, 0);
+        assert!(!mapper.irq_pending());
+
+        // Enabling doesn't raise an IRQ by itself
+        mapper.register_write(0xE001, 0);
+        assert!(!mapper.irq_pending());
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        a12_rise(&mut mapper, A12_FILTER_CYCLES);
+        assert!(mapper.irq_pending());
+    }
+}
@@ -0,0 +1,777 @@
+//! MMC5 (mapper 5): the most capable Nintendo mapper
+//!
+//! Besides PRG and CHR banking the chip has 1KB of internal ExRAM, which can
+//! act as a third nametable, as per-tile attribute and CHR bank data
+//! (extended attribute mode), or as plain RAM. It also provides a fill-mode
+//! nametable, a vertical split screen, a scanline IRQ, an 8x8 multiplier and
+//! expansion audio (two pulse channels and a PCM channel).
+//!
+//! The MMC5 isn't told where the PPU is in the frame. It finds the start of
+//! each scanline by watching for three reads in a row from the same
+//! nametable address (the two dummy fetches at the end of a line and the
+//! first fetch of the next), then counts PPU reads to tell background tile
+//! fetches from sprite fetches.
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::apu::{Envelope, DUTY_TABLE, LENGTH_TABLE};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveState, SaveStateError, StateReader, StateWriter};
+
+/// CPU cycles without a PPU read after which rendering is considered off
+const IDLE_CYCLES: u8 = 3;
+/// Reads into a scanline taken by the sprite fetches; before them come the
+/// 32 visible tiles
+const SPRITE_FETCHES: std::ops::Range<u8> = 128..160;
+/// Reads into a scanline where the next line's first two tiles are fetched
+const PREFETCHES: std::ops::Range<u8> = 160..168;
+
+// MMC5 (Mapper 5) - used by Castlevania III, Just Breed, Koei strategy games, etc.
+pub struct Mmc5Mapper {
+    has_chr_ram: bool,
+
+    // $5100: PRG mode, $5113: PRG-RAM bank at $6000, $5114-$5117: PRG banks
+    // (bit 7 selects ROM over RAM; $5117 is always ROM)
+    prg_mode: u8,
+    prg_ram_bank: u8,
+    prg_banks: [u8; 4],
+    // $5102/$5103: PRG-RAM is only writable while these hold %10 and %01
+    prg_ram_protect: [u8; 2],
+
+    // $5101: CHR mode, $5120-$5127: set A, $5128-$512B: set B, each with
+    // the $5130 upper bits latched when it was written
+    chr_mode: u8,
+    chr_banks: [u16; 12],
+    chr_upper: u8,
+    // Set B was written last; $2007 accesses outside rendering use that set
+    chr_b_last: bool,
+    // PPUCTRL bit 5, snooped from CPU writes. Only 8x16 sprites get their
+    // own CHR banks.
+    sprites_8x16: bool,
+
+    // $5104: ExRAM mode, $5105: nametable mapping, $5106/$5107: fill mode
+    exram_mode: u8,
+    nametable_mapping: u8,
+    fill_tile: u8,
+    fill_attr: u8,
+    exram: [u8; 0x400],
+
+    // $5200: split enable/side/tile, $5201: split scroll, $5202: split CHR bank
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+
+    // Scanline detection
+    last_read_addr: u16,
+    same_reads: u8,
+    idle_cycles: u8,
+    in_frame: bool,
+    scanline: u8,
+    // PPU reads since the scanline started
+    fetch_index: u8,
+    // Background tile being fetched: ExRAM byte (extended attribute mode),
+    // and whether it comes from the split region and which row of it
+    ext_attr: u8,
+    in_split: bool,
+    split_row: u8,
+
+    // $5203: IRQ scanline, $5204: IRQ enable / status
+    irq_compare: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    // $5205/$5206: unsigned 8x8 multiplier
+    multiplicand: u8,
+    multiplier: u8,
+
+    audio: Mmc5Audio,
+}
+
+impl Mmc5Mapper {
+    pub fn new(has_chr_ram: bool) -> Self {
+        Self {
+            has_chr_ram,
+            prg_mode: 3,
+            prg_ram_bank: 0,
+            prg_banks: [0xFF; 4],
+            prg_ram_protect: [0; 2],
+            chr_mode: 0,
+            chr_banks: [0; 12],
+            chr_upper: 0,
+            chr_b_last: false,
+            sprites_8x16: false,
+            exram_mode: 0,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_attr: 0,
+            exram: [0; 0x400],
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            last_read_addr: 0,
+            same_reads: 0,
+            idle_cycles: 0,
+            in_frame: false,
+            scanline: 0,
+            fetch_index: 0,
+            ext_attr: 0,
+            in_split: false,
+            split_row: 0,
+            irq_compare: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            audio: Mmc5Audio::new(),
+        }
+    }
+
+    /// Where a CPU address in $8000-$FFFF points: (is RAM, offset into that
+    /// memory)
+    fn prg_slot(&self, addr: u16) -> (bool, usize) {
+        let a = addr as usize;
+        let (register, bank, offset) = match (self.prg_mode, addr) {
+            (0, _) => (3, self.prg_banks[3] & 0x7C, a & 0x7FFF),
+            (1, 0x8000..=0xBFFF) | (2, 0x8000..=0xBFFF) => {
+                (1, self.prg_banks[1] & 0x7E, a & 0x3FFF)
+            }
+            (1, _) => (3, self.prg_banks[3] & 0x7E, a & 0x3FFF),
+            (2, 0xC000..=0xDFFF) => (2, self.prg_banks[2] & 0x7F, a & 0x1FFF),
+            (2, _) => (3, self.prg_banks[3] & 0x7F, a & 0x1FFF),
+            _ => {
+                let register = (a - 0x8000) / 0x2000;
+                (register, self.prg_banks[register] & 0x7F, a & 0x1FFF)
+            }
+        };
+        let is_ram = register != 3 && (self.prg_banks[register] & 0x80) == 0;
+        (is_ram, bank as usize * 0x2000 + offset)
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_protect == [0x02, 0x01]
+    }
+
+    /// Note a PPU read: spot the start of scanlines and count fetches
+    fn observe_read(&mut self, addr: u16) {
+        self.fetch_index = self.fetch_index.saturating_add(1);
+        if (0x2000..=0x2FFF).contains(&addr) && addr == self.last_read_addr {
+            self.same_reads += 1;
+            if self.same_reads == 2 {
+                self.start_scanline();
+            }
+        } else {
+            self.same_reads = 0;
+        }
+        self.last_read_addr = addr;
+        self.idle_cycles = 0;
+    }
+
+    fn start_scanline(&mut self) {
+        // The read that completed the match is the line's first fetch
+        self.fetch_index = 0;
+        if self.in_frame {
+            self.scanline = self.scanline.wrapping_add(1);
+            if self.scanline == self.irq_compare {
+                self.irq_pending = true;
+            }
+        } else {
+            self.in_frame = true;
+            self.scanline = 0;
+            self.irq_pending = false;
+        }
+    }
+
+    /// Rendering stopped (PPU idle, or the CPU fetched the NMI vector)
+    fn end_frame(&mut self) {
+        self.in_frame = false;
+        self.same_reads = 0;
+        self.last_read_addr = 0;
+    }
+
+    /// Screen column of the background tile being fetched, and whether it
+    /// belongs to the next scanline. `None` outside background fetches.
+    fn bg_column(&self) -> Option<(u8, bool)> {
+        if !self.in_frame {
+            return None;
+        }
+        match self.fetch_index {
+            index if index < SPRITE_FETCHES.start => Some((index / 4 + 2, false)),
+            index if PREFETCHES.contains(&index) => Some(((index - PREFETCHES.start) / 4, true)),
+            _ => None,
+        }
+    }
+
+    /// Nametable fetch of a background tile: latch what the rest of its
+    /// fetches need
+    fn start_tile(&mut self, addr: u16, column: u8, next_line: bool) {
+        self.ext_attr = self.exram[addr as usize & 0x03FF];
+
+        let threshold = self.split_control & 0x1F;
+        let right_side = (self.split_control & 0x40) != 0;
+        self.in_split = self.exram_mode <= 1
+            && (self.split_control & 0x80) != 0
+            && if right_side {
+                column >= threshold
+            } else {
+                column < threshold
+            };
+        let line = self.scanline as u16 + next_line as u16;
+        self.split_row = ((self.split_scroll as u16 + line) % 240) as u8;
+    }
+
+    fn split_tile(&self, column: u8) -> u8 {
+        self.exram[(self.split_row as usize / 8) * 32 + (column as usize & 0x1F)]
+    }
+
+    fn split_attribute(&self, column: u8) -> u8 {
+        let row = self.split_row as usize;
+        let column = column as usize & 0x1F;
+        let attr = self.exram[0x3C0 + (row / 32) * 8 + column / 4];
+        let shift = ((row / 16) & 1) * 4 + ((column / 2) & 1) * 2;
+        ((attr >> shift) & 0x03) * 0x55
+    }
+
+    /// What $5105 maps to a nametable address: `None` for CIRAM
+    fn nametable(&self, addr: u16) -> Option<u8> {
+        match self.nametable_source(addr) {
+            0 | 1 => None,
+            2 if self.exram_mode <= 1 => Some(self.exram[addr as usize & 0x03FF]),
+            2 => Some(0),
+            _ if (addr & 0x03FF) < 0x03C0 => Some(self.fill_tile),
+            _ => Some(self.fill_attr * 0x55),
+        }
+    }
+
+    /// $5105 field for a nametable: 0/1 = CIRAM page, 2 = ExRAM, 3 = fill
+    fn nametable_source(&self, addr: u16) -> u8 {
+        (self.nametable_mapping >> (((addr >> 10) & 0x03) * 2)) & 0x03
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let a = addr as usize & 0x1FFF;
+        let bg_fetch = self.bg_column().is_some();
+        if bg_fetch && self.in_split {
+            // The split region has its own 4KB bank and vertical scroll
+            return self.split_bank as usize * 0x1000
+                + (a & 0x0FF8)
+                + (self.split_row as usize & 0x07);
+        }
+        if bg_fetch && self.exram_mode == 1 {
+            let bank = (self.chr_upper as usize) << 6 | (self.ext_attr as usize & 0x3F);
+            return bank * 0x1000 + (a & 0x0FFF);
+        }
+
+        let use_b = self.sprites_8x16
+            && if self.in_frame {
+                bg_fetch
+            } else {
+                self.chr_b_last
+            };
+        let banks = &self.chr_banks;
+        if use_b {
+            // Set B covers $0000-$0FFF and repeats at $1000
+            let b = a & 0x0FFF;
+            match self.chr_mode {
+                0 => banks[11] as usize * 0x2000 + a,
+                1 => banks[11] as usize * 0x1000 + b,
+                2 => banks[8 + (b >> 11) * 2 + 1] as usize * 0x800 + (b & 0x07FF),
+                _ => banks[8 + (b >> 10)] as usize * 0x400 + (b & 0x03FF),
+            }
+        } else {
+            match self.chr_mode {
+                0 => banks[7] as usize * 0x2000 + a,
+                1 => banks[(a >> 12) * 4 + 3] as usize * 0x1000 + (a & 0x0FFF),
+                2 => banks[(a >> 11) * 2 + 1] as usize * 0x800 + (a & 0x07FF),
+                _ => banks[a >> 10] as usize * 0x400 + (a & 0x03FF),
+            }
+        }
+    }
+
+    fn status(&self) -> u8 {
+        (self.irq_pending as u8) << 7 | (self.in_frame as u8) << 6
+    }
+}
+
+impl Mapper for Mmc5Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let (_, offset) = self.prg_slot(addr);
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, _addr: u16, _value: u8) {
+        // All registers are in $5000-$5FFF; see cpu_write
+    }
+
+    fn cpu_peek(&self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        match addr {
+            0x5010 => Some(self.audio.pcm_status()),
+            0x5015 => Some(self.audio.status()),
+            0x5204 => Some(self.status()),
+            0x5205 => Some((self.multiplicand as u16 * self.multiplier as u16) as u8),
+            0x5206 => Some(((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => Some(self.exram[addr as usize & 0x03FF]),
+            0x6000..=0x7FFF if !mem.prg_ram.is_empty() => {
+                let offset = self.prg_ram_bank as usize * 0x2000 + (addr as usize & 0x1FFF);
+                Some(mem.prg_ram[offset % mem.prg_ram.len()])
+            }
+            0x8000..=0xFFFF => match self.prg_slot(addr) {
+                (true, _) if mem.prg_ram.is_empty() => None,
+                (true, offset) => Some(mem.prg_ram[offset % mem.prg_ram.len()]),
+                (false, _) => Some(self.prg_rom_read(addr, &mem.prg_rom)),
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16, mem: &mut CartridgeMemory) -> Option<u8> {
+        let value = self.cpu_peek(addr, mem);
+        match addr {
+            0x5010 => self.audio.acknowledge_pcm_irq(),
+            0x5204 => self.irq_pending = false,
+            0x8000..=0xBFFF => self.audio.pcm_read(value.unwrap_or(0)),
+            // NMI vector fetch: the frame is over
+            0xFFFA | 0xFFFB => self.end_frame(),
+            _ => {}
+        }
+        value
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            0x2000..=0x3FFF if (addr & 0x07) == 0 => self.sprites_8x16 = (value & 0x20) != 0,
+            0x5000..=0x5015 => self.audio.write_register(addr, value),
+            0x5100 => self.prg_mode = value & 0x03,
+            0x5101 => self.chr_mode = value & 0x03,
+            0x5102 => self.prg_ram_protect[0] = value & 0x03,
+            0x5103 => self.prg_ram_protect[1] = value & 0x03,
+            0x5104 => self.exram_mode = value & 0x03,
+            0x5105 => self.nametable_mapping = value,
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_attr = value & 0x03,
+            0x5113 => self.prg_ram_bank = value & 0x0F,
+            0x5114..=0x5117 => self.prg_banks[addr as usize - 0x5114] = value,
+            0x5120..=0x512B => {
+                let index = addr as usize - 0x5120;
+                self.chr_banks[index] = (self.chr_upper as u16) << 8 | value as u16;
+                self.chr_b_last = index >= 8;
+            }
+            0x5130 => self.chr_upper = value & 0x03,
+            0x5200 => self.split_control = value,
+            0x5201 => self.split_scroll = value,
+            0x5202 => self.split_bank = value,
+            0x5203 => self.irq_compare = value,
+            0x5204 => self.irq_enabled = (value & 0x80) != 0,
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            0x5C00..=0x5FFF => {
+                // In the nametable modes the CPU can only write while the
+                // PPU is rendering; at other times the write stores 0
+                let index = addr as usize & 0x03FF;
+                match self.exram_mode {
+                    0 | 1 => self.exram[index] = if self.in_frame { value } else { 0 },
+                    2 => self.exram[index] = value,
+                    _ => {}
+                }
+            }
+            0x6000..=0x7FFF if self.prg_ram_writable() && !mem.prg_ram.is_empty() => {
+                let offset = self.prg_ram_bank as usize * 0x2000 + (addr as usize & 0x1FFF);
+                let len = mem.prg_ram.len();
+                mem.prg_ram[offset % len] = value;
+            }
+            0x8000..=0xDFFF if self.prg_ram_writable() && !mem.prg_ram.is_empty() => {
+                if let (true, offset) = self.prg_slot(addr) {
+                    let len = mem.prg_ram.len();
+                    mem.prg_ram[offset % len] = value;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.audio.clock();
+        self.idle_cycles = self.idle_cycles.saturating_add(1);
+        if self.idle_cycles >= IDLE_CYCLES && self.in_frame {
+            self.end_frame();
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        self.observe_read(addr);
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn nametable_read(&mut self, addr: u16, _mem: &CartridgeMemory) -> Option<u8> {
+        self.observe_read(addr);
+        if let Some((column, next_line)) = self.bg_column() {
+            match self.fetch_index % 4 {
+                0 => {
+                    self.start_tile(addr, column, next_line);
+                    if self.in_split {
+                        return Some(self.split_tile(column));
+                    }
+                }
+                1 if self.in_split => return Some(self.split_attribute(column)),
+                // Extended attributes: the palette comes from ExRAM,
+                // repeated for every quadrant of the attribute byte
+                1 if self.exram_mode == 1 => return Some((self.ext_attr >> 6) * 0x55),
+                _ => {}
+            }
+        }
+        self.nametable(addr)
+    }
+
+    fn nametable_write(&mut self, addr: u16, value: u8, _mem: &mut CartridgeMemory) -> bool {
+        match self.nametable_source(addr) {
+            0 | 1 => false,
+            2 => {
+                if self.exram_mode <= 1 {
+                    self.exram[addr as usize & 0x03FF] = value;
+                }
+                true
+            }
+            _ => true,
+        }
+    }
+
+    fn ciram_offset(&self, addr: u16) -> usize {
+        (self.nametable_source(addr) as usize & 0x01) * 0x400 + (addr as usize & 0x03FF)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.nametable_mapping {
+            0x00 => Mirroring::OneScreenLower,
+            0x55 => Mirroring::OneScreenUpper,
+            0x50 => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        (self.irq_pending && self.irq_enabled) || self.audio.irq_pending()
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.prg_mode);
+        w.u8(self.prg_ram_bank);
+        w.bytes(&self.prg_banks);
+        w.bytes(&self.prg_ram_protect);
+        w.u8(self.chr_mode);
+        for &bank in &self.chr_banks {
+            w.u16(bank);
+        }
+        w.u8(self.chr_upper);
+        w.bool(self.chr_b_last);
+        w.bool(self.sprites_8x16);
+        w.u8(self.exram_mode);
+        w.u8(self.nametable_mapping);
+        w.u8(self.fill_tile);
+        w.u8(self.fill_attr);
+        w.bytes(&self.exram);
+        w.u8(self.split_control);
+        w.u8(self.split_scroll);
+        w.u8(self.split_bank);
+        w.u16(self.last_read_addr);
+        w.u8(self.same_reads);
+        w.u8(self.idle_cycles);
+        w.bool(self.in_frame);
+        w.u8(self.scanline);
+        w.u8(self.fetch_index);
+        w.u8(self.ext_attr);
+        w.bool(self.in_split);
+        w.u8(self.split_row);
+        w.u8(self.irq_compare);
+        w.bool(self.irq_enabled);
+        w.bool(self.irq_pending);
+        w.u8(self.multiplicand);
+        w.u8(self.multiplier);
+        self.audio.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_mode = r.u8()? & 0x03;
+        self.prg_ram_bank = r.u8()?;
+        r.bytes_into(&mut self.prg_banks)?;
+        r.bytes_into(&mut self.prg_ram_protect)?;
+        self.chr_mode = r.u8()? & 0x03;
+        for bank in &mut self.chr_banks {
+            *bank = r.u16()?;
+        }
+        self.chr_upper = r.u8()?;
+        self.chr_b_last = r.bool()?;
+        self.sprites_8x16 = r.bool()?;
+        self.exram_mode = r.u8()? & 0x03;
+        self.nametable_mapping = r.u8()?;
+        self.fill_tile = r.u8()?;
+        self.fill_attr = r.u8()? & 0x03;
+        r.bytes_into(&mut self.exram)?;
+        self.split_control = r.u8()?;
+        self.split_scroll = r.u8()?;
+        self.split_bank = r.u8()?;
+        self.last_read_addr = r.u16()?;
+        self.same_reads = r.u8()?;
+        self.idle_cycles = r.u8()?;
+        self.in_frame = r.bool()?;
+        self.scanline = r.u8()?;
+        self.fetch_index = r.u8()?;
+        self.ext_attr = r.u8()?;
+        self.in_split = r.bool()?;
+        self.split_row = r.u8()?;
+        self.irq_compare = r.u8()?;
+        self.irq_enabled = r.bool()?;
+        self.irq_pending = r.bool()?;
+        self.multiplicand = r.u8()?;
+        self.multiplier = r.u8()?;
+        self.audio.load_state(r)
+    }
+}
+
+/// CPU cycles between the audio frame clocks (a fixed 240Hz, unlike the
+/// APU's frame counter)
+const AUDIO_FRAME_PERIOD: u16 = 7457;
+
+/// MMC5 pulse channel: an APU pulse without the sweep unit
+struct Mmc5Pulse {
+    enabled: bool,
+    duty_cycle: u8,
+    duty_step: u8,
+    envelope: Envelope,
+    length_counter: u8,
+    timer: u16,
+    timer_counter: u16,
+}
+
+impl Mmc5Pulse {
+    fn new() -> Self {
+        Self {
+            enabled: false,
+            duty_cycle: 0,
+            duty_step: 0,
+            envelope: Envelope::new(),
+            length_counter: 0,
+            timer: 0,
+            timer_counter: 0,
+        }
+    }
+
+    fn write_register(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.duty_cycle = (value >> 6) & 0x03;
+                self.envelope.loop_flag = (value & 0x20) != 0;
+                self.envelope.constant_volume = (value & 0x10) != 0;
+                self.envelope.volume = value & 0x0F;
+            }
+            2 => self.timer = (self.timer & 0xFF00) | value as u16,
+            3 => {
+                self.timer = (self.timer & 0x00FF) | (((value & 0x07) as u16) << 8);
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[((value >> 3) & 0x1F) as usize];
+                }
+                self.duty_step = 0;
+                self.envelope.start = true;
+            }
+            // No sweep unit behind $5001/$5005
+            _ => {}
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer_counter == 0 {
+            self.timer_counter = self.timer;
+            self.duty_step = (self.duty_step + 1) & 7;
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    fn clock_frame(&mut self) {
+        self.envelope.clock();
+        // The halt flag doubles as the envelope loop flag
+        if !self.envelope.loop_flag && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    /// Unlike the APU pulses, short periods aren't silenced
+    fn output(&self) -> u8 {
+        if self.length_counter == 0
+            || DUTY_TABLE[self.duty_cycle as usize][self.duty_step as usize] == 0
+        {
+            return 0;
+        }
+        if self.envelope.constant_volume {
+            self.envelope.volume
+        } else {
+            self.envelope.decay_counter
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bool(self.enabled);
+        w.u8(self.duty_cycle);
+        w.u8(self.duty_step);
+        self.envelope.save_state(w);
+        w.u8(self.length_counter);
+        w.u16(self.timer);
+        w.u16(self.timer_counter);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = r.bool()?;
+        self.duty_cycle = r.u8()? & 0x03;
+        self.duty_step = r.u8()? & 0x07;
+        self.envelope.load_state(r)?;
+        self.length_counter = r.u8()?;
+        self.timer = r.u16()?;
+        self.timer_counter = r.u16()?;
+        Ok(())
+    }
+}
+
+/// Expansion audio at $5000-$5015
+struct Mmc5Audio {
+    pulses: [Mmc5Pulse; 2],
+    // $5010: PCM mode (reads of $8000-$BFFF set the level) and IRQ enable
+    pcm_read_mode: bool,
+    pcm_irq_enabled: bool,
+    // Set by reading a 0 sample in read mode
+    pcm_irq: bool,
+    pcm_level: u8,
+    frame_counter: u16,
+    odd_cycle: bool,
+}
+
+impl Mmc5Audio {
+    fn new() -> Self {
+        Self {
+            pulses: [Mmc5Pulse::new(), Mmc5Pulse::new()],
+            pcm_read_mode: false,
+            pcm_irq_enabled: false,
+            pcm_irq: false,
+            pcm_level: 0,
+            frame_counter: 0,
+            odd_cycle: false,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5003 => self.pulses[0].write_register(addr & 0x03, value),
+            0x5004..=0x5007 => self.pulses[1].write_register(addr & 0x03, value),
+            0x5010 => {
+                self.pcm_read_mode = (value & 0x01) != 0;
+                self.pcm_irq_enabled = (value & 0x80) != 0;
+            }
+            // A 0 write is ignored, as 0 marks the end of a sample
+            0x5011 if !self.pcm_read_mode && value != 0 => self.pcm_level = value,
+            0x5015 => {
+                self.pulses[0].set_enabled((value & 0x01) != 0);
+                self.pulses[1].set_enabled((value & 0x02) != 0);
+            }
+            _ => {}
+        }
+    }
+
+    /// $5015: which pulse length counters are running
+    fn status(&self) -> u8 {
+        (self.pulses[0].length_counter > 0) as u8 | ((self.pulses[1].length_counter > 0) as u8) << 1
+    }
+
+    /// $5010: bit 7 is the PCM IRQ
+    fn pcm_status(&self) -> u8 {
+        (self.irq_pending() as u8) << 7
+    }
+
+    fn acknowledge_pcm_irq(&mut self) {
+        self.pcm_irq = false;
+    }
+
+    /// The CPU read `value` from $8000-$BFFF
+    fn pcm_read(&mut self, value: u8) {
+        if self.pcm_read_mode {
+            if value == 0 {
+                self.pcm_irq = true;
+            } else {
+                self.pcm_level = value;
+            }
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.pcm_irq && self.pcm_irq_enabled
+    }
+
+    fn clock(&mut self) {
+        // Pulse timers tick every other CPU cycle, like the APU's
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            for pulse in &mut self.pulses {
+                pulse.clock_timer();
+            }
+        }
+        self.frame_counter += 1;
+        if self.frame_counter >= AUDIO_FRAME_PERIOD {
+            self.frame_counter = 0;
+            for pulse in &mut self.pulses {
+                pulse.clock_frame();
+            }
+        }
+    }
+
+    /// Mixed like the APU's own pulses, with PCM at the DMC's level
+    fn output(&self) -> f32 {
+        let pulse = (self.pulses[0].output() + self.pulses[1].output()) as f32;
+        let pulse_out = if pulse > 0.0 {
+            95.52 / (8128.0 / pulse + 100.0)
+        } else {
+            0.0
+        };
+        let pcm = self.pcm_level as f32 / 2.0;
+        let pcm_out = if pcm > 0.0 {
+            163.67 / (24329.0 / pcm + 100.0)
+        } else {
+            0.0
+        };
+        pulse_out + pcm_out
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        for pulse in &self.pulses {
+            pulse.save_state(w);
+        }
+        w.bool(self.pcm_read_mode);
+        w.bool(self.pcm_irq_enabled);
+        w.bool(self.pcm_irq);
+        w.u8(self.pcm_level);
+        w.u16(self.frame_counter);
+        w.bool(self.odd_cycle);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        for pulse in &mut self.pulses {
+            pulse.load_state(r)?;
+        }
+        self.pcm_read_mode = r.bool()?;
+        self.pcm_irq_enabled = r.bool()?;
+        self.pcm_irq = r.bool()?;
+        self.pcm_level = r.u8()?;
+        self.frame_counter = r.u16()?;
+        self.odd_cycle = r.bool()?;
+        Ok(())
+    }
+}
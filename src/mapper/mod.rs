@@ -0,0 +1,427 @@
+//! Cartridge boards (mappers)
+//!
+//! A mapper is the logic on the cartridge between the console's two buses and
+//! the ROM and RAM chips on the board. The console hands it every CPU access
+//! in $4020-$FFFF (plus PPU register writes), every address the PPU puts out,
+//! each CPU cycle, and asks it for the IRQ line and expansion audio, so a
+//! board can be written entirely from its own point of view without special
+//! cases in the bus.
+//!
+//! Simple boards only implement [`Mapper::prg_rom_read`],
+//! [`Mapper::register_write`] and the pattern table accesses; the default
+//! methods route the rest of the address space the usual way.
+
+mod action53;
+mod axrom;
+mod bandai;
+mod bnrom;
+mod cnrom;
+mod color_dreams;
+mod eeprom;
+mod fds;
+mod fds_audio;
+mod fds_disk;
+mod flash;
+mod fme7;
+mod gtrom;
+mod gxrom;
+mod mmc1;
+mod mmc2;
+mod mmc3;
+mod mmc5;
+mod multicart;
+mod n163;
+mod nina001;
+mod nrom;
+mod opll;
+mod unrom512;
+mod uxrom;
+mod vrc24;
+mod vrc6;
+mod vrc7;
+mod vrc_irq;
+
+pub use action53::Action53Mapper;
+pub use axrom::AxromMapper;
+pub use bandai::BandaiMapper;
+pub use bnrom::BnromMapper;
+pub use cnrom::CnromMapper;
+pub use color_dreams::ColorDreamsMapper;
+pub use fds::FdsMapper;
+pub use fds_disk::FdsDisk;
+pub use fme7::Fme7Mapper;
+pub use gtrom::GtromMapper;
+pub use gxrom::GxromMapper;
+pub use mmc1::Mmc1Mapper;
+pub use mmc2::Mmc2Mapper;
+pub use mmc3::{Mmc3Board, Mmc3Mapper, Mmc3Revision};
+pub use mmc5::Mmc5Mapper;
+pub use multicart::MulticartMapper;
+pub use n163::{N163Mapper, N163Mixing};
+pub use nina001::Nina001Mapper;
+pub use nrom::NromMapper;
+pub use unrom512::Unrom512Mapper;
+pub use uxrom::UxromMapper;
+pub use vrc24::Vrc24Mapper;
+pub use vrc6::Vrc6Mapper;
+pub use vrc7::Vrc7Mapper;
+
+use crate::cartridge::{CartridgeError, Mirroring};
+use crate::header::RomHeader;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// The memory chips on a cartridge board. Sizes come from the ROM header;
+/// each is empty if the board doesn't have it.
+pub struct CartridgeMemory {
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    /// PRG-RAM: work RAM first, then battery-backed RAM
+    pub prg_ram: Vec<u8>,
+    pub chr_ram: Vec<u8>,
+    /// Extra 2KB of nametable RAM on four-screen boards, mapped at $2800-$2FFF
+    pub nametable_ram: Vec<u8>,
+}
+
+impl CartridgeMemory {
+    /// Index into `nametable_ram` for the two nametables a four-screen board
+    /// supplies itself ($2800-$2FFF and its $3800 mirror)
+    pub fn four_screen_index(&self, addr: u16) -> Option<usize> {
+        (!self.nametable_ram.is_empty() && (addr & 0x0800) != 0).then_some(addr as usize & 0x07FF)
+    }
+}
+
+pub trait Mapper: Send {
+    /// Read PRG-ROM at $8000-$FFFF through the current banks
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8;
+
+    /// Write to the board's registers at $8000-$FFFF
+    fn register_write(&mut self, addr: u16, value: u8);
+
+    /// Read PRG-RAM at $6000-$7FFF. `None` leaves the open bus value, for
+    /// boards without RAM or while the RAM is disabled.
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[(addr as usize - 0x6000) % prg_ram.len()])
+    }
+
+    /// Write PRG-RAM at $6000-$7FFF
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    /// Read anywhere in $4020-$FFFF without side effects (debugger, DMC
+    /// sample fetches). `None` means nothing on the board drives the bus.
+    fn cpu_peek(&self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram_read(addr, &mem.prg_ram),
+            0x8000..=0xFFFF => Some(self.prg_rom_read(addr, &mem.prg_rom)),
+            _ => None,
+        }
+    }
+
+    /// CPU read cycle in $4020-$FFFF. Boards whose registers have read
+    /// side effects override this; everyone else reads like a peek.
+    fn cpu_read(&mut self, addr: u16, mem: &mut CartridgeMemory) -> Option<u8> {
+        self.cpu_peek(addr, mem)
+    }
+
+    /// CPU write cycle in $4020-$FFFF. Writes to the PPU registers at
+    /// $2000-$3FFF are passed along too, for boards that snoop them.
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram_write(addr, value, &mut mem.prg_ram),
+            0x8000..=0xFFFF => {
+                let value = if self.has_bus_conflicts() {
+                    value & self.prg_rom_read(addr, &mem.prg_rom)
+                } else {
+                    value
+                };
+                self.register_write(addr, value);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether PRG-ROM stays enabled while the CPU writes the board's
+    /// registers at $8000-$FFFF. Both then drive the data bus and a bit only
+    /// reaches the register if the ROM byte at that address has it set too.
+    fn has_bus_conflicts(&self) -> bool {
+        false
+    }
+
+    /// Whether the board can reprogram its own PRG-ROM (a flash chip). The
+    /// ROM then goes into save states, and on boards with the battery bit
+    /// set it's what the save file holds.
+    fn has_flash(&self) -> bool {
+        false
+    }
+
+    /// Called once per CPU cycle (M2), after the cycle's bus access
+    fn cpu_clock(&mut self) {}
+
+    /// The console's reset button was pressed. The registers of most boards
+    /// survive it; a few multicarts count resets to pick a game.
+    fn reset(&mut self) {}
+
+    /// Every address the PPU drives onto its bus: rendering fetches, PPUDATA
+    /// accesses, and PPUADDR updates while rendering is off. Boards that
+    /// watch A12 or count fetches hook in here.
+    fn ppu_address(&mut self, _addr: u16) {}
+
+    /// Read the pattern tables at $0000-$1FFF
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8;
+
+    /// Write the pattern tables at $0000-$1FFF (CHR-RAM)
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory);
+
+    /// Offset into the console's nametable RAM for a pattern table access,
+    /// on boards that can map it there instead of CHR
+    fn chr_ciram_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// Read a nametable at $2000-$3EFF. `None` lets the console's own 2KB
+    /// of nametable RAM answer, arranged by [`Mapper::mirroring`].
+    fn nametable_read(&mut self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        mem.four_screen_index(addr).map(|i| mem.nametable_ram[i])
+    }
+
+    /// Write a nametable at $2000-$3EFF. Returns true if the board took the
+    /// write, false to let it reach the console's nametable RAM.
+    fn nametable_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) -> bool {
+        match mem.four_screen_index(addr) {
+            Some(i) => {
+                mem.nametable_ram[i] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How the console's nametable RAM is arranged right now
+    fn mirroring(&self) -> Mirroring;
+
+    /// Offset into the console's 2KB nametable RAM for a nametable access
+    /// the board didn't answer itself. Boards that pick the RAM page for
+    /// each nametable separately override this.
+    fn ciram_offset(&self, addr: u16) -> usize {
+        self.mirroring().ciram_offset(addr)
+    }
+
+    /// Level of the board's /IRQ output (true = asserted)
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    /// Current level of the board's expansion audio, on the same scale as the
+    /// APU's mixed output
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+
+    /// Apply an expansion audio setting; boards without that chip ignore it
+    fn set_audio_option(&mut self, _option: AudioOption) {}
+
+    /// The disk in a Famicom Disk System's drive; `None` for cartridges
+    fn disk(&self) -> Option<&FdsDisk> {
+        None
+    }
+
+    fn disk_mut(&mut self) -> Option<&mut FdsDisk> {
+        None
+    }
+
+    /// Disk side in the drive, if any
+    fn disk_side(&self) -> Option<usize> {
+        None
+    }
+
+    /// Eject the disk, then insert `side` once the BIOS has had time to
+    /// notice it was gone. `None` leaves the drive empty.
+    fn insert_disk(&mut self, _side: Option<usize>) {}
+
+    /// Write the mapper's banking and IRQ registers into a save state
+    fn save_state(&self, w: &mut StateWriter);
+
+    /// Restore the mapper's banking and IRQ registers from a save state
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+/// A user setting for one kind of expansion audio chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioOption {
+    /// How a Namco 163's wavetable channels are mixed
+    N163Mixing(N163Mixing),
+}
+
+/// Build the mapper for a board in its power-on state. `chr_ram_size` is the
+/// CHR-RAM the cartridge actually allocated.
+pub fn create_mapper(
+    header: &RomHeader,
+    chr_ram_size: usize,
+) -> Result<Box<dyn Mapper>, CartridgeError> {
+    let mirroring = header.mirroring;
+    let has_chr_ram = header.chr_rom_size == 0;
+    let prg_rom_size = header.prg_rom_size;
+    let chr_rom_size = header.chr_rom_size;
+    let mapper: Box<dyn Mapper> = match header.mapper {
+        0 => Box::new(NromMapper::new(mirroring, has_chr_ram)),
+        1 => Box::new(Mmc1Mapper::new(
+            mirroring,
+            has_chr_ram,
+            prg_rom_size,
+            chr_rom_size,
+        )),
+        2 => Box::new(UxromMapper::new(
+            mirroring,
+            has_chr_ram,
+            bus_conflicts(header.submapper),
+            prg_rom_size,
+        )),
+        3 => Box::new(CnromMapper::new(
+            mirroring,
+            has_chr_ram,
+            bus_conflicts(header.submapper),
+            chr_rom_size,
+        )),
+        4 | 64 | 118 | 119 | 206 => Box::new(Mmc3Mapper::new(
+            match (header.mapper, header.submapper) {
+                (4, 1) => Mmc3Board::Mmc6,
+                (64, _) => Mmc3Board::Rambo1,
+                (118, _) => Mmc3Board::Txsrom,
+                (119, _) => Mmc3Board::Tqrom,
+                (206, _) => Mmc3Board::Namco108,
+                _ => Mmc3Board::Mmc3,
+            },
+            // NES 2.0 submapper 4 marks boards with the older MMC3A
+            if header.mapper == 4 && header.submapper == 4 {
+                Mmc3Revision::Nec
+            } else {
+                Mmc3Revision::Sharp
+            },
+            mirroring,
+            has_chr_ram,
+            prg_rom_size,
+            if has_chr_ram {
+                chr_ram_size
+            } else {
+                chr_rom_size
+            },
+        )),
+        5 => Box::new(Mmc5Mapper::new(has_chr_ram)),
+        7 => Box::new(AxromMapper::new(
+            has_chr_ram,
+            bus_conflicts(header.submapper),
+        )),
+        9 => Box::new(Mmc2Mapper::new(mirroring, has_chr_ram, false)),
+        10 => Box::new(Mmc2Mapper::new(mirroring, has_chr_ram, true)),
+        11 => Box::new(ColorDreamsMapper::new(mirroring, has_chr_ram)),
+        15 | 60 | 225..=228 | 230 | 231 => {
+            Box::new(MulticartMapper::new(header.mapper, mirroring, has_chr_ram))
+        }
+        // EEPROM contents go in battery-backed PRG-RAM, after any work RAM
+        16 | 153 | 157 | 159 => Box::new(BandaiMapper::new(
+            header.mapper,
+            header.submapper,
+            has_chr_ram,
+            header.prg_nvram_size,
+            header.prg_ram_size,
+        )),
+        // A board with just 128 bytes of PRG-RAM keeps the chip's internal
+        // RAM battery-backed
+        19 => Box::new(N163Mapper::new(
+            header.submapper,
+            header.total_prg_ram_size() == 0x80,
+        )),
+        21..=23 | 25 => Box::new(Vrc24Mapper::new(
+            header.mapper,
+            header.submapper,
+            has_chr_ram,
+        )),
+        24 => Box::new(Vrc6Mapper::new(has_chr_ram, false)),
+        26 => Box::new(Vrc6Mapper::new(has_chr_ram, true)),
+        28 => Box::new(Action53Mapper::new(has_chr_ram)),
+        // The battery bit marks the self-flashable board
+        30 => Box::new(Unrom512Mapper::new(
+            mirroring,
+            header.mirroring_bit,
+            has_chr_ram,
+            header.has_battery,
+        )),
+        // Mapper 34 covers two unrelated boards; only NINA-001 has CHR-ROM
+        // banks to switch
+        34 if header.submapper == 1 || (header.submapper == 0 && chr_rom_size > 0x2000) => {
+            Box::new(Nina001Mapper::new(mirroring, has_chr_ram))
+        }
+        34 => Box::new(BnromMapper::new(mirroring, has_chr_ram)),
+        66 => Box::new(GxromMapper::new(mirroring, has_chr_ram)),
+        69 => Box::new(Fme7Mapper::new(has_chr_ram)),
+        85 => Box::new(Vrc7Mapper::new(header.submapper, has_chr_ram)),
+        111 => Box::new(GtromMapper::new(has_chr_ram)),
+        id => return Err(CartridgeError::UnsupportedMapper(id)),
+    };
+    Ok(mapper)
+}
+
+/// CHR-RAM a board carries when its header doesn't give a size (always the
+/// case for iNES 1.0). Without CHR-ROM that's 8KB, except on the homebrew
+/// boards that always carry 32KB: UNROM 512 and GTROM. TQROM has 8KB of
+/// CHR-RAM next to its CHR-ROM.
+pub fn default_chr_ram_size(header: &RomHeader) -> usize {
+    match header.mapper {
+        119 => 0x2000,
+        _ if header.chr_rom_size > 0 => 0,
+        30 | 111 => 0x8000,
+        _ => 0x2000,
+    }
+}
+
+/// Whether a discrete-logic board has bus conflicts, from its NES 2.0
+/// submapper: 2 means the written value is ANDed with the ROM byte, 1 that
+/// the board avoids them. Submapper 0 (every iNES 1.0 dump) doesn't say, and
+/// gets no conflicts: most UNROM and CNROM boards do have them, but plenty of
+/// dumps only work without, including hacks and homebrew tested on
+/// emulators that never had them.
+fn bus_conflicts(submapper: u8) -> bool {
+    submapper == 2
+}
+
+/// Read 8KB of CHR-ROM, or CHR-RAM on boards without it, at `offset`
+/// (wrapped to the chip size)
+fn chr_read(mem: &CartridgeMemory, has_chr_ram: bool, offset: usize) -> u8 {
+    let chr = if has_chr_ram {
+        &mem.chr_ram
+    } else {
+        &mem.chr_rom
+    };
+    if chr.is_empty() {
+        return 0;
+    }
+    chr[offset % chr.len()]
+}
+
+/// Write CHR-RAM at `offset`; CHR-ROM ignores writes
+fn chr_write(mem: &mut CartridgeMemory, has_chr_ram: bool, offset: usize, value: u8) {
+    let chr_ram = &mut mem.chr_ram;
+    if has_chr_ram && !chr_ram.is_empty() {
+        let len = chr_ram.len();
+        chr_ram[offset % len] = value;
+    }
+}
+
+// Helper function: next power of 2
+fn next_power_of_2(n: usize) -> usize {
+    if n == 0 {
+        return 1;
+    }
+    let mut power = 1;
+    while power < n {
+        power *= 2;
+    }
+    power
+}
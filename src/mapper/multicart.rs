@@ -0,0 +1,296 @@
+//! Pirate "N-in-1" multicart boards. Most latch the address (and sometimes
+//! the data) of any write to $8000-$FFFF and decode their banks from it.
+//!
+//! - 15 (K-1029/K-1030P): four NROM/UNROM-style PRG modes, picked by A0-A1
+//! - 60: four NROM-128 games, switched by pressing reset
+//! - 225, 226, 227, 231: 16KB/32KB PRG modes over up to 2MB, each with its
+//!   own bit layout; 225 also has four nibbles of RAM at $5800-$5FFF
+//! - 228 (Action 52, Cheetahmen II): three 512KB PRG chips, CHR banks from
+//!   address and data together, and four nibbles of RAM at $4020-$5FFF
+//! - 230: Contra on UNROM until reset is pressed, then a 20-in-1 menu
+//!
+//! The latches survive the reset button, like on the real boards: games
+//! return to the menu through their own code. The boards that count resets
+//! do so in [`Mapper::reset`].
+//!
+//! Reference: https://www.nesdev.org/wiki/Category:Multicart_mappers
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Board {
+    M15,
+    M60,
+    M225,
+    M226,
+    M227,
+    M228,
+    M230,
+    M231,
+}
+
+pub struct MulticartMapper {
+    board: Board,
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    latch_addr: u16,
+    // Value written with the address; mapper 226 has a second register at
+    // odd addresses
+    latch_data: [u8; 2],
+    nibble_ram: [u8; 4],
+    // Mappers 60 and 230: times reset has been pressed since power-on
+    resets: u8,
+}
+
+impl MulticartMapper {
+    pub fn new(mapper: u16, mirroring: Mirroring, has_chr_ram: bool) -> Self {
+        let board = match mapper {
+            15 => Board::M15,
+            60 => Board::M60,
+            225 => Board::M225,
+            226 => Board::M226,
+            227 => Board::M227,
+            228 => Board::M228,
+            230 => Board::M230,
+            _ => Board::M231,
+        };
+        Self {
+            board,
+            mirroring,
+            has_chr_ram,
+            latch_addr: 0,
+            latch_data: [0; 2],
+            nibble_ram: [0; 4],
+            resets: 0,
+        }
+    }
+
+    /// 16KB PRG banks at $8000 and $C000
+    fn prg_banks(&self) -> [usize; 2] {
+        let a = self.latch_addr as usize;
+        let d = self.latch_data[0] as usize;
+        let (low, high) = match self.board {
+            Board::M15 => {
+                let bank = d & 0x3F;
+                match a & 0x03 {
+                    0 => (bank & !1, bank | 1),
+                    1 => (bank, bank | 0x07),
+                    // NROM-64 is handled in prg_offset
+                    _ => (bank, bank),
+                }
+            }
+            Board::M60 => {
+                let game = (self.resets & 0x03) as usize;
+                (game, game)
+            }
+            Board::M225 => {
+                let bank = ((a >> 6) & 0x3F) | ((a >> 8) & 0x40);
+                if (a & 0x1000) != 0 {
+                    (bank, bank)
+                } else {
+                    (bank & !1, bank | 1)
+                }
+            }
+            Board::M226 => {
+                let high_reg = self.latch_data[1] as usize;
+                let bank = (d & 0x1F) | ((d >> 2) & 0x20) | ((high_reg & 0x01) << 6);
+                if (d & 0x20) != 0 {
+                    (bank, bank)
+                } else {
+                    (bank & !1, bank | 1)
+                }
+            }
+            Board::M227 => {
+                let bank = ((a >> 2) & 0x1F) | ((a & 0x100) >> 3);
+                let size_32k = (a & 0x01) != 0;
+                if (a & 0x80) != 0 {
+                    if size_32k {
+                        (bank & !1, bank | 1)
+                    } else {
+                        (bank, bank)
+                    }
+                } else {
+                    // UNROM-style: the upper half is fixed to the first or
+                    // last bank of the 128KB block
+                    let low = if size_32k { bank & 0x3E } else { bank };
+                    let high = if (a & 0x200) != 0 {
+                        bank | 0x07
+                    } else {
+                        bank & 0x38
+                    };
+                    (low, high)
+                }
+            }
+            Board::M228 => {
+                // Chip 2 was never fitted, so the file holds chip 3 in its place
+                let chip = match (a >> 11) & 0x03 {
+                    3 => 2,
+                    chip => chip,
+                };
+                let bank = ((a >> 6) & 0x1F) | (chip << 5);
+                if (a & 0x20) != 0 {
+                    (bank, bank)
+                } else {
+                    (bank & !1, bank | 1)
+                }
+            }
+            Board::M230 if self.contra_mode() => (d & 0x07, 0x07),
+            Board::M230 => {
+                // The multicart's games start after Contra's 128KB
+                if (d & 0x20) != 0 {
+                    ((d & 0x1F) + 8, (d & 0x1F) + 8)
+                } else {
+                    ((d & 0x1E) + 8, (d & 0x1E) + 9)
+                }
+            }
+            Board::M231 => {
+                let bank = (a & 0x1E) | ((a >> 5) & 0x01);
+                (bank & 0x1E, bank)
+            }
+        };
+        [low, high]
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        // Mapper 15's NROM-64 mode mirrors one 8KB bank over all of PRG
+        if self.board == Board::M15 && (self.latch_addr & 0x03) == 2 {
+            let d = self.latch_data[0] as usize;
+            let bank = ((d & 0x3F) << 1) | (d >> 7);
+            return bank * 0x2000 + (addr as usize & 0x1FFF);
+        }
+        let bank = self.prg_banks()[(addr as usize >> 14) & 0x01];
+        bank * 0x4000 + (addr as usize & 0x3FFF)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let a = self.latch_addr as usize;
+        let bank = match self.board {
+            Board::M60 => (self.resets & 0x03) as usize,
+            Board::M225 => (a & 0x3F) | ((a >> 8) & 0x40),
+            Board::M228 => ((a & 0x0F) << 2) | (self.latch_data[0] as usize & 0x03),
+            _ => 0,
+        };
+        bank * 0x2000 + (addr as usize & 0x1FFF)
+    }
+
+    /// Whether the game may write CHR-RAM. NROM-style modes protect it,
+    /// after the menu has loaded the game's tiles.
+    fn chr_writable(&self) -> bool {
+        match self.board {
+            Board::M15 => matches!(self.latch_addr & 0x03, 1 | 2),
+            Board::M227 => (self.latch_addr & 0x80) == 0,
+            _ => true,
+        }
+    }
+
+    fn contra_mode(&self) -> bool {
+        self.resets.is_multiple_of(2)
+    }
+
+    /// Address range of the four nibbles of RAM, on boards that have them
+    fn has_nibble_ram(&self, addr: u16) -> bool {
+        match self.board {
+            Board::M225 => (0x5800..=0x5FFF).contains(&addr),
+            Board::M228 => (0x4020..=0x5FFF).contains(&addr),
+            _ => false,
+        }
+    }
+}
+
+impl Mapper for MulticartMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        prg_rom[self.prg_offset(addr) % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        match self.board {
+            // Reset is the only way to switch games
+            Board::M60 => {}
+            Board::M226 => self.latch_data[addr as usize & 0x01] = value,
+            _ => {
+                self.latch_addr = addr;
+                self.latch_data[0] = value;
+            }
+        }
+    }
+
+    fn cpu_peek(&self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        match addr {
+            // Only the low four bits are driven; the rest is open bus
+            _ if self.has_nibble_ram(addr) => {
+                Some(((addr >> 8) as u8 & 0xF0) | self.nibble_ram[addr as usize & 0x03])
+            }
+            0x6000..=0x7FFF => self.prg_ram_read(addr, &mem.prg_ram),
+            0x8000..=0xFFFF => Some(self.prg_rom_read(addr, &mem.prg_rom)),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            _ if self.has_nibble_ram(addr) => self.nibble_ram[addr as usize & 0x03] = value & 0x0F,
+            0x6000..=0x7FFF => self.prg_ram_write(addr, value, &mut mem.prg_ram),
+            0x8000..=0xFFFF => self.register_write(addr, value),
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self) {
+        match self.board {
+            Board::M60 => self.resets = (self.resets + 1) & 0x03,
+            Board::M230 => {
+                self.resets ^= 1;
+                // Either way the new mode starts from its first bank
+                self.latch_data[0] = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        if self.chr_writable() {
+            chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        let a = self.latch_addr;
+        let d = self.latch_data[0];
+        let horizontal = match self.board {
+            Board::M60 => return self.mirroring,
+            Board::M230 if self.contra_mode() => return Mirroring::Vertical,
+            Board::M15 => (d & 0x40) != 0,
+            Board::M225 | Board::M228 => (a & 0x2000) != 0,
+            Board::M226 => (d & 0x40) == 0,
+            Board::M227 => (a & 0x02) != 0,
+            Board::M230 => (d & 0x40) == 0,
+            Board::M231 => (a & 0x80) != 0,
+        };
+        if horizontal {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.latch_addr);
+        w.bytes(&self.latch_data);
+        w.bytes(&self.nibble_ram);
+        w.u8(self.resets);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.latch_addr = r.u16()?;
+        r.bytes_into(&mut self.latch_data)?;
+        r.bytes_into(&mut self.nibble_ram)?;
+        self.resets = r.u8()?;
+        Ok(())
+    }
+}
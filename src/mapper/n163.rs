@@ -0,0 +1,412 @@
+//! Namco 163 (mapper 19): 8KB PRG banks, 1KB CHR banks that can also point
+//! at the console's nametable RAM, nametables that can come from CHR-ROM, a
+//! 15-bit CPU-cycle IRQ counter, and up to eight wavetable channels played
+//! from the chip's 128 bytes of internal RAM
+//!
+//! Reference: https://www.nesdev.org/wiki/Namco_163
+
+use super::{chr_read, chr_write, AudioOption, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// CPU cycles the sound hardware spends on each channel
+const CYCLES_PER_CHANNEL: u8 = 15;
+
+/// Peak-to-peak level of a full-volume APU pulse on its own
+const APU_PULSE_LEVEL: f32 = 95.88 / (8128.0 / 15.0 + 100.0);
+/// Output of one DAC step, before the board's gain. At 0dB a full-volume
+/// channel (-120..105) swings as far as a full-volume APU pulse.
+const DAC_STEP: f32 = APU_PULSE_LEVEL / 225.0;
+
+/// How the time-multiplexed channels reach the mix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "desktop", derive(serde::Serialize, serde::Deserialize))]
+pub enum N163Mixing {
+    /// Output one channel at a time, switching every 15 CPU cycles like the
+    /// real chip. With many channels enabled this adds an audible whine.
+    Multiplexed,
+    /// Output the average of the enabled channels, which is what the
+    /// multiplexed signal sounds like once the whine is filtered out
+    #[default]
+    Mixed,
+}
+
+pub struct N163Mapper {
+    prg_banks: [u8; 3], // $8000, $A000, $C000
+    // Values $E0-$FF select a page of the console's nametable RAM instead
+    // of CHR-ROM
+    chr_banks: [u8; 8],
+    nametable_banks: [u8; 4],
+    // $E800 bits 6-7: CHR banks $E0-$FF are CHR-ROM after all, for the
+    // pattern table at $0000 / $1000
+    chr_ram_disable: u8,
+    // $F800: bits 0-6 internal RAM address, bit 7 auto-increment. The same
+    // write protects PRG-RAM: writes need $4x, with bits 0-3 protecting
+    // each 2KB.
+    ram_port: u8,
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+    // The internal RAM is battery-backed and lives in the cartridge's
+    // PRG-RAM (NES 2.0 headers give 128 bytes of PRG-NVRAM); there's no
+    // work RAM at $6000 then
+    ram_in_prg_ram: bool,
+    audio: N163Audio,
+}
+
+impl N163Mapper {
+    /// `submapper` picks the board's expansion audio level (NES 2.0
+    /// submappers 2-5); `ram_in_prg_ram` whether PRG-RAM holds the chip's
+    /// internal RAM
+    pub fn new(submapper: u8, ram_in_prg_ram: bool) -> Self {
+        // Submapper 2 boards leave the sound output unconnected
+        let gain_db: Option<f32> = match submapper {
+            2 => None,
+            4 => Some(16.5),
+            5 => Some(18.75),
+            _ => Some(12.0),
+        };
+        Self {
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            nametable_banks: [0; 4],
+            chr_ram_disable: 0,
+            ram_port: 0,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            ram_in_prg_ram,
+            audio: N163Audio::new(gain_db.map_or(0.0, |db| 10f32.powf(db / 20.0))),
+        }
+    }
+
+    /// CIRAM page for a CHR or nametable register, if it points there
+    fn ciram_page(value: u8) -> Option<usize> {
+        (value >= 0xE0).then_some(value as usize & 0x01)
+    }
+
+    fn chr_register_page(&self, addr: u16) -> Option<usize> {
+        let disabled = (self.chr_ram_disable & (0x40 << (addr >> 12))) != 0;
+        if disabled {
+            None
+        } else {
+            Self::ciram_page(self.chr_banks[(addr as usize >> 10) & 0x07])
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        self.chr_banks[(addr as usize >> 10) & 0x07] as usize * 0x400 + (addr as usize & 0x03FF)
+    }
+
+    fn nametable_bank(&self, addr: u16) -> u8 {
+        self.nametable_banks[(addr as usize >> 10) & 0x03]
+    }
+
+    fn ram_address(&mut self) -> usize {
+        let addr = (self.ram_port & 0x7F) as usize;
+        if (self.ram_port & 0x80) != 0 {
+            self.ram_port = 0x80 | (self.ram_port.wrapping_add(1) & 0x7F);
+        }
+        addr
+    }
+}
+
+impl Mapper for N163Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = match addr {
+            0x8000..=0xDFFF => {
+                let bank = self.prg_banks[(addr as usize - 0x8000) >> 13] as usize;
+                bank * 0x2000 + (addr as usize & 0x1FFF)
+            }
+            _ => prg_rom.len().saturating_sub(0x2000) + (addr as usize & 0x1FFF),
+        };
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        match addr & 0xF800 {
+            0x8000..=0xB800 => self.chr_banks[(addr as usize - 0x8000) >> 11] = value,
+            0xC000..=0xD800 => self.nametable_banks[(addr as usize - 0xC000) >> 11] = value,
+            0xE000 => {
+                self.prg_banks[0] = value & 0x3F;
+                self.audio.disabled = (value & 0x40) != 0;
+            }
+            0xE800 => {
+                self.prg_banks[1] = value & 0x3F;
+                self.chr_ram_disable = value & 0xC0;
+            }
+            0xF000 => self.prg_banks[2] = value & 0x3F,
+            _ => self.ram_port = value,
+        }
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if self.ram_in_prg_ram || prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[(addr as usize - 0x6000) % prg_ram.len()])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        let window = (addr - 0x6000) >> 11;
+        let writable = (self.ram_port & 0xF0) == 0x40 && (self.ram_port & (1 << window)) == 0;
+        if writable && !self.ram_in_prg_ram && !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn cpu_peek(&self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => {
+                let addr = (self.ram_port & 0x7F) as usize;
+                Some(if self.ram_in_prg_ram {
+                    mem.prg_ram[addr % mem.prg_ram.len()]
+                } else {
+                    self.audio.ram[addr]
+                })
+            }
+            0x5000..=0x57FF => Some(self.irq_counter as u8),
+            0x5800..=0x5FFF => {
+                Some((self.irq_counter >> 8) as u8 | if self.irq_enabled { 0x80 } else { 0 })
+            }
+            0x6000..=0x7FFF => self.prg_ram_read(addr, &mem.prg_ram),
+            0x8000..=0xFFFF => Some(self.prg_rom_read(addr, &mem.prg_rom)),
+            _ => None,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16, mem: &mut CartridgeMemory) -> Option<u8> {
+        let value = self.cpu_peek(addr, mem);
+        if (0x4800..=0x4FFF).contains(&addr) {
+            self.ram_address();
+        }
+        value
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            0x4800..=0x4FFF => {
+                let addr = self.ram_address();
+                self.audio.ram[addr] = value;
+                if self.ram_in_prg_ram {
+                    let len = mem.prg_ram.len();
+                    mem.prg_ram[addr % len] = value;
+                }
+            }
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | value as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16 & 0x7F) << 8);
+                self.irq_enabled = (value & 0x80) != 0;
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF => self.prg_ram_write(addr, value, &mut mem.prg_ram),
+            0x8000..=0xFFFF => self.register_write(addr, value),
+            _ => {}
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        // Counts up to $7FFF, raises the IRQ and stops there
+        if self.irq_enabled && self.irq_counter < 0x7FFF {
+            self.irq_counter += 1;
+            if self.irq_counter == 0x7FFF {
+                self.irq_pending = true;
+            }
+        }
+        self.audio.clock();
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, false, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, false, self.chr_offset(addr), value);
+    }
+
+    fn chr_ciram_offset(&self, addr: u16) -> Option<usize> {
+        self.chr_register_page(addr)
+            .map(|page| page * 0x400 + (addr as usize & 0x03FF))
+    }
+
+    fn nametable_read(&mut self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        let bank = self.nametable_bank(addr);
+        match Self::ciram_page(bank) {
+            Some(_) => None,
+            None => Some(chr_read(
+                mem,
+                false,
+                bank as usize * 0x400 + (addr as usize & 0x03FF),
+            )),
+        }
+    }
+
+    fn nametable_write(&mut self, addr: u16, _value: u8, _mem: &mut CartridgeMemory) -> bool {
+        // Nametables in CHR-ROM swallow the write
+        Self::ciram_page(self.nametable_bank(addr)).is_none()
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        // Only meaningful for the common arrangements; the actual mapping
+        // comes from `ciram_offset`
+        match self.nametable_banks.map(|bank| bank & 0x01) {
+            [0, 0, 1, 1] => Mirroring::Horizontal,
+            [0, 1, 0, 1] => Mirroring::Vertical,
+            [1, 1, 1, 1] => Mirroring::OneScreenUpper,
+            _ => Mirroring::OneScreenLower,
+        }
+    }
+
+    fn ciram_offset(&self, addr: u16) -> usize {
+        (self.nametable_bank(addr) as usize & 0x01) * 0x400 + (addr as usize & 0x03FF)
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn set_audio_option(&mut self, option: AudioOption) {
+        let AudioOption::N163Mixing(mixing) = option;
+        self.audio.mixing = mixing;
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.prg_banks);
+        w.bytes(&self.chr_banks);
+        w.bytes(&self.nametable_banks);
+        w.u8(self.chr_ram_disable);
+        w.u8(self.ram_port);
+        w.u16(self.irq_counter);
+        w.bool(self.irq_enabled);
+        w.bool(self.irq_pending);
+        self.audio.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.bytes_into(&mut self.prg_banks)?;
+        r.bytes_into(&mut self.chr_banks)?;
+        r.bytes_into(&mut self.nametable_banks)?;
+        self.chr_ram_disable = r.u8()? & 0xC0;
+        self.ram_port = r.u8()?;
+        self.irq_counter = r.u16()? & 0x7FFF;
+        self.irq_enabled = r.bool()?;
+        self.irq_pending = r.bool()?;
+        self.audio.load_state(r)
+    }
+}
+
+/// Wavetable sound. Channel registers sit at the top of the internal RAM,
+/// channel 7 at $78-$7F down to channel 0 at $40-$47; waveforms are packed
+/// 4-bit samples anywhere in the RAM, low nibble first.
+struct N163Audio {
+    ram: [u8; 0x80],
+    // $E000 bit 6
+    disabled: bool,
+    mixing: N163Mixing,
+    /// Linear gain of the board's mixing resistors
+    gain: f32,
+    cycle: u8,
+    /// Channel being updated and output
+    channel: u8,
+    outputs: [i16; 8],
+}
+
+impl N163Audio {
+    fn new(gain: f32) -> Self {
+        Self {
+            ram: [0; 0x80],
+            disabled: false,
+            mixing: N163Mixing::default(),
+            gain,
+            cycle: 0,
+            channel: 7,
+            outputs: [0; 8],
+        }
+    }
+
+    /// Number of enabled channels, from $7F bits 4-6. Channels 7 down to
+    /// 8 - count are played.
+    fn channel_count(&self) -> u8 {
+        ((self.ram[0x7F] >> 4) & 0x07) + 1
+    }
+
+    fn clock(&mut self) {
+        if self.disabled {
+            return;
+        }
+        self.cycle += 1;
+        if self.cycle < CYCLES_PER_CHANNEL {
+            return;
+        }
+        self.cycle = 0;
+
+        let lowest = 8 - self.channel_count();
+        self.channel = if self.channel <= lowest || self.channel > 7 {
+            7
+        } else {
+            self.channel - 1
+        };
+        self.update_channel(self.channel as usize);
+    }
+
+    fn update_channel(&mut self, channel: usize) {
+        let base = 0x40 + channel * 8;
+        let regs = &mut self.ram[base..base + 8];
+        let frequency = regs[0] as u32 | ((regs[2] as u32) << 8) | ((regs[4] as u32 & 0x03) << 16);
+        let length = 256 - (regs[4] as u32 & 0xFC);
+        let mut phase = regs[1] as u32 | ((regs[3] as u32) << 8) | ((regs[5] as u32) << 16);
+        phase = (phase + frequency) % (length << 16);
+        regs[1] = phase as u8;
+        regs[3] = (phase >> 8) as u8;
+        regs[5] = (phase >> 16) as u8;
+
+        let sample_addr = ((phase >> 16) + regs[6] as u32) as usize & 0xFF;
+        let volume = (regs[7] & 0x0F) as i16;
+        let sample = (self.ram[sample_addr >> 1] >> ((sample_addr & 0x01) * 4)) & 0x0F;
+        self.outputs[channel] = (sample as i16 - 8) * volume;
+    }
+
+    fn output(&self) -> f32 {
+        if self.disabled {
+            return 0.0;
+        }
+        let level = match self.mixing {
+            N163Mixing::Multiplexed => self.outputs[self.channel as usize] as f32,
+            N163Mixing::Mixed => {
+                let count = self.channel_count();
+                let sum: i16 = self.outputs[(8 - count) as usize..].iter().sum();
+                sum as f32 / count as f32
+            }
+        };
+        level * DAC_STEP * self.gain
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+        w.bool(self.disabled);
+        w.u8(self.cycle);
+        w.u8(self.channel);
+        for &output in &self.outputs {
+            w.u16(output as u16);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.bytes_into(&mut self.ram)?;
+        self.disabled = r.bool()?;
+        self.cycle = r.u8()? % CYCLES_PER_CHANNEL;
+        self.channel = r.u8()? & 0x07;
+        for output in &mut self.outputs {
+            *output = r.u16()? as i16;
+        }
+        Ok(())
+    }
+}
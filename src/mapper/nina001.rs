@@ -0,0 +1,77 @@
+//! NINA-001 (mapper 34): 32KB PRG and two 4KB CHR banks, registers in PRG-RAM
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+// AVE NINA-001 (Mapper 34) - used by Impossible Mission II
+pub struct Nina001Mapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    prg_bank: u8,       // $7FFD: 32KB bank at $8000-$FFFF
+    chr_banks: [u8; 2], // $7FFE/$7FFF: 4KB banks at $0000 and $1000
+}
+
+impl Nina001Mapper {
+    pub fn new(mirroring: Mirroring, has_chr_ram: bool) -> Self {
+        Self {
+            mirroring,
+            has_chr_ram,
+            prg_bank: 0,
+            chr_banks: [0, 1],
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 12) & 0x01] as usize;
+        bank * 0x1000 + (addr as usize & 0x0FFF)
+    }
+}
+
+impl Mapper for Nina001Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = self.prg_bank as usize * 0x8000 + (addr as usize & 0x7FFF);
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, _addr: u16, _value: u8) {
+        // The registers live at $7FFD-$7FFF; $8000-$FFFF is plain ROM
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        match addr {
+            0x7FFD => self.prg_bank = value & 0x01,
+            0x7FFE => self.chr_banks[0] = value & 0x0F,
+            0x7FFF => self.chr_banks[1] = value & 0x0F,
+            _ => {}
+        }
+        // The registers overlay RAM, and the write still lands in it
+        if !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.prg_bank);
+        w.bytes(&self.chr_banks);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_bank = r.u8()?;
+        r.bytes_into(&mut self.chr_banks)?;
+        Ok(())
+    }
+}
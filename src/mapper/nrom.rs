@@ -0,0 +1,89 @@
+//! NROM (mapper 0): no bank switching
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct NromMapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+}
+
+impl NromMapper {
+    pub fn new(mirroring: Mirroring, has_chr_ram: bool) -> Self {
+        Self {
+            mirroring,
+            has_chr_ram,
+        }
+    }
+}
+
+impl Mapper for NromMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let addr = addr - 0x8000;
+        if prg_rom.len() == 0x4000 {
+            // 16KB PRG ROM, mirrored
+            prg_rom[addr as usize % 0x4000]
+        } else {
+            // 32KB PRG ROM
+            prg_rom[addr as usize]
+        }
+    }
+
+    fn register_write(&mut self, _addr: u16, _value: u8) {
+        // NROM has no mapper registers
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        // CHR smaller than 8KB is mirrored
+        chr_read(mem, self.has_chr_ram, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, _w: &mut StateWriter) {
+        // NROM has no registers
+    }
+
+    fn load_state(&mut self, _r: &mut StateReader) -> Result<(), SaveStateError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(chr_ram_size: usize) -> CartridgeMemory {
+        CartridgeMemory {
+            prg_rom: vec![0; 0x4000],
+            chr_rom: Vec::new(),
+            prg_ram: Vec::new(),
+            chr_ram: vec![0; chr_ram_size],
+            nametable_ram: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn chr_ram_smaller_than_8kb_is_mirrored() {
+        let mut mapper = NromMapper::new(Mirroring::Horizontal, true);
+        let mut mem = memory(0x800);
+        mapper.ppu_write(0x1805, 0x42, &mut mem);
+        assert_eq!(mem.chr_ram[0x005], 0x42);
+        assert_eq!(mapper.ppu_read(0x0005, &mem), 0x42);
+    }
+
+    #[test]
+    fn missing_chr_ram_reads_zero() {
+        let mut mapper = NromMapper::new(Mirroring::Horizontal, true);
+        let mut mem = memory(0);
+        mapper.ppu_write(0x1FFF, 0x42, &mut mem);
+        assert_eq!(mapper.ppu_read(0x1FFF, &mem), 0);
+    }
+}
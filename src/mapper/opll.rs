@@ -0,0 +1,544 @@
+//! FM synthesizer in the VRC7, a cut-down Yamaha YM2413 (OPLL)
+//!
+//! Six two-operator channels. A modulator operator, optionally fed back on
+//! itself, varies the phase of a carrier operator, whose output is the
+//! channel's sound. Each operator has a sine (or half-sine) wave, a
+//! frequency multiplier, an ADSR envelope and optional tremolo/vibrato.
+//! Instruments come from a ROM of 15 presets or one user-defined patch.
+//!
+//! The chip produces a sample every 36 CPU cycles (about 49.7kHz). Levels
+//! are handled in the chip's own units: envelope steps of 0.375dB.
+//!
+//! Reference: https://www.nesdev.org/wiki/VRC7_audio
+
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// CPU cycles per synthesizer sample
+const CYCLES_PER_SAMPLE: u8 = 36;
+/// Samples per second the synthesizer produces (NTSC CPU clock / 36)
+const SAMPLE_RATE: f32 = 1_789_773.0 / 36.0;
+
+/// Envelope counters are 22-bit; the top 7 bits are the attenuation
+const EG_BITS: u32 = 7;
+const EG_DP_BITS: u32 = 22;
+const EG_DP_WIDTH: u32 = 1 << EG_DP_BITS;
+/// Attenuation of one envelope step, in dB
+const EG_STEP_DB: f32 = 0.375;
+/// Largest attenuation worth computing; anything beyond is silence
+const MAX_ATTENUATION: usize = 512;
+const SINE_SIZE: usize = 1024;
+
+/// Tremolo: 4.8dB deep at 3.7Hz. Vibrato: +/-7 cents at 6.4Hz.
+const AM_DEPTH_STEPS: f32 = 4.8 / EG_STEP_DB;
+const AM_RATE: f32 = 3.7;
+const VIB_DEPTH: f32 = 7.0 / 1200.0;
+const VIB_RATE: f32 = 6.4;
+
+/// Peak output of one channel at full volume, chosen so a loud FM voice
+/// sits at about the level of a full-volume APU pulse
+const CHANNEL_LEVEL: f32 = 0.075;
+
+/// Frequency multipliers (x2, so the 1/2 setting stays an integer)
+const MULTIPLIER_X2: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+/// Key scale level: attenuation in dB at block 7 for the top four bits of
+/// the frequency, dropping 6dB per octave below that
+const KSL_DB: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25,
+    42.0,
+];
+
+/// The VRC7's built-in instruments (patch 0 is the user patch in $00-$07)
+const PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27], // Buzzy bell
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12], // Guitar
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12], // Wurly
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27], // Flute
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28], // Clarinet
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4], // Synth
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07], // Trumpet
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17], // Organ
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01], // Bells
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02], // Vibes
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12], // Vibraphone
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16], // Tutti
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02], // Fretless
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6], // Synth bass
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06], // Sweep
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    /// Held at the sustain level while the key is down (sustained patches)
+    SustainHold,
+    /// Fading at the release rate while the key is down (percussive patches)
+    Sustain,
+    Release,
+    Off,
+}
+
+impl EnvelopeState {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => EnvelopeState::Attack,
+            1 => EnvelopeState::Decay,
+            2 => EnvelopeState::SustainHold,
+            3 => EnvelopeState::Sustain,
+            4 => EnvelopeState::Release,
+            5 => EnvelopeState::Off,
+            _ => return None,
+        })
+    }
+}
+
+/// One operator's settings, decoded from a patch
+struct OperatorPatch {
+    tremolo: bool,
+    vibrato: bool,
+    /// Hold at the sustain level instead of fading while the key is down
+    sustained: bool,
+    /// Key scale rate: envelopes speed up more with pitch
+    ksr: bool,
+    multiplier: u8,
+    ksl: u8,
+    half_sine: bool,
+    attack: u8,
+    decay: u8,
+    sustain_level: u8,
+    release: u8,
+}
+
+impl OperatorPatch {
+    /// Operator `op` (0 = modulator, 1 = carrier) of an 8-byte patch
+    fn decode(patch: &[u8; 8], op: usize) -> Self {
+        let flags = patch[op];
+        Self {
+            tremolo: (flags & 0x80) != 0,
+            vibrato: (flags & 0x40) != 0,
+            sustained: (flags & 0x20) != 0,
+            ksr: (flags & 0x10) != 0,
+            multiplier: flags & 0x0F,
+            ksl: patch[2 + op] >> 6,
+            half_sine: (patch[3] & (0x08 << op)) != 0,
+            attack: patch[4 + op] >> 4,
+            decay: patch[4 + op] & 0x0F,
+            sustain_level: patch[6 + op] >> 4,
+            release: patch[6 + op] & 0x0F,
+        }
+    }
+}
+
+/// Phase and envelope of one operator
+struct Operator {
+    /// Position in the waveform, in cycles
+    phase: f32,
+    eg_state: EnvelopeState,
+    eg_counter: u32,
+    /// Last two outputs, for modulator feedback
+    output: [f32; 2],
+}
+
+impl Operator {
+    fn new() -> Self {
+        Self {
+            phase: 0.0,
+            eg_state: EnvelopeState::Off,
+            eg_counter: 0,
+            output: [0.0; 2],
+        }
+    }
+
+    fn key_on(&mut self) {
+        self.phase = 0.0;
+        self.eg_state = EnvelopeState::Attack;
+        self.eg_counter = 0;
+    }
+
+    fn key_off(&mut self) {
+        if self.eg_state != EnvelopeState::Off {
+            // Carry on from the current level, which in attack is on a
+            // different curve from the other stages
+            if self.eg_state == EnvelopeState::Attack {
+                self.eg_counter = (attack_curve(self.eg_counter >> (EG_DP_BITS - EG_BITS)) as u32)
+                    << (EG_DP_BITS - EG_BITS);
+            }
+            self.eg_state = EnvelopeState::Release;
+        }
+    }
+
+    /// Advance the envelope by one sample and return its attenuation in
+    /// envelope steps. `rks` is the key scale rate offset; `sustain` is the
+    /// channel's sustain flag.
+    fn clock_envelope(&mut self, patch: &OperatorPatch, rks: u32, sustain: bool) -> u32 {
+        let level = self.eg_counter >> (EG_DP_BITS - EG_BITS);
+        match self.eg_state {
+            EnvelopeState::Attack => {
+                let attenuation = attack_curve(level);
+                self.eg_counter += attack_rate(patch.attack, rks);
+                if self.eg_counter >= EG_DP_WIDTH || patch.attack == 15 {
+                    self.eg_counter = 0;
+                    self.eg_state = EnvelopeState::Decay;
+                    return 0;
+                }
+                attenuation as u32
+            }
+            EnvelopeState::Decay => {
+                self.eg_counter += decay_rate(patch.decay, rks);
+                let sustain_level = sustain_level(patch.sustain_level);
+                if self.eg_counter >= sustain_level {
+                    self.eg_counter = sustain_level;
+                    self.eg_state = if patch.sustained {
+                        EnvelopeState::SustainHold
+                    } else {
+                        EnvelopeState::Sustain
+                    };
+                }
+                level
+            }
+            EnvelopeState::SustainHold => {
+                if !patch.sustained {
+                    self.eg_state = EnvelopeState::Sustain;
+                }
+                level
+            }
+            EnvelopeState::Sustain | EnvelopeState::Release => {
+                let rate = if self.eg_state == EnvelopeState::Sustain {
+                    patch.release
+                } else if sustain {
+                    5
+                } else if patch.sustained {
+                    patch.release
+                } else {
+                    7
+                };
+                self.eg_counter += decay_rate(rate, rks);
+                if self.eg_counter >= EG_DP_WIDTH {
+                    self.eg_state = EnvelopeState::Off;
+                }
+                level
+            }
+            EnvelopeState::Off => (1 << EG_BITS) - 1,
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.f32(self.phase);
+        w.u8(self.eg_state as u8);
+        w.u32(self.eg_counter);
+        w.f32(self.output[0]);
+        w.f32(self.output[1]);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.phase = r.f32()?;
+        self.eg_state = EnvelopeState::from_u8(r.u8()?)
+            .ok_or(SaveStateError::Corrupt("invalid VRC7 envelope state"))?;
+        self.eg_counter = r.u32()?;
+        // Only a finished release leaves the counter past full attenuation
+        if self.eg_state != EnvelopeState::Off && self.eg_counter > EG_DP_WIDTH {
+            return Err(SaveStateError::Corrupt("VRC7 envelope counter"));
+        }
+        self.output = [r.f32()?, r.f32()?];
+        Ok(())
+    }
+}
+
+/// Attenuation during attack: the counter rises linearly, the level follows
+/// an exponential curve
+fn attack_curve(level: u32) -> f32 {
+    let max = ((1 << EG_BITS) - 1) as f32;
+    if level == 0 {
+        max
+    } else {
+        max - max * (level as f32).ln() / max.ln()
+    }
+}
+
+fn attack_rate(attack: u8, rks: u32) -> u32 {
+    match attack {
+        0 | 15 => 0,
+        _ => {
+            let rm = (attack as u32 + (rks >> 2)).min(15);
+            (3 * ((rks & 3) + 4)) << (rm + 1)
+        }
+    }
+}
+
+fn decay_rate(decay: u8, rks: u32) -> u32 {
+    match decay {
+        0 => 0,
+        _ => {
+            let rm = (decay as u32 + (rks >> 2)).min(15);
+            ((rks & 3) + 4) << (rm - 1)
+        }
+    }
+}
+
+/// Sustain level in envelope counter units: 3dB per step, 15 = 48dB
+fn sustain_level(level: u8) -> u32 {
+    let db = if level == 15 {
+        48.0
+    } else {
+        level as f32 * 3.0
+    };
+    ((db / EG_STEP_DB) as u32) << (EG_DP_BITS - EG_BITS)
+}
+
+pub(super) struct Opll {
+    /// Register being addressed by the next data write
+    address: u8,
+    /// $00-$07 user patch, $10-$15 frequency low, $20-$25 key/block/freq
+    /// high, $30-$35 instrument/volume
+    registers: [u8; 0x40],
+    operators: [[Operator; 2]; 6],
+    cycle: u8,
+    am_phase: f32,
+    vib_phase: f32,
+    output: f32,
+    sine: Vec<f32>,
+    /// Linear gain for each attenuation in envelope steps
+    gain: Vec<f32>,
+}
+
+impl Opll {
+    pub(super) fn new() -> Self {
+        let sine = (0..SINE_SIZE)
+            .map(|i| (i as f32 / SINE_SIZE as f32 * std::f32::consts::TAU).sin())
+            .collect();
+        let gain = (0..MAX_ATTENUATION)
+            .map(|steps| 10f32.powf(-(steps as f32) * EG_STEP_DB / 20.0))
+            .collect();
+        Self {
+            address: 0,
+            registers: [0; 0x40],
+            operators: std::array::from_fn(|_| [Operator::new(), Operator::new()]),
+            cycle: 0,
+            am_phase: 0.0,
+            vib_phase: 0.0,
+            output: 0.0,
+            sine,
+            gain,
+        }
+    }
+
+    /// Silence every channel and clear the registers
+    pub(super) fn reset(&mut self) {
+        self.address = 0;
+        self.registers = [0; 0x40];
+        self.operators = std::array::from_fn(|_| [Operator::new(), Operator::new()]);
+        self.output = 0.0;
+    }
+
+    pub(super) fn write_address(&mut self, value: u8) {
+        self.address = value;
+    }
+
+    pub(super) fn write_data(&mut self, value: u8) {
+        let reg = self.address as usize;
+        if reg >= self.registers.len() {
+            return;
+        }
+        let old = self.registers[reg];
+        self.registers[reg] = value;
+
+        // Key on/off for channels 0-5
+        if (0x20..=0x25).contains(&reg) {
+            let channel = reg - 0x20;
+            let was_on = (old & 0x10) != 0;
+            let is_on = (value & 0x10) != 0;
+            if is_on && !was_on {
+                for op in &mut self.operators[channel] {
+                    op.key_on();
+                }
+            } else if was_on && !is_on {
+                for op in &mut self.operators[channel] {
+                    op.key_off();
+                }
+            }
+        }
+    }
+
+    /// One CPU cycle
+    pub(super) fn clock(&mut self) {
+        self.cycle += 1;
+        if self.cycle >= CYCLES_PER_SAMPLE {
+            self.cycle = 0;
+            self.output = self.render_sample();
+        }
+    }
+
+    pub(super) fn output(&self) -> f32 {
+        self.output
+    }
+
+    fn patch(&self, channel: usize) -> [u8; 8] {
+        match self.registers[0x30 + channel] >> 4 {
+            0 => self.registers[0..8].try_into().unwrap_or([0; 8]),
+            instrument => PATCHES[instrument as usize - 1],
+        }
+    }
+
+    fn render_sample(&mut self) -> f32 {
+        self.am_phase = (self.am_phase + AM_RATE / SAMPLE_RATE).fract();
+        self.vib_phase = (self.vib_phase + VIB_RATE / SAMPLE_RATE).fract();
+        // Triangle LFOs
+        let am_steps = (1.0 - (self.am_phase * 2.0 - 1.0).abs()) * AM_DEPTH_STEPS;
+        let vib = (self.vib_phase * std::f32::consts::TAU).sin() * VIB_DEPTH;
+
+        let mut mix = 0.0;
+        for channel in 0..6 {
+            mix += self.render_channel(channel, am_steps as u32, vib);
+        }
+        mix * CHANNEL_LEVEL
+    }
+
+    fn render_channel(&mut self, channel: usize, am_steps: u32, vib: f32) -> f32 {
+        let patch = self.patch(channel);
+        let fnum = self.registers[0x10 + channel] as u32
+            | ((self.registers[0x20 + channel] as u32 & 0x01) << 8);
+        let block = (self.registers[0x20 + channel] >> 1) & 0x07;
+        let sustain = (self.registers[0x20 + channel] & 0x20) != 0;
+        let volume = self.registers[0x30 + channel] & 0x0F;
+        let total_level = patch[2] & 0x3F;
+        let feedback = patch[3] & 0x07;
+
+        let ksl_db = (KSL_DB[(fnum >> 5) as usize] - 6.0 * (7 - block) as f32).max(0.0);
+        // Envelope rate scaling from the block and top frequency bit
+        let key_code = ((block as u32) << 1) | (fnum >> 8);
+
+        let mut carrier_input = 0.0;
+        let mut out = 0.0;
+        for op_index in 0..2 {
+            let op_patch = OperatorPatch::decode(&patch, op_index);
+            let rks = if op_patch.ksr {
+                key_code
+            } else {
+                key_code >> 2
+            };
+            let op = &mut self.operators[channel][op_index];
+            let envelope = op.clock_envelope(&op_patch, rks, sustain);
+
+            // Phase: 19-bit accumulator stepped by fnum << block times the
+            // multiplier, here in whole cycles
+            let mut step = ((fnum << block) * MULTIPLIER_X2[op_patch.multiplier as usize]) as f32
+                / 2.0
+                / (1 << 19) as f32;
+            if op_patch.vibrato {
+                step *= 1.0 + vib;
+            }
+            op.phase = (op.phase + step).fract();
+
+            let mut attenuation = envelope;
+            attenuation += match op_patch.ksl {
+                0 => 0,
+                ksl => (ksl_db / EG_STEP_DB) as u32 >> (3 - ksl),
+            };
+            if op_patch.tremolo {
+                attenuation += am_steps;
+            }
+            let phase_offset = if op_index == 0 {
+                attenuation += total_level as u32 * 2;
+                // Feedback of the modulator's last two outputs, up to +/-4pi
+                if feedback > 0 {
+                    (op.output[0] + op.output[1]) / 2.0 * 2.0 / (1 << (7 - feedback)) as f32
+                } else {
+                    0.0
+                }
+            } else {
+                attenuation += volume as u32 * 8;
+                // Modulator at full level moves the carrier by +/-4pi
+                carrier_input * 2.0
+            };
+
+            let mut value = 0.0;
+            if op.eg_state != EnvelopeState::Off && (attenuation as usize) < MAX_ATTENUATION {
+                let position = (op.phase + phase_offset).rem_euclid(1.0);
+                let index = (position * SINE_SIZE as f32) as usize % SINE_SIZE;
+                value = self.sine[index];
+                if op_patch.half_sine && value < 0.0 {
+                    value = 0.0;
+                }
+                value *= self.gain[attenuation as usize];
+            }
+            op.output = [value, op.output[0]];
+
+            if op_index == 0 {
+                carrier_input = value;
+            } else {
+                out = value;
+            }
+        }
+        out
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.address);
+        w.bytes(&self.registers);
+        for channel in &self.operators {
+            for op in channel {
+                op.save_state(w);
+            }
+        }
+        w.u8(self.cycle);
+        w.f32(self.am_phase);
+        w.f32(self.vib_phase);
+        w.f32(self.output);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.address = r.u8()?;
+        r.bytes_into(&mut self.registers)?;
+        for channel in &mut self.operators {
+            for op in channel {
+                op.load_state(r)?;
+            }
+        }
+        self.cycle = r.u8()? % CYCLES_PER_SAMPLE;
+        self.am_phase = r.f32()?;
+        self.vib_phase = r.f32()?;
+        self.output = r.f32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sustain_level_steps_are_3db() {
+        let step = 1 << (EG_DP_BITS - EG_BITS);
+        assert_eq!(sustain_level(0), 0);
+        assert_eq!(sustain_level(1), 8 * step);
+        assert_eq!(sustain_level(14), 112 * step);
+        assert_eq!(sustain_level(15), EG_DP_WIDTH);
+    }
+
+    fn operator_state(eg_state: EnvelopeState, eg_counter: u32) -> Vec<u8> {
+        let mut op = Operator::new();
+        op.eg_state = eg_state;
+        op.eg_counter = eg_counter;
+        let mut w = StateWriter::new();
+        op.save_state(&mut w);
+        w.into_inner()
+    }
+
+    #[test]
+    fn load_state_rejects_envelope_counter_past_full_attenuation() {
+        let state = operator_state(EnvelopeState::Decay, EG_DP_WIDTH);
+        let mut op = Operator::new();
+        assert!(op.load_state(&mut StateReader::new(&state)).is_ok());
+
+        let state = operator_state(EnvelopeState::Attack, u32::MAX);
+        assert!(matches!(
+            op.load_state(&mut StateReader::new(&state)),
+            Err(SaveStateError::Corrupt(_))
+        ));
+
+        // A released operator stops where its last step took it
+        let state = operator_state(EnvelopeState::Off, EG_DP_WIDTH + 1000);
+        assert!(op.load_state(&mut StateReader::new(&state)).is_ok());
+    }
+}
@@ -0,0 +1,166 @@
+//! UNROM 512 (mapper 30): UxROM-style 16KB PRG banking over up to 512KB,
+//! four 8KB CHR-RAM pages, and optionally a self-flashable PRG chip
+//!
+//! One register, `MCCPPPPP`: the PRG bank at $8000 (the last bank is fixed
+//! at $C000), the CHR-RAM page, and the nametable page on one-screen
+//! boards. Header byte 6 bits 3 and 0 give the nametable wiring:
+//! horizontal, vertical, one-screen switched by bit 7, or four-screen from
+//! the last 8KB of CHR-RAM.
+//!
+//! Boards with the battery bit set carry an SST39SF040 flash that games
+//! write saves into: writes to $8000-$BFFF go to the flash and the
+//! register moves to $C000-$FFFF. The other boards have bus conflicts.
+//!
+//! Reference: https://www.nesdev.org/wiki/UNROM_512
+
+use super::flash::Flash;
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Nametables {
+    Fixed(Mirroring),
+    /// One-screen, page chosen by bit 7 of the register
+    OneScreen,
+    /// Four-screen, from the last 8KB of CHR-RAM
+    FourScreen,
+}
+
+pub struct Unrom512Mapper {
+    nametables: Nametables,
+    has_chr_ram: bool,
+    flash: Option<Flash>,
+    register: u8,
+}
+
+impl Unrom512Mapper {
+    /// `mirroring_bit` is header byte 6 bit 0, which picks between the two
+    /// wirings behind the four-screen bit
+    pub fn new(
+        mirroring: Mirroring,
+        mirroring_bit: bool,
+        has_chr_ram: bool,
+        flashable: bool,
+    ) -> Self {
+        let nametables = match mirroring {
+            Mirroring::FourScreen if mirroring_bit => Nametables::FourScreen,
+            Mirroring::FourScreen => Nametables::OneScreen,
+            mirroring => Nametables::Fixed(mirroring),
+        };
+        Self {
+            nametables,
+            has_chr_ram,
+            flash: flashable.then(Flash::new),
+            register: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16, prg_rom_len: usize) -> usize {
+        let bank = match addr {
+            0x8000..=0xBFFF => (self.register & 0x1F) as usize,
+            _ => (prg_rom_len / 0x4000).saturating_sub(1),
+        };
+        (bank * 0x4000 + (addr as usize & 0x3FFF)) % prg_rom_len
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        ((self.register >> 5) & 0x03) as usize * 0x2000 + (addr as usize & 0x1FFF)
+    }
+
+    fn four_screen_offset(addr: u16, chr_ram_len: usize) -> usize {
+        chr_ram_len.saturating_sub(0x2000) + (addr as usize & 0x1FFF)
+    }
+}
+
+impl Mapper for Unrom512Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = self.prg_offset(addr, prg_rom.len());
+        match self.flash.as_ref().and_then(|flash| flash.read(offset)) {
+            Some(id) => id,
+            None => prg_rom[offset],
+        }
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        self.register = value;
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram_write(addr, value, &mut mem.prg_ram),
+            0x8000..=0xBFFF if self.flash.is_some() => {
+                let offset = self.prg_offset(addr, mem.prg_rom.len());
+                if let Some(flash) = &mut self.flash {
+                    flash.write(offset, value, &mut mem.prg_rom);
+                }
+            }
+            0x8000..=0xFFFF => {
+                let value = if self.has_bus_conflicts() {
+                    value & self.prg_rom_read(addr, &mem.prg_rom)
+                } else {
+                    value
+                };
+                self.register_write(addr, value);
+            }
+            _ => {}
+        }
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        self.flash.is_none()
+    }
+
+    fn has_flash(&self) -> bool {
+        self.flash.is_some()
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn nametable_read(&mut self, addr: u16, mem: &CartridgeMemory) -> Option<u8> {
+        if self.nametables != Nametables::FourScreen || mem.chr_ram.is_empty() {
+            return None;
+        }
+        let len = mem.chr_ram.len();
+        Some(mem.chr_ram[Self::four_screen_offset(addr, len) % len])
+    }
+
+    fn nametable_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) -> bool {
+        if self.nametables != Nametables::FourScreen || mem.chr_ram.is_empty() {
+            return false;
+        }
+        let len = mem.chr_ram.len();
+        mem.chr_ram[Self::four_screen_offset(addr, len) % len] = value;
+        true
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.nametables {
+            Nametables::Fixed(mirroring) => mirroring,
+            Nametables::OneScreen if (self.register & 0x80) != 0 => Mirroring::OneScreenUpper,
+            Nametables::OneScreen => Mirroring::OneScreenLower,
+            Nametables::FourScreen => Mirroring::FourScreen,
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.register);
+        if let Some(flash) = &self.flash {
+            flash.save_state(w);
+        }
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.register = r.u8()?;
+        if let Some(flash) = &mut self.flash {
+            flash.load_state(r)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! UxROM (mapper 2): switchable 16KB PRG bank at $8000, last bank fixed
+
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct UxromMapper {
+    mirroring: Mirroring,
+    has_chr_ram: bool,
+    // Licensed boards leave PRG-ROM enabled on writes
+    bus_conflicts: bool,
+    prg_bank: u8,  // Current bank selected for 0x8000-0xBFFF
+    prg_banks: u8, // Total number of PRG banks (16KB each)
+}
+
+impl UxromMapper {
+    pub fn new(
+        mirroring: Mirroring,
+        has_chr_ram: bool,
+        bus_conflicts: bool,
+        prg_rom_size: usize,
+    ) -> Self {
+        let prg_banks = (prg_rom_size / 0x4000) as u8; // Number of 16KB banks
+        Self {
+            mirroring,
+            has_chr_ram,
+            bus_conflicts,
+            prg_bank: 0, // Start with first bank
+            prg_banks,
+        }
+    }
+}
+
+impl Mapper for UxromMapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        if addr < 0xC000 {
+            // First 16KB: bank-switchable (0x8000-0xBFFF)
+            let bank_offset = (self.prg_bank as usize * 0x4000) % prg_rom.len();
+            let addr_in_bank = (addr - 0x8000) as usize;
+            prg_rom[(bank_offset + addr_in_bank) % prg_rom.len()]
+        } else {
+            // Last 16KB: fixed to last bank (0xC000-0xFFFF)
+            let last_bank_start = ((self.prg_banks - 1) as usize * 0x4000) % prg_rom.len();
+            let addr_in_bank = (addr - 0xC000) as usize;
+            prg_rom[(last_bank_start + addr_in_bank) % prg_rom.len()]
+        }
+    }
+
+    fn register_write(&mut self, _addr: u16, value: u8) {
+        // Writing to 0x8000-0xFFFF selects the PRG bank for 0x8000-0xBFFF
+        // From C reference: mapper->PRG_ptrs[0] = mapper->PRG_ROM + (value & 0x7) * 0x4000;
+        // Match C reference: use 3 bits (0-7), modulo by available banks
+        let selected_bank = (value & 0x07) as usize;
+        self.prg_bank = (selected_bank % self.prg_banks as usize) as u8;
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        // CHR smaller than 8KB is mirrored
+        chr_read(mem, self.has_chr_ram, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, addr as usize & 0x1FFF, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_bank = r.u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Konami VRC2 and VRC4 (mappers 21, 22, 23 and 25): 8KB PRG and 1KB CHR
+//! banking, plus the VRC IRQ counter on the VRC4
+//!
+//! The boards differ mainly in which CPU address lines reach the chip's two
+//! register-select pins. Each is decoded into a pair of address masks here;
+//! headers without a submapper get both candidate lines ORed together, which
+//! works because a game only ever uses its own.
+//!
+//! Reference: https://www.nesdev.org/wiki/VRC2_and_VRC4
+
+use super::vrc_irq::VrcIrq;
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct Vrc24Mapper {
+    has_chr_ram: bool,
+    is_vrc4: bool,
+    // CPU address lines wired to the chip's A0 and A1
+    a0_lines: u16,
+    a1_lines: u16,
+    // VRC2a ignores the lowest bit of its CHR banks
+    chr_shift: u8,
+    prg_banks: [u8; 2], // $8000 (or $C000), $A000
+    chr_banks: [u16; 8],
+    mirroring: u8,
+    // VRC4 $9002 bit 1: the first PRG bank moves to $C000
+    prg_swap: bool,
+    // VRC2 boards without PRG-RAM keep one bit at $6000-$6FFF, meant for
+    // an EEPROM that was never fitted; some games test it
+    microwire_latch: u8,
+    irq: VrcIrq,
+}
+
+impl Vrc24Mapper {
+    pub fn new(mapper: u16, submapper: u8, has_chr_ram: bool) -> Self {
+        // (VRC4, A0 lines, A1 lines)
+        let (is_vrc4, a0_lines, a1_lines) = match (mapper, submapper) {
+            (21, 1) => (true, 0x02, 0x04), // VRC4a
+            (21, 2) => (true, 0x40, 0x80), // VRC4c
+            (21, _) => (true, 0x42, 0x84),
+            (22, _) => (false, 0x02, 0x01), // VRC2a
+            (23, 1) => (true, 0x01, 0x02),  // VRC4f
+            (23, 2) => (true, 0x04, 0x08),  // VRC4e
+            (23, 3) => (false, 0x01, 0x02), // VRC2b
+            (23, _) => (true, 0x05, 0x0A),
+            (25, 1) => (true, 0x02, 0x01),  // VRC4b
+            (25, 2) => (true, 0x08, 0x04),  // VRC4d
+            (25, 3) => (false, 0x02, 0x01), // VRC2c
+            (_, _) => (true, 0x0A, 0x05),
+        };
+        Self {
+            has_chr_ram,
+            is_vrc4,
+            a0_lines,
+            a1_lines,
+            chr_shift: if mapper == 22 { 1 } else { 0 },
+            prg_banks: [0; 2],
+            chr_banks: [0; 8],
+            mirroring: 0,
+            prg_swap: false,
+            microwire_latch: 0,
+            irq: VrcIrq::new(),
+        }
+    }
+
+    /// Register address with the board's select lines moved to A0/A1
+    fn register(&self, addr: u16) -> u16 {
+        let a0 = ((addr & self.a0_lines) != 0) as u16;
+        let a1 = ((addr & self.a1_lines) != 0) as u16;
+        (addr & 0xF000) | (a1 << 1) | a0
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 10) & 0x07] >> self.chr_shift;
+        bank as usize * 0x400 + (addr as usize & 0x03FF)
+    }
+
+    fn write_chr_bank(&mut self, reg: u16, value: u8) {
+        let index = ((reg as usize >> 12) - 0xB) * 2 + ((reg as usize >> 1) & 0x01);
+        let bank = &mut self.chr_banks[index];
+        if (reg & 0x01) == 0 {
+            *bank = (*bank & !0x0F) | (value & 0x0F) as u16;
+        } else {
+            let high_mask = if self.is_vrc4 { 0x1F } else { 0x0F };
+            *bank = (*bank & 0x0F) | (((value & high_mask) as u16) << 4);
+        }
+    }
+
+    fn uses_microwire(&self, addr: u16, prg_ram: &[u8]) -> bool {
+        !self.is_vrc4 && prg_ram.is_empty() && addr < 0x7000
+    }
+}
+
+impl Mapper for Vrc24Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let second_last = (prg_rom.len() / 0x2000).saturating_sub(2);
+        let bank = match addr {
+            0x8000..=0x9FFF if self.prg_swap => second_last,
+            0x8000..=0x9FFF => self.prg_banks[0] as usize,
+            0xA000..=0xBFFF => self.prg_banks[1] as usize,
+            0xC000..=0xDFFF if self.prg_swap => self.prg_banks[0] as usize,
+            0xC000..=0xDFFF => second_last,
+            _ => second_last + 1,
+        };
+        prg_rom[(bank * 0x2000 + (addr as usize & 0x1FFF)) % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        let reg = self.register(addr);
+        match reg {
+            0x8000..=0x8003 => self.prg_banks[0] = value & 0x1F,
+            0x9000..=0x9001 => {
+                self.mirroring = if self.is_vrc4 {
+                    value & 0x03
+                } else {
+                    value & 0x01
+                }
+            }
+            // Bit 0 is meant to enable PRG-RAM, but games don't rely on it
+            0x9002..=0x9003 if self.is_vrc4 => self.prg_swap = (value & 0x02) != 0,
+            0xA000..=0xA003 => self.prg_banks[1] = value & 0x1F,
+            0xB000..=0xE003 => self.write_chr_bank(reg, value),
+            0xF000 if self.is_vrc4 => self.irq.write_latch_low(value),
+            0xF001 if self.is_vrc4 => self.irq.write_latch_high(value),
+            0xF002 if self.is_vrc4 => self.irq.write_control(value),
+            0xF003 if self.is_vrc4 => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if self.uses_microwire(addr, prg_ram) {
+            // Only bit 0 is driven; the rest is the open bus, which after an
+            // absolute read is the address's high byte
+            return Some(((addr >> 8) as u8 & 0xFE) | self.microwire_latch);
+        }
+        if prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[(addr as usize - 0x6000) % prg_ram.len()])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if self.uses_microwire(addr, prg_ram) {
+            self.microwire_latch = value & 0x01;
+        } else if !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.is_vrc4 {
+            self.irq.clock();
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::OneScreenLower,
+            _ => Mirroring::OneScreenUpper,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.prg_banks);
+        for &bank in &self.chr_banks {
+            w.u16(bank);
+        }
+        w.u8(self.mirroring);
+        w.bool(self.prg_swap);
+        w.u8(self.microwire_latch);
+        self.irq.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.bytes_into(&mut self.prg_banks)?;
+        for bank in &mut self.chr_banks {
+            *bank = r.u16()? & 0x1FF;
+        }
+        self.mirroring = r.u8()? & 0x03;
+        self.prg_swap = r.bool()?;
+        self.microwire_latch = r.u8()? & 0x01;
+        self.irq.load_state(r)
+    }
+}
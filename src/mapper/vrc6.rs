@@ -0,0 +1,383 @@
+//! Konami VRC6 (mappers 24 and 26): 16KB + 8KB PRG and 1KB CHR banking, a
+//! CPU-cycle IRQ counter, and expansion audio (two pulses and a sawtooth)
+
+use super::vrc_irq::VrcIrq;
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// Output level of one step of the VRC6's DAC. A full-volume VRC6 pulse is
+/// about as loud as a full-volume APU pulse on its own.
+const OUTPUT_STEP: f32 = 95.52 / (8128.0 / 15.0 + 100.0) / 15.0;
+
+// VRC6a (Mapper 24) - Akumajou Densetsu; VRC6b (Mapper 26) - Madara, Esper Dream 2
+pub struct Vrc6Mapper {
+    has_chr_ram: bool,
+    // VRC6b boards wire CPU A0 and A1 the other way round
+    swap_a0_a1: bool,
+    prg_bank_16k: u8, // $8000-$BFFF
+    prg_bank_8k: u8,  // $C000-$DFFF
+    chr_banks: [u8; 8],
+    // $B003: bits 0-1 CHR layout, bits 2-3 mirroring, bit 7 PRG-RAM enable.
+    // The CHR-ROM nametable modes (bits 4-5) aren't used by any game.
+    banking_control: u8,
+    irq: VrcIrq,
+    audio: Vrc6Audio,
+}
+
+impl Vrc6Mapper {
+    pub fn new(has_chr_ram: bool, swap_a0_a1: bool) -> Self {
+        Self {
+            has_chr_ram,
+            swap_a0_a1,
+            prg_bank_16k: 0,
+            prg_bank_8k: 0,
+            chr_banks: [0; 8],
+            banking_control: 0,
+            irq: VrcIrq::new(),
+            audio: Vrc6Audio::new(),
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let slot = (addr as usize >> 10) & 0x07;
+        let a10 = slot & 0x01;
+        let bank = match self.banking_control & 0x03 {
+            0 => self.chr_banks[slot] as usize,
+            // Four 2KB banks
+            1 => (self.chr_banks[slot >> 1] as usize & !0x01) | a10,
+            // 1KB banks below $1000, 2KB banks above
+            _ if slot < 4 => self.chr_banks[slot] as usize,
+            _ => (self.chr_banks[4 + ((slot - 4) >> 1)] as usize & !0x01) | a10,
+        };
+        bank * 0x400 + (addr as usize & 0x03FF)
+    }
+}
+
+impl Mapper for Vrc6Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = match addr {
+            0x8000..=0xBFFF => self.prg_bank_16k as usize * 0x4000 + (addr as usize & 0x3FFF),
+            0xC000..=0xDFFF => self.prg_bank_8k as usize * 0x2000 + (addr as usize & 0x1FFF),
+            _ => prg_rom.len().saturating_sub(0x2000) + (addr as usize & 0x1FFF),
+        };
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        let addr = if self.swap_a0_a1 {
+            (addr & !0x03) | ((addr & 0x01) << 1) | ((addr & 0x02) >> 1)
+        } else {
+            addr
+        };
+        match addr & 0xF003 {
+            0x8000..=0x8003 => self.prg_bank_16k = value & 0x0F,
+            0x9000..=0x9003 | 0xA000..=0xA002 | 0xB000..=0xB002 => {
+                self.audio.write_register(addr & 0xF003, value)
+            }
+            0xB003 => self.banking_control = value,
+            0xC000..=0xC003 => self.prg_bank_8k = value & 0x1F,
+            0xD000..=0xD003 => self.chr_banks[(addr & 0x03) as usize] = value,
+            0xE000..=0xE003 => self.chr_banks[4 + (addr & 0x03) as usize] = value,
+            0xF000 => self.irq.write_latch(value),
+            0xF001 => self.irq.write_control(value),
+            0xF002 => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if (self.banking_control & 0x80) == 0 || prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[(addr as usize - 0x6000) % prg_ram.len()])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if (self.banking_control & 0x80) != 0 && !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        self.audio.clock();
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match (self.banking_control >> 2) & 0x03 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::OneScreenLower,
+            _ => Mirroring::OneScreenUpper,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.prg_bank_16k);
+        w.u8(self.prg_bank_8k);
+        w.bytes(&self.chr_banks);
+        w.u8(self.banking_control);
+        self.irq.save_state(w);
+        self.audio.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_bank_16k = r.u8()?;
+        self.prg_bank_8k = r.u8()?;
+        r.bytes_into(&mut self.chr_banks)?;
+        self.banking_control = r.u8()?;
+        self.irq.load_state(r)?;
+        self.audio.load_state(r)
+    }
+}
+
+/// VRC6 pulse: 16-step sequencer with a selectable duty and 4-bit volume
+struct Vrc6Pulse {
+    volume: u8,
+    duty: u8,
+    // Ignore the duty and output the volume constantly (used for samples)
+    constant: bool,
+    enabled: bool,
+    period: u16,
+    counter: u16,
+    step: u8,
+}
+
+impl Vrc6Pulse {
+    fn new() -> Self {
+        Self {
+            volume: 0,
+            duty: 0,
+            constant: false,
+            enabled: false,
+            period: 0,
+            counter: 0,
+            step: 15,
+        }
+    }
+
+    fn write_register(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.constant = (value & 0x80) != 0;
+                self.duty = (value >> 4) & 0x07;
+                self.volume = value & 0x0F;
+            }
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | (((value & 0x0F) as u16) << 8);
+                self.enabled = (value & 0x80) != 0;
+                if !self.enabled {
+                    self.step = 15;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.counter == 0 {
+            self.counter = self.period >> shift;
+            self.step = self.step.wrapping_sub(1) & 0x0F;
+        } else {
+            self.counter -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.enabled && (self.constant || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.volume);
+        w.u8(self.duty);
+        w.bool(self.constant);
+        w.bool(self.enabled);
+        w.u16(self.period);
+        w.u16(self.counter);
+        w.u8(self.step);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.volume = r.u8()? & 0x0F;
+        self.duty = r.u8()? & 0x07;
+        self.constant = r.bool()?;
+        self.enabled = r.bool()?;
+        self.period = r.u16()?;
+        self.counter = r.u16()?;
+        self.step = r.u8()? & 0x0F;
+        Ok(())
+    }
+}
+
+/// VRC6 sawtooth: an accumulator that grows by `rate` every other clock
+/// and resets every 14 clocks
+struct Vrc6Saw {
+    rate: u8,
+    enabled: bool,
+    period: u16,
+    counter: u16,
+    step: u8,
+    accumulator: u8,
+}
+
+impl Vrc6Saw {
+    fn new() -> Self {
+        Self {
+            rate: 0,
+            enabled: false,
+            period: 0,
+            counter: 0,
+            step: 0,
+            accumulator: 0,
+        }
+    }
+
+    fn write_register(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => self.rate = value & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | (((value & 0x0F) as u16) << 8);
+                self.enabled = (value & 0x80) != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.counter == 0 {
+            self.counter = self.period >> shift;
+            self.step += 1;
+            if self.step == 14 {
+                self.step = 0;
+                self.accumulator = 0;
+            } else if self.step.is_multiple_of(2) {
+                self.accumulator = self.accumulator.wrapping_add(self.rate);
+            }
+        } else {
+            self.counter -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.rate);
+        w.bool(self.enabled);
+        w.u16(self.period);
+        w.u16(self.counter);
+        w.u8(self.step);
+        w.u8(self.accumulator);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.rate = r.u8()? & 0x3F;
+        self.enabled = r.bool()?;
+        self.period = r.u16()?;
+        self.counter = r.u16()?;
+        self.step = r.u8()? % 14;
+        self.accumulator = r.u8()?;
+        Ok(())
+    }
+}
+
+/// Expansion audio at $9000-$B002
+struct Vrc6Audio {
+    pulses: [Vrc6Pulse; 2],
+    saw: Vrc6Saw,
+    // $9003: bit 0 halts every channel, bits 1-2 speed them up 16x / 256x
+    frequency_control: u8,
+}
+
+impl Vrc6Audio {
+    fn new() -> Self {
+        Self {
+            pulses: [Vrc6Pulse::new(), Vrc6Pulse::new()],
+            saw: Vrc6Saw::new(),
+            frequency_control: 0,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let reg = addr & 0x03;
+        match addr {
+            0x9003 => self.frequency_control = value & 0x07,
+            0x9000..=0x9002 => self.pulses[0].write_register(reg, value),
+            0xA000..=0xA002 => self.pulses[1].write_register(reg, value),
+            _ => self.saw.write_register(reg, value),
+        }
+    }
+
+    fn clock(&mut self) {
+        if (self.frequency_control & 0x01) != 0 {
+            return;
+        }
+        let shift = if (self.frequency_control & 0x04) != 0 {
+            8
+        } else if (self.frequency_control & 0x02) != 0 {
+            4
+        } else {
+            0
+        };
+        for pulse in &mut self.pulses {
+            pulse.clock(shift);
+        }
+        self.saw.clock(shift);
+    }
+
+    /// The three channels are summed by a linear 6-bit DAC
+    fn output(&self) -> f32 {
+        let sum = self.pulses[0].output() + self.pulses[1].output() + self.saw.output();
+        sum as f32 * OUTPUT_STEP
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        for pulse in &self.pulses {
+            pulse.save_state(w);
+        }
+        self.saw.save_state(w);
+        w.u8(self.frequency_control);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        for pulse in &mut self.pulses {
+            pulse.load_state(r)?;
+        }
+        self.saw.load_state(r)?;
+        self.frequency_control = r.u8()? & 0x07;
+        Ok(())
+    }
+}
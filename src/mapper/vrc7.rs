@@ -0,0 +1,168 @@
+//! Konami VRC7 (mapper 85): three 8KB PRG banks, eight 1KB CHR banks, the
+//! VRC IRQ counter, and a six-channel FM synthesizer
+
+use super::opll::Opll;
+use super::vrc_irq::VrcIrq;
+use super::{chr_read, chr_write, CartridgeMemory, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub struct Vrc7Mapper {
+    has_chr_ram: bool,
+    // CPU address lines selecting the second register of each pair
+    select_lines: u16,
+    prg_banks: [u8; 3], // $8000, $A000, $C000
+    chr_banks: [u8; 8],
+    // $E000: bits 0-1 mirroring, bit 6 silences and resets the audio,
+    // bit 7 PRG-RAM enable
+    control: u8,
+    irq: VrcIrq,
+    audio: Opll,
+}
+
+impl Vrc7Mapper {
+    pub fn new(submapper: u8, has_chr_ram: bool) -> Self {
+        // VRC7a (Lagrange Point) decodes it on A4; VRC7b (Tiny Toon
+        // Adventures 2) on A3, and has no audio. Without a submapper either
+        // line selects it.
+        let select_lines = match submapper {
+            1 => 0x08,
+            2 => 0x10,
+            _ => 0x18,
+        };
+        Self {
+            has_chr_ram,
+            select_lines,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            control: 0,
+            irq: VrcIrq::new(),
+            audio: Opll::new(),
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 10) & 0x07] as usize;
+        bank * 0x400 + (addr as usize & 0x03FF)
+    }
+}
+
+impl Mapper for Vrc7Mapper {
+    fn prg_rom_read(&self, addr: u16, prg_rom: &[u8]) -> u8 {
+        let offset = match addr {
+            0x8000..=0xDFFF => {
+                let bank = self.prg_banks[(addr as usize - 0x8000) >> 13] as usize;
+                bank * 0x2000 + (addr as usize & 0x1FFF)
+            }
+            _ => prg_rom.len().saturating_sub(0x2000) + (addr as usize & 0x1FFF),
+        };
+        prg_rom[offset % prg_rom.len()]
+    }
+
+    fn register_write(&mut self, addr: u16, value: u8) {
+        let high = (addr & self.select_lines) != 0;
+        match (addr & 0xF000, high) {
+            (0x8000, false) => self.prg_banks[0] = value & 0x3F,
+            (0x8000, true) => self.prg_banks[1] = value & 0x3F,
+            (0x9000, false) => self.prg_banks[2] = value & 0x3F,
+            (0x9000, true) if (addr & 0x0020) != 0 => self.audio.write_data(value),
+            (0x9000, true) => self.audio.write_address(value),
+            (0xA000..=0xD000, _) => {
+                let slot = (((addr & 0xF000) as usize - 0xA000) >> 11) | high as usize;
+                self.chr_banks[slot] = value;
+            }
+            (0xE000, false) => {
+                self.control = value;
+                if (value & 0x40) != 0 {
+                    self.audio.reset();
+                }
+            }
+            (0xE000, true) => self.irq.write_latch(value),
+            (0xF000, false) => self.irq.write_control(value),
+            (0xF000, true) => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn prg_ram_read(&self, addr: u16, prg_ram: &[u8]) -> Option<u8> {
+        if (self.control & 0x80) == 0 || prg_ram.is_empty() {
+            return None;
+        }
+        Some(prg_ram[(addr as usize - 0x6000) % prg_ram.len()])
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, value: u8, prg_ram: &mut [u8]) {
+        if (self.control & 0x80) != 0 && !prg_ram.is_empty() {
+            let len = prg_ram.len();
+            prg_ram[(addr as usize - 0x6000) % len] = value;
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        if (self.control & 0x40) == 0 {
+            self.audio.clock();
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, mem: &CartridgeMemory) -> u8 {
+        chr_read(mem, self.has_chr_ram, self.chr_offset(addr))
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, mem: &mut CartridgeMemory) {
+        chr_write(mem, self.has_chr_ram, self.chr_offset(addr), value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::OneScreenLower,
+            _ => Mirroring::OneScreenUpper,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn audio_output(&self) -> f32 {
+        if (self.control & 0x40) != 0 {
+            0.0
+        } else {
+            self.audio.output()
+        }
+    }
+
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.prg_banks);
+        w.bytes(&self.chr_banks);
+        w.u8(self.control);
+        self.irq.save_state(w);
+        self.audio.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.bytes_into(&mut self.prg_banks)?;
+        r.bytes_into(&mut self.chr_banks)?;
+        self.control = r.u8()?;
+        self.irq.load_state(r)?;
+        self.audio.load_state(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chr_select_ignores_undecoded_address_lines() {
+        // VRC7a: A4 picks the second register of each pair
+        let mut mapper = Vrc7Mapper::new(2, false);
+        mapper.register_write(0xA800, 0x11);
+        mapper.register_write(0xA810, 0x22);
+        mapper.register_write(0xC800, 0x33);
+        mapper.register_write(0xDFF0, 0x44);
+        assert_eq!(mapper.chr_banks, [0x11, 0x22, 0, 0, 0x33, 0, 0, 0x44]);
+    }
+}
@@ -0,0 +1,148 @@
+//! IRQ counter shared by Konami's VRC4, VRC6 and VRC7
+//!
+//! An 8-bit up-counter clocked by the CPU, either every cycle or once per
+//! scanline by a prescaler that approximates 341 PPU dots in CPU cycles
+//! (it counts down by 3 per cycle). The IRQ fires when the counter
+//! overflows, and the counter reloads from the latch.
+
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub(super) struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i32,
+    // Control register: re-enable after acknowledge, enable, cycle mode
+    enable_after_ack: bool,
+    enabled: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub(super) fn new() -> Self {
+        Self {
+            latch: 0,
+            counter: 0,
+            prescaler: 341,
+            enable_after_ack: false,
+            enabled: false,
+            cycle_mode: false,
+            pending: false,
+        }
+    }
+
+    pub(super) fn write_latch(&mut self, value: u8) {
+        self.latch = value;
+    }
+
+    /// VRC4 writes the latch a nibble at a time
+    pub(super) fn write_latch_low(&mut self, value: u8) {
+        self.latch = (self.latch & 0xF0) | (value & 0x0F);
+    }
+
+    pub(super) fn write_latch_high(&mut self, value: u8) {
+        self.latch = (self.latch & 0x0F) | (value << 4);
+    }
+
+    pub(super) fn write_control(&mut self, value: u8) {
+        self.enable_after_ack = (value & 0x01) != 0;
+        self.enabled = (value & 0x02) != 0;
+        self.cycle_mode = (value & 0x04) != 0;
+        self.pending = false;
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = 341;
+        }
+    }
+
+    pub(super) fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enable_after_ack;
+    }
+
+    /// One CPU cycle
+    pub(super) fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        if self.cycle_mode {
+            self.clock_counter();
+        } else {
+            self.prescaler -= 3;
+            if self.prescaler <= 0 {
+                self.prescaler += 341;
+                self.clock_counter();
+            }
+        }
+    }
+
+    fn clock_counter(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+
+    pub(super) fn pending(&self) -> bool {
+        self.pending
+    }
+
+    pub(super) fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.latch);
+        w.u8(self.counter);
+        w.i32(self.prescaler);
+        w.bool(self.enable_after_ack);
+        w.bool(self.enabled);
+        w.bool(self.cycle_mode);
+        w.bool(self.pending);
+    }
+
+    pub(super) fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.latch = r.u8()?;
+        self.counter = r.u8()?;
+        self.prescaler = r.i32()?;
+        if !(0..=341).contains(&self.prescaler) {
+            return Err(SaveStateError::Corrupt("VRC IRQ prescaler"));
+        }
+        self.enable_after_ack = r.bool()?;
+        self.enabled = r.bool()?;
+        self.cycle_mode = r.bool()?;
+        self.pending = r.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scanline_mode_counts_every_341_dots() {
+        let mut irq = VrcIrq::new();
+        irq.write_latch(0xFE);
+        irq.write_control(0x02);
+
+        // Two scanlines take 227 or 228 CPU cycles (682 dots / 3)
+        for _ in 0..227 {
+            irq.clock();
+        }
+        assert!(!irq.pending());
+        irq.clock();
+        assert!(irq.pending());
+        assert_eq!(irq.counter, 0xFE);
+    }
+
+    #[test]
+    fn load_state_rejects_prescaler_out_of_range() {
+        let mut irq = VrcIrq::new();
+        irq.prescaler = i32::MIN;
+        let mut w = StateWriter::new();
+        irq.save_state(&mut w);
+        let state = w.into_inner();
+
+        let result = VrcIrq::new().load_state(&mut StateReader::new(&state));
+        assert!(matches!(result, Err(SaveStateError::Corrupt(_))));
+    }
+}
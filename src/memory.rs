@@ -2,83 +2,148 @@ use crate::apu::Apu;
 use crate::cartridge::Cartridge;
 use crate::cpu::CpuBus;
 use crate::input::Input;
-use crate::ppu::Ppu;
+use crate::ppu::{Ppu, PpuBus};
+use crate::region::Region;
+use crate::savestate::{SaveState, SaveStateError, StateReader, StateWriter};
 
 pub struct MemoryBus {
     pub ram: [u8; 0x800],
+    /// The console's 2KB of nametable RAM. The cartridge decides how it is
+    /// mirrored into $2000-$2FFF, or whether it is used at all.
+    pub ciram: [u8; 0x800],
     pub ppu: Ppu,
     pub apu: Apu,
     pub input: Input,
     pub cartridge: Cartridge,
-    pub prg_ram: [u8; 0x2000],
-    pub chr_ram: [u8; 0x2000],
     open_bus: u8, // Track open bus value for accurate emulation
+    oam_dma_page: Option<u8>,
+    /// Master clock cycles elapsed, advanced by the CPU's bus accesses
+    master_clock: u64,
+    /// Master clock time the PPU has been run up to
+    ppu_master_clock: u64,
+    region: Region,
 }
 
 impl MemoryBus {
     pub fn new(cartridge: Cartridge) -> Self {
-        let mut ppu = Ppu::new();
-        ppu.set_mirroring(cartridge.mirroring);
-        log::info!("Nametable mirroring: {:?}", cartridge.mirroring);
+        log::info!("Nametable mirroring: {:?}", cartridge.mirroring());
 
         Self {
             // Initialize RAM with garbage values (0xFF) instead of zeros
             // Real NES hardware has random RAM values on power-on, and some games
             // like Paperboy are sensitive to this and may not boot with zero-initialized RAM
             ram: [0xFF; 0x800],
-            ppu,
+            // Garbage on power-on too; SMB expects it not to be zero
+            ciram: [0xFF; 0x800],
+            ppu: Ppu::new(),
             apu: Apu::new(),
             input: Input::new(),
             cartridge,
-            prg_ram: [0; 0x2000],
-            chr_ram: [0; 0x2000],
             open_bus: 0x40, // Initialize to common open bus value
+            oam_dma_page: None,
+            master_clock: 0,
+            ppu_master_clock: 0,
+            region: Region::Ntsc,
         }
     }
 
+    /// Return every chip on the board to its power-on state.
+    /// Battery-backed PRG-RAM keeps its contents, like the real cartridge.
+    pub fn power_cycle(&mut self) {
+        self.cartridge.power_cycle();
+        self.ppu = Ppu::new();
+        self.ppu.set_region(self.region);
+        self.apu = Apu::new();
+        self.apu.set_region(self.region);
+        self.input = Input::new();
+        self.ram = [0xFF; 0x800];
+        self.ciram = [0xFF; 0x800];
+        self.open_bus = 0x40;
+        self.oam_dma_page = None;
+        self.master_clock = 0;
+        self.ppu_master_clock = 0;
+    }
+
+    /// Switch every chip to the timing of `region` (must not be `Auto`)
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.set_region(region);
+        self.apu.set_region(region);
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
     fn mirror_ram_addr(&self, addr: u16) -> usize {
         (addr & 0x07FF) as usize
     }
 }
 
-impl CpuBus for MemoryBus {
-    fn is_oamdma_addr(&self, addr: u16) -> bool {
-        // Check if writing to $4014 (OAMDMA register)
-        // This is simplified - in real hardware, any write to $4014 triggers DMA
-        addr == 0x4014
+impl SaveState for MemoryBus {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.bytes(&self.ram);
+        w.bytes(&self.ciram);
+        w.u8(self.open_bus);
+        w.u64(self.master_clock);
+        w.u64(self.ppu_master_clock);
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        self.input.save_state(w);
+        self.cartridge.save_state(w);
     }
 
-    fn write_oam(&mut self, oam_addr: u16, value: u8) {
-        self.ppu.oam[oam_addr as usize & 0xFF] = value;
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        r.bytes_into(&mut self.ram)?;
+        r.bytes_into(&mut self.ciram)?;
+        self.open_bus = r.u8()?;
+        self.master_clock = r.u64()?;
+        self.ppu_master_clock = r.u64()?;
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.input.load_state(r)?;
+        self.cartridge.load_state(r)
     }
+}
 
-    fn trigger_oamdma(&mut self, page: u8, current_cycle_odd: bool) -> u64 {
-        // OAMDMA: Copy 256 bytes from CPU RAM page to OAM
-        // The DMA transfer takes 512 cycles (256 bytes, 2 cycles per byte on alternating cycles)
-        // Total stall: 512 cycles if write was on odd cycle, 513 if even
-        // This is because the write cycle itself is counted, and there's an extra cycle
-        // if the write happened on an even cycle
-        let page_addr = (page as u16) << 8;
-
-        // Perform DMA transfer: copy 256 bytes from CPU RAM to OAM
-        for i in 0..256u16 {
-            let byte = match page_addr + i {
-                0x0000..=0x1FFF => self.ram[self.mirror_ram_addr(page_addr + i)],
-                _ => 0, // Can't DMA from other areas (returns 0xFF on real hardware, but 0 is safer)
-            };
-            self.ppu.oam[i as usize] = byte;
+impl CpuBus for MemoryBus {
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => self.ram[self.mirror_ram_addr(addr)],
+            0x4020..=0xFFFF => self.cartridge.cpu_peek(addr).unwrap_or(self.open_bus),
+            // Registers have read side effects; report the open bus instead
+            _ => self.open_bus,
         }
+    }
 
-        // Return stall cycles: 512 if odd cycle, 513 if even cycle
-        // (The write cycle is already counted in the instruction, so we stall for the remaining cycles)
-        if current_cycle_odd {
-            512 // Total: 513 cycles (1 write + 512 stall)
-        } else {
-            513 // Total: 514 cycles (1 write + 513 stall)
-        }
+    fn take_oam_dma(&mut self) -> Option<u8> {
+        self.oam_dma_page.take()
     }
 
     fn read(&mut self, addr: u16) -> u8 {
+        self.start_cpu_cycle(true);
+        let value = self.read_memory(addr);
+        self.end_cpu_cycle(true);
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.start_cpu_cycle(false);
+        self.write_memory(addr, value);
+        self.end_cpu_cycle(false);
+    }
+
+    fn nmi_line(&self) -> bool {
+        self.ppu.nmi_line()
+    }
+
+    fn irq_line(&self) -> bool {
+        self.apu.irq_line() || self.cartridge.irq_pending()
+    }
+}
+
+impl MemoryBus {
+    fn read_memory(&mut self, addr: u16) -> u8 {
         let result = match addr {
             0x0000..=0x1FFF => {
                 // Internal RAM (mirrored)
@@ -86,18 +151,11 @@ impl CpuBus for MemoryBus {
             }
             0x2000..=0x3FFF => {
                 // PPU registers (mirrored every 8 bytes)
-                // For PPUDATA reads, we need cartridge access for pattern tables
-                if (addr & 0x2007) == 0x2007 {
-                    // PPUDATA read - provide cartridge read function
-                    let cartridge = &self.cartridge;
-                    let chr_ram = &self.chr_ram;
-                    let mut chr_read_fn = |a: u16| -> u8 { cartridge.ppu_read(a, chr_ram) };
-                    let mut chr_read_opt = Some(&mut chr_read_fn as &mut dyn FnMut(u16) -> u8);
-                    self.ppu.read_register(addr, &mut chr_read_opt)
-                } else {
-                    let mut no_chr_read = None;
-                    self.ppu.read_register(addr, &mut no_chr_read)
-                }
+                let mut ppu_bus = PpuMemory {
+                    cartridge: &mut self.cartridge,
+                    ciram: &mut self.ciram,
+                };
+                self.ppu.read_register(addr, &mut ppu_bus)
             }
             0x4000..=0x4013 => {
                 // APU registers
@@ -134,22 +192,10 @@ impl CpuBus for MemoryBus {
                 // APU and I/O test registers
                 self.open_bus // Return open bus
             }
-            0x4020..=0x5FFF => {
-                // Expansion ROM (unused in most games)
-                self.open_bus // Return open bus
-            }
-            0x6000..=0x7FFF => {
-                // Cartridge RAM
-                let addr = addr - 0x6000;
-                if addr < self.prg_ram.len() as u16 {
-                    self.prg_ram[addr as usize]
-                } else {
-                    self.open_bus
-                }
-            }
-            0x8000..=0xFFFF => {
-                // Cartridge PRG ROM
-                self.cartridge.cpu_read(addr, &mut self.prg_ram)
+            0x4020..=0xFFFF => {
+                // Cartridge space: expansion area, PRG-RAM and PRG-ROM as the
+                // mapper arranges them
+                self.cartridge.cpu_read(addr).unwrap_or(self.open_bus)
             }
         };
 
@@ -163,7 +209,7 @@ impl CpuBus for MemoryBus {
         result
     }
 
-    fn write(&mut self, addr: u16, value: u8) {
+    fn write_memory(&mut self, addr: u16, value: u8) {
         match addr {
             0x0000..=0x1FFF => {
                 // Internal RAM (mirrored)
@@ -171,12 +217,13 @@ impl CpuBus for MemoryBus {
             }
             0x2000..=0x3FFF => {
                 // PPU registers (mirrored every 8 bytes)
-                // write_register returns Some((chr_addr, value)) for CHR-RAM writes
-                if let Some((chr_addr, chr_value)) = self.ppu.write_register(addr, value) {
-                    // Pattern table write (0x0000-0x1FFF) - write to CHR-RAM
-                    self.cartridge
-                        .ppu_write(chr_addr, chr_value, &mut self.chr_ram);
-                }
+                let mut ppu_bus = PpuMemory {
+                    cartridge: &mut self.cartridge,
+                    ciram: &mut self.ciram,
+                };
+                self.ppu.write_register(addr, value, &mut ppu_bus);
+                // Some boards watch PPUCTRL/PPUMASK writes
+                self.cartridge.cpu_write(addr, value);
             }
             0x4000..=0x4013 | 0x4015 => {
                 // APU registers
@@ -186,11 +233,9 @@ impl CpuBus for MemoryBus {
                 });
             }
             0x4014 => {
-                // OAMDMA: Write page number, triggers DMA from CPU RAM to OAM
-                // The actual DMA transfer is handled by trigger_oamdma() in the CPU
-                // This write is just a placeholder - the CPU will detect $4014 and call trigger_oamdma
-                // We still perform the write here for compatibility, but the stall is handled by CPU
-                // Note: In real hardware, the write triggers DMA and the CPU is stalled
+                // OAMDMA: the CPU picks up the page and performs the transfer
+                // once the current instruction finishes
+                self.oam_dma_page = Some(value);
             }
             0x4016 => {
                 // Controller strobe
@@ -208,25 +253,9 @@ impl CpuBus for MemoryBus {
             0x4018..=0x401F => {
                 // APU and I/O test registers
             }
-            0x4020..=0x5FFF => {
-                // Expansion ROM
-            }
-            0x6000..=0x7FFF => {
-                // Cartridge RAM
-                let addr = addr - 0x6000;
-                if addr < self.prg_ram.len() as u16 {
-                    self.prg_ram[addr as usize] = value;
-                    self.cartridge
-                        .cpu_write(addr + 0x6000, value, &mut self.prg_ram);
-                }
-            }
-            0x8000..=0xFFFF => {
-                // Cartridge PRG ROM (mapper registers)
-                let mirroring_changed = self.cartridge.cpu_write(addr, value, &mut self.prg_ram);
-                // Check if mirroring changed (for MMC1 and other mappers that support dynamic mirroring)
-                if mirroring_changed {
-                    self.ppu.set_mirroring(self.cartridge.mapper.mirroring());
-                }
+            0x4020..=0xFFFF => {
+                // Cartridge space (PRG-RAM, mapper registers)
+                self.cartridge.cpu_write(addr, value);
             }
         }
     }
@@ -236,56 +265,113 @@ impl MemoryBus {
     fn cpu_read_for_apu(&self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x1FFF => self.ram[self.mirror_ram_addr(addr)],
-            0x8000..=0xFFFF => {
-                // For DMC channel
-                if addr < self.cartridge.prg_rom.len() as u16 + 0x8000 {
-                    let rom_addr = addr - 0x8000;
-                    if rom_addr < self.cartridge.prg_rom.len() as u16 {
-                        self.cartridge.prg_rom[rom_addr as usize]
-                    } else {
-                        0
-                    }
-                } else {
-                    0
-                }
+            0x4020..=0xFFFF => {
+                // For DMC channel - sample fetches go through the mapper's PRG banking
+                self.cartridge.cpu_peek(addr).unwrap_or(self.open_bus)
             }
             _ => 0,
         }
     }
 
-    pub fn step_ppu(&mut self) -> bool {
-        let chr_read = |addr: u16| self.cartridge.ppu_read(addr, &self.chr_ram);
+    /// Master cycles from the start of a CPU cycle to the moment the bus is
+    /// accessed. Reads are sampled slightly earlier than writes land.
+    fn cpu_cycle_split(&self, read: bool) -> u64 {
+        let half = self.region.master_cycles_per_cpu_cycle() / 2;
+        if read {
+            half - 1
+        } else {
+            half + 1
+        }
+    }
 
-        // Check for scanline transition (for MMC3 IRQ)
-        let old_scanline = self.ppu.scanline;
-        let nmi = self.ppu.step(chr_read);
+    /// First part of a CPU cycle, up to the bus access
+    fn start_cpu_cycle(&mut self, read: bool) {
+        self.advance_master_clock(self.cpu_cycle_split(read));
+    }
 
-        // Clock mapper scanline counter at the start of each visible scanline
-        // MMC3 clocks when A12 rises, which happens at cycle 260 of visible scanlines
-        if self.ppu.scanline != old_scanline && self.ppu.scanline >= 0 && self.ppu.scanline < 240 {
-            // Only clock if rendering is enabled (bg or sprites)
-            if (self.ppu.mask & 0x18) != 0 {
-                self.cartridge.clock_scanline();
-            }
+    /// Rest of the CPU cycle, then clock everything that runs at CPU rate
+    fn end_cpu_cycle(&mut self, read: bool) {
+        let rest = self.region.master_cycles_per_cpu_cycle() - self.cpu_cycle_split(read);
+        self.advance_master_clock(rest);
+        self.cartridge.cpu_clock();
+        self.step_apu();
+    }
+
+    /// Move the master clock forward and run the PPU up to it
+    fn advance_master_clock(&mut self, master_cycles: u64) {
+        self.master_clock += master_cycles;
+        let ppu_cycle = self.region.master_cycles_per_ppu_cycle();
+        while self.ppu_master_clock + ppu_cycle <= self.master_clock {
+            self.ppu_master_clock += ppu_cycle;
+            self.step_ppu();
         }
+    }
 
-        nmi
+    /// Master clock cycles since power-on
+    pub fn master_clock(&self) -> u64 {
+        self.master_clock
     }
 
-    /// Check if mapper has a pending IRQ (for MMC3)
-    pub fn mapper_irq_pending(&self) -> bool {
-        self.cartridge.irq_pending()
+    /// PPU dots since power-on
+    pub fn ppu_cycles(&self) -> u64 {
+        self.ppu_master_clock / self.region.master_cycles_per_ppu_cycle()
     }
 
-    /// Acknowledge mapper IRQ
-    pub fn acknowledge_mapper_irq(&mut self) {
-        self.cartridge.acknowledge_irq();
+    fn step_ppu(&mut self) {
+        let mut ppu_bus = PpuMemory {
+            cartridge: &mut self.cartridge,
+            ciram: &mut self.ciram,
+        };
+        self.ppu.step(&mut ppu_bus);
     }
 
-    pub fn step_apu(&mut self, cpu_cycles: u64) -> bool {
+    fn step_apu(&mut self) {
+        self.apu.set_expansion_audio(self.cartridge.audio_output());
         let bus_ptr = self as *const Self;
-        self.apu.step(cpu_cycles, move |addr: u16| unsafe {
+        self.apu.step(1, move |addr: u16| unsafe {
             (*bus_ptr).cpu_read_for_apu(addr)
-        })
+        });
+    }
+}
+
+/// What the PPU sees on its bus: pattern tables and nametables are decoded by
+/// the cartridge, which can also hand nametables back to the console's CIRAM
+struct PpuMemory<'a> {
+    cartridge: &'a mut Cartridge,
+    ciram: &'a mut [u8; 0x800],
+}
+
+impl PpuBus for PpuMemory<'_> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        self.cartridge.ppu_address(addr);
+        if addr < 0x2000 {
+            match self.cartridge.chr_ciram_offset(addr) {
+                Some(offset) => self.ciram[offset],
+                None => self.cartridge.ppu_read(addr),
+            }
+        } else {
+            match self.cartridge.nametable_read(addr) {
+                Some(value) => value,
+                None => self.ciram[self.cartridge.ciram_offset(addr)],
+            }
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let addr = addr & 0x3FFF;
+        self.cartridge.ppu_address(addr);
+        if addr < 0x2000 {
+            match self.cartridge.chr_ciram_offset(addr) {
+                Some(offset) => self.ciram[offset] = value,
+                None => self.cartridge.ppu_write(addr, value),
+            }
+        } else if !self.cartridge.nametable_write(addr, value) {
+            self.ciram[self.cartridge.ciram_offset(addr)] = value;
+        }
+    }
+
+    fn set_address(&mut self, addr: u16) {
+        self.cartridge.ppu_address(addr & 0x3FFF);
     }
 }
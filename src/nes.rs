@@ -0,0 +1,223 @@
+//! The complete console: CPU and memory bus (PPU/APU/input/cartridge).
+//!
+//! Timing is driven by an integer master clock kept by the bus. Every CPU bus
+//! access advances it by one CPU cycle and runs the PPU and APU up to the new
+//! time, so the chips stay interleaved to within a fraction of a CPU cycle.
+//!
+//! Every frontend (desktop, Android, tools) drives emulation through [`Nes`]
+//! rather than stepping the individual chips itself.
+
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::cpu::{Cpu, CpuBus};
+use crate::input::Button;
+use crate::mapper::AudioOption;
+use crate::memory::MemoryBus;
+use crate::region::Region;
+use crate::savestate::{SaveState, SaveStateError, StateReader, StateWriter};
+use crate::trace::TraceState;
+
+/// CPU cycles [`Nes::run_frame`] gives up after: two of the longest (PAL)
+/// frames
+const MAX_FRAME_CYCLES: u64 = 2 * 33248;
+
+pub struct Nes {
+    pub cpu: Cpu,
+    pub bus: MemoryBus,
+    pub trace: TraceState,
+}
+
+impl Nes {
+    /// Insert a cartridge and power the console on. The region comes from
+    /// the ROM header (NTSC if it doesn't say); see [`Nes::set_region`].
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mut bus = MemoryBus::new(cartridge);
+        bus.set_region(Region::Auto.resolve(bus.cartridge.header.region(), None));
+        let mut cpu = Cpu::new();
+        cpu.reset(&mut bus as &mut dyn CpuBus);
+
+        Self {
+            cpu,
+            bus,
+            trace: TraceState::new(false),
+        }
+    }
+
+    /// Run until the PPU finishes the current frame. Gives up after two
+    /// frames' worth of CPU cycles, so a stuck machine can't hang the caller.
+    pub fn run_frame(&mut self) {
+        let frame = self.bus.ppu.frame;
+        let start_cycles = self.cpu.cycles;
+        while self.bus.ppu.frame == frame && self.cpu.cycles - start_cycles < MAX_FRAME_CYCLES {
+            self.step_instruction();
+        }
+    }
+
+    /// Execute exactly one instruction (or interrupt). The PPU, APU and
+    /// mapper are clocked by the bus as the CPU makes each access.
+    /// Returns the number of CPU cycles it took.
+    pub fn step_instruction(&mut self) -> u64 {
+        if self.trace.enabled {
+            self.trace.ppu_cycle_count = self.bus.ppu_cycles();
+        }
+        self.cpu
+            .step(&mut self.bus as &mut dyn CpuBus, &mut self.trace)
+    }
+
+    /// Press the reset button. RAM and cartridge state survive.
+    pub fn reset(&mut self) {
+        self.bus.cartridge.reset();
+        self.cpu.reset(&mut self.bus as &mut dyn CpuBus);
+    }
+
+    /// Turn the console off and on again. Only battery-backed RAM survives.
+    pub fn power_cycle(&mut self) {
+        self.bus.power_cycle();
+        self.cpu = Cpu::new();
+        self.cpu.reset(&mut self.bus as &mut dyn CpuBus);
+    }
+
+    /// Switch the console's TV system. `Auto` picks the region from the ROM
+    /// header. Takes effect immediately; call [`Nes::power_cycle`] for a
+    /// clean start.
+    pub fn set_region(&mut self, region: Region) {
+        let region = region.resolve(self.bus.cartridge.header.region(), None);
+        self.bus.set_region(region);
+    }
+
+    /// The TV system being emulated (never `Auto`)
+    pub fn region(&self) -> Region {
+        self.bus.region()
+    }
+
+    /// Apply an expansion audio setting (see [`AudioOption`]). It sticks
+    /// across power cycles; boards without that chip ignore it.
+    pub fn set_audio_option(&mut self, option: AudioOption) {
+        self.bus.cartridge.set_audio_option(option);
+    }
+
+    /// Set a button on controller 1 (`port` 0) or controller 2 (`port` 1)
+    pub fn set_button(&mut self, port: usize, button: Button, pressed: bool) {
+        match port {
+            0 => self.bus.input.controller1.set_button(button, pressed),
+            1 => self.bus.input.controller2.set_button(button, pressed),
+            _ => {}
+        }
+    }
+
+    /// Current frame as 256x240 palette indices (see [`crate::NES_PALETTE`])
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.bus.ppu.framebuffer
+    }
+
+    /// Number of frames the PPU has completed since power-on
+    pub fn frame_count(&self) -> u64 {
+        self.bus.ppu.frame
+    }
+
+    /// Drain the audio samples generated since the last call
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.bus.apu.take_samples()
+    }
+
+    /// Nudge the resampling rate to keep the frontend's audio queue near its target
+    pub fn adjust_audio_rate(&mut self, queue_size: usize, target_size: usize) {
+        self.bus.apu.adjust_sample_rate(queue_size, target_size);
+    }
+
+    /// Whether the cartridge has battery-backed PRG-RAM worth saving
+    pub fn has_battery(&self) -> bool {
+        self.bus.cartridge.has_battery()
+    }
+
+    /// Battery-backed PRG-RAM contents, sized from the ROM header
+    pub fn sram(&self) -> &[u8] {
+        self.bus.cartridge.battery_ram()
+    }
+
+    /// Restore battery-backed PRG-RAM contents from a save file
+    pub fn set_sram(&mut self, data: &[u8]) {
+        let sram = self.bus.cartridge.battery_ram_mut();
+        let len = data.len().min(sram.len());
+        sram[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Number of disk sides of a Famicom Disk System image (0 for a cartridge)
+    pub fn disk_side_count(&self) -> usize {
+        self.bus
+            .cartridge
+            .disk()
+            .map_or(0, |disk| disk.side_count())
+    }
+
+    /// Disk side in the drive; `None` while the disk is out, including the
+    /// moment it takes to switch sides
+    pub fn disk_side(&self) -> Option<usize> {
+        self.bus.cartridge.disk_side()
+    }
+
+    /// Eject the disk and insert `side` a moment later, or leave the drive
+    /// empty with `None`
+    pub fn insert_disk(&mut self, side: Option<usize>) {
+        self.bus.cartridge.insert_disk(side);
+    }
+
+    /// The disk as a `.fds` image, if the game has written to it since it
+    /// was loaded. FDS games save to the disk itself, so this is the save
+    /// file.
+    pub fn modified_disk(&self) -> Option<Vec<u8>> {
+        self.bus
+            .cartridge
+            .disk()
+            .filter(|disk| disk.is_modified())
+            .map(|disk| disk.to_image())
+    }
+
+    /// Restore the disk from a save file written from [`Nes::modified_disk`]
+    pub fn restore_disk(&mut self, data: &[u8]) -> Result<(), CartridgeError> {
+        match self.bus.cartridge.disk_mut() {
+            Some(disk) => disk.restore(data),
+            None => Err(CartridgeError::InvalidDiskImage),
+        }
+    }
+
+    /// Snapshot the whole machine into a versioned save state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        w.header(self.bus.cartridge.crc32);
+        self.save_body(&mut w);
+        w.into_inner()
+    }
+
+    /// Restore a save state made by [`Nes::save_state`] for the same ROM.
+    /// If the state is rejected, the running machine is left untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let mut r = StateReader::new(data);
+        r.header(self.bus.cartridge.crc32)?;
+
+        let backup = self.save_state();
+        let result = self.load_body(&mut r).and_then(|_| r.finish());
+        if result.is_err() {
+            // A state we just wrote always loads back cleanly
+            let mut r = StateReader::new(&backup);
+            let _ = r
+                .header(self.bus.cartridge.crc32)
+                .and_then(|_| self.load_body(&mut r));
+        }
+        result
+    }
+
+    fn save_body(&self, w: &mut StateWriter) {
+        w.u8(self.region().to_u8());
+        self.cpu.save_state(w);
+        self.bus.save_state(w);
+    }
+
+    fn load_body(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        let region = Region::from_u8(r.u8()?)
+            .filter(|&region| region != Region::Auto)
+            .ok_or(SaveStateError::Corrupt("invalid region"))?;
+        self.bus.set_region(region);
+        self.cpu.load_state(r)?;
+        self.bus.load_state(r)
+    }
+}
@@ -1,6 +1,19 @@
-use crate::cartridge::Mirroring;
+use crate::region::Region;
+use crate::savestate::{SaveState, SaveStateError, StateReader, StateWriter};
 use log::debug;
 
+/// The PPU's own address bus. Pattern tables and nametables live outside the
+/// PPU (on the cartridge and in the console's 2KB nametable RAM); palette RAM
+/// is inside it and never reaches the bus.
+pub trait PpuBus {
+    /// Read $0000-$3FFF; everything from $2000 up is nametable space
+    fn read(&mut self, addr: u16) -> u8;
+    /// Write $0000-$3FFF
+    fn write(&mut self, addr: u16, value: u8);
+    /// Put `addr` on the bus without reading or writing
+    fn set_address(&mut self, addr: u16);
+}
+
 #[derive(Debug, Clone)]
 pub struct Ppu {
     // Registers
@@ -14,9 +27,7 @@ pub struct Ppu {
     pub data: u8,     // PPUDATA (0x2007)
 
     // Internal state
-    pub vram: [u8; 0x800],         // 2KB VRAM
     pub palette: [u8; 0x20],       // 32 bytes palette RAM
-    pub mirroring: Mirroring,      // Nametable mirroring mode
     pub oam: [u8; 0x100],          // 256 bytes OAM (Object Attribute Memory)
     pub secondary_oam: [u8; 0x20], // Secondary OAM for current scanline
     pub vram_read_buffer: u8,      // PPUDATA read buffer (one-read delay for < $3F00)
@@ -30,8 +41,6 @@ pub struct Ppu {
     pub scanline: i32,
     pub cycle: u32,
     pub frame: u64,
-    pub nmi_occurred: bool,
-    pub nmi_output: bool,
 
     // Temporary registers (t, v, x, w)
     pub vram_addr_temp: u16, // t
@@ -44,10 +53,6 @@ pub struct Ppu {
     pub next_tile_attr: u8,
     pub next_tile_low: u8,
     pub next_tile_high: u8,
-    pub tile_id: u8,
-    pub tile_attr: u8,
-    pub tile_low: u8,
-    pub tile_high: u8,
     pub shift_pattern_low: u16,
     pub shift_pattern_high: u16,
     pub shift_attr_low: u16,
@@ -63,6 +68,102 @@ pub struct Ppu {
 
     // Framebuffer for pixel output
     pub framebuffer: [u8; 256 * 240],
+
+    // Frame layout (scanline count, vblank position, odd frame skip)
+    region: Region,
+}
+
+impl SaveState for Ppu {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.ctrl);
+        w.u8(self.mask);
+        w.u8(self.status);
+        w.u8(self.oam_addr);
+        w.u8(self.oam_data);
+        w.u8(self.scroll);
+        w.u8(self.addr);
+        w.u8(self.data);
+
+        w.bytes(&self.palette);
+        w.bytes(&self.oam);
+        w.bytes(&self.secondary_oam);
+        w.u8(self.vram_read_buffer);
+
+        w.i32(self.scanline);
+        w.u32(self.cycle);
+        w.u64(self.frame);
+
+        w.u16(self.vram_addr_temp);
+        w.u16(self.vram_addr);
+        w.u8(self.fine_x);
+        w.bool(self.write_toggle);
+
+        w.u8(self.next_tile_id);
+        w.u8(self.next_tile_attr);
+        w.u8(self.next_tile_low);
+        w.u8(self.next_tile_high);
+        w.u16(self.shift_pattern_low);
+        w.u16(self.shift_pattern_high);
+        w.u16(self.shift_attr_low);
+        w.u16(self.shift_attr_high);
+
+        w.u8(self.sprite_count);
+        w.bytes(&self.sprite_indices);
+        w.bytes(&self.sprite_positions);
+        w.bytes(&self.sprite_patterns_low);
+        w.bytes(&self.sprite_patterns_high);
+        w.bytes(&self.sprite_attributes);
+
+        // Saved so a restored state shows the right picture before the next frame
+        w.bytes(&self.framebuffer);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError> {
+        self.ctrl = r.u8()?;
+        self.mask = r.u8()?;
+        self.status = r.u8()?;
+        self.oam_addr = r.u8()?;
+        self.oam_data = r.u8()?;
+        self.scroll = r.u8()?;
+        self.addr = r.u8()?;
+        self.data = r.u8()?;
+
+        r.bytes_into(&mut self.palette)?;
+        r.bytes_into(&mut self.oam)?;
+        r.bytes_into(&mut self.secondary_oam)?;
+        self.vram_read_buffer = r.u8()?;
+
+        self.scanline = r.i32()?;
+        self.cycle = r.u32()?;
+        self.frame = r.u64()?;
+
+        self.vram_addr_temp = r.u16()?;
+        self.vram_addr = r.u16()?;
+        self.fine_x = r.u8()?;
+        self.write_toggle = r.bool()?;
+
+        self.next_tile_id = r.u8()?;
+        self.next_tile_attr = r.u8()?;
+        self.next_tile_low = r.u8()?;
+        self.next_tile_high = r.u8()?;
+        self.shift_pattern_low = r.u16()?;
+        self.shift_pattern_high = r.u16()?;
+        self.shift_attr_low = r.u16()?;
+        self.shift_attr_high = r.u16()?;
+
+        self.sprite_count = r.u8()?;
+        if self.sprite_count > 8 {
+            return Err(SaveStateError::Corrupt("sprite count"));
+        }
+        r.bytes_into(&mut self.sprite_indices)?;
+        r.bytes_into(&mut self.sprite_positions)?;
+        r.bytes_into(&mut self.sprite_patterns_low)?;
+        r.bytes_into(&mut self.sprite_patterns_high)?;
+        r.bytes_into(&mut self.sprite_attributes)?;
+
+        r.bytes_into(&mut self.framebuffer)?;
+        Ok(())
+    }
 }
 
 impl Default for Ppu {
@@ -73,7 +174,6 @@ impl Default for Ppu {
 
 impl Ppu {
     pub fn new() -> Self {
-        log::info!("PPU initialized: VRAM set to 0xFF (garbage) for SMB compatibility");
         Self {
             ctrl: 0,
             mask: 0,
@@ -83,9 +183,7 @@ impl Ppu {
             scroll: 0,
             addr: 0,
             data: 0,
-            vram: [0xFF; 0x800], // Initialize to 0xFF (garbage/random) to match real hardware power-on state
             palette: [0xFF; 0x20], // Initialize to 0xFF (will be overwritten by game, but avoids 0x00 issues)
-            mirroring: Mirroring::Horizontal, // Default to horizontal, will be set from cartridge
             oam: [0; 0x100],
             secondary_oam: [0; 0x20],
             vram_read_buffer: 0xFF, // PPUDATA read buffer (initialized to 0xFF to match real hardware garbage state)
@@ -95,8 +193,6 @@ impl Ppu {
             scanline: -1,
             cycle: 0,
             frame: 0,
-            nmi_occurred: false,
-            nmi_output: false,
             vram_addr_temp: 0,
             vram_addr: 0,
             fine_x: 0,
@@ -105,10 +201,6 @@ impl Ppu {
             next_tile_attr: 0,
             next_tile_low: 0,
             next_tile_high: 0,
-            tile_id: 0,
-            tile_attr: 0,
-            tile_low: 0,
-            tile_high: 0,
             shift_pattern_low: 0,
             shift_pattern_high: 0,
             shift_attr_low: 0,
@@ -120,14 +212,15 @@ impl Ppu {
             sprite_patterns_high: [0; 8],
             sprite_attributes: [0; 8],
             framebuffer: [0; 256 * 240],
+            region: Region::Ntsc,
         }
     }
 
-    pub fn set_mirroring(&mut self, mirroring: Mirroring) {
-        self.mirroring = mirroring;
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
     }
 
-    fn increment_vram_addr(&mut self) {
+    fn increment_vram_addr(&mut self, bus: &mut dyn PpuBus) {
         // PPUCTRL bit 2 (0x04) controls VRAM address increment:
         // - Bit 2 = 0: increment by 1 (horizontal fill) - used by most games
         // - Bit 2 = 1: increment by 32 (vertical fill)
@@ -138,13 +231,24 @@ impl Ppu {
         // Important: This is the correct behavior - the internal register can be 0x4000-0x7FFF
         // for fine Y scroll bits, but VRAM access masks it to 0x3FFF
         self.vram_addr = (self.vram_addr.wrapping_add(increment)) & 0x7FFF;
+        self.output_vram_addr(bus);
+    }
+
+    /// Pre-render and visible scanlines with rendering enabled, when the PPU
+    /// owns its bus
+    fn is_rendering(&self) -> bool {
+        (self.mask & 0x18) != 0 && self.scanline < 240
+    }
+
+    /// Outside rendering the PPU leaves `v` on its address bus, so mappers
+    /// see PPUADDR writes and PPUDATA increments
+    fn output_vram_addr(&mut self, bus: &mut dyn PpuBus) {
+        if !self.is_rendering() {
+            bus.set_address(self.vram_addr & 0x3FFF);
+        }
     }
 
-    pub fn read_register(
-        &mut self,
-        addr: u16,
-        chr_read: &mut Option<&mut dyn FnMut(u16) -> u8>,
-    ) -> u8 {
+    pub fn read_register(&mut self, addr: u16, bus: &mut dyn PpuBus) -> u8 {
         match addr & 0x2007 {
             0x2002 => {
                 // PPUSTATUS
@@ -165,19 +269,17 @@ impl Ppu {
                 let addr = self.vram_addr & 0x3FFF;
                 let result = if addr >= 0x3F00 {
                     // Palette read: immediate, no delay
-                    // Return palette value (with mirroring handled in read_vram)
-                    let palette_value = self.read_vram(addr, chr_read);
-                    // Fill buffer with mirrored nametable byte (for next read)
-                    // Mirror: $3F00-$3FFF -> $2000-$2FFF (nametable range)
-                    let mirrored_addr = addr & 0x2FFF; // Mirror palette address down to nametable
-                    self.vram_read_buffer = self.read_vram(mirrored_addr, chr_read);
+                    let palette_value = self.palette[palette_index(addr)];
+                    // Fill buffer with the nametable byte "under" the palette
+                    // (the bus mirrors $3F00-$3FFF down to $2F00-$2FFF)
+                    self.vram_read_buffer = bus.read(addr);
                     palette_value
                 } else {
                     // Nametable/pattern table read: return buffer, then fill buffer
                     let buffered_value = self.vram_read_buffer;
                     // Fill buffer with actual read (for next read)
                     // Pattern tables (0x0000-0x1FFF) must be read from cartridge
-                    let actual_value = self.read_vram(addr, chr_read);
+                    let actual_value = bus.read(addr);
                     self.vram_read_buffer = actual_value;
 
                     // Detailed logging for first 500 reads or until frame 30 (use INFO so it shows without --debug)
@@ -195,7 +297,7 @@ impl Ppu {
                 };
 
                 // Increment VRAM address (by 1 or 32 based on PPUCTRL bit 2)
-                self.increment_vram_addr();
+                self.increment_vram_addr(bus);
 
                 result
             }
@@ -203,8 +305,7 @@ impl Ppu {
         }
     }
 
-    pub fn write_register(&mut self, addr: u16, value: u8) -> Option<(u16, u8)> {
-        // Returns Some((chr_addr, value)) if this is a CHR-RAM write that needs to be handled
+    pub fn write_register(&mut self, addr: u16, value: u8, bus: &mut dyn PpuBus) {
         match addr & 0x2007 {
             0x2000 => {
                 // PPUCTRL ($2000)
@@ -286,6 +387,7 @@ impl Ppu {
                     // Second write: low byte
                     self.vram_addr_temp = (self.vram_addr_temp & 0xFF00) | value as u16;
                     self.vram_addr = self.vram_addr_temp;
+                    self.output_vram_addr(bus);
                     self.ppuaddr_write_count += 1;
 
                     // Log mid-frame PPUADDR writes (TLOZ vertical scrolling technique)
@@ -320,42 +422,10 @@ impl Ppu {
                 // Detailed logging for first 200 writes (use INFO so it shows without --debug)
                 if self.ppudata_write_count <= 200 {
                     if (0x2000..0x3F00).contains(&addr) {
-                        // Calculate actual VRAM index after mirroring
-                        let nt_addr = addr & 0x2FFF;
-                        let nt_index = match self.mirroring {
-                            Mirroring::Horizontal => {
-                                let base = nt_addr & 0x03FF;
-                                if nt_addr >= 0x2800 {
-                                    base + 0x0400
-                                } else {
-                                    base
-                                }
-                            }
-                            Mirroring::Vertical => {
-                                let base = nt_addr & 0x03FF;
-                                if (nt_addr & 0x0400) != 0 {
-                                    base + 0x0400
-                                } else {
-                                    base
-                                }
-                            }
-                            Mirroring::FourScreen => {
-                                let base = nt_addr & 0x03FF;
-                                if nt_addr >= 0x2800 {
-                                    base + 0x0400
-                                } else {
-                                    base
-                                }
-                            }
-                            Mirroring::OneScreenLower | Mirroring::OneScreenUpper => {
-                                // Single screen: all nametables map to same 1KB
-                                nt_addr & 0x03FF
-                            }
-                        };
                         // Calculate next address for logging (before increment)
                         let next_addr = (self.vram_addr.wrapping_add(increment)) & 0x7FFF; // 15-bit internal register
-                        log::info!("PPUDATA write #{}: frame={}, vram_addr=0x{:04X} -> VRAM[0x{:03X}], value=0x{:02X} (tile_id={}), increment={}, next_addr=0x{:04X}",
-                            self.ppudata_write_count, self.frame, addr, nt_index, value, value, increment, next_addr);
+                        log::info!("PPUDATA write #{}: frame={}, vram_addr=0x{:04X}, value=0x{:02X} (tile_id={}), increment={}, next_addr=0x{:04X}",
+                            self.ppudata_write_count, self.frame, addr, value, value, increment, next_addr);
                     } else if (0x3F00..0x3F20).contains(&addr) {
                         log::info!("PPUDATA write #{}: frame={}, addr=0x{:04X} (palette[0x{:02X}]), value=0x{:02X}, increment={}",
                             self.ppudata_write_count, self.frame, addr, (addr & 0x1F) as u8, value, increment);
@@ -367,334 +437,51 @@ impl Ppu {
 
                 // Write to VRAM, then increment address
                 // CRITICAL: increment MUST happen after write, and MUST update vram_addr correctly
-                self.write_vram(addr, value);
-                self.increment_vram_addr();
-
-                // Return pattern table writes for CHR-RAM handling
-                if addr < 0x2000 {
-                    return Some((addr, value));
+                if addr >= 0x3F00 {
+                    self.write_palette(addr, value);
+                } else {
+                    bus.write(addr, value);
                 }
+                self.increment_vram_addr(bus);
             }
             _ => {}
         }
-        None
     }
 
-    pub fn read_vram(&self, addr: u16, chr_read: &mut Option<&mut dyn FnMut(u16) -> u8>) -> u8 {
-        let addr = addr & 0x3FFF;
-        match addr {
-            0x0000..=0x1FFF => {
-                // Pattern tables: read from cartridge if available
-                if let Some(chr_read_fn) = chr_read {
-                    let data = chr_read_fn(addr);
-                    // Log CHR reads during early frames (especially high addresses $1EC0-$1FFF for title data)
-                    if self.frame < 30 && (addr >= 0x1EC0 || self.frame < 10) {
-                        log::info!(
-                            "CHR read: frame={}, addr=0x{:04X}, data=0x{:02X}",
-                            self.frame,
-                            addr,
-                            data
-                        );
-                    }
-                    data
-                } else {
-                    0 // Fallback if no cartridge read function provided
-                }
-            }
-            0x2000..=0x3EFF => {
-                // Nametables (mirrored based on cartridge setting)
-                // VRAM layout: $0000-$03FF = NT0, $0400-$07FF = NT1
-                let nt_addr = addr & 0x2FFF; // Mask to nametable range ($2000-$2FFF)
-                let nt_index = match self.mirroring {
-                    Mirroring::Horizontal => {
-                        // Horizontal: $2000/$2400 = NT0, $2800/$2C00 = NT1
-                        // $2000-$23FF -> NT0 ($0000-$03FF in VRAM)
-                        // $2400-$27FF -> NT0 mirror ($0000-$03FF in VRAM)
-                        // $2800-$2BFF -> NT1 ($0400-$07FF in VRAM)
-                        // $2C00-$2FFF -> NT1 mirror ($0400-$07FF in VRAM)
-                        let base = nt_addr & 0x03FF; // Get offset within nametable (0-1023)
-                        if nt_addr >= 0x2800 {
-                            base + 0x0400 // NT1: add $0400 offset
-                        } else {
-                            base // NT0: no offset
-                        }
-                    }
-                    Mirroring::Vertical => {
-                        // Vertical: $2000/$2800 = NT0, $2400/$2C00 = NT1
-                        // $2000-$23FF -> NT0 ($0000-$03FF in VRAM)
-                        // $2400-$27FF -> NT1 ($0400-$07FF in VRAM)
-                        // $2800-$2BFF -> NT0 mirror ($0000-$03FF in VRAM)
-                        // $2C00-$2FFF -> NT1 mirror ($0400-$07FF in VRAM)
-                        let base = nt_addr & 0x03FF; // Get offset within nametable (0-1023)
-                        if (nt_addr & 0x0400) != 0 {
-                            base + 0x0400 // NT1: add $0400 offset
-                        } else {
-                            base // NT0: no offset
-                        }
-                    }
-                    Mirroring::FourScreen => {
-                        // Four-screen: no mirroring, each 1KB nametable is unique
-                        // This requires 4KB VRAM, but we only have 2KB, so treat as horizontal
-                        let base = nt_addr & 0x03FF;
-                        if nt_addr >= 0x2800 {
-                            base + 0x0400
-                        } else {
-                            base
-                        }
-                    }
-                    Mirroring::OneScreenLower | Mirroring::OneScreenUpper => {
-                        // Single screen: all nametables map to same 1KB
-                        nt_addr & 0x03FF
-                    }
-                };
-                self.vram[nt_index as usize]
-            }
-            0x3F00..=0x3FFF => {
-                // Palette RAM
-                let index = addr & 0x001F;
-                let index = if (index & 0x03) == 0 {
-                    index & !0x10
-                } else {
-                    index
-                };
-                self.palette[index as usize]
-            }
-            _ => 0,
+    fn write_palette(&mut self, addr: u16, value: u8) {
+        let index = palette_index(addr);
+        self.palette[index] = value;
+        // Mirror writes to 0x3F10, 0x3F14, 0x3F18, 0x3F1C
+        if (index & 0x03) == 0 {
+            self.palette[index ^ 0x10] = value;
         }
     }
 
-    pub fn write_vram(&mut self, addr: u16, value: u8) {
-        let addr = addr & 0x3FFF;
-        match addr {
-            0x0000..=0x1FFF => {} // Pattern tables handled by cartridge (read-only)
-            0x2000..=0x3EFF => {
-                // Nametables (mirrored based on cartridge setting)
-                // VRAM layout: $0000-$03FF = NT0, $0400-$07FF = NT1
-                let nt_addr = addr & 0x2FFF; // Mask to nametable range ($2000-$2FFF)
-                let nt_index = match self.mirroring {
-                    Mirroring::Horizontal => {
-                        // Horizontal: $2000/$2400 = NT0, $2800/$2C00 = NT1
-                        let base = nt_addr & 0x03FF; // Get offset within nametable (0-1023)
-                        if nt_addr >= 0x2800 {
-                            base + 0x0400 // NT1: add $0400 offset
-                        } else {
-                            base // NT0: no offset
-                        }
-                    }
-                    Mirroring::Vertical => {
-                        // Vertical: $2000/$2800 = NT0, $2400/$2C00 = NT1
-                        let base = nt_addr & 0x03FF; // Get offset within nametable (0-1023)
-                        if (nt_addr & 0x0400) != 0 {
-                            base + 0x0400 // NT1: add $0400 offset
-                        } else {
-                            base // NT0: no offset
-                        }
-                    }
-                    Mirroring::FourScreen => {
-                        // Four-screen: no mirroring, each 1KB nametable is unique
-                        // This requires 4KB VRAM, but we only have 2KB, so treat as horizontal
-                        let base = nt_addr & 0x03FF;
-                        if nt_addr >= 0x2800 {
-                            base + 0x0400
-                        } else {
-                            base
-                        }
-                    }
-                    Mirroring::OneScreenLower | Mirroring::OneScreenUpper => {
-                        // Single screen: all nametables map to same 1KB
-                        nt_addr & 0x03FF
-                    }
-                };
-                self.vram[nt_index as usize] = value;
-            }
-            0x3F00..=0x3FFF => {
-                // Palette RAM
-                let index = addr & 0x001F;
-                let index = if (index & 0x03) == 0 {
-                    index & !0x10
-                } else {
-                    index
-                };
-                self.palette[index as usize] = value;
-                // Mirror writes to 0x3F10, 0x3F14, 0x3F18, 0x3F1C
-                if (index & 0x03) == 0 {
-                    self.palette[(index ^ 0x10) as usize] = value;
-                }
-            }
-            _ => {}
-        }
+    /// Level of the PPU's /NMI output: asserted while the vblank flag and
+    /// the NMI enable bit in PPUCTRL are both set
+    pub fn nmi_line(&self) -> bool {
+        (self.status & 0x80) != 0 && (self.ctrl & 0x80) != 0
     }
 
-    pub fn step(&mut self, mut chr_read: impl FnMut(u16) -> u8) -> bool {
-        let nmi_before = self.nmi_output;
-        self.nmi_occurred = false;
-
-        // Pre-render scanline (-1)
-        if self.scanline == -1 {
-            if self.cycle == 1 {
+    /// One PPU dot. NMI is signalled through [`Ppu::nmi_line`].
+    pub fn step(&mut self, bus: &mut dyn PpuBus) {
+        // Pre-render (-1) and visible scanlines (0-239)
+        if self.scanline < 240 {
+            if self.scanline == -1 && self.cycle == 1 {
                 // Clear flags at start of pre-render
                 self.status &= 0x1F; // Clear VBlank, sprite overflow, sprite 0 hit
-                self.nmi_output = false;
-            }
-
-            // Background tile fetching (cycles 1-256, 321-336)
-            if self.cycle >= 1 && self.cycle <= 256 {
-                // Background tile fetching happens at specific phases
-                if (self.mask & 0x08) != 0 {
-                    let phase = (self.cycle - 1) % 8;
-                    match phase {
-                        1 => {
-                            // Cycle 2, 10, 18, ...: Fetch nametable byte
-                            if self.cycle < 256 {
-                                self.fetch_tile_data(&mut chr_read);
-                            }
-                        }
-                        3 => {
-                            // Cycle 4, 12, 20, ...: Fetch attribute byte
-                            if self.cycle < 256 {
-                                self.fetch_tile_data(&mut chr_read);
-                            }
-                        }
-                        5 => {
-                            // Cycle 6, 14, 22, ...: Fetch low pattern byte
-                            if self.cycle < 256 {
-                                self.fetch_tile_data(&mut chr_read);
-                            }
-                        }
-                        7 => {
-                            // Cycle 8, 16, 24, ...: Fetch high pattern byte and reload
-                            if self.cycle < 256 {
-                                self.fetch_tile_data(&mut chr_read);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-
-                // Shift registers AFTER fetching (for pre-render, just shift, don't render)
-                if (self.mask & 0x08) != 0 {
-                    self.shift_registers();
-                }
-
-                // Increment Y scroll at cycle 256 - only when rendering is enabled
-            } else if self.cycle == 258 {
-                // Copy X scroll at cycle 258 (VISIBLE_DOTS + 2) - matches C reference
-                if (self.mask & 0x18) != 0 {
-                    self.copy_x();
-                }
-            } else if self.cycle >= 280 && self.cycle <= 304 {
-                // Copy Y scroll when rendering is enabled
-                if (self.mask & 0x18) != 0 {
-                    self.copy_y();
-                }
             }
-            // No sprite evaluation on pre-render scanline - matches C reference
-            // The first evaluation happens on scanline 0 at cycle 320
-            if self.cycle >= 321 && self.cycle <= 336 {
-                // Background tile fetching for next scanline
-                if (self.mask & 0x08) != 0 {
-                    let phase = (self.cycle - 1) % 8;
-                    match phase {
-                        1 => self.fetch_tile_data(&mut chr_read), // Nametable
-                        3 => self.fetch_tile_data(&mut chr_read), // Attribute
-                        5 => self.fetch_tile_data(&mut chr_read), // Low pattern
-                        7 => self.fetch_tile_data(&mut chr_read), // High pattern + reload
-                        _ => {}
-                    }
-                    // Shift after fetching
-                    self.shift_registers();
-                }
-            }
-        }
-        // Visible scanlines (0-239)
-        else if self.scanline >= 0 && self.scanline < 240 {
-            // Render visible pixels (cycles 1-256)
-            if self.cycle >= 1 && self.cycle <= 256 {
-                // Render pixel (matching C reference: reads tile data on-the-fly)
-                let x = self.cycle - 1;
-                let y = self.scanline as u32;
-                if x < 256 && y < 240 {
-                    let idx = (y * 256 + x) as usize;
-                    if idx < self.framebuffer.len() {
-                        // Render pixel if rendering enabled, otherwise use background color
-                        if (self.mask & 0x18) != 0 {
-                            let pixel = self.render_pixel(&mut chr_read);
-                            self.framebuffer[idx] = pixel;
-                        } else {
-                            // Rendering disabled: show background color (palette entry 0)
-                            self.framebuffer[idx] = self.palette[0] & 0x3F;
-                        }
-                    }
-                }
-
-                // Increment coarse X when fine_x wraps (matching C reference)
-                // C code: if(fine_x == 7) { if((ppu->v & COARSE_X) == 31) {...} else ppu->v++; }
-                if (self.mask & 0x08) != 0 {
-                    let fine_x = ((self.fine_x as u32 + x) % 8) as u8;
-                    if fine_x == 7 {
-                        if (self.vram_addr & 0x001F) == 0x001F {
-                            // Wrap to next nametable horizontally
-                            self.vram_addr &= !0x001F;
-                            self.vram_addr ^= 0x0400;
-                        } else {
-                            self.vram_addr += 1;
-                        }
-                    }
-                }
+            if (self.mask & 0x18) != 0 {
+                self.render_cycle(bus);
             }
-            // Increment Y scroll (cycle 257 = VISIBLE_DOTS + 1) - matching C reference
-            else if self.cycle == 257 {
-                if (self.mask & 0x08) != 0 {
-                    // C code: if((ppu->v & FINE_Y) != FINE_Y) ppu->v += 0x1000;
-                    if (self.vram_addr & 0x7000) != 0x7000 {
-                        self.vram_addr += 0x1000;
-                    } else {
-                        self.vram_addr &= !0x7000;
-                        let mut coarse_y = (self.vram_addr & 0x03E0) >> 5;
-                        if coarse_y == 29 {
-                            coarse_y = 0;
-                            self.vram_addr ^= 0x0800; // Switch vertical nametable
-                        } else if coarse_y == 31 {
-                            coarse_y = 0; // Nametable not switched
-                        } else {
-                            coarse_y += 1;
-                        }
-                        self.vram_addr = (self.vram_addr & !0x03E0) | (coarse_y << 5);
-                    }
-                }
-            }
-            // Copy X scroll (cycle 258 = VISIBLE_DOTS + 2) - matching C reference
-            else if self.cycle == 258 && (self.mask & 0x18) != 0 {
-                self.copy_x();
-            }
-
-            // Sprite evaluation at cycle 320 (matching C reference EXACTLY)
-            // C code: diff = scanlines - OAM[Y], evaluates for NEXT scanline
-            // This cache is used for rendering on the NEXT scanline
-            if self.cycle == 320 && (self.mask & 0x18) != 0 {
-                self.evaluate_sprites_for_scanline(self.scanline + 1);
-            }
-
-            // Background tile fetching for next scanline (cycles 321-336)
-            if self.cycle >= 321 && self.cycle <= 336 && (self.mask & 0x08) != 0 {
-                let phase = (self.cycle - 1) % 8;
-                match phase {
-                    1 => self.fetch_tile_data(&mut chr_read), // Nametable
-                    3 => self.fetch_tile_data(&mut chr_read), // Attribute
-                    5 => self.fetch_tile_data(&mut chr_read), // Low pattern
-                    7 => self.fetch_tile_data(&mut chr_read), // High pattern + reload
-                    _ => {}
-                }
-                self.shift_registers();
+            if self.scanline >= 0 && self.cycle >= 1 && self.cycle <= 256 {
+                self.output_pixel();
             }
         }
-        // VBlank scanlines (241-260)
-        else if self.scanline == 241 && self.cycle == 1 {
+        // VBlank scanlines (241-260 on NTSC)
+        else if self.scanline == self.region.vblank_scanline() && self.cycle == 1 {
             // Enter VBlank
             self.status |= 0x80;
-            if (self.ctrl & 0x80) != 0 {
-                self.nmi_occurred = true;
-            }
         }
 
         self.cycle += 1;
@@ -702,8 +489,11 @@ impl Ppu {
         // Odd/even frame timing: on odd frames, if rendering is enabled, skip cycle 340
         let rendering_enabled = (self.mask & 0x18) != 0;
         let is_odd_frame = (self.frame & 1) != 0;
-        let skip_cycle =
-            self.scanline == -1 && self.cycle == 340 && rendering_enabled && is_odd_frame;
+        let skip_cycle = self.scanline == -1
+            && self.cycle == 340
+            && rendering_enabled
+            && is_odd_frame
+            && self.region.skips_odd_frame_dot();
 
         if skip_cycle {
             // Skip cycle 340 on odd frames during pre-render scanline
@@ -714,493 +504,197 @@ impl Ppu {
             self.scanline += 1;
         }
 
-        if self.scanline > 260 {
+        if self.scanline > self.region.last_scanline() {
             self.scanline = -1;
             self.frame += 1;
         }
-
-        self.nmi_output = self.nmi_occurred && (self.ctrl & 0x80) != 0;
-        self.nmi_output && !nmi_before
     }
 
-    fn render_pixel(&mut self, chr_read: &mut impl FnMut(u16) -> u8) -> u8 {
-        let x = self.cycle - 1;
-
-        // Calculate fine_x for this pixel - matches C reference: ((ppu->x + x) % 8)
-        // This combines the fine scroll register with the pixel position
-        let fine_x = ((self.fine_x as u32 + x) % 8) as u8;
-
-        // Render background pixel - match C reference implementation exactly
-        // C code: render_background() in ppu.c
-        let bg_pixel = if (self.mask & 0x08) != 0 {
-            // Check if left 8 pixels are masked (PPUMASK bit 1 = SHOW_BG_8)
-            // C code: if(!(ppu->mask & SHOW_BG_8) && x < 8) return 0;
-            let bg_left_masked = x < 8 && (self.mask & 0x02) == 0;
-
-            if !bg_left_masked {
-                // Match C reference: render_background function
-                // tile_addr = 0x2000 | (ppu->v & 0xFFF)
-                let tile_addr = 0x2000 | (self.vram_addr & 0x0FFF);
-
-                // attr_addr = 0x23C0 | (ppu->v & 0x0C00) | ((ppu->v >> 4) & 0x38) | ((ppu->v >> 2) & 0x07)
-                let attr_addr = 0x23C0
-                    | (self.vram_addr & 0x0C00)
-                    | ((self.vram_addr >> 4) & 0x38)
-                    | ((self.vram_addr >> 2) & 0x07);
-
-                // Read tile ID from nametable
-                let mut no_chr = None;
-                let tile_id = self.read_vram(tile_addr, &mut no_chr);
-
-                // pattern_addr = (tile_id * 16 + ((ppu->v >> 12) & 0x7)) | ((ppu->ctrl & BG_TABLE) << 8)
-                let bg_pt_base = if (self.ctrl & 0x10) != 0 {
-                    0x1000
-                } else {
-                    0x0000
-                };
-                let fine_y = (self.vram_addr >> 12) & 0x07;
-                let pattern_addr = ((tile_id as u16) * 16 + fine_y) | bg_pt_base;
-
-                // Read pattern bytes directly from CHR
-                let pattern_low = chr_read(pattern_addr);
-                let pattern_high = chr_read(pattern_addr + 8);
-
-                // Extract pixel bits - C code uses (7 ^ fine_x) which is same as (7 - fine_x) for 0-7
-                // palette_addr = (read_vram(ppu, pattern_addr) >> (7 ^ fine_x)) & 1
-                // palette_addr |= ((read_vram(ppu, pattern_addr + 8) >> (7 ^ fine_x)) & 1) << 1
-                let bit_pos = 7 ^ fine_x;
-                let palette_idx =
-                    ((pattern_low >> bit_pos) & 1) | (((pattern_high >> bit_pos) & 1) << 1);
-
-                if palette_idx != 0 {
-                    // Read attribute byte
-                    let mut no_chr = None;
-                    let attr = self.read_vram(attr_addr, &mut no_chr);
-                    // C code: (attr >> ((ppu->v >> 4) & 4 | ppu->v & 2)) & 0x3
-                    let shift = ((self.vram_addr >> 4) & 0x04) | (self.vram_addr & 0x02);
-                    let attr_bits = (attr >> shift) & 0x03;
-                    Some((attr_bits << 2) | palette_idx)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        // Check if sprites are masked in left 8 pixels (PPUMASK bit 2 = SHOW_SPRITE_8)
-        // C code: if(ppu->mask & SHOW_SPRITE && ((ppu->mask & SHOW_SPRITE_8) || x >= 8))
-        let sprite_left_masked = x < 8 && (self.mask & 0x04) == 0;
+    /// One dot of memory fetches and scroll updates on a pre-render or
+    /// visible scanline with rendering enabled. Every access goes over the
+    /// bus in hardware order, so mappers see the same address sequence as on
+    /// a real console.
+    ///
+    /// Dots 1-256 fetch the background for this line and 321-336 the first
+    /// two tiles of the next one: nametable, attribute, pattern low and
+    /// pattern high, two dots each. Dots 257-320 fetch the sprites found for
+    /// the next line, 337-340 are two unused nametable fetches.
+    fn render_cycle(&mut self, bus: &mut dyn PpuBus) {
+        let cycle = self.cycle;
+        if cycle == 0 {
+            // Idle dot
+            return;
+        }
+        let phase = (cycle - 1) % 8;
 
-        // Render sprite pixel - matching C reference: render_sprites() in ppu.c
-        // Fetches pattern data on-the-fly like the C implementation
-        let mut sprite_result: Option<u8> = None;
-        let mut back_priority = false;
+        if (2..=257).contains(&cycle) || (322..=337).contains(&cycle) {
+            self.shift_registers();
+        }
+        if phase == 0 && ((9..=257).contains(&cycle) || (329..=337).contains(&cycle)) {
+            self.load_shifters();
+        }
 
-        if (self.mask & 0x10) != 0 && !sprite_left_masked {
-            let y = self.scanline;
-            let sprite_height: i32 = if (self.ctrl & 0x20) != 0 { 16 } else { 8 };
+        if (1..=256).contains(&cycle) || (321..=336).contains(&cycle) {
+            match phase {
+                0 => self.next_tile_id = bus.read(self.tile_addr()),
+                2 => {
+                    let v = self.vram_addr;
+                    let addr = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+                    // Quadrant of the 32x32 attribute area: bit 1 of coarse X and coarse Y
+                    let shift = ((v >> 4) & 0x04) | (v & 0x02);
+                    self.next_tile_attr = (bus.read(addr) >> shift) & 0x03;
+                }
+                4 => self.next_tile_low = bus.read(self.bg_pattern_addr()),
+                6 => self.next_tile_high = bus.read(self.bg_pattern_addr() | 8),
+                7 => self.increment_x(),
+                _ => {}
+            }
+        }
 
-            // Iterate through OAM directly like C reference (render_sprites fetches on-the-fly)
-            for i in 0..self.sprite_count as usize {
-                let oam_idx = (self.sprite_indices[i] as usize) * 4;
-                let tile_x = self.oam[oam_idx + 3] as i32;
-                let x_i32 = x as i32;
+        match cycle {
+            256 => self.increment_y(),
+            257 => {
+                self.copy_x();
+                self.evaluate_sprites();
+            }
+            337 | 339 => {
+                bus.read(self.tile_addr());
+            }
+            280..=304 if self.scanline == -1 => self.copy_y(),
+            _ => {}
+        }
 
-                // C code: if (x - tile_x < 0 || x - tile_x >= 8) continue;
-                if x_i32 - tile_x < 0 || x_i32 - tile_x >= 8 {
-                    continue;
+        if (257..=320).contains(&cycle) {
+            let slot = ((cycle - 257) / 8) as usize;
+            match phase {
+                // Two nametable fetches whose results are discarded
+                0 | 2 => {
+                    bus.read(self.tile_addr());
                 }
-
-                let tile_id = self.oam[oam_idx + 1] as u16;
-                let tile_y = self.oam[oam_idx] as i32 + 1; // C code: tile_y = OAM[i] + 1
-                let attr = self.oam[oam_idx + 2];
-
-                // Calculate x_off and y_off like C reference
-                let mut x_off = (x_i32 - tile_x) % 8;
-                let mut y_off = (y - tile_y) % sprite_height;
-
-                // Handle flipping
-                if (attr & 0x40) == 0 {
-                    // Not horizontally flipped
-                    x_off ^= 7;
+                4 => {
+                    let pattern = bus.read(self.sprite_pattern_addr(slot));
+                    self.sprite_patterns_low[slot] = self.sprite_pattern(slot, pattern);
                 }
-                if (attr & 0x80) != 0 {
-                    // Vertically flipped
-                    y_off ^= sprite_height - 1;
+                6 => {
+                    let pattern = bus.read(self.sprite_pattern_addr(slot) | 8);
+                    self.sprite_patterns_high[slot] = self.sprite_pattern(slot, pattern);
+                    self.sprite_positions[slot] = self.secondary_oam[slot * 4 + 3];
+                    self.sprite_attributes[slot] = self.secondary_oam[slot * 4 + 2];
                 }
+                _ => {}
+            }
+        }
+    }
 
-                // Calculate tile address - matches C reference exactly
-                let tile_addr = if (self.ctrl & 0x20) != 0 {
-                    // 8x16 sprites: C code: y_off = y_off & 7 | ((y_off & 8) << 1);
-                    //               tile_addr = (tile >> 1) * 32 + y_off; tile_addr |= (tile & 1) << 12;
-                    let y_off_adj = (y_off & 7) | ((y_off & 8) << 1);
-
-                    ((tile_id >> 1) * 32 + y_off_adj as u16) | ((tile_id & 1) << 12)
-                } else {
-                    // 8x8 sprites: tile_addr = tile * 16 + y_off + (ctrl & SPRITE_TABLE ? 0x1000 : 0)
-                    let sprite_table = if (self.ctrl & 0x08) != 0 {
-                        0x1000
-                    } else {
-                        0x0000
-                    };
-                    tile_id * 16 + y_off as u16 + sprite_table
-                };
-
-                // Read pattern data directly from CHR (on-the-fly like C reference)
-                let pattern_low = chr_read(tile_addr);
-                let pattern_high = chr_read(tile_addr + 8);
-
-                // Extract pixel bits
-                let palette_addr =
-                    ((pattern_low >> x_off) & 1) | (((pattern_high >> x_off) & 1) << 1);
+    /// Nametable byte for the tile `v` points at
+    fn tile_addr(&self) -> u16 {
+        0x2000 | (self.vram_addr & 0x0FFF)
+    }
 
-                if palette_addr == 0 {
-                    continue;
-                }
+    /// Low plane of the background tile in `next_tile_id`, row fine Y
+    fn bg_pattern_addr(&self) -> u16 {
+        // PPUCTRL bit 4 (0x10): 0 = background from $0000, 1 = background from $1000
+        let bg_pt_base = if (self.ctrl & 0x10) != 0 {
+            0x1000
+        } else {
+            0x0000
+        };
+        let fine_y = (self.vram_addr >> 12) & 0x07;
+        bg_pt_base | ((self.next_tile_id as u16) << 4) | fine_y
+    }
 
-                // Calculate final palette index
-                let palette_idx = (0x10 | ((attr & 0x03) << 2) | palette_addr) as usize;
-                back_priority = (attr & 0x20) != 0; // bit 5 = behind background
-
-                // Sprite 0 hit detection - matches C reference exactly:
-                // if (!(ppu->status & SPRITE_0_HIT) && (ppu->mask & SHOW_BG) && i == 0 && palette_addr && bg_addr && x < 255)
-                if (self.status & 0x40) == 0           // not already set
-                    && (self.mask & 0x08) != 0         // background enabled
-                    && self.sprite_indices[i] == 0    // sprite 0
-                    && bg_pixel.is_some()             // bg pixel opaque
-                    && x < 255
-                // not at x=255
-                {
-                    self.status |= 0x40; // Set sprite 0 hit flag
-                    log::info!("Sprite 0 hit at scanline={}, x={}", self.scanline, x);
-                }
+    /// Low plane of the row of a secondary OAM sprite on the next scanline.
+    /// Empty slots hold $FF and fetch tile $FF, like the real PPU.
+    fn sprite_pattern_addr(&self, slot: usize) -> u16 {
+        let y = self.secondary_oam[slot * 4] as i32;
+        let tile = self.secondary_oam[slot * 4 + 1] as u16;
+        let attr = self.secondary_oam[slot * 4 + 2];
+        let is_8x16 = (self.ctrl & 0x20) != 0;
+        let height = if is_8x16 { 16 } else { 8 };
 
-                // Return this sprite's palette color
-                sprite_result = Some(self.palette[palette_idx] & 0x3F);
-                break; // First opaque sprite wins (C code breaks here too)
-            }
+        // Only the low bits of the row reach the address lines, so a sprite
+        // height change after evaluation can't push the fetch past the tile
+        let mut row = if slot < self.sprite_count as usize {
+            (self.scanline - y) as u16 & (height - 1)
+        } else {
+            0
+        };
+        if (attr & 0x80) != 0 && slot < self.sprite_count as usize {
+            // Vertically flipped
+            row = height - 1 - row;
         }
 
-        // Final pixel composition - matches C reference exactly:
-        // if((!palette_addr && palette_addr_sp) || (palette_addr && palette_addr_sp && !back_priority))
-        //     palette_addr = palette_addr_sp;
-        // palette_addr = ppu->palette[palette_addr];
-
-        // Determine final color:
-        // - If no BG pixel and sprite exists: use sprite
-        // - If BG pixel and sprite exists and sprite in front: use sprite
-        // - Otherwise: use BG (or backdrop if no BG)
-
-        if let (None, Some(sprite)) = (bg_pixel, sprite_result) {
-            // Background transparent, sprite opaque -> use sprite
-            sprite
-        } else if let (Some(_), Some(sprite)) = (bg_pixel, sprite_result) {
-            if !back_priority {
-                // Both opaque, sprite in front -> use sprite
-                sprite
-            } else if let Some(bg) = bg_pixel {
-                self.palette[bg as usize] & 0x3F
-            } else {
-                self.palette[0] & 0x3F
-            }
-        } else if let Some(bg) = bg_pixel {
-            // Use background palette
-            self.palette[bg as usize] & 0x3F
+        if is_8x16 {
+            // 8x16 sprites: tile bit 0 selects the pattern table, the top
+            // half is the even tile and the bottom half the odd one
+            let table = (tile & 0x01) << 12;
+            let tile = (tile & 0xFE) | (row >> 3);
+            table | (tile << 4) | (row & 0x07)
         } else {
-            // Universal background color (palette entry 0)
-            self.palette[0] & 0x3F
+            // 8x8 sprites: pattern table from PPUCTRL bit 3
+            let table = if (self.ctrl & 0x08) != 0 {
+                0x1000
+            } else {
+                0x0000
+            };
+            table | (tile << 4) | row
         }
     }
 
-    pub fn build_framebuffer(&mut self, framebuffer: &mut [u8], _chr_read: impl Fn(u16) -> u8) {
-        // Re-render the visible frame
-        for y in 0..240 {
-            for x in 0..256 {
-                let idx = (y * 256 + x) as usize;
-                if idx >= framebuffer.len() {
-                    continue;
-                }
-
-                // Simplified rendering - would need to simulate PPU state for this pixel
-                // For now, use a simple approach: render from current PPU state
-                if (self.mask & 0x08) == 0 && (self.mask & 0x10) == 0 {
-                    framebuffer[idx] = self.palette[0] & 0x3F;
-                    continue;
-                }
-
-                // This is a simplified version - full accuracy would require
-                // tracking pixel state during rendering
-                framebuffer[idx] = self.palette[0] & 0x3F;
-            }
+    /// A fetched sprite pattern byte as the output shifter sees it:
+    /// horizontally flipped if requested, transparent for empty slots
+    fn sprite_pattern(&self, slot: usize, pattern: u8) -> u8 {
+        if slot >= self.sprite_count as usize {
+            0
+        } else if (self.secondary_oam[slot * 4 + 2] & 0x40) != 0 {
+            pattern.reverse_bits()
+        } else {
+            pattern
         }
     }
 
     fn shift_registers(&mut self) {
-        // Shift pattern registers (shift in new bits from tile data every 8 cycles)
-        // During visible rendering, shift happens every cycle
         self.shift_pattern_low <<= 1;
         self.shift_pattern_high <<= 1;
-        // Attribute shifters repeat their values
-        self.shift_attr_low = (self.shift_attr_low << 1) | (self.shift_attr_low & 0x01);
-        self.shift_attr_high = (self.shift_attr_high << 1) | (self.shift_attr_high & 0x01);
-    }
-
-    fn fetch_tile_data(&mut self, chr_read: &mut impl FnMut(u16) -> u8) {
-        // Fetch phases: 1, 3, 5, 7 (cycles 2, 4, 6, 8 of each 8-cycle group)
-        // Use cycle % 8 to determine fetch phase (cycle 1 = phase 0, cycle 2 = phase 1, etc.)
-        let phase = (self.cycle - 1) % 8;
-
-        match phase {
-            1 => {
-                // Fetch nametable byte
-                // vram_addr bits 10-11 select nametable: 00=$2000, 01=$2400, 10=$2800, 11=$2C00
-                // Nametable base = 0x2000 | ((vram_addr & 0x0C00) >> 8) * 0x400
-                // Simplified: 0x2000 | (vram_addr & 0x0C00) = correct base
-                let nametable_base = 0x2000 | (self.vram_addr & 0x0C00);
-                let addr = nametable_base | (self.vram_addr & 0x03FF);
-                let mut no_chr_read = None;
-                self.next_tile_id = self.read_vram(addr, &mut no_chr_read);
-
-                // Debug: log nametable reads to see what tiles are being fetched
-                if self.scanline >= 0 && self.scanline < 3 && self.cycle < 50 {
-                    debug!("Nametable read: sl={}, cy={}, vram_addr=0x{:04X}, nametable_base=0x{:04X}, nametable_addr=0x{:04X}, tile_id=0x{:02X}",
-                        self.scanline, self.cycle, self.vram_addr, nametable_base, addr, self.next_tile_id);
-                }
-                // Note: vram_addr is NOT incremented during tile fetching - only during rendering
-            }
-            3 => {
-                // Fetch attribute byte
-                // Attribute table is at nametable base + 0x03C0
-                // Use same nametable base as nametable fetch (from vram_addr bits 10-11)
-                let nametable_base = 0x2000 | (self.vram_addr & 0x0C00);
-                let addr = nametable_base
-                    | 0x03C0
-                    | ((self.vram_addr >> 4) & 0x38)
-                    | ((self.vram_addr >> 2) & 0x07);
-                let mut no_chr_read = None;
-                let attr = self.read_vram(addr, &mut no_chr_read);
-                // Attribute quadrant selection: matches C reference
-                // C code: ((ppu->v >> 4) & 4 | ppu->v & 2)
-                // This extracts: bit 1 from coarse_x (v bit 1) and bit 2 from coarse_y (v bit 6)
-                // Result: bit 0 = coarse_x bit 0, bit 1 = coarse_y bit 0
-                let shift = ((self.vram_addr >> 4) & 0x04) | (self.vram_addr & 0x02);
-                self.next_tile_attr = (attr >> shift) & 0x03;
-                // Note: vram_addr is NOT incremented during tile fetching - only during rendering
-            }
-            5 => {
-                // Fetch background pattern table low byte (plane 0)
-                // PPUCTRL bit 4 (0x10): 0 = background from $0000, 1 = background from $1000
-                // Address calculation: base + (tile_id * 16) + fine_y
-                // Each tile is 16 bytes: 8 bytes for low plane (0-7), 8 bytes for high plane (8-15)
-                let bg_pt_base = if (self.ctrl & 0x10) != 0 {
-                    0x1000
-                } else {
-                    0x0000
-                };
-                let fine_y = (self.vram_addr >> 12) & 0x07; // Fine Y scroll (0-7)
-                let low_plane_addr = bg_pt_base | ((self.next_tile_id as u16) << 4) | fine_y;
-                self.next_tile_low = chr_read(low_plane_addr);
-
-                // Debug: log first few fetches with bitplane info
-                if self.scanline >= 0 && self.scanline < 3 && self.cycle < 50 {
-                    log::info!("BG Pattern LOW: sl={}, cy={}, tile=0x{:02X}, fine_y={}, addr=0x{:04X}, value=0x{:02X} (bits: {:08b})",
-                        self.scanline, self.cycle, self.next_tile_id, fine_y, low_plane_addr, self.next_tile_low, self.next_tile_low);
-                }
-            }
-            7 => {
-                // Fetch background pattern table high byte (plane 1)
-                // High plane is 8 bytes after low plane: base + (tile_id * 16) + fine_y + 8
-                let bg_pt_base = if (self.ctrl & 0x10) != 0 {
-                    0x1000
-                } else {
-                    0x0000
-                };
-                let fine_y = (self.vram_addr >> 12) & 0x07; // Fine Y scroll (0-7)
-                let high_plane_addr = bg_pt_base | ((self.next_tile_id as u16) << 4) | fine_y | 8;
-                self.next_tile_high = chr_read(high_plane_addr);
-
-                // Debug: log first few fetches with bitplane composition
-                if self.scanline >= 0 && self.scanline < 3 && self.cycle < 50 {
-                    // Show what pixels would be generated from these bytes
-                    let pixel_samples = (0..8)
-                        .map(|i| {
-                            let low_bit = (self.next_tile_low >> (7 - i)) & 0x01;
-                            let high_bit = (self.next_tile_high >> (7 - i)) & 0x01;
-                            (high_bit << 1) | low_bit
-                        })
-                        .collect::<Vec<_>>();
-                    log::info!("BG Pattern HIGH: sl={}, cy={}, tile=0x{:02X}, fine_y={}, addr=0x{:04X}, value=0x{:02X} (bits: {:08b}) | pixels: {:?}",
-                        self.scanline, self.cycle, self.next_tile_id, fine_y, high_plane_addr, self.next_tile_high, self.next_tile_high, pixel_samples);
-                }
-
-                // Store fetched data and reload shift registers
-                // This happens at the END of the 8-cycle fetch group (phase 7)
-                self.tile_id = self.next_tile_id;
-                self.tile_attr = self.next_tile_attr;
-                self.tile_low = self.next_tile_low;
-                self.tile_high = self.next_tile_high;
-
-                // Reload shift registers: load into high byte (bits 8-15)
-                // The shift registers are 16-bit: bits 8-15 hold the current tile, bits 0-7 hold the next tile
-                // We shift LEFT each cycle, so we load into high byte and read from high byte
-                // After 8 shifts, the tile moves to low byte, and we load next tile into high byte
-                self.shift_pattern_low =
-                    (self.shift_pattern_low & 0x00FF) | ((self.tile_low as u16) << 8);
-                self.shift_pattern_high =
-                    (self.shift_pattern_high & 0x00FF) | ((self.tile_high as u16) << 8);
-
-                // Load attribute bits (repeat across 8 pixels)
-                // Attribute is 2 bits, expanded to 8 bits (one per pixel)
-                let attr_low = if (self.tile_attr & 0x01) != 0 {
-                    0xFF
-                } else {
-                    0x00
-                };
-                let attr_high = if (self.tile_attr & 0x02) != 0 {
-                    0xFF
-                } else {
-                    0x00
-                };
-                self.shift_attr_low = (self.shift_attr_low & 0x00FF) | (attr_low << 8);
-                self.shift_attr_high = (self.shift_attr_high & 0x00FF) | (attr_high << 8);
-
-                // Debug: log shift register loading
-                if self.scanline >= 0 && self.scanline < 3 && self.cycle < 30 {
-                    log::info!("Shift load: sl={}, cy={}, tile=0x{:02X}, tile_low=0x{:02X}, tile_high=0x{:02X}, shift_low=0x{:04X}, shift_high=0x{:04X}", 
-                        self.scanline, self.cycle, self.tile_id, self.tile_low, self.tile_high,
-                        self.shift_pattern_low, self.shift_pattern_high);
-                }
-            }
-            _ => {}
-        }
+        self.shift_attr_low <<= 1;
+        self.shift_attr_high <<= 1;
     }
 
-    #[allow(dead_code)]
-    fn fetch_sprite_data(
-        &mut self,
-        chr_read: &mut impl FnMut(u16) -> u8,
-        sprite_idx: usize,
-        phase: u32,
-    ) {
-        if sprite_idx >= self.sprite_count as usize {
-            return;
-        }
-
-        let sprite_y_oam = self.secondary_oam[sprite_idx * 4] as i32;
-        let sprite_tile = self.secondary_oam[sprite_idx * 4 + 1];
-        let sprite_attr = self.secondary_oam[sprite_idx * 4 + 2];
-        let flip_vertical = (sprite_attr & 0x80) != 0;
-        let is_8x16 = (self.ctrl & 0x20) != 0;
-        let sprite_height = if is_8x16 { 16 } else { 8 };
-
-        // Calculate which row of the sprite we're rendering
-        let mut row = self.scanline - sprite_y_oam;
-
-        // Handle vertical flip
-        if flip_vertical {
-            row = sprite_height - 1 - row;
-        }
-
-        // For 8x16 sprites, determine which tile (top or bottom half)
-        let (tile_index, row_in_tile) = if is_8x16 {
-            // 8x16 sprites: two 8x8 tiles stacked vertically
-            // tile_id bit 0 selects pattern table, bits 1-7 select tile pair
-            let base_tile = sprite_tile & 0xFE;
-            if row < 8 {
-                (base_tile, row as u16)
-            } else {
-                (base_tile | 0x01, (row - 8) as u16)
-            }
+    /// Load the tile fetched over the last 8 dots into the low byte of the
+    /// shifters; pixels are taken from the high byte
+    fn load_shifters(&mut self) {
+        self.shift_pattern_low = (self.shift_pattern_low & 0xFF00) | self.next_tile_low as u16;
+        self.shift_pattern_high = (self.shift_pattern_high & 0xFF00) | self.next_tile_high as u16;
+        // Attribute is 2 bits, expanded to 8 bits (one per pixel)
+        let attr_low = if (self.next_tile_attr & 0x01) != 0 {
+            0xFF
         } else {
-            (sprite_tile, row as u16)
+            0x00
         };
-
-        match phase {
-            0 => {
-                // Fetch sprite pattern low (cycle 0 of sprite fetch)
-                let addr = if is_8x16 {
-                    // 8x16 sprites: pattern table determined by bit 0 of original tile ID
-                    let pattern_table = (sprite_tile as u16 & 0x01) << 12;
-                    pattern_table | ((tile_index as u16) << 4) | row_in_tile
-                } else {
-                    // 8x8 sprites: pattern table from PPUCTRL bit 3
-                    let sprite_pt_base = if (self.ctrl & 0x08) != 0 {
-                        0x1000
-                    } else {
-                        0x0000
-                    };
-                    sprite_pt_base | ((tile_index as u16) << 4) | row_in_tile
-                };
-                let mut pattern = chr_read(addr);
-                // Horizontal flip
-                if (sprite_attr & 0x40) != 0 {
-                    pattern = pattern.reverse_bits();
-                }
-                self.sprite_patterns_low[sprite_idx] = pattern;
-            }
-            4 => {
-                // Fetch sprite pattern high (cycle 4 of sprite fetch)
-                let addr = if is_8x16 {
-                    // 8x16 sprites: pattern table determined by bit 0 of original tile ID
-                    let pattern_table = (sprite_tile as u16 & 0x01) << 12;
-                    pattern_table | ((tile_index as u16) << 4) | row_in_tile | 8
-                } else {
-                    // 8x8 sprites: pattern table from PPUCTRL bit 3
-                    let sprite_pt_base = if (self.ctrl & 0x08) != 0 {
-                        0x1000
-                    } else {
-                        0x0000
-                    };
-                    sprite_pt_base | ((tile_index as u16) << 4) | row_in_tile | 8
-                };
-                let mut pattern = chr_read(addr);
-                // Horizontal flip
-                if (sprite_attr & 0x40) != 0 {
-                    pattern = pattern.reverse_bits();
-                }
-                self.sprite_patterns_high[sprite_idx] = pattern;
-                self.sprite_positions[sprite_idx] = self.secondary_oam[sprite_idx * 4 + 3];
-                self.sprite_attributes[sprite_idx] = sprite_attr;
-            }
-            _ => {}
-        }
+        let attr_high = if (self.next_tile_attr & 0x02) != 0 {
+            0xFF
+        } else {
+            0x00
+        };
+        self.shift_attr_low = (self.shift_attr_low & 0xFF00) | attr_low;
+        self.shift_attr_high = (self.shift_attr_high & 0xFF00) | attr_high;
     }
 
-    fn evaluate_sprites_for_scanline(&mut self, target_scanline: i32) {
-        // Evaluate sprites that will be visible on target_scanline
-        // Called at cycle 320 of the PREVIOUS scanline
-        // C code: int diff = (int)ppu->scanlines - ppu->OAM[i * 4];
-        //         if(diff >= 0 && diff < range) { cache sprite }
-        // Note: NES sprites appear at OAM[Y] + 1, but the evaluation uses diff >= 0
-        // to match the C reference behavior (which includes the off-by-one)
-
+    /// Find the first 8 sprites on the current scanline; they are fetched
+    /// during dots 257-320 and drawn on the next line. OAM Y is one less than
+    /// the sprite's first line.
+    fn evaluate_sprites(&mut self) {
         self.secondary_oam = [0xFF; 0x20];
         self.sprite_count = 0;
 
-        // Don't evaluate if target scanline is out of visible range
-        if !(0..240).contains(&target_scanline) {
-            return;
-        }
-
         let sprite_height: i32 = if (self.ctrl & 0x20) != 0 { 16 } else { 8 };
 
         for i in 0..64 {
-            let y = self.oam[i * 4] as i32;
-            // Check if sprite is visible on target scanline
-            // NES sprites appear at OAM[Y] + 1, so sprite at Y=100 appears at scanlines 101-108
-            // The evaluation uses diff >= 0 to match C reference, which effectively
-            // evaluates for scanline Y (one before the sprite actually appears)
-            let diff = target_scanline - y;
+            let diff = self.scanline - self.oam[i * 4] as i32;
             if diff >= 0 && diff < sprite_height {
                 if self.sprite_count < 8 {
                     let idx = (self.sprite_count * 4) as usize;
-                    self.secondary_oam[idx] = self.oam[i * 4];
-                    self.secondary_oam[idx + 1] = self.oam[i * 4 + 1];
-                    self.secondary_oam[idx + 2] = self.oam[i * 4 + 2];
-                    self.secondary_oam[idx + 3] = self.oam[i * 4 + 3];
+                    self.secondary_oam[idx..idx + 4].copy_from_slice(&self.oam[i * 4..i * 4 + 4]);
                     self.sprite_indices[self.sprite_count as usize] = i as u8;
                     self.sprite_count += 1;
                 } else {
@@ -1212,9 +706,70 @@ impl Ppu {
         }
     }
 
-    #[allow(dead_code)]
+    /// Compose the pixel for dot 1-256 of a visible scanline from the
+    /// background shifters and the sprites fetched on the previous line
+    fn output_pixel(&mut self) {
+        let x = (self.cycle - 1) as usize;
+        let idx = self.scanline as usize * 256 + x;
+
+        if (self.mask & 0x18) == 0 {
+            // Rendering disabled: show background color (palette entry 0)
+            self.framebuffer[idx] = self.palette[0] & 0x3F;
+            return;
+        }
+
+        // PPUMASK bits 1 and 2 hide the background and sprites in the left 8 pixels
+        let mut bg = 0;
+        if (self.mask & 0x08) != 0 && (x >= 8 || (self.mask & 0x02) != 0) {
+            let bit = 15 - self.fine_x as u16;
+            let pattern = (((self.shift_pattern_high >> bit) & 1) << 1)
+                | ((self.shift_pattern_low >> bit) & 1);
+            let attr =
+                (((self.shift_attr_high >> bit) & 1) << 1) | ((self.shift_attr_low >> bit) & 1);
+            if pattern != 0 {
+                bg = ((attr << 2) | pattern) as u8;
+            }
+        }
+
+        let mut sprite = 0;
+        let mut behind_bg = false;
+        if (self.mask & 0x10) != 0 && (x >= 8 || (self.mask & 0x04) != 0) {
+            for i in 0..self.sprite_count as usize {
+                let offset = x as i32 - self.sprite_positions[i] as i32;
+                if !(0..8).contains(&offset) {
+                    continue;
+                }
+                let bit = 7 - offset;
+                let pattern = (((self.sprite_patterns_high[i] >> bit) & 1) << 1)
+                    | ((self.sprite_patterns_low[i] >> bit) & 1);
+                if pattern == 0 {
+                    continue;
+                }
+
+                // Sprite 0 hit: OAM sprite 0 over an opaque background pixel, never at x=255
+                if i == 0 && self.sprite_indices[0] == 0 && bg != 0 && x != 255 {
+                    self.status |= 0x40;
+                }
+
+                let attr = self.sprite_attributes[i];
+                sprite = 0x10 | ((attr & 0x03) << 2) | pattern;
+                behind_bg = (attr & 0x20) != 0;
+                // First opaque sprite wins
+                break;
+            }
+        }
+
+        let color = if sprite != 0 && (bg == 0 || !behind_bg) {
+            sprite
+        } else {
+            bg
+        };
+        self.framebuffer[idx] = self.palette[color as usize] & 0x3F;
+    }
+
     fn increment_x(&mut self) {
         if (self.vram_addr & 0x001F) == 0x001F {
+            // Wrap to next nametable horizontally
             self.vram_addr &= !0x001F;
             self.vram_addr ^= 0x0400;
         } else {
@@ -1222,7 +777,6 @@ impl Ppu {
         }
     }
 
-    #[allow(dead_code)]
     fn increment_y(&mut self) {
         if (self.vram_addr & 0x7000) != 0x7000 {
             self.vram_addr += 0x1000;
@@ -1231,9 +785,9 @@ impl Ppu {
             let mut y = (self.vram_addr & 0x03E0) >> 5;
             if y == 29 {
                 y = 0;
-                self.vram_addr ^= 0x0800;
+                self.vram_addr ^= 0x0800; // Switch vertical nametable
             } else if y == 31 {
-                y = 0;
+                y = 0; // Nametable not switched
             } else {
                 y += 1;
             }
@@ -1248,82 +802,50 @@ impl Ppu {
     fn copy_y(&mut self) {
         self.vram_addr = (self.vram_addr & 0x841F) | (self.vram_addr_temp & 0x7BE0);
     }
+}
 
-    pub fn render_pixel_to_buffer(&mut self, framebuffer: &mut [u8], x: u32, y: u32) {
-        if y >= 240 || x >= 256 {
-            return;
-        }
-
-        let idx = (y * 256 + x) as usize;
-        if idx >= framebuffer.len() {
-            return;
-        }
-
-        if (self.mask & 0x08) == 0 && (self.mask & 0x10) == 0 {
-            // Rendering disabled
-            framebuffer[idx] = self.palette[0] & 0x3F;
-            return;
-        }
-
-        let cycle = x;
-        let bg_pixel = if (self.mask & 0x08) != 0
-            && cycle < 256
-            && self.scanline >= 0
-            && self.scanline < 240
-        {
-            let shift = 15 - self.fine_x as u32 - (cycle % 8);
-            let pattern = ((self.shift_pattern_high >> shift) & 0x01) << 1
-                | ((self.shift_pattern_low >> shift) & 0x01);
-            let attr = ((self.shift_attr_high >> shift) & 0x01) << 1
-                | ((self.shift_attr_low >> shift) & 0x01);
-            if pattern != 0 {
-                Some((attr << 2) | pattern)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+/// Index into palette RAM. $3F10/$3F14/$3F18/$3F1C mirror $3F00/$3F04/$3F08/$3F0C.
+fn palette_index(addr: u16) -> usize {
+    let index = addr & 0x001F;
+    let index = if (index & 0x03) == 0 {
+        index & !0x10
+    } else {
+        index
+    };
+    index as usize
+}
 
-        let _sprite_pixel: Option<u8> = if (self.mask & 0x10) != 0
-            && cycle < 256
-            && self.scanline >= 0
-            && self.scanline < 240
-        {
-            for i in 0..self.sprite_count as usize {
-                let sprite_x = self.sprite_positions[i] as u32;
-                if cycle >= sprite_x && cycle < sprite_x + 8 {
-                    let shift = 7 - (cycle - sprite_x);
-                    let pattern = ((self.sprite_patterns_high[i] >> shift) & 0x01) << 1
-                        | ((self.sprite_patterns_low[i] >> shift) & 0x01);
-                    if pattern != 0 {
-                        let attr = self.sprite_attributes[i];
-                        let priority = (attr & 0x20) == 0;
-                        let palette = (attr & 0x03) << 2 | pattern;
-                        if i == 0 && bg_pixel.is_some() && cycle != 255 {
-                            // Sprite 0 hit
-                            self.status |= 0x40;
-                        }
-                        if bg_pixel.is_none() || priority {
-                            // Look up sprite palette
-                            let palette_addr = (0x10 | palette) as usize;
-                            framebuffer[idx] = self.palette[palette_addr] & 0x3F;
-                            return;
-                        }
-                    }
-                }
-            }
-            None
-        } else {
-            None
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_state_rejects_sprite_count_over_eight() {
+        let mut ppu = Ppu::new();
+        ppu.sprite_count = 9;
+        let mut w = StateWriter::new();
+        ppu.save_state(&mut w);
+        let state = w.into_inner();
+
+        let result = Ppu::new().load_state(&mut StateReader::new(&state));
+        assert!(matches!(
+            result,
+            Err(SaveStateError::Corrupt("sprite count"))
+        ));
+    }
 
-        if let Some(bg) = bg_pixel {
-            // Look up background palette
-            let palette_addr = bg as usize;
-            framebuffer[idx] = self.palette[palette_addr] & 0x3F;
-        } else {
-            framebuffer[idx] = self.palette[0] & 0x3F; // Background color
-        }
+    #[test]
+    fn sprite_row_stays_inside_the_tile() {
+        // Evaluated as 8x16 on row 12, fetched after switching to 8x8
+        let mut ppu = Ppu::new();
+        ppu.scanline = 52;
+        ppu.sprite_count = 1;
+        ppu.secondary_oam[..4].copy_from_slice(&[40, 0x10, 0x80, 0]);
+        // Flipped row 4 of tile $10
+        assert_eq!(ppu.sprite_pattern_addr(0), 0x0103);
+
+        // As 8x16, the flipped row 12 lands in the top tile
+        ppu.ctrl |= 0x20;
+        assert_eq!(ppu.sprite_pattern_addr(0), 0x0103);
     }
 }
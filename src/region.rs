@@ -0,0 +1,197 @@
+//! Console regions (TV systems) and the timing that depends on them
+//!
+//! NTSC, PAL and Dendy consoles run the same chips from different master
+//! clocks and dividers, and the PPU draws a different number of scanlines per
+//! frame. Everything region-specific lives in the tables here, so the CPU,
+//! PPU, APU and bus only ask their [`Region`] for numbers.
+
+/// TV system of the emulated console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "desktop", derive(serde::Serialize, serde::Deserialize))]
+pub enum Region {
+    /// Pick from the ROM header, falling back to the file name, then NTSC
+    #[default]
+    Auto,
+    /// North America / Japan (2C02 PPU, 60 Hz)
+    Ntsc,
+    /// Europe / Australia (2C07 PPU, 50 Hz)
+    Pal,
+    /// Famiclone common in Russia: PAL frame rate with NTSC-like CPU speed
+    Dendy,
+}
+
+// Noise channel periods in CPU cycles
+const NOISE_PERIOD_TABLE_NTSC: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const NOISE_PERIOD_TABLE_PAL: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+// DMC rates in CPU cycles
+const DMC_PERIOD_TABLE_NTSC: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const DMC_PERIOD_TABLE_PAL: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+// Frame sequencer steps in CPU cycles: three quarter-frame steps, then the
+// last step of the 4-step and 5-step sequences
+const FRAME_SEQUENCE_NTSC: [u64; 5] = [7457, 14913, 22371, 29829, 37281];
+const FRAME_SEQUENCE_PAL: [u64; 5] = [8313, 16627, 24939, 33252, 41565];
+
+impl Region {
+    pub const ALL: [Region; 4] = [Region::Auto, Region::Ntsc, Region::Pal, Region::Dendy];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Auto => "Auto",
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        }
+    }
+
+    /// Guess the region from the No-Intro / GoodNES tags in a ROM file name
+    pub fn from_file_name(name: &str) -> Option<Region> {
+        const PAL_TAGS: [&str; 8] = [
+            "(e)",
+            "(europe)",
+            "(pal)",
+            "(a)",
+            "(australia)",
+            "(g)",
+            "(f)",
+            "(s)",
+        ];
+        const DENDY_TAGS: [&str; 2] = ["(dendy)", "(r)"];
+        let name = name.to_ascii_lowercase();
+        if DENDY_TAGS.iter().any(|tag| name.contains(tag)) {
+            Some(Region::Dendy)
+        } else if PAL_TAGS.iter().any(|tag| name.contains(tag)) {
+            Some(Region::Pal)
+        } else if name.contains("(u)") || name.contains("(usa)") || name.contains("(j)") {
+            Some(Region::Ntsc)
+        } else {
+            None
+        }
+    }
+
+    /// Resolve `Auto` to a concrete region; other regions are returned as is
+    pub fn resolve(self, header: Option<Region>, file_name: Option<&str>) -> Region {
+        match self {
+            Region::Auto => header
+                .or_else(|| file_name.and_then(Region::from_file_name))
+                .unwrap_or(Region::Ntsc),
+            region => region,
+        }
+    }
+
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            Region::Auto => 0,
+            Region::Ntsc => 1,
+            Region::Pal => 2,
+            Region::Dendy => 3,
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Option<Region> {
+        match value {
+            0 => Some(Region::Auto),
+            1 => Some(Region::Ntsc),
+            2 => Some(Region::Pal),
+            3 => Some(Region::Dendy),
+            _ => None,
+        }
+    }
+
+    // Clocks. `Auto` is only a setting; if it reaches the emulator core it
+    // behaves as NTSC.
+
+    /// Master clock frequency in Hz
+    pub fn master_clock_rate(self) -> f64 {
+        match self {
+            Region::Pal | Region::Dendy => 26_601_712.0,
+            Region::Auto | Region::Ntsc => 21_477_272.0,
+        }
+    }
+
+    /// Master clock cycles per CPU cycle
+    pub fn master_cycles_per_cpu_cycle(self) -> u64 {
+        match self {
+            Region::Pal => 16,
+            Region::Dendy => 15,
+            Region::Auto | Region::Ntsc => 12,
+        }
+    }
+
+    /// Master clock cycles per PPU dot
+    pub fn master_cycles_per_ppu_cycle(self) -> u64 {
+        match self {
+            Region::Pal | Region::Dendy => 5,
+            Region::Auto | Region::Ntsc => 4,
+        }
+    }
+
+    /// CPU clock frequency in Hz
+    pub fn cpu_clock_rate(self) -> f64 {
+        self.master_clock_rate() / self.master_cycles_per_cpu_cycle() as f64
+    }
+
+    /// Frames per second the console produces
+    pub fn frame_rate(self) -> f64 {
+        match self {
+            Region::Pal | Region::Dendy => 50.0070,
+            Region::Auto | Region::Ntsc => 60.0988,
+        }
+    }
+
+    // PPU frame layout. Scanline -1 is the pre-render line.
+
+    /// Scanline whose dot 1 sets the vblank flag
+    pub fn vblank_scanline(self) -> i32 {
+        match self {
+            // Dendy adds 50 idle lines before vblank instead of after it
+            Region::Dendy => 291,
+            _ => 241,
+        }
+    }
+
+    /// Last scanline before the pre-render line
+    pub fn last_scanline(self) -> i32 {
+        match self {
+            Region::Pal | Region::Dendy => 310,
+            Region::Auto | Region::Ntsc => 260,
+        }
+    }
+
+    /// Whether the pre-render line is one dot shorter on odd frames
+    pub fn skips_odd_frame_dot(self) -> bool {
+        matches!(self, Region::Auto | Region::Ntsc)
+    }
+
+    // APU tables. Dendy's APU counts like an NTSC one.
+
+    pub(crate) fn noise_period_table(self) -> &'static [u16; 16] {
+        match self {
+            Region::Pal => &NOISE_PERIOD_TABLE_PAL,
+            _ => &NOISE_PERIOD_TABLE_NTSC,
+        }
+    }
+
+    pub(crate) fn dmc_period_table(self) -> &'static [u16; 16] {
+        match self {
+            Region::Pal => &DMC_PERIOD_TABLE_PAL,
+            _ => &DMC_PERIOD_TABLE_NTSC,
+        }
+    }
+
+    pub(crate) fn frame_sequence(self) -> &'static [u64; 5] {
+        match self {
+            Region::Pal => &FRAME_SEQUENCE_PAL,
+            _ => &FRAME_SEQUENCE_NTSC,
+        }
+    }
+}
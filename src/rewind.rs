@@ -0,0 +1,291 @@
+//! Rewind buffer
+//!
+//! Keeps a bounded history of save states (one per frame) so a frontend can
+//! step the machine backwards. Only the newest snapshot is stored whole; every
+//! older snapshot is stored as the XOR difference to the snapshot after it,
+//! run-length encoded. Consecutive frames differ in a few hundred bytes of RAM
+//! plus whatever changed on screen, so a delta is usually a small fraction of
+//! a full state.
+//!
+//! Stepping back decodes one delta against the newest snapshot, so it costs
+//! the same no matter how long the history is. Dropping the oldest entry when
+//! a limit is hit needs no re-encoding either, since nothing depends on it.
+
+use std::collections::VecDeque;
+
+pub struct RewindBuffer {
+    /// Most recent snapshot, stored whole
+    latest: Option<Vec<u8>>,
+    /// Encoded `snapshot[n] ^ snapshot[n + 1]`, oldest first
+    deltas: VecDeque<Vec<u8>>,
+    /// Maximum number of snapshots kept (including `latest`)
+    max_snapshots: usize,
+    /// Maximum bytes used by `latest` plus all deltas
+    max_bytes: usize,
+    bytes_used: usize,
+}
+
+impl RewindBuffer {
+    /// Create a buffer holding at most `max_snapshots` frames in `max_bytes` of memory
+    pub fn new(max_snapshots: usize, max_bytes: usize) -> Self {
+        Self {
+            latest: None,
+            deltas: VecDeque::new(),
+            max_snapshots: max_snapshots.max(1),
+            max_bytes,
+            bytes_used: 0,
+        }
+    }
+
+    /// Change the limits, dropping the oldest history if it no longer fits
+    pub fn set_limits(&mut self, max_snapshots: usize, max_bytes: usize) {
+        self.max_snapshots = max_snapshots.max(1);
+        self.max_bytes = max_bytes;
+        self.trim();
+    }
+
+    /// Record a new snapshot as the newest entry
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(prev) = self.latest.take() {
+            if prev.len() == state.len() {
+                let delta = encode_delta(&prev, &state);
+                self.bytes_used = self.bytes_used - prev.len() + delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                // Snapshot layout changed (different game); history is useless
+                self.clear();
+            }
+        }
+        self.bytes_used += state.len();
+        self.latest = Some(state);
+        self.trim();
+    }
+
+    /// Remove and return the newest snapshot
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let state = self.latest.take()?;
+        self.bytes_used -= state.len();
+        if let Some(delta) = self.deltas.pop_back() {
+            let mut prev = state.clone();
+            apply_delta(&mut prev, &delta);
+            self.bytes_used = self.bytes_used - delta.len() + prev.len();
+            self.latest = Some(prev);
+        }
+        Some(state)
+    }
+
+    /// Drop the newest snapshot, which is the frame already on screen, and
+    /// return the one before it, which becomes the newest. `None` once only
+    /// the oldest is left.
+    pub fn step_back(&mut self) -> Option<&[u8]> {
+        if self.len() < 2 {
+            return None;
+        }
+        self.pop();
+        self.latest.as_deref()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.bytes_used = 0;
+    }
+
+    /// Number of snapshots that can still be stepped back through
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Approximate memory held by the buffer, in bytes
+    pub fn memory_used(&self) -> usize {
+        self.bytes_used
+    }
+
+    fn trim(&mut self) {
+        while self.len() > self.max_snapshots
+            || (self.bytes_used > self.max_bytes && !self.deltas.is_empty())
+        {
+            match self.deltas.pop_front() {
+                Some(oldest) => self.bytes_used -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+// Delta encoding: a sequence of (zero run, literal run) pairs over `old ^ new`.
+// Both run lengths are LEB128 varints; literal bytes follow their length.
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0usize;
+    let mut shift = 0;
+    while let Some(&byte) = data.get(*pos) {
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+/// Encode the difference between two equal-length snapshots
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let len = old.len();
+    let mut i = 0;
+    while i < len {
+        let zero_start = i;
+        while i < len && old[i] == new[i] {
+            i += 1;
+        }
+        let literal_start = i;
+        // A literal run ends at the first pair of unchanged bytes, so single
+        // unchanged bytes inside a changed region don't cost a new header
+        while i < len && (old[i] != new[i] || (i + 1 < len && old[i + 1] != new[i + 1])) {
+            i += 1;
+        }
+        write_varint(&mut out, literal_start - zero_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend(
+            old[literal_start..i]
+                .iter()
+                .zip(&new[literal_start..i])
+                .map(|(a, b)| a ^ b),
+        );
+    }
+    out
+}
+
+/// Turn the newer snapshot back into the older one
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literal_len = read_varint(delta, &mut pos);
+        for (byte, x) in state[i..i + literal_len]
+            .iter_mut()
+            .zip(&delta[pos..pos + literal_len])
+        {
+            *byte ^= x;
+        }
+        i += literal_len;
+        pos += literal_len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(old: &[u8], new: &[u8]) -> Vec<u8> {
+        let delta = encode_delta(old, new);
+        let mut state = new.to_vec();
+        apply_delta(&mut state, &delta);
+        assert_eq!(state, old);
+        delta
+    }
+
+    #[test]
+    fn equal_snapshots_encode_to_one_zero_run() {
+        let state = vec![0x5A; 1000];
+        let delta = round_trip(&state, &state);
+        // 1000 unchanged bytes (a two-byte varint), then no literals
+        assert_eq!(delta, [0xE8, 0x07, 0x00]);
+    }
+
+    #[test]
+    fn all_bytes_differ() {
+        let old: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let new: Vec<u8> = old.iter().map(|b| !b).collect();
+        let delta = round_trip(&old, &new);
+        assert_eq!(delta[..3], [0x00, 0xAC, 0x02]);
+        assert_eq!(delta.len(), 3 + 300);
+    }
+
+    #[test]
+    fn single_unchanged_bytes_stay_in_the_literal_run() {
+        let old = [0u8; 8];
+        let new = [1, 0, 1, 0, 1, 0, 0, 1];
+        let delta = round_trip(&old, &new);
+        // One literal run over the first five bytes, then the pair of
+        // unchanged bytes splits off the last one
+        assert_eq!(delta, [0, 5, 1, 0, 1, 0, 1, 2, 1, 1]);
+        // A trailing unchanged byte ends the run rather than joining it
+        round_trip(&[0, 0, 0], &[1, 0, 1]);
+        round_trip(&[0, 0], &[1, 0]);
+    }
+
+    #[test]
+    fn long_runs_use_multi_byte_varints() {
+        for len in [127, 128, 129, 200, 16383, 16384, 20000] {
+            let old = vec![0u8; len * 2];
+            let mut new = old.clone();
+            new[len..].fill(0xFF);
+            let delta = round_trip(&old, &new);
+            let mut pos = 0;
+            assert_eq!(read_varint(&delta, &mut pos), len);
+            assert_eq!(read_varint(&delta, &mut pos), len);
+        }
+    }
+
+    #[test]
+    fn push_pop_trim_track_bytes_used() {
+        let frame = |n: u8| {
+            let mut state = vec![0u8; 256];
+            state[n as usize] = n;
+            state
+        };
+        let mut buffer = RewindBuffer::new(4, usize::MAX);
+        for n in 1..=6 {
+            buffer.push(frame(n));
+        }
+        assert_eq!(buffer.len(), 4);
+        let delta_len = encode_delta(&frame(1), &frame(2)).len();
+        assert_eq!(buffer.memory_used(), 256 + 3 * delta_len);
+
+        assert_eq!(buffer.pop(), Some(frame(6)));
+        assert_eq!(buffer.memory_used(), 256 + 2 * delta_len);
+
+        // Only the oldest deltas go when over the byte limit
+        buffer.set_limits(4, 256 + delta_len);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.memory_used(), 256 + delta_len);
+
+        assert_eq!(buffer.pop(), Some(frame(5)));
+        assert_eq!(buffer.pop(), Some(frame(4)));
+        assert_eq!(buffer.pop(), None);
+        assert_eq!(buffer.memory_used(), 0);
+    }
+
+    #[test]
+    fn step_back_skips_the_frame_on_screen() {
+        let mut buffer = RewindBuffer::new(10, usize::MAX);
+        for n in 0..3u8 {
+            buffer.push(vec![n; 16]);
+        }
+        assert_eq!(buffer.step_back(), Some(&[1u8; 16][..]));
+        assert_eq!(buffer.step_back(), Some(&[0u8; 16][..]));
+        assert_eq!(buffer.step_back(), None);
+        assert_eq!(buffer.len(), 1);
+
+        // A snapshot of another size starts the history over
+        buffer.push(vec![0; 8]);
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.memory_used(), 8);
+    }
+}
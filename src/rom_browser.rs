@@ -9,6 +9,8 @@
 
 use crate::artwork_scraper::ArtworkDownloader;
 use crate::config::Config;
+use crate::header::RomHeader;
+use crate::mapper::FdsDisk;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -27,7 +29,7 @@ pub struct RomEntry {
     pub title: String,
 
     /// Mapper number
-    pub mapper: u8,
+    pub mapper: u16,
 
     /// PRG ROM size in 16KB units
     pub prg_size: u8,
@@ -75,16 +77,18 @@ impl RomEntry {
             fs::read(&path)?
         };
 
-        // Parse iNES header
-        if data.len() < 16 || &data[0..4] != b"NES\x1A" {
-            return Err("Invalid iNES header".into());
-        }
-
-        let prg_size = data[4];
-        let chr_size = data[5];
-        let flags6 = data[6];
-        let flags7 = data[7];
-        let mapper = (flags7 & 0xF0) | (flags6 >> 4);
+        // Parse iNES / NES 2.0 header. Disk images have no ROM sizes to
+        // show and get the placeholder logo.
+        let is_disk = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("fds"));
+        let (mapper, prg_size, chr_size) = if is_disk {
+            FdsDisk::parse(&data)?;
+            (RomHeader::fds().mapper, 0, 0)
+        } else {
+            let header = RomHeader::parse(&data)?;
+            (header.mapper, data[4], data[5])
+        };
 
         // Extract title from filename (better than raw header data)
         let title = path
@@ -675,10 +679,10 @@ impl RomScanner {
             {
                 let path = entry.path();
 
-                // Check for .nes or .zip files
+                // Check for .nes, .fds or .zip files
                 if let Some(ext) = path.extension() {
                     let ext_str = ext.to_string_lossy().to_lowercase();
-                    if ext_str == "nes" || ext_str == "zip" {
+                    if ext_str == "nes" || ext_str == "fds" || ext_str == "zip" {
                         scanned += 1;
 
                         // Log progress every 10 files
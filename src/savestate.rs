@@ -0,0 +1,245 @@
+//! Save state serialization
+//!
+//! A save state is a flat little-endian byte stream. It starts with a small
+//! header (magic, format version, CRC32 of the ROM) followed by the state of
+//! every component in a fixed order. Each component writes and reads its own
+//! fields through [`SaveState`], so the order in `save_state` and `load_state`
+//! must always match. Any change to that layout must bump [`STATE_VERSION`].
+
+use thiserror::Error;
+
+/// Magic bytes at the start of every save state file
+pub const STATE_MAGIC: [u8; 4] = *b"NSST";
+
+/// Current save state format version
+pub const STATE_VERSION: u16 = 12;
+
+#[derive(Error, Debug)]
+pub enum SaveStateError {
+    #[error("Not a Nesium save state")]
+    InvalidMagic,
+    #[error("Unsupported save state version {found} (expected {expected})")]
+    UnsupportedVersion { found: u16, expected: u16 },
+    #[error("Save state was made with a different ROM (CRC32 {found:08X}, loaded ROM is {expected:08X})")]
+    RomMismatch { found: u32, expected: u32 },
+    #[error("Save state is truncated")]
+    UnexpectedEof,
+    #[error("Save state is corrupt: {0}")]
+    Corrupt(&'static str),
+}
+
+/// Implemented by every component that is part of a save state
+pub trait SaveState {
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+/// Appends state fields to a byte buffer
+#[derive(Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Write the file header for a ROM with the given CRC32
+    pub fn header(&mut self, rom_crc32: u32) {
+        self.buf.extend_from_slice(&STATE_MAGIC);
+        self.u16(STATE_VERSION);
+        self.u32(rom_crc32);
+    }
+
+    pub fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn bool(&mut self, v: bool) {
+        self.buf.push(v as u8);
+    }
+
+    pub fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, v: i32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn usize(&mut self, v: usize) {
+        self.u64(v as u64);
+    }
+
+    pub fn f32(&mut self, v: f32) {
+        self.u32(v.to_bits());
+    }
+
+    pub fn f64(&mut self, v: f64) {
+        self.u64(v.to_bits());
+    }
+
+    /// Length-prefixed byte block
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+}
+
+/// Reads state fields back in the order they were written
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Validate the file header against the currently loaded ROM
+    pub fn header(&mut self, rom_crc32: u32) -> Result<(), SaveStateError> {
+        if self.take(4).map_err(|_| SaveStateError::InvalidMagic)? != STATE_MAGIC {
+            return Err(SaveStateError::InvalidMagic);
+        }
+        let version = self.u16()?;
+        if version != STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion {
+                found: version,
+                expected: STATE_VERSION,
+            });
+        }
+        let crc = self.u32()?;
+        if crc != rom_crc32 {
+            return Err(SaveStateError::RomMismatch {
+                found: crc,
+                expected: rom_crc32,
+            });
+        }
+        Ok(())
+    }
+
+    /// Error unless every byte of the state has been consumed
+    pub fn finish(&self) -> Result<(), SaveStateError> {
+        if self.pos == self.data.len() {
+            Ok(())
+        } else {
+            Err(SaveStateError::Corrupt("trailing data"))
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(SaveStateError::UnexpectedEof)?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Corrupt("invalid bool")),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, SaveStateError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn usize(&mut self) -> Result<usize, SaveStateError> {
+        usize::try_from(self.u64()?).map_err(|_| SaveStateError::Corrupt("size out of range"))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, SaveStateError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, SaveStateError> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    /// Length-prefixed byte block of any size
+    pub fn bytes(&mut self) -> Result<Vec<u8>, SaveStateError> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// Length-prefixed byte block that must exactly fill `out`
+    pub fn bytes_into(&mut self, out: &mut [u8]) -> Result<(), SaveStateError> {
+        let len = self.u32()? as usize;
+        if len != out.len() {
+            return Err(SaveStateError::Corrupt("memory block size mismatch"));
+        }
+        out.copy_from_slice(self.take(len)?);
+        Ok(())
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Standard CRC-32 (IEEE), continuing from a previous value (start with 0)
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
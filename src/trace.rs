@@ -365,7 +365,32 @@ fn get_opcode_info(opcode: u8) -> (&'static str, AddrMode) {
         0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA => ("*NOP", AddrMode::Implied),
         0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC => ("*NOP", AddrMode::AbsoluteX),
         0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 => ("*NOP", AddrMode::Immediate),
+        // Unstable stores and loads
+        0x8B => ("*XAA", AddrMode::Immediate),
+        0x93 => ("*SHA", AddrMode::IndirectY),
+        0x9B => ("*TAS", AddrMode::AbsoluteY),
+        0x9C => ("*SHY", AddrMode::AbsoluteX),
+        0x9E => ("*SHX", AddrMode::AbsoluteY),
+        0x9F => ("*SHA", AddrMode::AbsoluteY),
+        0xBB => ("*LAS", AddrMode::AbsoluteY),
+        // JAM (halts the CPU)
+        0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 => {
+            ("*JAM", AddrMode::Implied)
+        }
+    }
+}
 
-        _ => ("???", AddrMode::Implied),
+/// Number of bytes (opcode plus operands) the instruction occupies
+pub fn instruction_length(opcode: u8) -> u16 {
+    match get_opcode_info(opcode).1 {
+        AddrMode::Implied | AddrMode::Accumulator => 1,
+        AddrMode::Immediate
+        | AddrMode::ZeroPage
+        | AddrMode::ZeroPageX
+        | AddrMode::ZeroPageY
+        | AddrMode::IndirectX
+        | AddrMode::IndirectY
+        | AddrMode::Relative => 2,
+        AddrMode::Absolute | AddrMode::AbsoluteX | AddrMode::AbsoluteY | AddrMode::Indirect => 3,
     }
 }
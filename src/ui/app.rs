@@ -8,24 +8,22 @@
 
 use super::audio::AudioOutput;
 use super::launcher::LauncherUi;
-use super::settings::{KeyBindings, Settings, Theme};
-use crate::cartridge::Cartridge;
+use super::settings::{EmulationSettings, KeyBindings, Settings, Theme};
+use crate::cartridge::{Cartridge, CartridgeError};
 use crate::config::Config;
-use crate::cpu::Cpu;
-use crate::memory::MemoryBus;
-use crate::trace::TraceState;
+use crate::input::Button;
+use crate::mapper::{AudioOption, N163Mixing};
+use crate::nes::Nes;
+use crate::region::Region;
+use crate::rewind::RewindBuffer;
 use egui::{Color32, ColorImage, TextureHandle, TextureOptions};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 // NES display dimensions
 const NES_WIDTH: usize = 256;
 const NES_HEIGHT: usize = 240;
 
-// NTSC timing
-const PPU_CYCLES_PER_FRAME: u64 = 89_342;
-const CPU_CYCLES_PER_PPU_CYCLE: f64 = 1.0 / 3.0;
-
 // NES 2C02 PPU palette (RGB values) - 64 colors
 // Matches default 2C02 PPU palette for accurate color reproduction
 // Reference: https://www.nesdev.org/wiki/PPU_palettes
@@ -102,12 +100,8 @@ const NES_PALETTE: [[u8; 3]; 64] = [
 
 /// Emulation state
 struct EmulationState {
-    cpu: Cpu,
-    memory: MemoryBus,
-    ppu_cycles_this_frame: u64,
-    cpu_cycle_accumulator: f64,
-    total_ppu_cycles: u64,
-    trace: TraceState,
+    nes: Nes,
+    rewind: RewindBuffer,
     rom_path: PathBuf,
     rom_name: String,
     has_battery: bool,
@@ -115,148 +109,127 @@ struct EmulationState {
 }
 
 impl EmulationState {
-    fn new(cartridge: Cartridge, rom_path: PathBuf) -> Self {
+    fn new(cartridge: Cartridge, rom_path: PathBuf, settings: &EmulationSettings) -> Self {
         let rom_name = rom_path
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("Unknown")
             .to_string();
-        let has_battery = cartridge.has_ram;
+        let has_battery = cartridge.has_battery();
 
-        let mut memory = MemoryBus::new(cartridge);
-        let mut cpu = Cpu::new();
-        cpu.reset(&mut memory as &mut dyn crate::cpu::CpuBus);
+        let nes = Nes::new(cartridge);
+        let cpu = &nes.cpu;
 
         // Log initial CPU state after reset
         log::info!("Initial CPU state: PC=0x{:04X}, A=0x{:02X}, X=0x{:02X}, Y=0x{:02X}, SP=0x{:02X}, Status=0x{:02X}",
             cpu.pc, cpu.a, cpu.x, cpu.y, cpu.sp, cpu.status);
 
         // Log first few bytes at reset vector (using a temporary read)
-        let mut temp_prg_ram = [0u8; 0x2000];
+        let pc = cpu.pc;
         let first_bytes: Vec<u8> = (0..16)
-            .map(|i| {
-                memory
-                    .cartridge
-                    .cpu_read(cpu.pc.wrapping_add(i), &mut temp_prg_ram)
-            })
+            .map(|i| nes.bus.cartridge.cpu_peek(pc.wrapping_add(i)).unwrap_or(0))
             .collect();
         log::info!("First 16 bytes at PC: {:02X?}", first_bytes);
 
-        Self {
-            cpu,
-            memory,
-            ppu_cycles_this_frame: 0,
-            cpu_cycle_accumulator: 0.0,
-            total_ppu_cycles: 0,
-            trace: TraceState::new(false),
+        let (max_snapshots, max_bytes) = settings.rewind_limits(nes.region().frame_rate());
+
+        let mut emulation = Self {
+            nes,
+            rewind: RewindBuffer::new(max_snapshots, max_bytes),
             rom_path,
             rom_name,
             has_battery,
             frame_count: 0,
+        };
+        emulation.set_region(settings);
+        log::info!("Region: {}", emulation.nes.region().name());
+        emulation
+    }
+
+    /// Switch to the region chosen in the settings, restarting the game if
+    /// it changes. `Auto` uses the ROM header, then the file name.
+    fn set_region(&mut self, settings: &EmulationSettings) {
+        let file_name = self.rom_path.file_name().and_then(|name| name.to_str());
+        let region = settings
+            .region
+            .resolve(self.nes.bus.cartridge.header.region(), file_name);
+        if region != self.nes.region() {
+            self.nes.set_region(region);
+            self.nes.power_cycle();
+            self.rewind.clear();
+            let (max_snapshots, max_bytes) = settings.rewind_limits(region.frame_rate());
+            self.rewind.set_limits(max_snapshots, max_bytes);
         }
     }
 
     fn step_frame(&mut self) {
-        self.ppu_cycles_this_frame = 0;
-
         // Log first few frames for debugging
         if self.frame_count < 3 {
+            let cpu = &self.nes.cpu;
             log::info!("Frame {}: PC=0x{:04X}, A=0x{:02X}, X=0x{:02X}, Y=0x{:02X}, SP=0x{:02X}, Status=0x{:02X}",
-                self.frame_count, self.cpu.pc, self.cpu.a, self.cpu.x, self.cpu.y, self.cpu.sp, self.cpu.status);
+                self.frame_count, cpu.pc, cpu.a, cpu.x, cpu.y, cpu.sp, cpu.status);
         }
         self.frame_count += 1;
 
-        while self.ppu_cycles_this_frame < PPU_CYCLES_PER_FRAME {
-            let nmi_triggered = self.memory.step_ppu();
-
-            if nmi_triggered {
-                self.cpu
-                    .trigger_nmi(&mut self.memory as &mut dyn crate::cpu::CpuBus);
-            }
-
-            if self.memory.mapper_irq_pending() && (self.cpu.status & crate::cpu::FLAG_I) == 0 {
-                self.memory.acknowledge_mapper_irq();
-                self.cpu
-                    .trigger_irq(&mut self.memory as &mut dyn crate::cpu::CpuBus);
-            }
-
-            self.cpu_cycle_accumulator += CPU_CYCLES_PER_PPU_CYCLE;
-
-            while self.cpu_cycle_accumulator >= 1.0 {
-                if self.trace.enabled {
-                    self.trace.ppu_cycle_count = self.total_ppu_cycles;
-                }
-                let cpu_cycles = self.cpu.step(
-                    &mut self.memory as &mut dyn crate::cpu::CpuBus,
-                    &mut self.trace,
-                );
-                self.cpu_cycle_accumulator -= cpu_cycles as f64;
+        self.nes.run_frame();
+    }
 
-                let irq = self.memory.step_apu(cpu_cycles);
-                if irq && (self.cpu.status & crate::cpu::FLAG_I) == 0 {
-                    self.cpu
-                        .trigger_irq(&mut self.memory as &mut dyn crate::cpu::CpuBus);
-                }
-            }
+    /// Record the current machine state for rewind
+    fn record_rewind(&mut self) {
+        self.rewind.push(self.nes.save_state());
+    }
 
-            self.ppu_cycles_this_frame += 1;
-            self.total_ppu_cycles += 1;
+    /// Step one frame back in the rewind history. Returns false when the
+    /// history is exhausted, leaving the oldest frame on screen.
+    fn rewind_frame(&mut self) -> bool {
+        let Some(state) = self.rewind.step_back() else {
+            return false;
+        };
+        if let Err(e) = self.nes.load_state(state) {
+            log::error!("Rewind failed: {}", e);
+            self.rewind.clear();
+            return false;
         }
+        // Audio stays muted while rewinding
+        self.nes.take_audio_samples();
+        true
     }
 
     fn get_framebuffer(&self) -> &[u8] {
-        &self.memory.ppu.framebuffer
+        self.nes.framebuffer()
     }
 
-    fn handle_input(&mut self, button: NesButton, pressed: bool) {
-        match button {
-            NesButton::A => self.memory.input.controller1.a = pressed,
-            NesButton::B => self.memory.input.controller1.b = pressed,
-            NesButton::Select => self.memory.input.controller1.select = pressed,
-            NesButton::Start => self.memory.input.controller1.start = pressed,
-            NesButton::Up => self.memory.input.controller1.up = pressed,
-            NesButton::Down => self.memory.input.controller1.down = pressed,
-            NesButton::Left => self.memory.input.controller1.left = pressed,
-            NesButton::Right => self.memory.input.controller1.right = pressed,
-        }
+    fn handle_input(&mut self, button: Button, pressed: bool) {
+        self.nes.set_button(0, button, pressed);
     }
 
     fn get_audio_samples(&mut self) -> Vec<f32> {
-        self.memory.apu.take_samples()
+        self.nes.take_audio_samples()
     }
 
     fn adjust_audio_rate(&mut self, queue_size: usize, target_size: usize) {
-        self.memory.apu.adjust_sample_rate(queue_size, target_size);
+        self.nes.adjust_audio_rate(queue_size, target_size);
     }
 
     fn get_sram(&self) -> &[u8] {
-        &self.memory.prg_ram
+        self.nes.sram()
     }
 
     fn set_sram(&mut self, data: &[u8]) {
-        let len = data.len().min(self.memory.prg_ram.len());
-        self.memory.prg_ram[..len].copy_from_slice(&data[..len]);
+        self.nes.set_sram(data);
     }
 
     fn reset(&mut self) {
-        self.cpu
-            .reset(&mut self.memory as &mut dyn crate::cpu::CpuBus);
-        self.ppu_cycles_this_frame = 0;
-        self.cpu_cycle_accumulator = 0.0;
+        self.nes.reset();
     }
-}
 
-/// NES controller buttons
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum NesButton {
-    A,
-    B,
-    Select,
-    Start,
-    Up,
-    Down,
-    Left,
-    Right,
+    fn power_cycle(&mut self) {
+        self.nes.power_cycle();
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.rom_path.with_extension("state")
+    }
 }
 
 /// UI dialog state
@@ -266,7 +239,7 @@ struct DialogState {
     #[allow(dead_code)]
     show_settings: bool,
     show_input_config: bool,
-    input_config_binding: Option<NesButton>,
+    input_config_binding: Option<Button>,
 }
 
 /// Application mode
@@ -299,6 +272,7 @@ pub struct NesiumApp {
     paused: bool,
     fast_forward: bool,
     fast_forward_speed: f32,
+    rewinding: bool,
     frame_advance_requested: bool,
 
     // Pending ROM to load (for drag-and-drop)
@@ -364,6 +338,7 @@ impl NesiumApp {
             last_emulation_frame_time: Instant::now(),
             paused: false,
             fast_forward: false,
+            rewinding: false,
             fast_forward_speed: 1.0,
             frame_advance_requested: false,
             pending_rom: rom_path,
@@ -380,14 +355,30 @@ impl NesiumApp {
         // Save current game if needed
         self.save_sram();
 
-        match Cartridge::load(path.to_str().unwrap_or("")) {
+        let is_disk = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("fds"));
+        let cartridge = if is_disk {
+            self.load_disk(&path)
+        } else {
+            Cartridge::load(path.to_str().unwrap_or(""))
+        };
+        match cartridge {
             Ok(cartridge) => {
                 log::info!("Loaded ROM: {}", path.display());
-                log::info!("Mapper: {}", cartridge.mapper_id);
-                log::info!("PRG ROM: {} KB", cartridge.prg_rom.len() / 1024);
-                log::info!("CHR ROM: {} KB", cartridge.chr_rom.len() / 1024);
+                log::info!(
+                    "Mapper: {}.{}",
+                    cartridge.header.mapper,
+                    cartridge.header.submapper
+                );
+                log::info!("PRG ROM: {} KB", cartridge.memory.prg_rom.len() / 1024);
+                log::info!("CHR ROM: {} KB", cartridge.memory.chr_rom.len() / 1024);
 
-                let mut emulation = EmulationState::new(cartridge, path.clone());
+                let mut emulation =
+                    EmulationState::new(cartridge, path.clone(), &self.settings.emulation);
+                emulation
+                    .nes
+                    .set_audio_option(AudioOption::N163Mixing(self.settings.audio.n163_mixing));
 
                 // Load save file if present
                 self.load_sram_for(&mut emulation);
@@ -410,8 +401,35 @@ impl NesiumApp {
         }
     }
 
+    /// Load a Famicom Disk System image, booting the BIOS from the settings
+    fn load_disk(&self, path: &Path) -> Result<Cartridge, CartridgeError> {
+        let bios_path = self
+            .settings
+            .emulation
+            .fds_bios
+            .clone()
+            .unwrap_or_else(|| path.with_file_name("disksys.rom"));
+        let bios = std::fs::read(&bios_path).inspect_err(|_| {
+            log::error!(
+                "FDS BIOS not found at {} (choose it under Settings > FDS BIOS)",
+                bios_path.display()
+            );
+        })?;
+        Cartridge::load_fds(&std::fs::read(path)?, bios)
+    }
+
     fn save_sram(&self) {
         if let Some(ref emu) = self.emulation {
+            // FDS games save to the disk itself; a modified disk is written
+            // out whole, leaving the original image untouched
+            if let Some(disk) = emu.nes.modified_disk() {
+                let save_path = emu.rom_path.with_extension("sav");
+                if let Err(e) = std::fs::write(&save_path, disk) {
+                    log::error!("Failed to save disk: {}", e);
+                } else {
+                    log::info!("Saved disk to: {}", save_path.display());
+                }
+            }
             if emu.has_battery {
                 let sram = emu.get_sram();
                 if !sram.iter().all(|&b| b == 0) {
@@ -427,6 +445,15 @@ impl NesiumApp {
     }
 
     fn load_sram_for(&self, emulation: &mut EmulationState) {
+        if emulation.nes.disk_side_count() > 0 {
+            let save_path = emulation.rom_path.with_extension("sav");
+            if let Ok(data) = std::fs::read(&save_path) {
+                match emulation.nes.restore_disk(&data) {
+                    Ok(()) => log::info!("Loaded saved disk: {}", save_path.display()),
+                    Err(e) => log::error!("Failed to load {}: {}", save_path.display(), e),
+                }
+            }
+        }
         if emulation.has_battery {
             let save_path = emulation.rom_path.with_extension("sav");
             if save_path.exists() {
@@ -438,9 +465,45 @@ impl NesiumApp {
         }
     }
 
+    fn save_state(&self) {
+        if let Some(ref emu) = self.emulation {
+            let state_path = emu.state_path();
+            if let Err(e) = std::fs::write(&state_path, emu.nes.save_state()) {
+                log::error!("Failed to save state: {}", e);
+            } else {
+                log::info!("Saved state to: {}", state_path.display());
+            }
+        }
+    }
+
+    fn load_state(&mut self) {
+        if let Some(ref mut emu) = self.emulation {
+            let state_path = emu.state_path();
+            match std::fs::read(&state_path) {
+                Ok(data) => match emu.nes.load_state(&data) {
+                    Ok(()) => log::info!("Loaded state from: {}", state_path.display()),
+                    Err(e) => log::error!("Failed to load state: {}", e),
+                },
+                Err(e) => log::error!("Failed to read {}: {}", state_path.display(), e),
+            }
+        }
+    }
+
+    /// Flip the disk to its next side (or the next disk), the way a player
+    /// would take it out and put it back in
+    fn switch_disk_side(&mut self) {
+        if let Some(ref mut emu) = self.emulation {
+            let sides = emu.nes.disk_side_count();
+            if sides > 0 {
+                let next = emu.nes.disk_side().map_or(0, |side| (side + 1) % sides);
+                emu.nes.insert_disk(Some(next));
+            }
+        }
+    }
+
     fn open_rom_dialog(&mut self) {
         let mut dialog = rfd::FileDialog::new()
-            .add_filter("NES ROM", &["nes"])
+            .add_filter("NES ROM", &["nes", "fds"])
             .add_filter("All files", &["*"]);
 
         if let Some(ref dir) = self.settings.last_rom_directory {
@@ -452,6 +515,20 @@ impl NesiumApp {
         }
     }
 
+    /// Frames per second of the emulated console (NTSC when nothing is loaded)
+    fn console_frame_rate(&self) -> f64 {
+        self.emulation
+            .as_ref()
+            .map_or(Region::Ntsc, |emu| emu.nes.region())
+            .frame_rate()
+    }
+
+    fn apply_region(&mut self) {
+        if let Some(ref mut emu) = self.emulation {
+            emu.set_region(&self.settings.emulation);
+        }
+    }
+
     fn update_emulation(&mut self, ctx: &egui::Context) {
         // Handle pending ROM load
         if let Some(path) = self.pending_rom.take() {
@@ -466,8 +543,9 @@ impl NesiumApp {
         let should_run = self.emulation.is_some() && (!self.paused || self.frame_advance_requested);
 
         if should_run {
-            // Target frame time: 60fps = ~16.67ms per frame (NES-master approach)
-            let target_frame_time = std::time::Duration::from_secs_f64(1.0 / 60.0);
+            // Target frame time follows the console's refresh rate (~16.64ms on NTSC)
+            let target_frame_time =
+                std::time::Duration::from_secs_f64(1.0 / self.console_frame_rate());
 
             // Calculate speed multiplier
             let speed_multiplier = if self.fast_forward {
@@ -507,10 +585,20 @@ impl NesiumApp {
                 }
             };
 
+            let rewind_enabled = self.settings.emulation.rewind_enabled;
+            let rewinding = rewind_enabled && self.rewinding && !self.frame_advance_requested;
+
             for _ in 0..frames_to_run {
-                // Step emulation
+                // Step emulation (or step back through the rewind history)
                 if let Some(ref mut emu) = self.emulation {
-                    emu.step_frame();
+                    if rewinding {
+                        emu.rewind_frame();
+                    } else {
+                        emu.step_frame();
+                        if rewind_enabled {
+                            emu.record_rewind();
+                        }
+                    }
                 }
 
                 // Update texture from framebuffer (copy to avoid borrow issues)
@@ -524,7 +612,9 @@ impl NesiumApp {
                 }
 
                 // Handle audio
-                if let Some(ref mut emu) = self.emulation {
+                if rewinding {
+                    // Nothing to queue; the APU output is discarded while rewinding
+                } else if let Some(ref mut emu) = self.emulation {
                     let samples = emu.get_audio_samples();
                     if let Some(ref audio) = self.audio {
                         audio.queue_samples(&samples);
@@ -546,7 +636,7 @@ impl NesiumApp {
         let elapsed = self.last_fps_time.elapsed();
         if elapsed.as_secs_f32() >= 1.0 {
             self.fps = self.fps_counter as f32 / elapsed.as_secs_f32();
-            self.speed_percent = (self.fps / 60.0988) * 100.0;
+            self.speed_percent = (self.fps / self.console_frame_rate() as f32) * 100.0;
             self.fps_counter = 0;
             self.last_fps_time = Instant::now();
         }
@@ -582,14 +672,14 @@ impl NesiumApp {
         ctx.input(|i| {
             // Check each bound key
             let buttons = [
-                (bindings.a, NesButton::A),
-                (bindings.b, NesButton::B),
-                (bindings.select, NesButton::Select),
-                (bindings.start, NesButton::Start),
-                (bindings.up, NesButton::Up),
-                (bindings.down, NesButton::Down),
-                (bindings.left, NesButton::Left),
-                (bindings.right, NesButton::Right),
+                (bindings.a, Button::A),
+                (bindings.b, Button::B),
+                (bindings.select, Button::Select),
+                (bindings.start, Button::Start),
+                (bindings.up, Button::Up),
+                (bindings.down, Button::Down),
+                (bindings.left, Button::Left),
+                (bindings.right, Button::Right),
             ];
 
             for (key, button) in buttons {
@@ -633,7 +723,28 @@ impl NesiumApp {
 
             // Fast forward toggle
             self.fast_forward = i.key_down(egui::Key::F);
+
+            // Rewind (hold)
+            self.rewinding = i.key_down(egui::Key::Backspace);
+        });
+
+        // F5 / F8 - Save / load state, F6 - Switch disk side
+        let (save_state, load_state, switch_disk) = ctx.input(|i| {
+            (
+                i.key_pressed(egui::Key::F5),
+                i.key_pressed(egui::Key::F8),
+                i.key_pressed(egui::Key::F6),
+            )
         });
+        if save_state {
+            self.save_state();
+        }
+        if load_state {
+            self.load_state();
+        }
+        if switch_disk {
+            self.switch_disk_side();
+        }
     }
 
     fn render_menu_bar(&mut self, ctx: &egui::Context) {
@@ -708,11 +819,65 @@ impl NesiumApp {
                         ui.close_menu();
                     }
 
+                    if ui.button("⏻ Power Cycle").clicked() {
+                        if let Some(ref mut emu) = self.emulation {
+                            emu.power_cycle();
+                        }
+                        ui.close_menu();
+                    }
+
                     if ui.button("⏭ Frame Advance").clicked() {
                         self.frame_advance_requested = true;
                         ui.close_menu();
                     }
 
+                    let disk_sides = self
+                        .emulation
+                        .as_ref()
+                        .map_or(0, |emu| emu.nes.disk_side_count());
+                    if disk_sides > 0 {
+                        ui.separator();
+
+                        if ui.button("🔃 Switch Disk Side (F6)").clicked() {
+                            self.switch_disk_side();
+                            ui.close_menu();
+                        }
+
+                        ui.menu_button("💿 Disk", |ui| {
+                            if let Some(ref mut emu) = self.emulation {
+                                let current = emu.nes.disk_side();
+                                for side in 0..disk_sides {
+                                    let label = format!(
+                                        "Disk {} Side {}",
+                                        side / 2 + 1,
+                                        if side % 2 == 0 { 'A' } else { 'B' }
+                                    );
+                                    if ui.radio(current == Some(side), label).clicked() {
+                                        emu.nes.insert_disk(Some(side));
+                                        ui.close_menu();
+                                    }
+                                }
+                                ui.separator();
+                                if ui.button("⏏ Eject").clicked() {
+                                    emu.nes.insert_disk(None);
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+
+                    ui.separator();
+
+                    if ui.button("💾 Save State (F5)").clicked() {
+                        self.save_state();
+                        ui.close_menu();
+                    }
+
+                    if ui.button("📂 Load State (F8)").clicked() {
+                        self.load_state();
+                        ui.close_menu();
+                    }
+
                     ui.separator();
 
                     ui.menu_button("Speed", |ui| {
@@ -806,6 +971,94 @@ impl NesiumApp {
                         }
                     });
 
+                    ui.menu_button("⏪ Rewind", |ui| {
+                        let mut limits_changed = false;
+                        if ui
+                            .checkbox(
+                                &mut self.settings.emulation.rewind_enabled,
+                                "Enable Rewind (hold Backspace)",
+                            )
+                            .changed()
+                        {
+                            if !self.settings.emulation.rewind_enabled {
+                                if let Some(ref mut emu) = self.emulation {
+                                    emu.rewind.clear();
+                                }
+                            }
+                            self.settings.save();
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Length (s):");
+                            limits_changed |= ui
+                                .add(egui::Slider::new(
+                                    &mut self.settings.emulation.rewind_seconds,
+                                    5..=300,
+                                ))
+                                .changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Memory (MB):");
+                            limits_changed |= ui
+                                .add(egui::Slider::new(
+                                    &mut self.settings.emulation.rewind_memory_mb,
+                                    16..=1024,
+                                ))
+                                .changed();
+                        });
+                        if let Some(ref emu) = self.emulation {
+                            ui.label(format!(
+                                "History: {} frames, {:.1} MB",
+                                emu.rewind.len(),
+                                emu.rewind.memory_used() as f64 / (1024.0 * 1024.0)
+                            ));
+                        }
+                        if limits_changed {
+                            let (max_snapshots, max_bytes) = self
+                                .settings
+                                .emulation
+                                .rewind_limits(self.console_frame_rate());
+                            if let Some(ref mut emu) = self.emulation {
+                                emu.rewind.set_limits(max_snapshots, max_bytes);
+                            }
+                            self.settings.save();
+                        }
+                    });
+
+                    ui.menu_button("🌍 Region", |ui| {
+                        for region in Region::ALL {
+                            if ui
+                                .radio_value(
+                                    &mut self.settings.emulation.region,
+                                    region,
+                                    region.name(),
+                                )
+                                .clicked()
+                            {
+                                self.apply_region();
+                                self.settings.save();
+                                ui.close_menu();
+                            }
+                        }
+                    });
+
+                    ui.menu_button("💿 FDS BIOS", |ui| {
+                        match self.settings.emulation.fds_bios {
+                            Some(ref path) => ui.label(path.display().to_string()),
+                            None => ui.label("Not set: disksys.rom next to the disk image"),
+                        };
+                        if ui.button("Choose...").clicked() {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("FDS BIOS", &["rom", "bin"])
+                                .add_filter("All files", &["*"])
+                                .pick_file()
+                            {
+                                self.settings.emulation.fds_bios = Some(path);
+                                self.settings.save();
+                            }
+                            ui.close_menu();
+                        }
+                    });
+
                     ui.menu_button("🔊 Audio", |ui| {
                         ui.horizontal(|ui| {
                             ui.label("Volume:");
@@ -832,6 +1085,23 @@ impl NesiumApp {
                             }
                             self.settings.save();
                         }
+
+                        ui.separator();
+                        ui.label("Namco 163 channels:");
+                        for (mixing, label) in [
+                            (N163Mixing::Mixed, "Mixed"),
+                            (N163Mixing::Multiplexed, "Multiplexed (authentic)"),
+                        ] {
+                            if ui
+                                .radio_value(&mut self.settings.audio.n163_mixing, mixing, label)
+                                .clicked()
+                            {
+                                if let Some(ref mut emu) = self.emulation {
+                                    emu.nes.set_audio_option(AudioOption::N163Mixing(mixing));
+                                }
+                                self.settings.save();
+                            }
+                        }
                     });
                 });
 
@@ -863,6 +1133,8 @@ impl NesiumApp {
                     // Pause indicator
                     if self.paused {
                         ui.colored_label(Color32::from_rgb(255, 180, 0), "⏸ PAUSED");
+                    } else if self.rewinding && self.settings.emulation.rewind_enabled {
+                        ui.colored_label(Color32::from_rgb(100, 180, 255), "⏪ REWIND");
                     } else if self.fast_forward {
                         ui.colored_label(Color32::from_rgb(0, 200, 100), "⏩ FAST");
                     } else {
@@ -1042,7 +1314,8 @@ impl NesiumApp {
 
                         ui.label(egui::RichText::new("Features:").strong());
                         ui.label("• Cycle-accurate CPU, PPU, APU");
-                        ui.label("• NROM, MMC1, UxROM, MMC3 mappers");
+                        ui.label("• NROM, MMC1, UxROM, CNROM, MMC2, MMC3, MMC4, MMC5, MMC6, RAMBO-1, TxSROM, TQROM, Namco 108, N163, VRC2, VRC4, VRC6, VRC7, FME-7, Bandai FCG, AxROM, GxROM, BNROM, Color Dreams, UNROM 512, Action 53, GTROM, multicart mappers");
+                        ui.label("• Famicom Disk System, with FDS sound");
                         ui.label("• Battery-backed SRAM saves");
 
                         ui.add_space(16.0);
@@ -1077,18 +1350,14 @@ impl NesiumApp {
                     .spacing([20.0, 8.0])
                     .show(ui, |ui| {
                         let bindings = [
-                            ("A Button", NesButton::A, self.settings.key_bindings.a),
-                            ("B Button", NesButton::B, self.settings.key_bindings.b),
-                            (
-                                "Select",
-                                NesButton::Select,
-                                self.settings.key_bindings.select,
-                            ),
-                            ("Start", NesButton::Start, self.settings.key_bindings.start),
-                            ("Up", NesButton::Up, self.settings.key_bindings.up),
-                            ("Down", NesButton::Down, self.settings.key_bindings.down),
-                            ("Left", NesButton::Left, self.settings.key_bindings.left),
-                            ("Right", NesButton::Right, self.settings.key_bindings.right),
+                            ("A Button", Button::A, self.settings.key_bindings.a),
+                            ("B Button", Button::B, self.settings.key_bindings.b),
+                            ("Select", Button::Select, self.settings.key_bindings.select),
+                            ("Start", Button::Start, self.settings.key_bindings.start),
+                            ("Up", Button::Up, self.settings.key_bindings.up),
+                            ("Down", Button::Down, self.settings.key_bindings.down),
+                            ("Left", Button::Left, self.settings.key_bindings.left),
+                            ("Right", Button::Right, self.settings.key_bindings.right),
                         ];
 
                         for (name, button, key) in bindings {
@@ -1116,14 +1385,14 @@ impl NesiumApp {
                         for key in egui::Key::ALL {
                             if i.key_pressed(*key) {
                                 match binding_button {
-                                    NesButton::A => self.settings.key_bindings.a = *key,
-                                    NesButton::B => self.settings.key_bindings.b = *key,
-                                    NesButton::Select => self.settings.key_bindings.select = *key,
-                                    NesButton::Start => self.settings.key_bindings.start = *key,
-                                    NesButton::Up => self.settings.key_bindings.up = *key,
-                                    NesButton::Down => self.settings.key_bindings.down = *key,
-                                    NesButton::Left => self.settings.key_bindings.left = *key,
-                                    NesButton::Right => self.settings.key_bindings.right = *key,
+                                    Button::A => self.settings.key_bindings.a = *key,
+                                    Button::B => self.settings.key_bindings.b = *key,
+                                    Button::Select => self.settings.key_bindings.select = *key,
+                                    Button::Start => self.settings.key_bindings.start = *key,
+                                    Button::Up => self.settings.key_bindings.up = *key,
+                                    Button::Down => self.settings.key_bindings.down = *key,
+                                    Button::Left => self.settings.key_bindings.left = *key,
+                                    Button::Right => self.settings.key_bindings.right = *key,
                                 }
                                 self.dialogs.input_config_binding = None;
                                 self.settings.save();
@@ -1166,13 +1435,16 @@ impl NesiumApp {
                     if path
                         .extension()
                         .and_then(|e| e.to_str())
-                        .map(|e| e.eq_ignore_ascii_case("nes"))
+                        .map(|e| e.eq_ignore_ascii_case("nes") || e.eq_ignore_ascii_case("fds"))
                         .unwrap_or(false)
                     {
                         log::info!("Loading ROM from dropped file: {}", path.display());
                         self.pending_rom = Some(path.clone());
                     } else {
-                        log::warn!("Dropped file is not a .nes file: {}", path.display());
+                        log::warn!(
+                            "Dropped file is not a .nes or .fds file: {}",
+                            path.display()
+                        );
                     }
                 }
                 // If no path, log warning
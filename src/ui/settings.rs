@@ -1,5 +1,7 @@
 //! Settings persistence and configuration for Nesium
 
+use crate::mapper::N163Mixing;
+use crate::region::Region;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::path::PathBuf;
@@ -64,6 +66,9 @@ impl Default for VideoSettings {
 pub struct AudioSettings {
     pub volume: f32,
     pub muted: bool,
+    /// How Namco 163 wavetable channels are mixed
+    #[serde(default)]
+    pub n163_mixing: N163Mixing,
 }
 
 impl Default for AudioSettings {
@@ -71,6 +76,7 @@ impl Default for AudioSettings {
         Self {
             volume: 0.7,
             muted: false,
+            n163_mixing: N163Mixing::default(),
         }
     }
 }
@@ -80,6 +86,27 @@ impl Default for AudioSettings {
 pub struct EmulationSettings {
     pub speed_multiplier: f32,
     pub rewind_enabled: bool,
+    /// How far back rewind can go
+    #[serde(default = "default_rewind_seconds")]
+    pub rewind_seconds: u32,
+    /// Memory cap for the rewind history, in megabytes
+    #[serde(default = "default_rewind_memory_mb")]
+    pub rewind_memory_mb: u32,
+    /// TV system to emulate; `Auto` follows the ROM header and file name
+    #[serde(default)]
+    pub region: Region,
+    /// Famicom Disk System BIOS; without one, `disksys.rom` next to the
+    /// disk image is used
+    #[serde(default)]
+    pub fds_bios: Option<PathBuf>,
+}
+
+fn default_rewind_seconds() -> u32 {
+    30
+}
+
+fn default_rewind_memory_mb() -> u32 {
+    128
 }
 
 impl Default for EmulationSettings {
@@ -87,10 +114,24 @@ impl Default for EmulationSettings {
         Self {
             speed_multiplier: 1.0,
             rewind_enabled: false,
+            rewind_seconds: default_rewind_seconds(),
+            rewind_memory_mb: default_rewind_memory_mb(),
+            region: Region::Auto,
+            fds_bios: None,
         }
     }
 }
 
+impl EmulationSettings {
+    /// Rewind buffer limits as (snapshots, bytes); one snapshot is taken per frame
+    pub fn rewind_limits(&self, frame_rate: f64) -> (usize, usize) {
+        (
+            (self.rewind_seconds as f64 * frame_rate).round() as usize,
+            self.rewind_memory_mb as usize * 1024 * 1024,
+        )
+    }
+}
+
 /// UI Theme
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
 pub enum Theme {